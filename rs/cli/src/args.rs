@@ -0,0 +1,389 @@
+//! POSIX-style argument parsing shared by the cli utilities.
+//!
+//! [`split`] turns an argument string into words, handling single quotes,
+//! double quotes and backslash escapes like a shell. [`Parser`] then walks
+//! the words as options and operands: short options can be clustered
+//! (`-in`) and take values attached or separate (`-n5`, `-n 5`), long
+//! options take values as `--name=value` or `--name value`, and `--` ends
+//! option parsing. [`quote`] is the inverse of [`split`], for utilities
+//! that build command lines.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Short(char),
+    Long(String),
+    /// A bare `-<digits>` word, e.g. `head -5`.
+    Number(String),
+    /// An operand (non-option argument).
+    Value(String),
+}
+
+impl std::fmt::Display for Arg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arg::Short(c) => write!(f, "-{c}"),
+            Arg::Long(name) => write!(f, "--{name}"),
+            Arg::Number(n) => write!(f, "-{n}"),
+            Arg::Value(v) => f.write_str(v),
+        }
+    }
+}
+
+/// Splits an argument string into words.
+/// Single quotes preserve everything literally, double quotes allow
+/// `\"`, `\\`, `\$` and `` \` `` escapes, and a backslash outside
+/// quotes escapes the next character.
+pub fn split(args: &str) -> Result<Vec<String>, String> {
+    Ok(words(args)?.into_iter().map(|word| word.value).collect())
+}
+
+/// A word of an argument string.
+#[derive(Default)]
+pub struct Word {
+    /// The word as written, quotes included.
+    pub raw: String,
+    /// The word with quotes and escapes removed, as [`split`] returns it.
+    pub value: String,
+    /// The value with a backslash before each quoted or escaped character
+    /// that [`crate::glob`] treats as special, so only unquoted wildcards
+    /// and braces expand.
+    pub pattern: String,
+}
+
+/// Like [`split`], keeping each word as written and as a glob pattern.
+pub fn words(args: &str) -> Result<Vec<Word>, String> {
+    let mut words = Vec::new();
+    let mut word = Word::default();
+    let mut in_word = false;
+    // Adds a character that quoting or a backslash made literal.
+    let literal = |word: &mut Word, c: char| {
+        word.value.push(c);
+        if "*?[]{},\\".contains(c) {
+            word.pattern.push('\\');
+        }
+        word.pattern.push(c);
+    };
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() {
+            word.raw.push(c);
+        }
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    let c = chars.next().ok_or("unterminated quote")?;
+                    word.raw.push(c);
+                    if c == '\'' {
+                        break;
+                    }
+                    literal(&mut word, c);
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    let c = chars.next().ok_or("unterminated quote")?;
+                    word.raw.push(c);
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            let next = chars.next().ok_or("unterminated quote")?;
+                            word.raw.push(next);
+                            if !matches!(next, '"' | '\\' | '$' | '`') {
+                                literal(&mut word, '\\');
+                            }
+                            literal(&mut word, next);
+                        }
+                        c => literal(&mut word, c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(next) = chars.next() {
+                    word.raw.push(next);
+                    literal(&mut word, next);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.value.push(c);
+                word.pattern.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Quotes `word` so that [`split`] turns it back into the same word.
+pub fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-+=:,./@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Parses arguments for a utility that takes operands but no options.
+pub fn operands(cmd: &'static str, args: &str) -> Result<Vec<String>, String> {
+    let mut parser = Parser::new(cmd, args)?;
+    let mut operands = Vec::new();
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Value(value) => operands.push(value),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    Ok(operands)
+}
+
+pub struct Parser {
+    cmd: &'static str,
+    words: std::vec::IntoIter<String>,
+    /// The rest of a short option cluster, e.g. `n` after `-i` in `-in`.
+    cluster: String,
+    /// The value attached to the last long option, e.g. `never` in `--color=never`.
+    long_value: Option<String>,
+    last: Option<Arg>,
+    options_done: bool,
+}
+
+impl Parser {
+    /// Splits `args` into words. `cmd` prefixes error messages.
+    pub fn new(cmd: &'static str, args: &str) -> Result<Self, String> {
+        let words = split(args).map_err(|e| format!("{cmd}: {e}"))?;
+        Ok(Self {
+            cmd,
+            words: words.into_iter(),
+            cluster: String::new(),
+            long_value: None,
+            last: None,
+            options_done: false,
+        })
+    }
+
+    /// Returns the next option or operand.
+    pub fn next_arg(&mut self) -> Result<Option<Arg>, String> {
+        if let Some(value) = self.long_value.take() {
+            return Err(format!(
+                "{}: option '{}' doesn't allow an argument: {value}",
+                self.cmd,
+                self.last_option()
+            ));
+        }
+        let arg = match self.read_arg() {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+        self.last = Some(arg.clone());
+        Ok(Some(arg))
+    }
+
+    fn read_arg(&mut self) -> Option<Arg> {
+        if let Some(c) = self.cluster.chars().next() {
+            self.cluster.drain(..c.len_utf8());
+            return Some(Arg::Short(c));
+        }
+        let word = self.words.next()?;
+        if self.options_done || word == "-" || !word.starts_with('-') {
+            return Some(Arg::Value(word));
+        }
+        if word == "--" {
+            self.options_done = true;
+            return self.read_arg();
+        }
+        if let Some(long) = word.strip_prefix("--") {
+            return Some(match long.split_once('=') {
+                Some((name, value)) => {
+                    self.long_value = Some(value.to_owned());
+                    Arg::Long(name.to_owned())
+                }
+                None => Arg::Long(long.to_owned()),
+            });
+        }
+        let rest = &word[1..];
+        if rest.chars().all(|c| c.is_ascii_digit()) {
+            return Some(Arg::Number(rest.to_owned()));
+        }
+        let mut chars = rest.chars();
+        let first = chars.next()?;
+        self.cluster = chars.collect();
+        Some(Arg::Short(first))
+    }
+
+    /// Returns the value of the option just returned by [`Parser::next_arg`]:
+    /// the rest of a short option cluster, the `=value` of a long option,
+    /// or else the next word.
+    pub fn value(&mut self) -> Result<String, String> {
+        if let Some(value) = self.long_value.take() {
+            return Ok(value);
+        }
+        if !self.cluster.is_empty() {
+            return Ok(std::mem::take(&mut self.cluster));
+        }
+        self.words
+            .next()
+            .ok_or_else(|| format!("{}: missing argument to {}", self.cmd, self.last_option()))
+    }
+
+    /// Parses the value of the option just returned as a number.
+    pub fn number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let value = self.value()?;
+        value.parse().map_err(|_| {
+            let option = self.last_option();
+            format!("{}: invalid number for {option}: {value}", self.cmd)
+        })
+    }
+
+    fn last_option(&self) -> String {
+        self.last.as_ref().map(Arg::to_string).unwrap_or_default()
+    }
+
+    /// The words not parsed yet, for utilities such as `xargs` whose
+    /// operands after the first are passed on untouched.
+    pub fn rest(&mut self) -> Vec<String> {
+        self.words.by_ref().collect()
+    }
+
+    /// The error for an option the utility does not support.
+    pub fn unexpected(&self, arg: &Arg) -> String {
+        match arg {
+            Arg::Value(v) => format!("{}: extra operand: {v}", self.cmd),
+            arg => format!("{}: invalid option: {arg}", self.cmd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Vec<Arg> {
+        let mut parser = Parser::new("test", args).unwrap();
+        let mut out = Vec::new();
+        while let Some(arg) = parser.next_arg().unwrap() {
+            out.push(arg);
+        }
+        out
+    }
+
+    fn short(c: char) -> Arg {
+        Arg::Short(c)
+    }
+
+    fn value(v: &str) -> Arg {
+        Arg::Value(v.into())
+    }
+
+    #[test]
+    fn split_quotes_and_escapes() {
+        assert_eq!(
+            split(r#"grep "foo bar" 'a  b' c\ d "x\"y" 'it''s' "" e"#).unwrap(),
+            vec!["grep", "foo bar", "a  b", "c d", "x\"y", "its", "", "e"]
+        );
+    }
+
+    #[test]
+    fn split_quoted_regex_keeps_backslashes() {
+        assert_eq!(
+            split(r#"'\d+\s\w' "\d+\.txt""#).unwrap(),
+            vec![r"\d+\s\w", r"\d+\.txt"]
+        );
+    }
+
+    #[test]
+    fn quote_round_trips() {
+        let words = ["plain", "a b", "it's", "", "$x", "-n", "back\\slash"];
+        let quoted: Vec<String> = words.iter().map(|w| quote(w)).collect();
+        assert_eq!(quoted[0], "plain");
+        assert_eq!(quoted[2], "'it'\\''s'");
+        assert_eq!(split(&quoted.join(" ")).unwrap(), words);
+    }
+
+    #[test]
+    fn rest_after_operand() {
+        let mut parser = Parser::new("xargs", "-n 1 grep -i 'a b'").unwrap();
+        assert_eq!(parser.next_arg().unwrap(), Some(short('n')));
+        assert_eq!(parser.value().unwrap(), "1");
+        assert_eq!(parser.next_arg().unwrap(), Some(value("grep")));
+        assert_eq!(parser.rest(), vec!["-i", "a b"]);
+    }
+
+    #[test]
+    fn split_unterminated() {
+        assert!(split("'abc").is_err());
+        assert!(split("\"abc").is_err());
+        assert!(Parser::new("grep", "\"abc").is_err());
+    }
+
+    #[test]
+    fn short_clusters() {
+        assert_eq!(
+            parse("-in -v file"),
+            vec![short('i'), short('n'), short('v'), value("file")]
+        );
+    }
+
+    #[test]
+    fn short_option_values() {
+        let mut parser = Parser::new("head", "-n5 -n 7 -c").unwrap();
+        assert_eq!(parser.next_arg().unwrap(), Some(short('n')));
+        assert_eq!(parser.number::<usize>().unwrap(), 5);
+        assert_eq!(parser.next_arg().unwrap(), Some(short('n')));
+        assert_eq!(parser.value().unwrap(), "7");
+        assert_eq!(parser.next_arg().unwrap(), Some(short('c')));
+        assert_eq!(parser.value().unwrap_err(), "head: missing argument to -c");
+    }
+
+    #[test]
+    fn long_options() {
+        let mut parser = Parser::new("grep", "--count --max-count=3 --regexp x").unwrap();
+        assert_eq!(parser.next_arg().unwrap(), Some(Arg::Long("count".into())));
+        assert_eq!(
+            parser.next_arg().unwrap(),
+            Some(Arg::Long("max-count".into()))
+        );
+        assert_eq!(parser.value().unwrap(), "3");
+        assert_eq!(parser.next_arg().unwrap(), Some(Arg::Long("regexp".into())));
+        assert_eq!(parser.value().unwrap(), "x");
+        assert_eq!(parser.next_arg().unwrap(), None);
+    }
+
+    #[test]
+    fn long_option_unexpected_value() {
+        let mut parser = Parser::new("grep", "--count=3").unwrap();
+        parser.next_arg().unwrap();
+        assert!(parser.next_arg().unwrap_err().contains("--count"));
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        assert_eq!(
+            parse("-v -- -n --x -"),
+            vec![short('v'), value("-n"), value("--x"), value("-")]
+        );
+    }
+
+    #[test]
+    fn numbers_and_stdin() {
+        assert_eq!(parse("-5 -"), vec![Arg::Number("5".into()), value("-")]);
+    }
+
+    #[test]
+    fn filenames_with_spaces() {
+        assert_eq!(
+            parse(r#"-r "my dir/a file.txt" other\ file"#),
+            vec![short('r'), value("my dir/a file.txt"), value("other file")]
+        );
+    }
+}
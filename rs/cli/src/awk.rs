@@ -1,43 +1,57 @@
-//! Partial awk implementation covering the common subset: field extraction, patterns,
-//! BEGIN/END, arithmetic, variables, and regex matching. Unsupported features (loops,
-//! arrays, functions, printf, etc.) return clear "not implemented" errors.
+//! awk implementation: patterns (regex, expression and range), BEGIN/END,
+//! control flow (if/else, while, do, for, for-in, break, continue, next,
+//! nextfile, exit), associative arrays, user-defined functions, printf and
+//! sprintf, the standard string and math functions, `-v` assignments, the
+//! special variables (FS, OFS, ORS, RS, NR, NF, FNR, SUBSEP, ...) and
+//! `print > file` redirection into `asterai:fs`.
+//! `getline`, pipes and `system()` return clear "not implemented" errors.
+use crate::args::{self, Arg};
 use crate::fs_ops;
 use regex::Regex;
+use std::collections::HashMap;
+
+const MAX_CALL_DEPTH: usize = 200;
+const SUBSEP: &str = "\x1c";
 
 struct Opts {
-    field_sep: String,
+    field_sep: Option<String>,
+    assignments: Vec<String>,
     program: String,
-    paths: Vec<String>,
+    /// Input files and `name=value` assignments, in order.
+    operands: Vec<String>,
 }
 
 fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
-        field_sep: " ".into(),
+        field_sep: None,
+        assignments: Vec::new(),
         program: String::new(),
-        paths: Vec::new(),
+        operands: Vec::new(),
     };
-    let tokens = shell_split(args)?;
-    let mut i = 0;
     let mut got_program = false;
-    while i < tokens.len() {
-        match tokens[i].as_str() {
-            "-F" => {
-                i += 1;
-                opts.field_sep = tokens.get(i).ok_or("awk: missing argument to -F")?.clone();
-            }
-            t if t.starts_with("-F") => {
-                opts.field_sep = t[2..].to_string();
+    let mut parser = args::Parser::new("awk", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('F') => opts.field_sep = Some(parser.value()?),
+            Arg::Short('v') => opts.assignments.push(parser.value()?),
+            Arg::Short('f') => {
+                let path = parser.value()?;
+                opts.program =
+                    fs_ops::read_to_string(&path).map_err(|e| format!("awk: {path}: {e}"))?;
+                got_program = true;
             }
-            _ => {
-                if !got_program {
-                    opts.program = tokens[i].clone();
-                    got_program = true;
-                } else {
-                    opts.paths.push(tokens[i].clone());
-                }
+            Arg::Long(l) => match l.as_str() {
+                "field-separator" => opts.field_sep = Some(parser.value()?),
+                "assign" => opts.assignments.push(parser.value()?),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(value) if !got_program => {
+                opts.program = value;
+                got_program = true;
             }
+            Arg::Value(operand) => opts.operands.push(operand),
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
     if !got_program {
         return Err("awk: missing program".into());
@@ -45,769 +59,2710 @@ fn parse_opts(args: &str) -> Result<Opts, String> {
     Ok(opts)
 }
 
-/// Simple shell-like splitting that handles single and double quotes.
-fn shell_split(s: &str) -> Result<Vec<String>, String> {
-    let mut tokens = Vec::new();
-    let mut current = String::new();
-    let mut in_single = false;
-    let mut in_double = false;
-    let mut chars = s.chars().peekable();
-    while let Some(c) = chars.next() {
-        if in_single {
-            if c == '\'' {
-                in_single = false;
-            } else {
-                current.push(c);
+// ---------------------------------------------------------------------------
+// Lexer
+
+const KEYWORDS: &[&str] = &[
+    "BEGIN", "END", "function", "func", "if", "else", "while", "for", "do", "break", "continue",
+    "next", "nextfile", "exit", "return", "delete", "in", "getline", "print", "printf",
+];
+
+const BUILTINS: &[&str] = &[
+    "length", "substr", "index", "split", "sub", "gsub", "match", "sprintf", "tolower", "toupper",
+    "int", "sqrt", "exp", "log", "sin", "cos", "atan2", "rand", "srand", "system", "close",
+    "fflush",
+];
+
+/// Operators and punctuation, longest first.
+const PUNCT: &[&str] = &[
+    "+=", "-=", "*=", "/=", "%=", "^=", "==", "<=", ">=", "!=", "++", "--", "!~", "&&", "||", ">>",
+    "{", "}", "(", ")", "[", "]", ";", ",", "+", "-", "*", "/", "%", "^", "!", ">", "<", "|", "?",
+    ":", "~", "$", "=",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Name(String),
+    /// A user function name directly followed by `(`.
+    FuncName(String),
+    Builtin(&'static str),
+    Keyword(&'static str),
+    Punct(&'static str),
+    Newline,
+    Eof,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format_number(*n),
+            Token::Str(s) => format!("\"{s}\""),
+            Token::Regex(r) => format!("/{r}/"),
+            Token::Name(s) | Token::FuncName(s) => s.clone(),
+            Token::Builtin(s) | Token::Keyword(s) | Token::Punct(s) => s.to_string(),
+            Token::Newline => "newline".into(),
+            Token::Eof => "end of program".into(),
+        }
+    }
+}
+
+/// Whether a `/` after `prev` starts a regex rather than a division.
+fn regex_allowed(prev: Option<&Token>) -> bool {
+    !matches!(
+        prev,
+        Some(
+            Token::Number(_)
+                | Token::Str(_)
+                | Token::Regex(_)
+                | Token::Name(_)
+                | Token::Builtin(_)
+                | Token::Punct(")" | "]" | "$" | "++" | "--")
+        )
+    )
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\r' => i += 1,
+            '\\' if chars.get(i + 1) == Some(&'\n') => i += 2,
+            '\\' if chars.get(i + 1) == Some(&'\r') && chars.get(i + 2) == Some(&'\n') => i += 3,
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
             }
-        } else if in_double {
-            if c == '"' {
-                in_double = false;
-            } else {
-                current.push(c);
+            '\n' => {
+                tokens.push(Token::Newline);
+                i += 1;
             }
-        } else if c == '\'' {
-            in_single = true;
-        } else if c == '"' {
-            in_double = true;
-        } else if c.is_whitespace() {
-            if !current.is_empty() {
-                tokens.push(std::mem::take(&mut current));
+            '"' => {
+                i += 1;
+                let mut raw = String::new();
+                loop {
+                    match chars.get(i) {
+                        Some('"') => break,
+                        Some('\\') => {
+                            raw.push('\\');
+                            if let Some(&next) = chars.get(i + 1) {
+                                raw.push(next);
+                            }
+                            i += 2;
+                        }
+                        Some('\n') | None => return Err("awk: unterminated string".into()),
+                        Some(&c) => {
+                            raw.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push(Token::Str(unescape(&raw)));
+            }
+            '/' if regex_allowed(tokens.last()) => {
+                i += 1;
+                let mut re = String::new();
+                let mut in_bracket = false;
+                loop {
+                    match chars.get(i) {
+                        Some('/') if !in_bracket => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                            re.push('/');
+                            i += 2;
+                        }
+                        Some('\\') => {
+                            re.push('\\');
+                            if let Some(&next) = chars.get(i + 1) {
+                                re.push(next);
+                            }
+                            i += 2;
+                        }
+                        Some('\n') | None => return Err("awk: unterminated regex".into()),
+                        Some(&c) => {
+                            if c == '[' && !in_bracket {
+                                in_bracket = true;
+                                re.push(c);
+                                i += 1;
+                                // `]` right after `[` or `[^` is literal.
+                                if chars.get(i) == Some(&'^') {
+                                    re.push('^');
+                                    i += 1;
+                                }
+                                if chars.get(i) == Some(&']') {
+                                    re.push(']');
+                                    i += 1;
+                                }
+                                continue;
+                            }
+                            if c == ']' {
+                                in_bracket = false;
+                            }
+                            re.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push(Token::Regex(re));
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) =>
+            {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let n = text
+                    .parse()
+                    .map_err(|_| format!("awk: invalid number: {text}"))?;
+                tokens.push(Token::Number(n));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = if let Some(k) = KEYWORDS.iter().find(|k| **k == word) {
+                    Token::Keyword(k)
+                } else if let Some(b) = BUILTINS.iter().find(|b| **b == word) {
+                    Token::Builtin(b)
+                } else if chars.get(i) == Some(&'(') {
+                    Token::FuncName(word)
+                } else {
+                    Token::Name(word)
+                };
+                tokens.push(token);
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let punct = PUNCT
+                    .iter()
+                    .find(|p| rest.starts_with(**p))
+                    .ok_or_else(|| format!("awk: unexpected character: '{c}'"))?;
+                i += punct.chars().count();
+                tokens.push(Token::Punct(punct));
             }
-        } else {
-            current.push(c);
         }
     }
-    if in_single || in_double {
-        return Err("awk: unterminated quote".into());
-    }
-    if !current.is_empty() {
-        tokens.push(current);
-    }
+    tokens.push(Token::Eof);
     Ok(tokens)
 }
 
-#[derive(Debug)]
+/// Processes escape sequences in string literals and `-v` values.
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some('"') => out.push('"'),
+            Some('/') => out.push('/'),
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0c'),
+            Some('v') => out.push('\x0b'),
+            Some(d @ '0'..='7') => {
+                let mut code = d.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.push(char::from_u32(code).unwrap_or('\0'));
+            }
+            // Unknown escapes are kept, so "\." still escapes a regex metacharacter.
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Syntax tree
+
+struct Program {
+    begin: Vec<Vec<Stmt>>,
+    end: Vec<Vec<Stmt>>,
+    rules: Vec<Rule>,
+    functions: HashMap<String, Function>,
+}
+
 struct Rule {
     pattern: Pattern,
-    actions: Vec<Action>,
+    /// `None` means the default action, `print $0`.
+    action: Option<Vec<Stmt>>,
 }
 
-#[derive(Debug)]
 enum Pattern {
-    Begin,
-    End,
-    Always,
-    Regex(String),
-    Condition(Expr),
+    All,
+    Expr(Expr),
+    Range(Expr, Expr),
 }
 
-#[derive(Debug, Clone)]
-enum Expr {
-    Field(Box<Expr>),
-    Literal(f64),
-    StringLit(String),
-    NR,
-    NF,
-    Var(String),
-    BinOp(Box<Expr>, BinOp, Box<Expr>),
-    Assign(String, Box<Expr>),
-    AssignOp(String, BinOp, Box<Expr>),
-    Match(Box<Expr>, String),
-    NotMatch(Box<Expr>, String),
-    Concat(Box<Expr>, Box<Expr>),
+struct Function {
+    params: Vec<String>,
+    /// Parameters the body uses as arrays, so uninitialized arguments can
+    /// be passed by reference.
+    array_params: Vec<String>,
+    body: Vec<Stmt>,
 }
 
-#[derive(Debug, Clone)]
+struct Output {
+    append: bool,
+    target: Expr,
+}
+
+enum Stmt {
+    Print(Vec<Expr>, Option<Output>),
+    Printf(Vec<Expr>, Option<Output>),
+    Expr(Expr),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Do(Box<Stmt>, Expr),
+    For(Option<Expr>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    ForIn(String, String, Box<Stmt>),
+    Block(Vec<Stmt>),
+    Delete(String, Option<Vec<Expr>>),
+    Next,
+    NextFile,
+    Exit(Option<Expr>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+}
+
+#[derive(Clone, Copy)]
 enum BinOp {
     Add,
     Sub,
     Mul,
     Div,
     Mod,
-    Gt,
+    Pow,
+}
+
+#[derive(Clone, Copy)]
+enum CmpOp {
     Lt,
-    Ge,
     Le,
+    Gt,
+    Ge,
     Eq,
     Ne,
 }
 
-#[derive(Debug)]
-enum Action {
-    Print(Vec<Expr>),
-    Expr(Expr),
+enum Expr {
+    Num(f64),
+    Str(String),
+    /// A regex literal; on its own it matches against `$0`.
+    Regex(String),
+    Var(String),
+    Field(Box<Expr>),
+    Index(String, Vec<Expr>),
+    /// A parenthesized list, only valid before `in`: `(i, j) in arr`.
+    Group(Vec<Expr>),
+    Assign(Box<Expr>, Option<BinOp>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Pos(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Compare(CmpOp, Box<Expr>, Box<Expr>),
+    /// `lhs ~ rhs`, or `lhs !~ rhs` when the flag is set.
+    Match(bool, Box<Expr>, Box<Expr>),
+    Concat(Box<Expr>, Box<Expr>),
+    In(Vec<Expr>, String),
+    IncDec {
+        target: Box<Expr>,
+        delta: f64,
+        prefix: bool,
+    },
+    Builtin(&'static str, Vec<Expr>),
+    Call(String, Vec<Expr>),
 }
 
-struct Parser<'a> {
-    input: &'a str,
-    pos: usize,
+impl Expr {
+    fn is_lvalue(&self) -> bool {
+        matches!(self, Expr::Var(_) | Expr::Field(_) | Expr::Index(..))
+    }
 }
 
-impl<'a> Parser<'a> {
-    fn new(input: &'a str) -> Self {
-        Self { input, pos: 0 }
-    }
+// ---------------------------------------------------------------------------
+// Parser
 
-    fn skip_ws(&mut self) {
-        while self.pos < self.input.len() {
-            let c = self.input.as_bytes()[self.pos];
-            if c == b' ' || c == b'\t' || c == b'\n' || c == b'\r' {
-                self.pos += 1;
-            } else {
-                break;
-            }
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Set while parsing an unparenthesized print list, where `>` is a
+    /// redirection rather than a comparison.
+    no_gt: bool,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            no_gt: false,
         }
     }
 
-    fn peek(&self) -> Option<u8> {
-        self.input.as_bytes().get(self.pos).copied()
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
     }
 
-    fn peek2(&self) -> Option<u8> {
-        self.input.as_bytes().get(self.pos + 1).copied()
+    fn peek_at(&self, offset: usize) -> &Token {
+        let i = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[i]
     }
 
-    fn advance(&mut self) -> Option<u8> {
-        let c = self.input.as_bytes().get(self.pos).copied();
-        if c.is_some() {
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
             self.pos += 1;
         }
-        c
+        token
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek(), Token::Punct(q) if *q == p)
+    }
+
+    fn is_keyword(&self, k: &str) -> bool {
+        matches!(self.peek(), Token::Keyword(q) if *q == k)
     }
 
-    fn expect(&mut self, c: u8) -> Result<(), String> {
-        self.skip_ws();
-        if self.peek() == Some(c) {
+    fn eat_punct(&mut self, p: &str) -> bool {
+        if self.is_punct(p) {
             self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), String> {
+        if self.eat_punct(p) {
             Ok(())
         } else {
             Err(format!(
-                "awk: expected '{}', got '{}'",
-                c as char,
-                self.peek().map(|b| b as char).unwrap_or('\0')
+                "awk: expected '{p}', got '{}'",
+                self.peek().describe()
             ))
         }
     }
 
-    fn parse_program(&mut self) -> Result<Vec<Rule>, String> {
-        let mut rules = Vec::new();
-        loop {
-            self.skip_ws();
-            if self.pos >= self.input.len() {
-                break;
-            }
-            // skip semicolons between rules
-            if self.peek() == Some(b';') {
-                self.advance();
-                continue;
-            }
-            rules.push(self.parse_rule()?);
+    fn syntax_error(&self) -> String {
+        match self.peek() {
+            Token::Keyword("getline") => "awk: not implemented: getline".into(),
+            Token::Punct("|") => "awk: not implemented: pipes".into(),
+            token => format!("awk: syntax error at '{}'", token.describe()),
         }
-        Ok(rules)
-    }
-
-    fn parse_rule(&mut self) -> Result<Rule, String> {
-        self.skip_ws();
-        let pattern = self.parse_pattern()?;
-        self.skip_ws();
-        let actions = if self.peek() == Some(b'{') {
-            self.parse_action_block()?
-        } else {
-            match &pattern {
-                Pattern::Begin | Pattern::End => {
-                    return Err("awk: BEGIN/END requires action block".into());
-                }
-                _ => vec![Action::Print(vec![Expr::Field(Box::new(Expr::Literal(0.0)))])],
-            }
-        };
-        Ok(Rule { pattern, actions })
     }
 
-    fn parse_pattern(&mut self) -> Result<Pattern, String> {
-        self.skip_ws();
-        if self.starts_with("BEGIN") && self.is_word_boundary(5) {
-            self.pos += 5;
-            return Ok(Pattern::Begin);
-        }
-        if self.starts_with("END") && self.is_word_boundary(3) {
-            self.pos += 3;
-            return Ok(Pattern::End);
-        }
-        if self.peek() == Some(b'{') {
-            return Ok(Pattern::Always);
-        }
-        if self.peek() == Some(b'/') {
-            let re = self.parse_regex_literal()?;
-            return Ok(Pattern::Regex(re));
-        }
-        // try to parse a condition expression
-        if self.pos < self.input.len() && self.peek() != Some(b'{') {
-            let expr = self.parse_expr()?;
-            return Ok(Pattern::Condition(expr));
+    fn skip_newlines(&mut self) {
+        while *self.peek() == Token::Newline {
+            self.advance();
         }
-        Ok(Pattern::Always)
     }
 
-    fn starts_with(&self, s: &str) -> bool {
-        self.input[self.pos..].starts_with(s)
+    fn skip_terminators(&mut self) {
+        while *self.peek() == Token::Newline || self.is_punct(";") {
+            self.advance();
+        }
     }
 
-    fn is_word_boundary(&self, offset: usize) -> bool {
-        let p = self.pos + offset;
-        if p >= self.input.len() {
-            return true;
+    fn expect_name(&mut self) -> Result<String, String> {
+        match self.advance() {
+            Token::Name(name) => Ok(name),
+            token => Err(format!("awk: expected a name, got '{}'", token.describe())),
         }
-        let c = self.input.as_bytes()[p];
-        !c.is_ascii_alphanumeric() && c != b'_'
     }
 
-    fn parse_regex_literal(&mut self) -> Result<String, String> {
-        self.expect(b'/')?;
-        let start = self.pos;
-        while self.pos < self.input.len() {
-            let c = self.input.as_bytes()[self.pos];
-            if c == b'/' {
-                let re = self.input[start..self.pos].to_string();
+    fn parse_program(mut self) -> Result<Program, String> {
+        let mut program = Program {
+            begin: Vec::new(),
+            end: Vec::new(),
+            rules: Vec::new(),
+            functions: HashMap::new(),
+        };
+        self.skip_terminators();
+        while *self.peek() != Token::Eof {
+            if self.is_keyword("BEGIN") || self.is_keyword("END") {
+                let begin = self.is_keyword("BEGIN");
+                self.advance();
+                if !self.is_punct("{") {
+                    return Err("awk: BEGIN/END requires action block".into());
+                }
+                let block = self.parse_block()?;
+                if begin {
+                    program.begin.push(block);
+                } else {
+                    program.end.push(block);
+                }
+            } else if self.is_keyword("function") || self.is_keyword("func") {
                 self.advance();
-                return Ok(re);
+                let name = match self.advance() {
+                    Token::Name(name) | Token::FuncName(name) => name,
+                    token => {
+                        return Err(format!(
+                            "awk: invalid function name: '{}'",
+                            token.describe()
+                        ));
+                    }
+                };
+                if program.functions.contains_key(&name) {
+                    return Err(format!("awk: function {name} redefined"));
+                }
+                self.expect("(")?;
+                let mut params = Vec::new();
+                while !self.eat_punct(")") {
+                    if !params.is_empty() {
+                        self.expect(",")?;
+                        self.skip_newlines();
+                    }
+                    params.push(self.expect_name()?);
+                }
+                self.skip_newlines();
+                let body = self.parse_block()?;
+                let mut array_params = Vec::new();
+                for stmt in &body {
+                    collect_array_names(stmt, &mut array_params);
+                }
+                array_params.retain(|name| params.contains(name));
+                program.functions.insert(
+                    name,
+                    Function {
+                        params,
+                        array_params,
+                        body,
+                    },
+                );
+            } else {
+                let pattern = if self.is_punct("{") {
+                    Pattern::All
+                } else {
+                    let start = self.parse_expr()?;
+                    if self.eat_punct(",") {
+                        self.skip_newlines();
+                        Pattern::Range(start, self.parse_expr()?)
+                    } else {
+                        Pattern::Expr(start)
+                    }
+                };
+                let action = if self.is_punct("{") {
+                    Some(self.parse_block()?)
+                } else {
+                    None
+                };
+                program.rules.push(Rule { pattern, action });
             }
-            if c == b'\\' {
-                self.advance(); // skip escaped char
+            match self.peek() {
+                Token::Newline | Token::Punct(";") | Token::Eof => self.skip_terminators(),
+                _ if matches!(self.tokens[self.pos - 1], Token::Punct("}")) => {}
+                _ => return Err(self.syntax_error()),
             }
-            self.advance();
         }
-        Err("awk: unterminated regex".into())
+        Ok(program)
     }
 
-    fn parse_action_block(&mut self) -> Result<Vec<Action>, String> {
-        self.expect(b'{')?;
-        let mut actions = Vec::new();
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
         loop {
-            self.skip_ws();
-            if self.peek() == Some(b'}') {
-                self.advance();
-                break;
+            self.skip_terminators();
+            if self.eat_punct("}") {
+                return Ok(stmts);
             }
-            if self.pos >= self.input.len() {
+            if *self.peek() == Token::Eof {
                 return Err("awk: unterminated action block".into());
             }
-            // skip semicolons
-            if self.peek() == Some(b';') {
-                self.advance();
-                continue;
-            }
-            actions.push(self.parse_action()?);
+            stmts.push(self.parse_stmt()?);
         }
-        Ok(actions)
     }
 
-    fn parse_action(&mut self) -> Result<Action, String> {
-        self.skip_ws();
-        if self.starts_with("print") && self.is_word_boundary(5) {
-            self.pos += 5;
-            return self.parse_print();
-        }
-        if self.starts_with("if") && self.is_word_boundary(2) {
-            return Err("awk: not implemented: if statements".into());
-        }
-        if self.starts_with("for") && self.is_word_boundary(3) {
-            return Err("awk: not implemented: for loops".into());
+    /// Parses a statement that may span lines, e.g. the body of an `if`.
+    fn parse_body(&mut self) -> Result<Stmt, String> {
+        self.skip_newlines();
+        if self.eat_punct(";") {
+            return Ok(Stmt::Block(Vec::new()));
         }
-        if self.starts_with("while") && self.is_word_boundary(5) {
-            return Err("awk: not implemented: while loops".into());
-        }
-        if self.starts_with("function") && self.is_word_boundary(8) {
-            return Err("awk: not implemented: user-defined functions".into());
-        }
-        if self.starts_with("printf") && self.is_word_boundary(6) {
-            return Err("awk: not implemented: printf".into());
-        }
-        if self.starts_with("getline") && self.is_word_boundary(7) {
-            return Err("awk: not implemented: getline".into());
+        self.parse_stmt()
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, String> {
+        match self.peek() {
+            Token::Punct("{") => Ok(Stmt::Block(self.parse_block()?)),
+            Token::Keyword("if") => {
+                self.advance();
+                self.expect("(")?;
+                let cond = self.parse_expr()?;
+                self.expect(")")?;
+                let then = self.parse_body()?;
+                // `else` may follow on a later line or after a `;`.
+                let save = self.pos;
+                self.skip_terminators();
+                if self.is_keyword("else") {
+                    self.advance();
+                    let otherwise = self.parse_body()?;
+                    Ok(Stmt::If(cond, Box::new(then), Some(Box::new(otherwise))))
+                } else {
+                    self.pos = save;
+                    Ok(Stmt::If(cond, Box::new(then), None))
+                }
+            }
+            Token::Keyword("while") => {
+                self.advance();
+                self.expect("(")?;
+                let cond = self.parse_expr()?;
+                self.expect(")")?;
+                if self.eat_punct(";") {
+                    return Ok(Stmt::While(cond, Box::new(Stmt::Block(Vec::new()))));
+                }
+                Ok(Stmt::While(cond, Box::new(self.parse_body()?)))
+            }
+            Token::Keyword("do") => {
+                self.advance();
+                let body = self.parse_body()?;
+                self.skip_terminators();
+                if !self.is_keyword("while") {
+                    return Err(self.syntax_error());
+                }
+                self.advance();
+                self.expect("(")?;
+                let cond = self.parse_expr()?;
+                self.expect(")")?;
+                self.end_simple_stmt()?;
+                Ok(Stmt::Do(Box::new(body), cond))
+            }
+            Token::Keyword("for") => self.parse_for(),
+            _ => {
+                let stmt = self.parse_simple_stmt()?;
+                self.end_simple_stmt()?;
+                Ok(stmt)
+            }
         }
-        if self.starts_with("next") && self.is_word_boundary(4) {
-            return Err("awk: not implemented: next".into());
+    }
+
+    fn parse_for(&mut self) -> Result<Stmt, String> {
+        self.advance();
+        self.expect("(")?;
+        if let (Token::Name(var), Token::Keyword("in"), Token::Name(array), Token::Punct(")")) = (
+            self.peek(),
+            self.peek_at(1),
+            self.peek_at(2),
+            self.peek_at(3),
+        ) {
+            let (var, array) = (var.clone(), array.clone());
+            self.pos += 4;
+            return Ok(Stmt::ForIn(var, array, Box::new(self.parse_body()?)));
         }
-        if self.starts_with("delete") && self.is_word_boundary(6) {
-            return Err("awk: not implemented: delete".into());
+        let init = if self.is_punct(";") {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect(";")?;
+        self.skip_newlines();
+        let cond = if self.is_punct(";") {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect(";")?;
+        self.skip_newlines();
+        let step = if self.is_punct(")") {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect(")")?;
+        if self.eat_punct(";") {
+            return Ok(Stmt::For(
+                init,
+                cond,
+                step,
+                Box::new(Stmt::Block(Vec::new())),
+            ));
         }
-        // expression statement (e.g. sum += $1)
-        let expr = self.parse_expr()?;
-        Ok(Action::Expr(expr))
+        Ok(Stmt::For(init, cond, step, Box::new(self.parse_body()?)))
     }
 
-    fn parse_print(&mut self) -> Result<Action, String> {
-        self.skip_ws();
-        if self.peek() == Some(b';') || self.peek() == Some(b'}') || self.pos >= self.input.len() {
-            return Ok(Action::Print(vec![Expr::Field(Box::new(Expr::Literal(
-                0.0,
-            )))]));
-        }
-        let mut exprs = Vec::new();
-        exprs.push(self.parse_expr()?);
-        loop {
-            self.skip_ws();
-            if self.peek() == Some(b',') {
+    fn end_simple_stmt(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Token::Newline | Token::Punct(";") => {
                 self.advance();
-                self.skip_ws();
-                exprs.push(self.parse_expr()?);
-            } else {
-                break;
+                Ok(())
             }
+            Token::Punct("}") | Token::Eof => Ok(()),
+            _ => Err(self.syntax_error()),
         }
-        Ok(Action::Print(exprs))
     }
 
-    fn parse_expr(&mut self) -> Result<Expr, String> {
-        self.parse_assignment()
+    fn at_stmt_end(&self) -> bool {
+        matches!(
+            self.peek(),
+            Token::Newline | Token::Eof | Token::Punct(";" | "}")
+        )
     }
 
-    fn parse_assignment(&mut self) -> Result<Expr, String> {
-        let expr = self.parse_comparison()?;
-        self.skip_ws();
-        // check for assignment operators on variables
-        if let Expr::Var(ref name) = expr {
-            if self.peek() == Some(b'=') && self.peek2() != Some(b'=') {
+    fn parse_simple_stmt(&mut self) -> Result<Stmt, String> {
+        let keyword = match self.peek() {
+            Token::Keyword(k) => *k,
+            _ => return Ok(Stmt::Expr(self.parse_expr()?)),
+        };
+        match keyword {
+            "print" | "printf" => {
                 self.advance();
-                let rhs = self.parse_expr()?;
-                return Ok(Expr::Assign(name.clone(), Box::new(rhs)));
-            }
-            if self.starts_with("+=") {
-                self.pos += 2;
-                let rhs = self.parse_expr()?;
-                return Ok(Expr::AssignOp(name.clone(), BinOp::Add, Box::new(rhs)));
+                let args = self.parse_print_args()?;
+                let output = if self.is_punct(">") || self.is_punct(">>") {
+                    let append = self.advance() == Token::Punct(">>");
+                    let target = self.parse_concat()?;
+                    Some(Output { append, target })
+                } else {
+                    None
+                };
+                if keyword == "print" {
+                    Ok(Stmt::Print(args, output))
+                } else if args.is_empty() {
+                    Err("awk: printf: no format".into())
+                } else {
+                    Ok(Stmt::Printf(args, output))
+                }
             }
-            if self.starts_with("-=") {
-                self.pos += 2;
-                let rhs = self.parse_expr()?;
-                return Ok(Expr::AssignOp(name.clone(), BinOp::Sub, Box::new(rhs)));
+            "delete" => {
+                self.advance();
+                let name = self.expect_name()?;
+                if !self.eat_punct("[") {
+                    return Ok(Stmt::Delete(name, None));
+                }
+                let subs = self.parse_subscripts()?;
+                Ok(Stmt::Delete(name, Some(subs)))
             }
-            if self.starts_with("*=") {
-                self.pos += 2;
-                let rhs = self.parse_expr()?;
-                return Ok(Expr::AssignOp(name.clone(), BinOp::Mul, Box::new(rhs)));
+            "next" | "nextfile" | "break" | "continue" => {
+                self.advance();
+                Ok(match keyword {
+                    "next" => Stmt::Next,
+                    "nextfile" => Stmt::NextFile,
+                    "break" => Stmt::Break,
+                    _ => Stmt::Continue,
+                })
             }
-            if self.starts_with("/=") {
-                self.pos += 2;
-                let rhs = self.parse_expr()?;
-                return Ok(Expr::AssignOp(name.clone(), BinOp::Div, Box::new(rhs)));
+            "exit" | "return" => {
+                self.advance();
+                let value = if self.at_stmt_end() {
+                    None
+                } else {
+                    Some(self.parse_expr()?)
+                };
+                Ok(if keyword == "exit" {
+                    Stmt::Exit(value)
+                } else {
+                    Stmt::Return(value)
+                })
             }
+            _ => Err(self.syntax_error()),
         }
-        Ok(expr)
     }
 
-    fn parse_comparison(&mut self) -> Result<Expr, String> {
-        let mut left = self.parse_addition()?;
-        loop {
-            self.skip_ws();
-            let op = if self.starts_with("==") {
-                self.pos += 2;
-                BinOp::Eq
-            } else if self.starts_with("!=") {
-                self.pos += 2;
-                BinOp::Ne
-            } else if self.starts_with(">=") {
-                self.pos += 2;
-                BinOp::Ge
-            } else if self.starts_with("<=") {
-                self.pos += 2;
-                BinOp::Le
-            } else if self.peek() == Some(b'>') {
-                self.advance();
-                BinOp::Gt
-            } else if self.peek() == Some(b'<') {
-                self.advance();
-                BinOp::Lt
-            } else if self.peek() == Some(b'~') {
-                self.advance();
-                self.skip_ws();
-                let re = self.parse_regex_literal()?;
-                left = Expr::Match(Box::new(left), re);
-                continue;
-            } else if self.starts_with("!~") {
-                self.pos += 2;
-                self.skip_ws();
-                let re = self.parse_regex_literal()?;
-                left = Expr::NotMatch(Box::new(left), re);
-                continue;
-            } else {
-                break;
-            };
-            let right = self.parse_addition()?;
-            left = Expr::BinOp(Box::new(left), op, Box::new(right));
+    fn parse_print_args(&mut self) -> Result<Vec<Expr>, String> {
+        if self.at_stmt_end() || self.is_punct(">") || self.is_punct(">>") || self.is_punct("|") {
+            return Ok(Vec::new());
+        }
+        let saved = self.no_gt;
+        self.no_gt = true;
+        let mut args = vec![self.parse_expr()?];
+        while self.eat_punct(",") {
+            self.skip_newlines();
+            args.push(self.parse_expr()?);
+        }
+        self.no_gt = saved;
+        // `print (a, b) > "file"`
+        if let [Expr::Group(items)] = args.as_mut_slice() {
+            return Ok(std::mem::take(items));
         }
-        Ok(left)
+        Ok(args)
     }
 
-    fn parse_addition(&mut self) -> Result<Expr, String> {
-        let mut left = self.parse_multiplication()?;
-        loop {
-            self.skip_ws();
-            let op = if self.peek() == Some(b'+') && self.peek2() != Some(b'=') {
-                self.advance();
-                BinOp::Add
-            } else if self.peek() == Some(b'-') && self.peek2() != Some(b'=') {
-                self.advance();
-                BinOp::Sub
-            } else {
-                break;
+    fn parse_subscripts(&mut self) -> Result<Vec<Expr>, String> {
+        let saved = self.no_gt;
+        self.no_gt = false;
+        let mut subs = vec![self.parse_expr()?];
+        while self.eat_punct(",") {
+            self.skip_newlines();
+            subs.push(self.parse_expr()?);
+        }
+        self.expect("]")?;
+        self.no_gt = saved;
+        Ok(subs)
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_ternary()?;
+        let op = match self.peek() {
+            Token::Punct("=") => None,
+            Token::Punct("+=") => Some(BinOp::Add),
+            Token::Punct("-=") => Some(BinOp::Sub),
+            Token::Punct("*=") => Some(BinOp::Mul),
+            Token::Punct("/=") => Some(BinOp::Div),
+            Token::Punct("%=") => Some(BinOp::Mod),
+            Token::Punct("^=") => Some(BinOp::Pow),
+            _ => return Ok(lhs),
+        };
+        if !lhs.is_lvalue() {
+            return Err(self.syntax_error());
+        }
+        self.advance();
+        self.skip_newlines();
+        let rhs = self.parse_expr()?;
+        Ok(Expr::Assign(Box::new(lhs), op, Box::new(rhs)))
+    }
+
+    fn parse_ternary(&mut self) -> Result<Expr, String> {
+        let cond = self.parse_or()?;
+        if !self.eat_punct("?") {
+            return Ok(cond);
+        }
+        self.skip_newlines();
+        let then = self.parse_expr()?;
+        self.skip_newlines();
+        self.expect(":")?;
+        self.skip_newlines();
+        let otherwise = self.parse_expr()?;
+        Ok(Expr::Cond(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_and()?;
+        while self.eat_punct("||") {
+            self.skip_newlines();
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_in()?;
+        while self.eat_punct("&&") {
+            self.skip_newlines();
+            lhs = Expr::And(Box::new(lhs), Box::new(self.parse_in()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_in(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_match()?;
+        while self.is_keyword("in") {
+            self.advance();
+            let array = self.expect_name()?;
+            let subs = match lhs {
+                Expr::Group(items) => items,
+                expr => vec![expr],
             };
-            let right = self.parse_multiplication()?;
-            left = Expr::BinOp(Box::new(left), op, Box::new(right));
+            lhs = Expr::In(subs, array);
         }
-        Ok(left)
+        Ok(lhs)
     }
 
-    fn parse_multiplication(&mut self) -> Result<Expr, String> {
-        let mut left = self.parse_concat()?;
+    fn parse_match(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_comparison()?;
         loop {
-            self.skip_ws();
-            let op = if self.peek() == Some(b'*') && self.peek2() != Some(b'=') {
-                self.advance();
-                BinOp::Mul
-            } else if self.peek() == Some(b'/') && self.peek2() != Some(b'=') {
-                self.advance();
-                BinOp::Div
-            } else if self.peek() == Some(b'%') {
-                self.advance();
-                BinOp::Mod
-            } else {
-                break;
+            let negate = match self.peek() {
+                Token::Punct("~") => false,
+                Token::Punct("!~") => true,
+                _ => return Ok(lhs),
             };
-            let right = self.parse_concat()?;
-            left = Expr::BinOp(Box::new(left), op, Box::new(right));
+            self.advance();
+            let rhs = self.parse_comparison()?;
+            lhs = Expr::Match(negate, Box::new(lhs), Box::new(rhs));
         }
-        Ok(left)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_concat()?;
+        let op = match self.peek() {
+            Token::Punct("<") => CmpOp::Lt,
+            Token::Punct("<=") => CmpOp::Le,
+            Token::Punct(">") if !self.no_gt => CmpOp::Gt,
+            Token::Punct(">=") => CmpOp::Ge,
+            Token::Punct("==") => CmpOp::Eq,
+            Token::Punct("!=") => CmpOp::Ne,
+            _ => return Ok(lhs),
+        };
+        self.advance();
+        let rhs = self.parse_concat()?;
+        Ok(Expr::Compare(op, Box::new(lhs), Box::new(rhs)))
     }
 
     fn parse_concat(&mut self) -> Result<Expr, String> {
-        let left = self.parse_primary()?;
-        self.skip_ws();
-        // string concatenation: two adjacent expressions with space between
-        // only if next char could start an expression (not an operator or delimiter)
-        if let Some(c) = self.peek() {
-            if c == b'$' || c == b'"' || c.is_ascii_alphabetic() || c == b'(' {
-                let right = self.parse_concat()?;
-                return Ok(Expr::Concat(Box::new(left), Box::new(right)));
-            }
+        let mut lhs = self.parse_additive()?;
+        while matches!(
+            self.peek(),
+            Token::Number(_)
+                | Token::Str(_)
+                | Token::Regex(_)
+                | Token::Name(_)
+                | Token::FuncName(_)
+                | Token::Builtin(_)
+                | Token::Punct("$" | "(" | "++" | "--")
+        ) {
+            let rhs = self.parse_additive()?;
+            lhs = Expr::Concat(Box::new(lhs), Box::new(rhs));
         }
-        Ok(left)
+        Ok(lhs)
     }
 
-    fn parse_primary(&mut self) -> Result<Expr, String> {
-        self.skip_ws();
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Punct("+") => BinOp::Add,
+                Token::Punct("-") => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Punct("*") => BinOp::Mul,
+                Token::Punct("/") => BinOp::Div,
+                Token::Punct("%") => BinOp::Mod,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
         match self.peek() {
-            Some(b'$') => {
+            Token::Punct("!") => {
                 self.advance();
-                let idx = self.parse_primary()?;
-                Ok(Expr::Field(Box::new(idx)))
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
             }
-            Some(b'"') => {
+            Token::Punct("-") => {
                 self.advance();
-                let start = self.pos;
-                while self.pos < self.input.len() {
-                    if self.input.as_bytes()[self.pos] == b'"' {
-                        let s = self.input[start..self.pos].to_string();
+                Ok(Expr::Neg(Box::new(self.parse_unary()?)))
+            }
+            Token::Punct("+") => {
+                self.advance();
+                Ok(Expr::Pos(Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Expr, String> {
+        let base = self.parse_incdec()?;
+        if !self.eat_punct("^") {
+            return Ok(base);
+        }
+        // Right associative, and the exponent may be negated: 2^-1.
+        let exponent = match self.peek() {
+            Token::Punct("-" | "+" | "!") => self.parse_unary()?,
+            _ => self.parse_power()?,
+        };
+        Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)))
+    }
+
+    fn parse_incdec(&mut self) -> Result<Expr, String> {
+        if self.is_punct("++") || self.is_punct("--") {
+            let delta = if self.advance() == Token::Punct("++") {
+                1.0
+            } else {
+                -1.0
+            };
+            let target = self.parse_incdec()?;
+            if !target.is_lvalue() {
+                return Err(self.syntax_error());
+            }
+            return Ok(Expr::IncDec {
+                target: Box::new(target),
+                delta,
+                prefix: true,
+            });
+        }
+        let expr = self.parse_primary()?;
+        if expr.is_lvalue() && (self.is_punct("++") || self.is_punct("--")) {
+            let delta = if self.advance() == Token::Punct("++") {
+                1.0
+            } else {
+                -1.0
+            };
+            return Ok(Expr::IncDec {
+                target: Box::new(expr),
+                delta,
+                prefix: false,
+            });
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.advance() {
+            Token::Number(n) => Ok(Expr::Num(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Regex(re) => Ok(Expr::Regex(re)),
+            Token::Punct("$") => {
+                let index = match self.peek() {
+                    Token::Punct("++" | "--") => self.parse_incdec()?,
+                    Token::Punct("-") => {
                         self.advance();
-                        return Ok(Expr::StringLit(s));
+                        Expr::Neg(Box::new(self.parse_primary()?))
                     }
-                    if self.input.as_bytes()[self.pos] == b'\\' {
-                        self.advance();
+                    _ => self.parse_primary()?,
+                };
+                Ok(Expr::Field(Box::new(index)))
+            }
+            Token::Punct("(") => {
+                let saved = self.no_gt;
+                self.no_gt = false;
+                let mut items = vec![self.parse_expr()?];
+                while self.eat_punct(",") {
+                    self.skip_newlines();
+                    items.push(self.parse_expr()?);
+                }
+                self.expect(")")?;
+                self.no_gt = saved;
+                if items.len() == 1 {
+                    Ok(items.remove(0))
+                } else {
+                    Ok(Expr::Group(items))
+                }
+            }
+            Token::Name(name) => {
+                if self.eat_punct("[") {
+                    Ok(Expr::Index(name, self.parse_subscripts()?))
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
+            Token::FuncName(name) => {
+                self.advance();
+                Ok(Expr::Call(name, self.parse_call_args()?))
+            }
+            Token::Builtin(name) => {
+                if self.eat_punct("(") {
+                    Ok(Expr::Builtin(name, self.parse_call_args()?))
+                } else if name == "length" {
+                    Ok(Expr::Builtin(name, Vec::new()))
+                } else {
+                    Err(format!("awk: expected '(' after {name}"))
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.syntax_error())
+            }
+        }
+    }
+
+    /// Parses arguments after the opening parenthesis of a call.
+    fn parse_call_args(&mut self) -> Result<Vec<Expr>, String> {
+        let saved = self.no_gt;
+        self.no_gt = false;
+        let mut args = Vec::new();
+        self.skip_newlines();
+        while !self.eat_punct(")") {
+            if !args.is_empty() {
+                self.expect(",")?;
+                self.skip_newlines();
+            }
+            args.push(self.parse_expr()?);
+            self.skip_newlines();
+        }
+        self.no_gt = saved;
+        Ok(args)
+    }
+}
+
+/// Collects names used as arrays in `stmt`.
+fn collect_array_names(stmt: &Stmt, names: &mut Vec<String>) {
+    let mut exprs: Vec<&Expr> = Vec::new();
+    let mut stmts: Vec<&Stmt> = Vec::new();
+    match stmt {
+        Stmt::Print(args, output) | Stmt::Printf(args, output) => {
+            exprs.extend(args);
+            exprs.extend(output.as_ref().map(|o| &o.target));
+        }
+        Stmt::Expr(e) | Stmt::Do(_, e) | Stmt::While(e, _) => exprs.push(e),
+        Stmt::If(cond, ..) => exprs.push(cond),
+        Stmt::For(init, cond, step, _) => exprs.extend([init, cond, step].into_iter().flatten()),
+        Stmt::ForIn(_, array, _) | Stmt::Delete(array, None) => names.push(array.clone()),
+        Stmt::Delete(array, Some(subs)) => {
+            names.push(array.clone());
+            exprs.extend(subs);
+        }
+        Stmt::Exit(e) | Stmt::Return(e) => exprs.extend(e),
+        _ => {}
+    }
+    match stmt {
+        Stmt::If(_, then, otherwise) => {
+            stmts.push(then);
+            stmts.extend(otherwise.as_deref());
+        }
+        Stmt::While(_, body) | Stmt::Do(body, _) | Stmt::For(.., body) | Stmt::ForIn(.., body) => {
+            stmts.push(body)
+        }
+        Stmt::Block(body) => stmts.extend(body),
+        _ => {}
+    }
+    for expr in exprs {
+        collect_expr_array_names(expr, names);
+    }
+    for stmt in stmts {
+        collect_array_names(stmt, names);
+    }
+}
+
+fn collect_expr_array_names(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Index(name, subs) => {
+            names.push(name.clone());
+            subs.iter().for_each(|e| collect_expr_array_names(e, names));
+        }
+        Expr::In(subs, name) => {
+            names.push(name.clone());
+            subs.iter().for_each(|e| collect_expr_array_names(e, names));
+        }
+        Expr::Builtin("split", args) => {
+            if let Some(Expr::Var(name)) = args.get(1) {
+                names.push(name.clone());
+            }
+            args.iter().for_each(|e| collect_expr_array_names(e, names));
+        }
+        Expr::Builtin(_, args) | Expr::Call(_, args) | Expr::Group(args) => {
+            args.iter().for_each(|e| collect_expr_array_names(e, names));
+        }
+        Expr::Field(e) | Expr::Not(e) | Expr::Neg(e) | Expr::Pos(e) => {
+            collect_expr_array_names(e, names)
+        }
+        Expr::IncDec { target, .. } => collect_expr_array_names(target, names),
+        Expr::Assign(a, _, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b)
+        | Expr::Binary(_, a, b)
+        | Expr::Compare(_, a, b)
+        | Expr::Match(_, a, b)
+        | Expr::Concat(a, b) => {
+            collect_expr_array_names(a, names);
+            collect_expr_array_names(b, names);
+        }
+        Expr::Cond(a, b, c) => {
+            collect_expr_array_names(a, names);
+            collect_expr_array_names(b, names);
+            collect_expr_array_names(c, names);
+        }
+        Expr::Num(_) | Expr::Str(_) | Expr::Regex(_) | Expr::Var(_) => {}
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Values
+
+#[derive(Debug, Clone)]
+enum Value {
+    Uninit,
+    Num(f64),
+    Str(String),
+    /// Input-derived string (fields, `split` elements, `-v` values) that
+    /// compares numerically when it looks like a number.
+    StrNum(String),
+}
+
+impl Value {
+    fn to_num(&self) -> f64 {
+        match self {
+            Value::Uninit => 0.0,
+            Value::Num(n) => *n,
+            Value::Str(s) | Value::StrNum(s) => parse_number_prefix(s).0,
+        }
+    }
+
+    fn to_str(&self, convfmt: &str) -> String {
+        match self {
+            Value::Uninit => String::new(),
+            Value::Num(n) => number_to_string(*n, convfmt),
+            Value::Str(s) | Value::StrNum(s) => s.clone(),
+        }
+    }
+
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Uninit => false,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::StrNum(s) => {
+                if looks_numeric(s) {
+                    parse_number_prefix(s).0 != 0.0
+                } else {
+                    !s.is_empty()
+                }
+            }
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        match self {
+            Value::Uninit | Value::Num(_) => true,
+            Value::Str(_) => false,
+            Value::StrNum(s) => looks_numeric(s),
+        }
+    }
+}
+
+/// Parses the longest numeric prefix of `s` like `strtod`, returning the
+/// value and the number of bytes consumed (0 if there is no number).
+fn parse_number_prefix(s: &str) -> (f64, usize) {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && matches!(bytes[i], b' ' | b'\t' | b'\n' | b'\r') {
+        i += 1;
+    }
+    let start = i;
+    if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+        i += 1;
+    }
+    let digits_start = i;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        i += 1;
+    }
+    let mut has_digits = i > digits_start;
+    if i < bytes.len() && bytes[i] == b'.' {
+        i += 1;
+        let frac_start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        has_digits |= i > frac_start;
+    }
+    if !has_digits {
+        return (0.0, 0);
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        let mut j = i + 1;
+        if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+            j += 1;
+        }
+        if j < bytes.len() && bytes[j].is_ascii_digit() {
+            while j < bytes.len() && bytes[j].is_ascii_digit() {
+                j += 1;
+            }
+            i = j;
+        }
+    }
+    (s[start..i].parse().unwrap_or(0.0), i)
+}
+
+fn looks_numeric(s: &str) -> bool {
+    let (_, end) = parse_number_prefix(s);
+    end > 0 && s[end..].trim_matches([' ', '\t', '\n', '\r']).is_empty()
+}
+
+/// Formats a number: integers print as integers, anything else with `fmt`
+/// (CONVFMT or OFMT).
+fn number_to_string(n: f64, fmt: &str) -> String {
+    if n == n.trunc() && n.abs() < 1e16 {
+        format!("{}", n as i64)
+    } else if n.is_nan() {
+        "nan".into()
+    } else if n.is_infinite() {
+        if n > 0.0 { "inf" } else { "-inf" }.into()
+    } else {
+        sprintf(fmt, &[Value::Num(n)], "%.6g")
+    }
+}
+
+fn format_number(n: f64) -> String {
+    number_to_string(n, "%.6g")
+}
+
+// ---------------------------------------------------------------------------
+// printf
+
+/// Formats `args` according to the printf-style `fmt`. Missing arguments
+/// are treated as uninitialized.
+fn sprintf(fmt: &str, args: &[Value], convfmt: &str) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut spec = String::from("%");
+        let mut left = false;
+        let mut plus = false;
+        let mut space = false;
+        let mut alt = false;
+        let mut zero = false;
+        while let Some(&f) = chars.peek() {
+            match f {
+                '-' => left = true,
+                '+' => plus = true,
+                ' ' => space = true,
+                '#' => alt = true,
+                '0' => zero = true,
+                _ => break,
+            }
+            spec.push(f);
+            chars.next();
+        }
+        let width;
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            spec.push('*');
+            let w = args.next().map_or(0.0, Value::to_num) as i64;
+            if w < 0 {
+                left = true;
+            }
+            width = w.unsigned_abs() as usize;
+        } else {
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            spec.push_str(&digits);
+            width = digits.parse().unwrap_or(0);
+        }
+        let mut precision = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.push('.');
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                spec.push('*');
+                let p = args.next().map_or(0.0, Value::to_num) as i64;
+                precision = (p >= 0).then_some(p as usize);
+            } else {
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                }
+                spec.push_str(&digits);
+                precision = Some(digits.parse().unwrap_or(0));
+            }
+        }
+        let Some(conv) = chars.next() else {
+            out.push_str(&spec);
+            break;
+        };
+        let sign = |negative: bool| {
+            if negative {
+                "-"
+            } else if plus {
+                "+"
+            } else if space {
+                " "
+            } else {
+                ""
+            }
+        };
+        let (prefix, body, numeric) = match conv {
+            '%' => {
+                out.push('%');
+                continue;
+            }
+            'd' | 'i' => {
+                let n = args.next().map_or(0.0, Value::to_num);
+                if !n.is_finite() {
+                    let body = if n.is_nan() { "nan" } else { "inf" };
+                    (sign(n < 0.0).to_string(), body.to_string(), false)
+                } else {
+                    let n = n.trunc();
+                    let mut digits = format!("{}", n.abs() as u64);
+                    if let Some(p) = precision {
+                        if p == 0 && n == 0.0 {
+                            digits.clear();
+                        }
+                        digits = format!("{digits:0>p$}");
+                    }
+                    (sign(n < 0.0).to_string(), digits, precision.is_none())
+                }
+            }
+            'o' | 'x' | 'X' | 'u' => {
+                let n = args.next().map_or(0.0, Value::to_num).trunc();
+                let n = if n < 0.0 { n as i64 as u64 } else { n as u64 };
+                let mut digits = match conv {
+                    'o' => format!("{n:o}"),
+                    'x' => format!("{n:x}"),
+                    'X' => format!("{n:X}"),
+                    _ => format!("{n}"),
+                };
+                if let Some(p) = precision {
+                    if p == 0 && n == 0 {
+                        digits.clear();
+                    }
+                    digits = format!("{digits:0>p$}");
+                }
+                let prefix = match conv {
+                    'o' if alt && !digits.starts_with('0') => "0",
+                    'x' if alt && n != 0 => "0x",
+                    'X' if alt && n != 0 => "0X",
+                    _ => "",
+                };
+                (prefix.to_string(), digits, precision.is_none())
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let n = args.next().map_or(0.0, Value::to_num);
+                let p = precision.unwrap_or(6);
+                let body = if n.is_nan() {
+                    "nan".to_string()
+                } else if n.is_infinite() {
+                    "inf".to_string()
+                } else {
+                    match conv {
+                        'e' | 'E' => format_exp(n.abs(), p, alt),
+                        'f' | 'F' => format_fixed(n.abs(), p, alt),
+                        _ => format_general(n.abs(), p, alt),
+                    }
+                };
+                let body = if conv.is_ascii_uppercase() {
+                    body.to_uppercase()
+                } else {
+                    body
+                };
+                let negative = n.is_sign_negative() && !n.is_nan();
+                (sign(negative).to_string(), body, n.is_finite())
+            }
+            'c' => {
+                let body = match args.next() {
+                    Some(Value::Num(n)) => char::from_u32(*n as u32)
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    Some(v) => v
+                        .to_str(convfmt)
+                        .chars()
+                        .next()
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    None => String::new(),
+                };
+                (String::new(), body, false)
+            }
+            's' => {
+                let s = args.next().map_or(String::new(), |v| v.to_str(convfmt));
+                let body = match precision {
+                    Some(p) => s.chars().take(p).collect(),
+                    None => s,
+                };
+                (String::new(), body, false)
+            }
+            other => {
+                out.push_str(&spec);
+                out.push(other);
+                continue;
+            }
+        };
+        let len = prefix.chars().count() + body.chars().count();
+        let pad = width.saturating_sub(len);
+        if left {
+            out.push_str(&prefix);
+            out.push_str(&body);
+            out.extend(std::iter::repeat_n(' ', pad));
+        } else if zero && numeric {
+            out.push_str(&prefix);
+            out.extend(std::iter::repeat_n('0', pad));
+            out.push_str(&body);
+        } else {
+            out.extend(std::iter::repeat_n(' ', pad));
+            out.push_str(&prefix);
+            out.push_str(&body);
+        }
+    }
+    out
+}
+
+fn format_fixed(n: f64, precision: usize, alt: bool) -> String {
+    let mut s = format!("{n:.precision$}");
+    if alt && precision == 0 {
+        s.push('.');
+    }
+    s
+}
+
+fn format_exp(n: f64, precision: usize, alt: bool) -> String {
+    let s = format!("{n:.precision$e}");
+    let (mantissa, exponent) = s.split_once('e').unwrap_or((&s, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let dot = if alt && precision == 0 { "." } else { "" };
+    format!("{mantissa}{dot}e{sign}{:02}", exponent.abs())
+}
+
+fn format_general(n: f64, precision: usize, alt: bool) -> String {
+    let p = precision.max(1);
+    let exponent = if n == 0.0 {
+        0
+    } else {
+        let s = format!("{n:.prec$e}", prec = p - 1);
+        s.split_once('e')
+            .and_then(|(_, e)| e.parse::<i32>().ok())
+            .unwrap_or(0)
+    };
+    let s = if exponent < -4 || exponent >= p as i32 {
+        format_exp(n, p - 1, alt)
+    } else {
+        format_fixed(n, (p as i32 - 1 - exponent) as usize, alt)
+    };
+    if alt {
+        return s;
+    }
+    match s.split_once('e') {
+        Some((mantissa, exponent)) => format!("{}e{exponent}", strip_zeros(mantissa)),
+        None => strip_zeros(&s).to_string(),
+    }
+}
+
+fn strip_zeros(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Interpreter
+
+enum Jump {
+    Next,
+    NextFile,
+    Exit,
+    Break,
+    Continue,
+    Return(Value),
+    Error(String),
+}
+
+impl From<String> for Jump {
+    fn from(e: String) -> Self {
+        Jump::Error(e)
+    }
+}
+
+enum Cell {
+    Value(Value),
+    /// Index into [`Interp::arrays`]; arrays are passed by reference.
+    Array(usize),
+}
+
+struct Frame {
+    locals: HashMap<String, Cell>,
+    /// Arrays created for local variables, freed when the call returns.
+    owned: Vec<usize>,
+}
+
+/// A resolved assignment target.
+enum Place {
+    Var(String),
+    Field(usize),
+    Elem(usize, String),
+}
+
+struct OutFile {
+    data: String,
+    /// Whether the next flush replaces the file (`>`) rather than appends.
+    truncate: bool,
+}
+
+struct Interp<'p> {
+    program: &'p Program,
+    globals: HashMap<String, Cell>,
+    frames: Vec<Frame>,
+    arrays: Vec<HashMap<String, Value>>,
+    free_arrays: Vec<usize>,
+    record: String,
+    fields: Vec<String>,
+    range_active: Vec<bool>,
+    regexes: HashMap<String, Regex>,
+    files: HashMap<String, OutFile>,
+    output: String,
+    exit_code: i32,
+    rand_seed: f64,
+    rand_state: u64,
+}
+
+impl<'p> Interp<'p> {
+    fn new(program: &'p Program) -> Self {
+        let mut interp = Self {
+            program,
+            globals: HashMap::new(),
+            frames: Vec::new(),
+            arrays: Vec::new(),
+            free_arrays: Vec::new(),
+            record: String::new(),
+            fields: Vec::new(),
+            range_active: vec![false; program.rules.len()],
+            regexes: HashMap::new(),
+            files: HashMap::new(),
+            output: String::new(),
+            exit_code: 0,
+            rand_seed: 0.0,
+            rand_state: 0,
+        };
+        for (name, value) in [
+            ("FS", " "),
+            ("OFS", " "),
+            ("ORS", "\n"),
+            ("RS", "\n"),
+            ("SUBSEP", SUBSEP),
+            ("CONVFMT", "%.6g"),
+            ("OFMT", "%.6g"),
+            ("FILENAME", ""),
+        ] {
+            interp.set_global(name, Value::Str(value.into()));
+        }
+        for name in ["NR", "FNR", "RSTART"] {
+            interp.set_global(name, Value::Num(0.0));
+        }
+        interp.set_global("RLENGTH", Value::Num(-1.0));
+        let environ = interp.new_array();
+        for (key, value) in std::env::vars() {
+            interp.arrays[environ].insert(key, Value::StrNum(value));
+        }
+        interp
+            .globals
+            .insert("ENVIRON".into(), Cell::Array(environ));
+        interp
+    }
+
+    fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.into(), Cell::Value(value));
+    }
+
+    fn global_str(&self, name: &str) -> String {
+        match self.globals.get(name) {
+            Some(Cell::Value(v)) => v.to_str(&self.convfmt()),
+            _ => String::new(),
+        }
+    }
+
+    fn global_num(&self, name: &str) -> f64 {
+        match self.globals.get(name) {
+            Some(Cell::Value(v)) => v.to_num(),
+            _ => 0.0,
+        }
+    }
+
+    fn convfmt(&self) -> String {
+        match self.globals.get("CONVFMT") {
+            Some(Cell::Value(v)) => v.to_str("%.6g"),
+            _ => "%.6g".into(),
+        }
+    }
+
+    fn to_str(&self, value: &Value) -> String {
+        value.to_str(&self.convfmt())
+    }
+
+    /// Converts a value for output, using OFMT for non-integral numbers.
+    fn to_output_str(&self, value: &Value) -> String {
+        match value {
+            Value::Num(n) => number_to_string(*n, &self.global_str("OFMT")),
+            other => self.to_str(other),
+        }
+    }
+
+    fn new_array(&mut self) -> usize {
+        match self.free_arrays.pop() {
+            Some(id) => id,
+            None => {
+                self.arrays.push(HashMap::new());
+                self.arrays.len() - 1
+            }
+        }
+    }
+
+    fn cell_mut(&mut self, name: &str) -> &mut Cell {
+        match self.frames.last_mut().and_then(|f| f.locals.get_mut(name)) {
+            Some(cell) => cell,
+            None => self
+                .globals
+                .entry(name.to_string())
+                .or_insert(Cell::Value(Value::Uninit)),
+        }
+    }
+
+    fn cell(&self, name: &str) -> Option<&Cell> {
+        self.frames
+            .last()
+            .and_then(|frame| frame.locals.get(name))
+            .or_else(|| self.globals.get(name))
+    }
+
+    fn array_id(&mut self, name: &str) -> Result<usize, String> {
+        match self.cell_mut(name) {
+            Cell::Array(id) => return Ok(*id),
+            Cell::Value(Value::Uninit) => {}
+            Cell::Value(_) => return Err(format!("awk: can't use scalar {name} as array")),
+        }
+        let id = self.new_array();
+        *self.cell_mut(name) = Cell::Array(id);
+        if let Some(frame) = self
+            .frames
+            .last_mut()
+            .filter(|f| f.locals.contains_key(name))
+        {
+            frame.owned.push(id);
+        }
+        Ok(id)
+    }
+
+    fn get_var(&self, name: &str) -> Result<Value, String> {
+        if name == "NF"
+            && self
+                .frames
+                .last()
+                .is_none_or(|f| !f.locals.contains_key(name))
+        {
+            return Ok(Value::Num(self.fields.len() as f64));
+        }
+        match self.cell(name) {
+            None => Ok(Value::Uninit),
+            Some(Cell::Value(v)) => Ok(v.clone()),
+            Some(Cell::Array(_)) => Err(format!("awk: can't use array {name} in scalar context")),
+        }
+    }
+
+    fn set_var(&mut self, name: &str, value: Value) -> Result<(), String> {
+        if name == "NF"
+            && self
+                .frames
+                .last()
+                .is_none_or(|f| !f.locals.contains_key(name))
+        {
+            let nf = value.to_num();
+            if nf < 0.0 {
+                return Err("awk: NF set to negative value".into());
+            }
+            self.fields.resize(nf as usize, String::new());
+            self.rebuild_record();
+            return Ok(());
+        }
+        match self.cell_mut(name) {
+            Cell::Array(_) => Err(format!("awk: can't assign to array {name}")),
+            cell => {
+                *cell = Cell::Value(value);
+                Ok(())
+            }
+        }
+    }
+
+    fn get_field(&self, index: usize) -> Value {
+        if index == 0 {
+            Value::StrNum(self.record.clone())
+        } else {
+            self.fields
+                .get(index - 1)
+                .map_or(Value::Uninit, |f| Value::StrNum(f.clone()))
+        }
+    }
+
+    fn set_field(&mut self, index: usize, value: String) -> Result<(), String> {
+        if index == 0 {
+            return self.set_record(value);
+        }
+        if index > self.fields.len() {
+            self.fields.resize(index, String::new());
+        }
+        self.fields[index - 1] = value;
+        self.rebuild_record();
+        Ok(())
+    }
+
+    fn rebuild_record(&mut self) {
+        self.record = self.fields.join(&self.global_str("OFS"));
+    }
+
+    fn set_record(&mut self, record: String) -> Result<(), String> {
+        let fs = self.global_str("FS");
+        let paragraph = self.global_str("RS").is_empty();
+        self.fields = self.split_with(&record, &fs, paragraph)?;
+        self.record = record;
+        Ok(())
+    }
+
+    /// Splits `s` into fields using the field separator `fs`.
+    fn split_with(&mut self, s: &str, fs: &str, paragraph: bool) -> Result<Vec<String>, String> {
+        if s.is_empty() {
+            return Ok(Vec::new());
+        }
+        if fs == " " {
+            return Ok(s
+                .split([' ', '\t', '\n'])
+                .filter(|f| !f.is_empty())
+                .map(String::from)
+                .collect());
+        }
+        if fs.is_empty() {
+            return Ok(s.chars().map(String::from).collect());
+        }
+        let mut chars = fs.chars();
+        if let (Some(c), None, false) = (chars.next(), chars.next(), paragraph) {
+            return Ok(s.split(c).map(String::from).collect());
+        }
+        let pattern = if fs.chars().count() == 1 {
+            regex::escape(fs)
+        } else {
+            fs.to_string()
+        };
+        let pattern = if paragraph {
+            format!("(?:{pattern})|\n")
+        } else {
+            pattern
+        };
+        let re = self.regex(&pattern)?;
+        Ok(re.split(s).map(String::from).collect())
+    }
+
+    fn regex(&mut self, pattern: &str) -> Result<Regex, String> {
+        if let Some(re) = self.regexes.get(pattern) {
+            return Ok(re.clone());
+        }
+        let re = Regex::new(pattern).map_err(|e| format!("awk: invalid regex: {e}"))?;
+        self.regexes.insert(pattern.to_string(), re.clone());
+        Ok(re)
+    }
+
+    /// Compiles the regex operand of `~`, `split`, `sub`, `gsub` and `match`:
+    /// a regex literal is used as is, anything else as a dynamic regex.
+    fn regex_operand(&mut self, expr: &Expr) -> Result<Regex, Jump> {
+        let pattern = match expr {
+            Expr::Regex(re) => re.clone(),
+            expr => {
+                let value = self.eval(expr)?;
+                self.to_str(&value)
+            }
+        };
+        Ok(self.regex(&pattern)?)
+    }
+
+    fn subscript(&mut self, subs: &[Expr]) -> Result<String, Jump> {
+        let mut key = String::new();
+        for (i, sub) in subs.iter().enumerate() {
+            if i > 0 {
+                key.push_str(&self.global_str("SUBSEP"));
+            }
+            let value = self.eval(sub)?;
+            key.push_str(&self.to_str(&value));
+        }
+        Ok(key)
+    }
+
+    fn field_index(&mut self, expr: &Expr) -> Result<usize, Jump> {
+        let n = self.eval(expr)?.to_num();
+        if n < 0.0 {
+            return Err(Jump::Error(format!(
+                "awk: trying to access out of range field {}",
+                n as i64
+            )));
+        }
+        Ok(n as usize)
+    }
+
+    fn place(&mut self, target: &Expr) -> Result<Place, Jump> {
+        match target {
+            Expr::Var(name) => Ok(Place::Var(name.clone())),
+            Expr::Field(index) => Ok(Place::Field(self.field_index(index)?)),
+            Expr::Index(name, subs) => {
+                let key = self.subscript(subs)?;
+                Ok(Place::Elem(self.array_id(name)?, key))
+            }
+            _ => Err(Jump::Error("awk: invalid assignment target".into())),
+        }
+    }
+
+    fn get_place(&mut self, place: &Place) -> Result<Value, String> {
+        match place {
+            Place::Var(name) => self.get_var(name),
+            Place::Field(index) => Ok(self.get_field(*index)),
+            Place::Elem(id, key) => Ok(self.arrays[*id]
+                .entry(key.clone())
+                .or_insert(Value::Uninit)
+                .clone()),
+        }
+    }
+
+    fn set_place(&mut self, place: Place, value: Value) -> Result<(), String> {
+        match place {
+            Place::Var(name) => self.set_var(&name, value),
+            Place::Field(index) => {
+                let s = self.to_str(&value);
+                self.set_field(index, s)
+            }
+            Place::Elem(id, key) => {
+                self.arrays[id].insert(key, value);
+                Ok(())
+            }
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, Jump> {
+        Ok(match expr {
+            Expr::Num(n) => Value::Num(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Regex(re) => {
+                let re = self.regex(re)?;
+                Value::Num(re.is_match(&self.record) as i32 as f64)
+            }
+            Expr::Var(name) => self.get_var(name)?,
+            Expr::Field(index) => {
+                let index = self.field_index(index)?;
+                self.get_field(index)
+            }
+            Expr::Index(..) => {
+                let place = self.place(expr)?;
+                self.get_place(&place)?
+            }
+            Expr::Group(_) => return Err(Jump::Error("awk: syntax error at ','".into())),
+            Expr::Assign(target, op, rhs) => {
+                let value = self.eval(rhs)?;
+                let place = self.place(target)?;
+                let value = match op {
+                    None => value,
+                    Some(op) => {
+                        let current = self.get_place(&place)?.to_num();
+                        Value::Num(arith(*op, current, value.to_num())?)
+                    }
+                };
+                self.set_place(place, value.clone())?;
+                value
+            }
+            Expr::Cond(cond, then, otherwise) => {
+                if self.eval(cond)?.is_true() {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::And(a, b) => {
+                Value::Num((self.eval(a)?.is_true() && self.eval(b)?.is_true()) as i32 as f64)
+            }
+            Expr::Or(a, b) => {
+                Value::Num((self.eval(a)?.is_true() || self.eval(b)?.is_true()) as i32 as f64)
+            }
+            Expr::Not(e) => Value::Num(!self.eval(e)?.is_true() as i32 as f64),
+            Expr::Neg(e) => Value::Num(-self.eval(e)?.to_num()),
+            Expr::Pos(e) => Value::Num(self.eval(e)?.to_num()),
+            Expr::Binary(op, a, b) => {
+                let a = self.eval(a)?.to_num();
+                let b = self.eval(b)?.to_num();
+                Value::Num(arith(*op, a, b)?)
+            }
+            Expr::Compare(op, a, b) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                let ordering = if a.is_numeric() && b.is_numeric() {
+                    a.to_num().partial_cmp(&b.to_num())
+                } else {
+                    Some(self.to_str(&a).cmp(&self.to_str(&b)))
+                };
+                let result = match ordering {
+                    Some(o) => match op {
+                        CmpOp::Lt => o.is_lt(),
+                        CmpOp::Le => o.is_le(),
+                        CmpOp::Gt => o.is_gt(),
+                        CmpOp::Ge => o.is_ge(),
+                        CmpOp::Eq => o.is_eq(),
+                        CmpOp::Ne => o.is_ne(),
+                    },
+                    None => matches!(op, CmpOp::Ne),
+                };
+                Value::Num(result as i32 as f64)
+            }
+            Expr::Match(negate, lhs, rhs) => {
+                let value = self.eval(lhs)?;
+                let s = self.to_str(&value);
+                let re = self.regex_operand(rhs)?;
+                Value::Num((re.is_match(&s) != *negate) as i32 as f64)
+            }
+            Expr::Concat(a, b) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                Value::Str(self.to_str(&a) + &self.to_str(&b))
+            }
+            Expr::In(subs, name) => {
+                let key = self.subscript(subs)?;
+                let id = self.array_id(name)?;
+                Value::Num(self.arrays[id].contains_key(&key) as i32 as f64)
+            }
+            Expr::IncDec {
+                target,
+                delta,
+                prefix,
+            } => {
+                let place = self.place(target)?;
+                let old = self.get_place(&place)?.to_num();
+                self.set_place(place, Value::Num(old + delta))?;
+                Value::Num(if *prefix { old + delta } else { old })
+            }
+            Expr::Builtin(name, args) => self.builtin(name, args)?,
+            Expr::Call(name, args) => self.call(name, args)?,
+        })
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Value, Jump> {
+        let program = self.program;
+        let func = program
+            .functions
+            .get(name)
+            .ok_or_else(|| format!("awk: calling undefined function {name}"))?;
+        if args.len() > func.params.len() {
+            return Err(Jump::Error(format!(
+                "awk: function {name} called with {} args, accepts only {}",
+                args.len(),
+                func.params.len()
+            )));
+        }
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(Jump::Error(format!(
+                "awk: function call nesting too deep in {name}"
+            )));
+        }
+        let mut locals = HashMap::new();
+        for (i, param) in func.params.iter().enumerate() {
+            let cell = match args.get(i) {
+                Some(Expr::Var(var)) => match self.cell(var) {
+                    Some(Cell::Array(id)) => Cell::Array(*id),
+                    Some(Cell::Value(Value::Uninit)) | None
+                        if func.array_params.contains(param) =>
+                    {
+                        Cell::Array(self.array_id(var)?)
+                    }
+                    _ => Cell::Value(self.get_var(var)?),
+                },
+                Some(expr) => Cell::Value(self.eval(expr)?),
+                None => Cell::Value(Value::Uninit),
+            };
+            locals.insert(param.clone(), cell);
+        }
+        self.frames.push(Frame {
+            locals,
+            owned: Vec::new(),
+        });
+        let result = self.exec_block(&func.body);
+        if let Some(frame) = self.frames.pop() {
+            for id in frame.owned {
+                self.arrays[id].clear();
+                self.free_arrays.push(id);
+            }
+        }
+        match result {
+            Ok(()) => Ok(Value::Uninit),
+            Err(Jump::Return(value)) => Ok(value),
+            Err(Jump::Break | Jump::Continue) => {
+                Err(Jump::Error("awk: break or continue outside a loop".into()))
+            }
+            Err(jump) => Err(jump),
+        }
+    }
+
+    fn builtin(&mut self, name: &str, args: &[Expr]) -> Result<Value, Jump> {
+        let arity = |min: usize, max: usize| -> Result<(), Jump> {
+            if args.len() < min || args.len() > max {
+                return Err(Jump::Error(format!(
+                    "awk: {name}: wrong number of arguments"
+                )));
+            }
+            Ok(())
+        };
+        Ok(match name {
+            "length" => {
+                arity(0, 1)?;
+                match args.first() {
+                    None => Value::Num(self.record.chars().count() as f64),
+                    Some(Expr::Var(var)) if matches!(self.cell(var), Some(Cell::Array(_))) => {
+                        let id = self.array_id(var)?;
+                        Value::Num(self.arrays[id].len() as f64)
+                    }
+                    Some(expr) => {
+                        let value = self.eval(expr)?;
+                        Value::Num(self.to_str(&value).chars().count() as f64)
+                    }
+                }
+            }
+            "substr" => {
+                arity(2, 3)?;
+                let value = self.eval(&args[0])?;
+                let s: Vec<char> = self.to_str(&value).chars().collect();
+                let start = self.eval(&args[1])?.to_num().round();
+                let end = match args.get(2) {
+                    Some(len) => start + self.eval(len)?.to_num().round(),
+                    None => f64::INFINITY,
+                };
+                let start = start.max(1.0);
+                let end = end.min(s.len() as f64 + 1.0);
+                if end <= start {
+                    Value::Str(String::new())
+                } else {
+                    Value::Str(s[start as usize - 1..end as usize - 1].iter().collect())
+                }
+            }
+            "index" => {
+                arity(2, 2)?;
+                let s = self.eval(&args[0])?;
+                let t = self.eval(&args[1])?;
+                let (s, t) = (self.to_str(&s), self.to_str(&t));
+                let position = match s.find(&t) {
+                    Some(i) if !t.is_empty() => s[..i].chars().count() + 1,
+                    _ => 0,
+                };
+                Value::Num(position as f64)
+            }
+            "split" => {
+                arity(2, 3)?;
+                let value = self.eval(&args[0])?;
+                let s = self.to_str(&value);
+                let Expr::Var(array) = &args[1] else {
+                    return Err(Jump::Error(
+                        "awk: split: second argument must be an array".into(),
+                    ));
+                };
+                let parts = match args.get(2) {
+                    None => {
+                        let fs = self.global_str("FS");
+                        self.split_with(&s, &fs, false)?
+                    }
+                    Some(Expr::Regex(re)) => {
+                        let re = self.regex(re)?;
+                        if s.is_empty() {
+                            Vec::new()
+                        } else {
+                            re.split(&s).map(String::from).collect()
+                        }
+                    }
+                    Some(expr) => {
+                        let fs = self.eval(expr)?;
+                        let fs = self.to_str(&fs);
+                        self.split_with(&s, &fs, false)?
+                    }
+                };
+                let id = self.array_id(array)?;
+                self.arrays[id].clear();
+                for (i, part) in parts.iter().enumerate() {
+                    self.arrays[id].insert((i + 1).to_string(), Value::StrNum(part.clone()));
+                }
+                Value::Num(parts.len() as f64)
+            }
+            "sub" | "gsub" => {
+                arity(2, 3)?;
+                let re = self.regex_operand(&args[0])?;
+                let repl = self.eval(&args[1])?;
+                let repl = self.to_str(&repl);
+                let record = Expr::Field(Box::new(Expr::Num(0.0)));
+                let target = args.get(2).unwrap_or(&record);
+                if !target.is_lvalue() {
+                    return Err(Jump::Error(format!(
+                        "awk: {name}: third argument must be a variable"
+                    )));
+                }
+                let place = self.place(target)?;
+                let value = self.get_place(&place)?;
+                let s = self.to_str(&value);
+                let (result, count) = substitute(&re, &s, &repl, name == "gsub");
+                if count > 0 {
+                    self.set_place(place, Value::Str(result))?;
+                }
+                Value::Num(count as f64)
+            }
+            "match" => {
+                arity(2, 2)?;
+                let value = self.eval(&args[0])?;
+                let s = self.to_str(&value);
+                let re = self.regex_operand(&args[1])?;
+                let (start, length) = match re.find(&s) {
+                    Some(m) => (
+                        s[..m.start()].chars().count() as f64 + 1.0,
+                        m.as_str().chars().count() as f64,
+                    ),
+                    None => (0.0, -1.0),
+                };
+                self.set_global("RSTART", Value::Num(start));
+                self.set_global("RLENGTH", Value::Num(length));
+                Value::Num(start)
+            }
+            "sprintf" => {
+                if args.is_empty() {
+                    return Err(Jump::Error("awk: sprintf: no format".into()));
+                }
+                let values = args
+                    .iter()
+                    .map(|e| self.eval(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let fmt = self.to_str(&values[0]);
+                Value::Str(sprintf(&fmt, &values[1..], &self.convfmt()))
+            }
+            "tolower" | "toupper" => {
+                arity(1, 1)?;
+                let value = self.eval(&args[0])?;
+                let s = self.to_str(&value);
+                Value::Str(if name == "tolower" {
+                    s.to_lowercase()
+                } else {
+                    s.to_uppercase()
+                })
+            }
+            "int" | "sqrt" | "exp" | "log" | "sin" | "cos" => {
+                arity(1, 1)?;
+                let n = self.eval(&args[0])?.to_num();
+                Value::Num(match name {
+                    "int" => n.trunc(),
+                    "sqrt" => n.sqrt(),
+                    "exp" => n.exp(),
+                    "log" => n.ln(),
+                    "sin" => n.sin(),
+                    _ => n.cos(),
+                })
+            }
+            "atan2" => {
+                arity(2, 2)?;
+                let y = self.eval(&args[0])?.to_num();
+                let x = self.eval(&args[1])?.to_num();
+                Value::Num(y.atan2(x))
+            }
+            "rand" => {
+                arity(0, 0)?;
+                // xorshift64*, seeded from srand().
+                let mut x = self.rand_state ^ 0x9e37_79b9_7f4a_7c15;
+                x ^= x >> 12;
+                x ^= x << 25;
+                x ^= x >> 27;
+                self.rand_state = x;
+                let r = x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+                Value::Num(r as f64 / (1u64 << 53) as f64)
+            }
+            "srand" => {
+                arity(0, 1)?;
+                let seed = match args.first() {
+                    Some(expr) => self.eval(expr)?.to_num().trunc(),
+                    None => std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0.0, |d| d.as_secs() as f64),
+                };
+                let previous = self.rand_seed;
+                self.rand_seed = seed;
+                self.rand_state = seed as i64 as u64;
+                Value::Num(previous)
+            }
+            "close" => {
+                arity(1, 1)?;
+                let value = self.eval(&args[0])?;
+                let name = self.to_str(&value);
+                match self.files.remove(&name) {
+                    Some(mut file) => {
+                        flush_file(&name, &mut file)?;
+                        Value::Num(0.0)
+                    }
+                    None => Value::Num(-1.0),
+                }
+            }
+            "fflush" => {
+                arity(0, 1)?;
+                self.flush_files()?;
+                Value::Num(0.0)
+            }
+            _ => return Err(Jump::Error(format!("awk: not implemented: {name}()"))),
+        })
+    }
+
+    fn write_output(&mut self, output: Option<&Output>, text: &str) -> Result<(), Jump> {
+        let Some(output) = output else {
+            self.output.push_str(text);
+            return Ok(());
+        };
+        let value = self.eval(&output.target)?;
+        let name = self.to_str(&value);
+        if name == "/dev/stdout" || name == "/dev/stderr" || name == "-" {
+            self.output.push_str(text);
+            return Ok(());
+        }
+        self.files
+            .entry(name)
+            .or_insert(OutFile {
+                data: String::new(),
+                truncate: !output.append,
+            })
+            .data
+            .push_str(text);
+        Ok(())
+    }
+
+    fn flush_files(&mut self) -> Result<(), String> {
+        let mut names: Vec<&String> = self.files.keys().collect();
+        names.sort();
+        let names: Vec<String> = names.into_iter().cloned().collect();
+        for name in names {
+            if let Some(file) = self.files.get_mut(&name) {
+                flush_file(&name, file)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn exec_block(&mut self, stmts: &[Stmt]) -> Result<(), Jump> {
+        for stmt in stmts {
+            self.exec(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Runs a loop body, returning whether the loop should stop.
+    fn exec_loop_body(&mut self, body: &Stmt) -> Result<bool, Jump> {
+        match self.exec(body) {
+            Ok(()) | Err(Jump::Continue) => Ok(false),
+            Err(Jump::Break) => Ok(true),
+            Err(jump) => Err(jump),
+        }
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<(), Jump> {
+        match stmt {
+            Stmt::Print(args, output) => {
+                let mut line = if args.is_empty() {
+                    self.record.clone()
+                } else {
+                    let ofs = self.global_str("OFS");
+                    let mut parts = Vec::new();
+                    for arg in args {
+                        let value = self.eval(arg)?;
+                        parts.push(self.to_output_str(&value));
                     }
-                    self.advance();
+                    parts.join(&ofs)
+                };
+                line.push_str(&self.global_str("ORS"));
+                self.write_output(output.as_ref(), &line)?;
+            }
+            Stmt::Printf(args, output) => {
+                let values = args
+                    .iter()
+                    .map(|e| self.eval(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let fmt = self.to_str(&values[0]);
+                let text = sprintf(&fmt, &values[1..], &self.convfmt());
+                self.write_output(output.as_ref(), &text)?;
+            }
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::If(cond, then, otherwise) => {
+                if self.eval(cond)?.is_true() {
+                    self.exec(then)?;
+                } else if let Some(otherwise) = otherwise {
+                    self.exec(otherwise)?;
                 }
-                Err("awk: unterminated string".into())
             }
-            Some(b'(') => {
-                self.advance();
-                let expr = self.parse_expr()?;
-                self.expect(b')')?;
-                Ok(expr)
-            }
-            Some(c) if c.is_ascii_digit() || c == b'.' => {
-                let start = self.pos;
-                while self.pos < self.input.len() {
-                    let c = self.input.as_bytes()[self.pos];
-                    if c.is_ascii_digit() || c == b'.' {
-                        self.advance();
-                    } else {
+            Stmt::While(cond, body) => {
+                while self.eval(cond)?.is_true() {
+                    if self.exec_loop_body(body)? {
                         break;
                     }
                 }
-                let num: f64 = self.input[start..self.pos]
-                    .parse()
-                    .map_err(|_| "awk: invalid number")?;
-                Ok(Expr::Literal(num))
-            }
-            Some(c) if c.is_ascii_alphabetic() || c == b'_' => {
-                let start = self.pos;
-                while self.pos < self.input.len() {
-                    let c = self.input.as_bytes()[self.pos];
-                    if c.is_ascii_alphanumeric() || c == b'_' {
-                        self.advance();
-                    } else {
+            }
+            Stmt::Do(body, cond) => loop {
+                if self.exec_loop_body(body)? || !self.eval(cond)?.is_true() {
+                    break;
+                }
+            },
+            Stmt::For(init, cond, step, body) => {
+                if let Some(init) = init {
+                    self.eval(init)?;
+                }
+                loop {
+                    let proceed = match cond {
+                        Some(cond) => self.eval(cond)?.is_true(),
+                        None => true,
+                    };
+                    if !proceed {
+                        break;
+                    }
+                    if self.exec_loop_body(body)? {
                         break;
                     }
+                    if let Some(step) = step {
+                        self.eval(step)?;
+                    }
                 }
-                let ident = &self.input[start..self.pos];
-                match ident {
-                    "NR" => Ok(Expr::NR),
-                    "NF" => Ok(Expr::NF),
-                    // built-in functions we don't support
-                    "length" | "substr" | "index" | "split" | "sub" | "gsub" | "sprintf"
-                    | "tolower" | "toupper" | "sin" | "cos" | "exp" | "log" | "sqrt" | "int" => {
-                        Err(format!("awk: not implemented: {ident}()"))
+            }
+            Stmt::ForIn(var, array, body) => {
+                let id = self.array_id(array)?;
+                let mut keys: Vec<String> = self.arrays[id].keys().cloned().collect();
+                keys.sort_by(|a, b| compare_keys(a, b));
+                for key in keys {
+                    // Elements deleted during the loop are skipped.
+                    if !self.arrays[id].contains_key(&key) {
+                        continue;
                     }
-                    _ => {
-                        // check if it's an array access
-                        self.skip_ws();
-                        if self.peek() == Some(b'[') {
-                            return Err("awk: not implemented: arrays".into());
-                        }
-                        Ok(Expr::Var(ident.to_string()))
+                    self.set_var(var, Value::Str(key))?;
+                    if self.exec_loop_body(body)? {
+                        break;
+                    }
+                }
+            }
+            Stmt::Block(stmts) => self.exec_block(stmts)?,
+            Stmt::Delete(array, subs) => {
+                let key = match subs {
+                    Some(subs) => Some(self.subscript(subs)?),
+                    None => None,
+                };
+                let id = self.array_id(array)?;
+                match key {
+                    Some(key) => self.arrays[id].remove(&key),
+                    None => {
+                        self.arrays[id].clear();
+                        None
                     }
+                };
+            }
+            Stmt::Next => return Err(Jump::Next),
+            Stmt::NextFile => return Err(Jump::NextFile),
+            Stmt::Exit(code) => {
+                if let Some(code) = code {
+                    self.exit_code = self.eval(code)?.to_num() as i32;
+                }
+                return Err(Jump::Exit);
+            }
+            Stmt::Return(value) => {
+                if self.frames.is_empty() {
+                    return Err(Jump::Error("awk: return outside function".into()));
                 }
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Uninit,
+                };
+                return Err(Jump::Return(value));
             }
-            _ => Err(format!(
-                "awk: unexpected character: '{}'",
-                self.peek().map(|b| b as char).unwrap_or('\0')
-            )),
+            Stmt::Break => return Err(Jump::Break),
+            Stmt::Continue => return Err(Jump::Continue),
         }
+        Ok(())
     }
-}
-
-struct Env {
-    vars: std::collections::HashMap<String, f64>,
-    ofs: String,
-}
 
-impl Env {
-    fn new() -> Self {
-        Self {
-            vars: std::collections::HashMap::new(),
-            ofs: " ".into(),
+    fn pattern_matches(&mut self, index: usize, pattern: &Pattern) -> Result<bool, Jump> {
+        match pattern {
+            Pattern::All => Ok(true),
+            Pattern::Expr(expr) => Ok(self.eval(expr)?.is_true()),
+            Pattern::Range(start, end) => {
+                if !self.range_active[index] {
+                    if !self.eval(start)?.is_true() {
+                        return Ok(false);
+                    }
+                    self.range_active[index] = true;
+                }
+                if self.eval(end)?.is_true() {
+                    self.range_active[index] = false;
+                }
+                Ok(true)
+            }
         }
     }
 
-    fn eval(&mut self, expr: &Expr, fields: &[&str], nr: usize) -> Result<String, String> {
-        match expr {
-            Expr::Field(idx) => {
-                let i = self.eval_num(idx, fields, nr)? as usize;
-                if i == 0 {
-                    Ok(fields.join(&self.ofs))
-                } else {
-                    Ok(fields.get(i - 1).unwrap_or(&"").to_string())
+    /// Runs the main rules against the current record.
+    fn process_record(&mut self) -> Result<(), Jump> {
+        let program = self.program;
+        for (i, rule) in program.rules.iter().enumerate() {
+            if !self.pattern_matches(i, &rule.pattern)? {
+                continue;
+            }
+            match &rule.action {
+                Some(action) => self.exec_block(action)?,
+                None => {
+                    let line = self.record.clone() + &self.global_str("ORS");
+                    self.output.push_str(&line);
                 }
             }
-            Expr::Literal(n) => Ok(format_number(*n)),
-            Expr::StringLit(s) => Ok(unescape(s)),
-            Expr::NR => Ok(nr.to_string()),
-            Expr::NF => Ok(fields.len().to_string()),
-            Expr::Var(name) => {
-                let v = self.vars.get(name).copied().unwrap_or(0.0);
-                Ok(format_number(v))
-            }
-            Expr::BinOp(left, op, right) => {
-                let l = self.eval_num(left, fields, nr)?;
-                let r = self.eval_num(right, fields, nr)?;
-                let result = match op {
-                    BinOp::Add => l + r,
-                    BinOp::Sub => l - r,
-                    BinOp::Mul => l * r,
-                    BinOp::Div => {
-                        if r == 0.0 {
-                            return Err("awk: division by zero".into());
-                        }
-                        l / r
-                    }
-                    BinOp::Mod => {
-                        if r == 0.0 {
-                            return Err("awk: division by zero".into());
-                        }
-                        l % r
-                    }
-                    BinOp::Gt => if l > r { 1.0 } else { 0.0 },
-                    BinOp::Lt => if l < r { 1.0 } else { 0.0 },
-                    BinOp::Ge => if l >= r { 1.0 } else { 0.0 },
-                    BinOp::Le => if l <= r { 1.0 } else { 0.0 },
-                    BinOp::Eq => if l == r { 1.0 } else { 0.0 },
-                    BinOp::Ne => if l != r { 1.0 } else { 0.0 },
-                };
-                Ok(format_number(result))
-            }
-            Expr::Assign(name, val) => {
-                let v = self.eval_num(val, fields, nr)?;
-                self.vars.insert(name.clone(), v);
-                Ok(format_number(v))
-            }
-            Expr::AssignOp(name, op, val) => {
-                let current = self.vars.get(name).copied().unwrap_or(0.0);
-                let rhs = self.eval_num(val, fields, nr)?;
-                let result = match op {
-                    BinOp::Add => current + rhs,
-                    BinOp::Sub => current - rhs,
-                    BinOp::Mul => current * rhs,
-                    BinOp::Div => current / rhs,
-                    _ => current,
-                };
-                self.vars.insert(name.clone(), result);
-                Ok(format_number(result))
+        }
+        Ok(())
+    }
+
+    /// Applies a `name=value` assignment from `-v` or the operands.
+    fn assign_operand(&mut self, assignment: &str) -> Result<bool, String> {
+        match split_assignment(assignment) {
+            Some((name, value)) => {
+                self.set_var(name, Value::StrNum(unescape(value)))?;
+                Ok(true)
             }
-            Expr::Concat(left, right) => {
-                let l = self.eval(left, fields, nr)?;
-                let r = self.eval(right, fields, nr)?;
-                Ok(format!("{l}{r}"))
+            None => Ok(false),
+        }
+    }
+
+    fn split_records(&mut self, text: &str) -> Result<Vec<String>, String> {
+        let rs = self.global_str("RS");
+        if rs == "\n" {
+            return Ok(text.lines().map(String::from).collect());
+        }
+        if rs.is_empty() {
+            let re = self.regex("\n\n+")?;
+            return Ok(re
+                .split(text.trim_start_matches('\n').trim_end_matches('\n'))
+                .filter(|r| !r.is_empty())
+                .map(String::from)
+                .collect());
+        }
+        let mut records: Vec<String> = if rs.chars().count() == 1 {
+            text.split(rs.as_str()).map(String::from).collect()
+        } else {
+            let re = self.regex(&rs)?;
+            re.split(text).map(String::from).collect()
+        };
+        if records.last().is_some_and(String::is_empty) {
+            records.pop();
+        }
+        Ok(records)
+    }
+
+    /// Reads the files named in ARGV (or stdin) and runs the main rules.
+    fn run_main(&mut self, mut stdin: Option<String>) -> Result<(), Jump> {
+        let argv = self.array_id("ARGV")?;
+        let argc = self.global_num("ARGC") as usize;
+        let mut inputs = Vec::new();
+        for i in 1..argc {
+            let arg = self.arrays[argv]
+                .get(&i.to_string())
+                .map(|v| self.to_str(v))
+                .unwrap_or_default();
+            if !arg.is_empty() {
+                inputs.push(arg);
             }
-            Expr::Match(expr, re) => {
-                let s = self.eval(expr, fields, nr)?;
-                let re = Regex::new(re).map_err(|e| format!("awk: invalid regex: {e}"))?;
-                Ok(if re.is_match(&s) { "1" } else { "0" }.into())
+        }
+        if inputs.iter().all(|arg| split_assignment(arg).is_some()) {
+            inputs.push("-".into());
+        }
+        for input in inputs {
+            if input != "-" && self.assign_operand(&input)? {
+                continue;
             }
-            Expr::NotMatch(expr, re) => {
-                let s = self.eval(expr, fields, nr)?;
-                let re = Regex::new(re).map_err(|e| format!("awk: invalid regex: {e}"))?;
-                Ok(if re.is_match(&s) { "0" } else { "1" }.into())
+            let text = if input == "-" {
+                stdin.take().unwrap_or_default()
+            } else {
+                fs_ops::read_to_string(&input).map_err(|e| format!("awk: {input}: {e}"))?
+            };
+            let filename = if input == "-" { String::new() } else { input };
+            self.set_global("FILENAME", Value::Str(filename));
+            let records = self.split_records(&text)?;
+            for (fnr, record) in records.into_iter().enumerate() {
+                let nr = self.global_num("NR") + 1.0;
+                self.set_global("NR", Value::Num(nr));
+                self.set_global("FNR", Value::Num(fnr as f64 + 1.0));
+                self.set_record(record)?;
+                match self.process_record() {
+                    Ok(()) | Err(Jump::Next) => {}
+                    Err(Jump::NextFile) => break,
+                    Err(Jump::Break | Jump::Continue) => {
+                        return Err(Jump::Error("awk: break or continue outside a loop".into()));
+                    }
+                    Err(jump) => return Err(jump),
+                }
             }
         }
+        Ok(())
     }
+}
 
-    fn eval_num(&mut self, expr: &Expr, fields: &[&str], nr: usize) -> Result<f64, String> {
-        let s = self.eval(expr, fields, nr)?;
-        Ok(s.parse::<f64>().unwrap_or(0.0))
-    }
+/// Splits a `name=value` operand, if `name` is a valid variable name.
+fn split_assignment(arg: &str) -> Option<(&str, &str)> {
+    let (name, value) = arg.split_once('=')?;
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some((name, value))
 }
 
-fn format_number(n: f64) -> String {
-    if n == n.trunc() && n.abs() < 1e15 {
-        format!("{}", n as i64)
+fn flush_file(name: &str, file: &mut OutFile) -> Result<(), String> {
+    let result = if file.truncate {
+        fs_ops::write(name, file.data.as_bytes())
     } else {
-        format!("{n}")
+        fs_ops::append(name, file.data.as_bytes())
+    };
+    result.map_err(|e| format!("awk: {name}: {e}"))?;
+    file.truncate = false;
+    file.data.clear();
+    Ok(())
+}
+
+/// Orders array keys for `for (k in a)`: numeric keys first, by value.
+fn compare_keys(a: &str, b: &str) -> std::cmp::Ordering {
+    match (looks_numeric(a), looks_numeric(b)) {
+        (true, true) => parse_number_prefix(a)
+            .0
+            .partial_cmp(&parse_number_prefix(b).0)
+            .unwrap_or(std::cmp::Ordering::Equal),
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        (false, false) => a.cmp(b),
     }
 }
 
-fn unescape(s: &str) -> String {
-    let mut out = String::new();
-    let mut chars = s.chars();
-    while let Some(c) = chars.next() {
-        if c == '\\' {
-            match chars.next() {
-                Some('n') => out.push('\n'),
-                Some('t') => out.push('\t'),
-                Some('\\') => out.push('\\'),
-                Some('"') => out.push('"'),
-                Some(other) => {
-                    out.push('\\');
-                    out.push(other);
-                }
-                None => out.push('\\'),
+fn arith(op: BinOp, a: f64, b: f64) -> Result<f64, String> {
+    Ok(match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::Div => {
+            if b == 0.0 {
+                return Err("awk: division by zero".into());
             }
-        } else {
-            out.push(c);
+            a / b
         }
-    }
-    out
+        BinOp::Mod => {
+            if b == 0.0 {
+                return Err("awk: division by zero in %".into());
+            }
+            a % b
+        }
+        BinOp::Pow => a.powf(b),
+    })
 }
 
-fn matches_pattern(
-    pattern: &Pattern,
-    line: &str,
-    fields: &[&str],
-    nr: usize,
-    env: &mut Env,
-) -> Result<bool, String> {
-    match pattern {
-        Pattern::Always => Ok(true),
-        Pattern::Begin | Pattern::End => Ok(false),
-        Pattern::Regex(re) => {
-            let re = Regex::new(re).map_err(|e| format!("awk: invalid regex: {e}"))?;
-            Ok(re.is_match(line))
-        }
-        Pattern::Condition(expr) => {
-            let val = env.eval(expr, fields, nr)?;
-            Ok(val != "0" && !val.is_empty())
+/// Replaces the first (or every) match of `re` in `s`. In `repl`, `&`
+/// stands for the matched text and `\&` for a literal `&`.
+fn substitute(re: &Regex, s: &str, repl: &str, global: bool) -> (String, usize) {
+    let mut out = String::new();
+    let mut count = 0;
+    let mut last = 0;
+    for m in re.find_iter(s) {
+        out.push_str(&s[last..m.start()]);
+        let mut chars = repl.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if matches!(chars.peek(), Some('&' | '\\')) => {
+                    out.extend(chars.next());
+                }
+                '&' => out.push_str(m.as_str()),
+                c => out.push(c),
+            }
+        }
+        last = m.end();
+        count += 1;
+        if !global {
+            break;
         }
     }
+    out.push_str(&s[last..]);
+    (out, count)
 }
 
 pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
     let opts = parse_opts(args)?;
-    let mut parser = Parser::new(&opts.program);
-    let rules = parser.parse_program()?;
-
-    let input = if opts.paths.is_empty() {
-        stdin.unwrap_or_default()
-    } else {
-        let mut combined = String::new();
-        for path in &opts.paths {
-            let content =
-                fs_ops::read_to_string(path).map_err(|e| format!("awk: {path}: {e}"))?;
-            combined.push_str(&content);
+    let program = Parser::new(tokenize(&opts.program)?).parse_program()?;
+    let mut interp = Interp::new(&program);
+    if let Some(fs) = &opts.field_sep {
+        let fs = match fs.as_str() {
+            "t" => "\t".to_string(),
+            fs => unescape(fs),
+        };
+        interp.set_global("FS", Value::Str(fs));
+    }
+    for assignment in &opts.assignments {
+        if !interp.assign_operand(assignment)? {
+            return Err(format!("awk: invalid -v argument: {assignment}"));
         }
-        combined
-    };
-
-    let mut env = Env::new();
-    let mut output = String::new();
+    }
+    let argv = interp.new_array();
+    interp.arrays[argv].insert("0".into(), Value::Str("awk".into()));
+    for (i, operand) in opts.operands.iter().enumerate() {
+        interp.arrays[argv].insert((i + 1).to_string(), Value::StrNum(operand.clone()));
+    }
+    interp.globals.insert("ARGV".into(), Cell::Array(argv));
+    interp.set_global("ARGC", Value::Num(opts.operands.len() as f64 + 1.0));
 
-    // BEGIN rules
-    for rule in &rules {
-        if matches!(rule.pattern, Pattern::Begin) {
-            for action in &rule.actions {
-                exec_action(action, &[], 0, &mut env, &mut output)?;
+    let mut exited = false;
+    for block in &program.begin {
+        match interp.exec_block(block) {
+            Ok(()) => {}
+            Err(Jump::Exit) => {
+                exited = true;
+                break;
             }
+            Err(jump) => return Err(jump_error(jump)),
         }
     }
-
-    // main rules
-    for (nr, line) in input.lines().enumerate() {
-        let nr = nr + 1;
-        let fields: Vec<&str> = if opts.field_sep == " " {
-            line.split_whitespace().collect()
-        } else {
-            line.split(&opts.field_sep).collect()
-        };
-        for rule in &rules {
-            if matches_pattern(&rule.pattern, line, &fields, nr, &mut env)? {
-                for action in &rule.actions {
-                    exec_action(action, &fields, nr, &mut env, &mut output)?;
-                }
-            }
+    if !exited && (!program.rules.is_empty() || !program.end.is_empty()) {
+        match interp.run_main(stdin) {
+            Ok(()) => {}
+            Err(Jump::Exit) => {}
+            Err(jump) => return Err(jump_error(jump)),
         }
     }
-
-    // END rules
-    for rule in &rules {
-        if matches!(rule.pattern, Pattern::End) {
-            for action in &rule.actions {
-                exec_action(action, &[], 0, &mut env, &mut output)?;
-            }
+    for block in &program.end {
+        match interp.exec_block(block) {
+            Ok(()) => {}
+            Err(Jump::Exit) => break,
+            Err(jump) => return Err(jump_error(jump)),
         }
     }
-
-    Ok(output)
+    interp.flush_files()?;
+    if interp.exit_code != 0 {
+        return Err(format!(
+            "{}awk: exit status {}",
+            interp.output, interp.exit_code
+        ));
+    }
+    Ok(interp.output)
 }
 
-fn exec_action(
-    action: &Action,
-    fields: &[&str],
-    nr: usize,
-    env: &mut Env,
-    output: &mut String,
-) -> Result<(), String> {
-    match action {
-        Action::Print(exprs) => {
-            let vals: Vec<String> = exprs
-                .iter()
-                .map(|e| env.eval(e, fields, nr))
-                .collect::<Result<_, _>>()?;
-            output.push_str(&vals.join(&env.ofs));
-            output.push('\n');
-        }
-        Action::Expr(expr) => {
-            env.eval(expr, fields, nr)?;
-        }
+fn jump_error(jump: Jump) -> String {
+    match jump {
+        Jump::Error(e) => e,
+        Jump::Next | Jump::NextFile => "awk: next used in BEGIN or END".into(),
+        Jump::Break | Jump::Continue => "awk: break or continue outside a loop".into(),
+        Jump::Return(_) => "awk: return outside function".into(),
+        Jump::Exit => "awk: exit".into(),
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -1029,7 +2984,11 @@ mod tests {
 
     #[test]
     fn multiple_rules() {
-        let out = awk("/a/ {print \"found a\"} /b/ {print \"found b\"}", "a\nb\nab").unwrap();
+        let out = awk(
+            "/a/ {print \"found a\"} /b/ {print \"found b\"}",
+            "a\nb\nab",
+        )
+        .unwrap();
         assert_eq!(out, "found a\nfound b\nfound a\nfound b\n");
     }
 
@@ -1040,31 +2999,390 @@ mod tests {
     }
 
     #[test]
-    fn if_not_implemented() {
-        let out = awk("{if ($1 > 0) print}", "1");
-        assert!(out.is_err());
-        assert!(out.unwrap_err().contains("not implemented: if"));
+    fn if_else() {
+        let out = awk("{if ($1 > 1) print \"big\"; else print \"small\"}", "1\n2").unwrap();
+        assert_eq!(out, "small\nbig\n");
     }
 
     #[test]
-    fn for_not_implemented() {
-        let out = awk("{for (i=0;i<3;i++) print}", "1");
-        assert!(out.is_err());
-        assert!(out.unwrap_err().contains("not implemented: for"));
+    fn else_on_next_line() {
+        let program = "{\n  if ($1 == \"a\")\n    print 1\n  else if ($1 == \"b\")\n    print 2\n  else\n    print 3\n}";
+        let out = awk(program, "a\nb\nc").unwrap();
+        assert_eq!(out, "1\n2\n3\n");
     }
 
     #[test]
-    fn arrays_not_implemented() {
-        let out = awk("{a[1] = 1}", "x");
-        assert!(out.is_err());
-        assert!(out.unwrap_err().contains("not implemented: arrays"));
+    fn for_loop() {
+        let out = awk(
+            "{for (i = NF; i > 0; i--) printf \"%s \", $i; print \"\"}",
+            "a b c",
+        )
+        .unwrap();
+        assert_eq!(out, "c b a \n");
     }
 
     #[test]
-    fn function_not_implemented() {
-        let out = awk("{print length($0)}", "hello");
-        assert!(out.is_err());
-        assert!(out.unwrap_err().contains("not implemented: length()"));
+    fn while_and_do_loops() {
+        let out = awk(
+            "BEGIN {i = 0; while (i < 3) i++; do i *= 2; while (i < 20); print i}",
+            "",
+        )
+        .unwrap();
+        assert_eq!(out, "24\n");
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let program = "BEGIN {for (i = 1; i <= 10; i++) {if (i % 2) continue; if (i > 6) break; s = s i} print s}";
+        assert_eq!(awk(program, "").unwrap(), "246\n");
+    }
+
+    #[test]
+    fn associative_arrays() {
+        let out = awk(
+            "{count[$1]++} END {for (k in count) print k, count[k]}",
+            "b\na\nb\nc\nb",
+        )
+        .unwrap();
+        assert_eq!(out, "a 1\nb 3\nc 1\n");
+    }
+
+    #[test]
+    fn array_in_and_delete() {
+        let program = "BEGIN {a[1]; a[2]; delete a[1]; print (1 in a), (2 in a), length(a); delete a; print length(a)}";
+        assert_eq!(awk(program, "").unwrap(), "0 1 1\n0\n");
+    }
+
+    #[test]
+    fn multi_dimensional_arrays() {
+        let program = "BEGIN {a[1, 2] = 3; for (k in a) {split(k, p, SUBSEP); print p[1], p[2], a[k]} print ((1, 2) in a)}";
+        assert_eq!(awk(program, "").unwrap(), "1 2 3\n1\n");
+    }
+
+    #[test]
+    fn next_skips_rules() {
+        let out = awk("/skip/ {next} {print}", "a\nskip me\nb").unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[test]
+    fn exit_runs_end() {
+        let out = awk("{print; if (NR == 2) exit} END {print \"end\"}", "a\nb\nc").unwrap();
+        assert_eq!(out, "a\nb\nend\n");
+    }
+
+    #[test]
+    fn exit_status() {
+        let err = awk("BEGIN {print \"oops\"; exit 3}", "").unwrap_err();
+        assert_eq!(err, "oops\nawk: exit status 3");
+    }
+
+    #[test]
+    fn user_functions() {
+        let program = "function fact(n) {return n <= 1 ? 1 : n * fact(n - 1)} {print fact($1)}";
+        assert_eq!(awk(program, "5\n10").unwrap(), "120\n3628800\n");
+    }
+
+    #[test]
+    fn function_arrays_by_reference() {
+        let program = "function fill(arr, n,   i) {for (i = 1; i <= n; i++) arr[i] = i * i}\nBEGIN {fill(sq, 3); print sq[1], sq[2], sq[3], i}";
+        assert_eq!(awk(program, "").unwrap(), "1 4 9 \n");
+    }
+
+    #[test]
+    fn printf_formats() {
+        let program = r#"BEGIN {printf "%5.2f|%-4d|%04d|%x|%o|%c|%c|%10s|%-3s|%.2s|%e|%g|%%\n", 3.14159, 7, 42, 255, 8, 65, "hi", "right", "l", "abc", 12345.678, 0.0001}"#;
+        assert_eq!(
+            awk(program, "").unwrap(),
+            " 3.14|7   |0042|ff|10|A|h|     right|l  |ab|1.234568e+04|0.0001|%\n"
+        );
+    }
+
+    #[test]
+    fn printf_star_width() {
+        let out = awk(
+            r#"BEGIN {printf "[%*d] [%-*s] [%+d]\n", 4, 7, 3, "x", 5}"#,
+            "",
+        )
+        .unwrap();
+        assert_eq!(out, "[   7] [x  ] [+5]\n");
+    }
+
+    #[test]
+    fn sprintf_and_number_output() {
+        let out = awk(r#"BEGIN {s = sprintf("%03d-%s", 5, "x"); print s, 1/3, 1e6, 0.1 + 0.2; OFMT = "%.2f"; print 1/3}"#, "").unwrap();
+        assert_eq!(out, "005-x 0.333333 1000000 0.3\n0.33\n");
+    }
+
+    #[test]
+    fn string_functions() {
+        let program = r#"{print length($0), length, substr($0, 2, 3), substr($0, 4), index($0, "lo"), toupper($1), tolower("ABC")}"#;
+        assert_eq!(
+            awk(program, "hello world").unwrap(),
+            "11 11 ell lo world 4 HELLO abc\n"
+        );
+    }
+
+    #[test]
+    fn substr_edge_cases() {
+        let program = r#"BEGIN {s = "hello"; print substr(s, 0, 2) "|" substr(s, -1) "|" substr(s, 5, 10) "|" substr(s, 9)}"#;
+        assert_eq!(awk(program, "").unwrap(), "h|hello|o|\n");
+    }
+
+    #[test]
+    fn split_function() {
+        let program = r#"{n = split($0, parts, ","); print n, parts[1], parts[n]; m = split("a1b22c", x, /[0-9]+/); print m, x[3]}"#;
+        assert_eq!(awk(program, "x,y,z").unwrap(), "3 x z\n3 c\n");
+    }
+
+    #[test]
+    fn sub_and_gsub() {
+        let program = r#"{n = gsub(/o/, "0"); sub(/l+/, "[&]"); print n, $0; s = "a.b.c"; gsub(/\./, "\\&", s); print s}"#;
+        assert_eq!(
+            awk(program, "hello world").unwrap(),
+            "2 he[ll]0 w0rld\na&b&c\n"
+        );
+    }
+
+    #[test]
+    fn gsub_updates_fields() {
+        let out = awk("{gsub(/a/, \"b\", $2); print; print NF}", "a a a").unwrap();
+        assert_eq!(out, "a b a\n3\n");
+    }
+
+    #[test]
+    fn gsub_empty_matches() {
+        let out = awk(r#"BEGIN {s = "abc"; gsub(/x*/, "-", s); print s}"#, "").unwrap();
+        assert_eq!(out, "-a-b-c-\n");
+    }
+
+    #[test]
+    fn match_function() {
+        let program = r#"{if (match($0, /[0-9]+/)) print RSTART, RLENGTH, substr($0, RSTART, RLENGTH); else print RSTART, RLENGTH}"#;
+        assert_eq!(awk(program, "abc123def\nnone").unwrap(), "4 3 123\n0 -1\n");
+    }
+
+    #[test]
+    fn math_functions() {
+        let program = "BEGIN {print int(3.9), int(-3.9), sqrt(16), exp(0), log(1), 2^10, -2^2, 2^3^2, (atan2(0, -1) > 3.14)}";
+        assert_eq!(awk(program, "").unwrap(), "3 -3 4 1 0 1024 -4 512 1\n");
+    }
+
+    #[test]
+    fn rand_is_repeatable() {
+        let program =
+            "BEGIN {srand(1); a = rand(); srand(1); b = rand(); print (a == b), (a >= 0 && a < 1)}";
+        assert_eq!(awk(program, "").unwrap(), "1 1\n");
+    }
+
+    #[test]
+    fn assign_option() {
+        let out = run("-v n=3 -v 'msg=a\\tb' 'BEGIN {print n + 1, msg}'", None).unwrap();
+        assert_eq!(out, "4 a\tb\n");
+    }
+
+    #[test]
+    fn operand_assignment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("in.txt");
+        std::fs::write(&path, "a:b\n").unwrap();
+        let path = path.to_str().unwrap();
+        let out = run(&format!("'{{print $2}}' FS=: {path}"), None).unwrap();
+        assert_eq!(out, "b\n");
+    }
+
+    #[test]
+    fn output_field_separator() {
+        let out = run(
+            "-F, -v OFS=- '{$1 = $1; print; print $1, $2}'",
+            Some("a,b,c".into()),
+        )
+        .unwrap();
+        assert_eq!(out, "a-b-c\na-b\n");
+    }
+
+    #[test]
+    fn output_record_separator() {
+        let out = awk("BEGIN {ORS = \";\"} {print $1}", "a\nb").unwrap();
+        assert_eq!(out, "a;b;");
+    }
+
+    #[test]
+    fn field_separator_in_begin() {
+        let out = awk("BEGIN {FS = \":\"} {print $2}", "a:b:c").unwrap();
+        assert_eq!(out, "b\n");
+    }
+
+    #[test]
+    fn regex_field_separator() {
+        let out = awk_f("'[,;]+'", "{print $3}", "a,b;;c").unwrap();
+        assert_eq!(out, "c\n");
+    }
+
+    #[test]
+    fn tab_field_separator() {
+        let out = awk_f("t", "{print $2}", "a b\tc").unwrap();
+        assert_eq!(out, "c\n");
+    }
+
+    #[test]
+    fn record_separator() {
+        let out = awk("BEGIN {RS = \";\"} {print NR \": \" $0}", "a;b;c;").unwrap();
+        assert_eq!(out, "1: a\n2: b\n3: c\n");
+    }
+
+    #[test]
+    fn paragraph_mode() {
+        let out = awk(
+            "BEGIN {RS = \"\"} {print NR, NF, $1}",
+            "\na b\nc\n\n\nd e\n",
+        )
+        .unwrap();
+        assert_eq!(out, "1 3 a\n2 2 d\n");
+    }
+
+    #[test]
+    fn assign_nf_rebuilds_record() {
+        let out = awk("{NF = 2; print; $5 = \"e\"; print; print NF}", "a b c").unwrap();
+        assert_eq!(out, "a b\na b   e\n5\n");
+    }
+
+    #[test]
+    fn assign_record_resplits() {
+        let out = awk("{$0 = \"x y z\"; print NF, $3}", "a").unwrap();
+        assert_eq!(out, "3 z\n");
+    }
+
+    #[test]
+    fn range_pattern() {
+        let out = awk("/start/,/end/", "a\nstart\nb\nend\nc\nstart\nd").unwrap();
+        assert_eq!(out, "start\nb\nend\nstart\nd\n");
+    }
+
+    #[test]
+    fn string_vs_number_comparison() {
+        let out = awk(
+            "{print ($1 < $2), (\"10\" < \"9\"), ($1 == \"10\")}",
+            "10 9",
+        )
+        .unwrap();
+        assert_eq!(out, "0 1 1\n");
+    }
+
+    #[test]
+    fn ternary_and_logic() {
+        let out = awk(
+            "{print ($1 > 2 ? \"yes\" : \"no\"), !$1, ($1 && 0) || 1}",
+            "3\n0",
+        )
+        .unwrap();
+        assert_eq!(out, "yes 0 1\nno 1 1\n");
+    }
+
+    #[test]
+    fn increment_and_compound_assignment() {
+        let out = awk(
+            "BEGIN {x = 5; y = x++ + ++x; x -= 2; x *= 3; x /= 2; x %= 4; x ^= 2; print x, y}",
+            "",
+        )
+        .unwrap();
+        assert_eq!(out, "12.25 12\n");
+    }
+
+    #[test]
+    fn field_increment() {
+        let out = awk("{$2++; print; print $NF, $(NF-1)}", "1 2 3").unwrap();
+        assert_eq!(out, "1 3 3\n3 3\n");
+    }
+
+    #[test]
+    fn dynamic_regex() {
+        let out = run("-v 're=^a.c$' '$0 ~ re'", Some("abc\nabcd\na.c".into())).unwrap();
+        assert_eq!(out, "abc\na.c\n");
+    }
+
+    #[test]
+    fn comments_and_multiline_program() {
+        let program = "# count words\n{\n  words += NF  # per line\n}\nEND {\n  print words\n}";
+        assert_eq!(awk(program, "a b\nc").unwrap(), "3\n");
+    }
+
+    #[test]
+    fn print_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let path = path.to_str().unwrap();
+        let program = format!(
+            "{{print $1 > \"{path}\"}} END {{close(\"{path}\"); print \"x\" >> \"{path}\"}}"
+        );
+        let out = awk(&program, "a\nb").unwrap();
+        assert_eq!(out, "");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "a\nb\nx\n");
+    }
+
+    #[test]
+    fn print_comparison_in_parens() {
+        let out = awk("{print ($1 > 2)}", "3").unwrap();
+        assert_eq!(out, "1\n");
+    }
+
+    #[test]
+    fn multiple_files_fnr_and_filename() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "1\n2\n").unwrap();
+        std::fs::write(&b, "3\n").unwrap();
+        let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+        let out = run(
+            &format!("'FNR == 1 {{n++}} END {{print NR, n}}' {a} {b}"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(out, "3 2\n");
+        let out = run(
+            &format!("'{{print FILENAME == ARGV[1]; nextfile}}' {a} {b}"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(out, "1\n0\n");
+    }
+
+    #[test]
+    fn getline_not_implemented() {
+        let out = awk("{getline line < \"x\"}", "a");
+        assert!(out.unwrap_err().contains("not implemented: getline"));
+    }
+
+    #[test]
+    fn pipes_not_implemented() {
+        let out = awk("{print | \"sort\"}", "a");
+        assert!(out.unwrap_err().contains("not implemented: pipes"));
+    }
+
+    #[test]
+    fn modulo_by_zero() {
+        let out = awk("{print $1 % 0}", "5");
+        assert_eq!(out.unwrap_err(), "awk: division by zero in %");
+    }
+
+    #[test]
+    fn syntax_error() {
+        let out = awk("{print (}", "a");
+        assert!(out.unwrap_err().starts_with("awk: syntax error"));
+    }
+
+    #[test]
+    fn program_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prog.awk");
+        std::fs::write(&path, "{ print NR \": \" $0 }\n").unwrap();
+        let out = run(
+            &format!("-f {}", path.to_str().unwrap()),
+            Some("a\nb".into()),
+        )
+        .unwrap();
+        assert_eq!(out, "1: a\n2: b\n");
     }
 
     #[test]
@@ -1086,19 +3404,23 @@ mod tests {
     }
 
     #[test]
-    fn shell_split_basic() {
-        let tokens = shell_split("'{print $1}' file.txt").unwrap();
-        assert_eq!(tokens, vec!["{print $1}", "file.txt"]);
+    fn parse_quoted_program() {
+        let opts = parse_opts("'{print $1}' file.txt").unwrap();
+        assert_eq!(opts.program, "{print $1}");
+        assert_eq!(opts.operands, vec!["file.txt"]);
     }
 
     #[test]
-    fn shell_split_double_quotes() {
-        let tokens = shell_split("\"{print $1}\" file.txt").unwrap();
-        assert_eq!(tokens, vec!["{print $1}", "file.txt"]);
+    fn parse_double_quoted_program() {
+        let opts = parse_opts("-F: -v x=1 \"{print $1}\" 'my file.txt'").unwrap();
+        assert_eq!(opts.field_sep.as_deref(), Some(":"));
+        assert_eq!(opts.assignments, vec!["x=1"]);
+        assert_eq!(opts.program, "{print $1}");
+        assert_eq!(opts.operands, vec!["my file.txt"]);
     }
 
     #[test]
-    fn shell_split_unterminated() {
-        assert!(shell_split("'unterminated").is_err());
+    fn parse_unterminated_quote() {
+        assert!(parse_opts("'unterminated").is_err());
     }
 }
@@ -0,0 +1,156 @@
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+use ::base64::Engine;
+use ::base64::engine::general_purpose::STANDARD;
+
+struct Opts {
+    decode: bool,
+    ignore_garbage: bool,
+    /// Wrap encoded lines after this many characters; 0 disables wrapping.
+    wrap: usize,
+    path: Option<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        decode: false,
+        ignore_garbage: false,
+        wrap: 76,
+        path: None,
+    };
+    let mut parser = Parser::new("base64", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('d') => opts.decode = true,
+            Arg::Short('i') => opts.ignore_garbage = true,
+            Arg::Short('w') => opts.wrap = parser.number()?,
+            Arg::Long(l) => match l.as_str() {
+                "decode" => opts.decode = true,
+                "ignore-garbage" => opts.ignore_garbage = true,
+                "wrap" => opts.wrap = parser.number()?,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) if opts.path.is_none() && path != "-" => opts.path = Some(path),
+            Arg::Value(path) if path == "-" => {}
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    Ok(opts)
+}
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    let input = match &opts.path {
+        Some(path) => fs_ops::read(path).map_err(|e| format!("base64: {path}: {e}"))?,
+        None => stdin.unwrap_or_default(),
+    };
+    if opts.decode {
+        return decode(&input, opts.ignore_garbage);
+    }
+    let encoded = STANDARD.encode(&input).into_bytes();
+    let mut output = Vec::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    if opts.wrap == 0 {
+        output.extend(encoded);
+    } else {
+        for (i, line) in encoded.chunks(opts.wrap).enumerate() {
+            if i > 0 {
+                output.push(b'\n');
+            }
+            output.extend(line);
+        }
+    }
+    // Like GNU base64, unwrapped output has no final newline.
+    if !output.is_empty() && opts.wrap > 0 {
+        output.push(b'\n');
+    }
+    Ok(output)
+}
+
+/// Decodes base64, skipping line breaks (and, with `ignore_garbage`, any
+/// other character outside the alphabet).
+fn decode(data: &[u8], ignore_garbage: bool) -> Result<Vec<u8>, String> {
+    let mut input = Vec::with_capacity(data.len());
+    for &c in data {
+        match c {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' | b'=' => input.push(c),
+            b'\n' | b'\r' => {}
+            _ if ignore_garbage => {}
+            _ => return Err("base64: invalid input".into()),
+        }
+    }
+    STANDARD
+        .decode(input)
+        .map_err(|_| "base64: invalid input".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("base64", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn cmd(args: &str, stdin: &[u8]) -> Result<Vec<u8>, String> {
+        run_bytes(args, Some(stdin.to_vec()))
+    }
+
+    #[test]
+    fn encode_padding() {
+        assert_eq!(cmd("", b"").unwrap(), b"");
+        assert_eq!(cmd("", b"f").unwrap(), b"Zg==\n");
+        assert_eq!(cmd("", b"fo").unwrap(), b"Zm8=\n");
+        assert_eq!(cmd("", b"foo").unwrap(), b"Zm9v\n");
+        assert_eq!(cmd("", b"foobar").unwrap(), b"Zm9vYmFy\n");
+    }
+
+    #[test]
+    fn encode_binary() {
+        assert_eq!(cmd("", &[0, 0xff, 0xfe, 0x80]).unwrap(), b"AP/+gA==\n");
+    }
+
+    #[test]
+    fn wraps_lines() {
+        let out = cmd("", &[b'x'; 60]).unwrap();
+        let lines: Vec<&[u8]> = bytes::lines(&out).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 76);
+        let out = cmd("-w 4", b"foobar").unwrap();
+        assert_eq!(out, b"Zm9v\nYmFy\n");
+        let out = cmd("-w 0", &[b'x'; 60]).unwrap();
+        assert_eq!(out.len(), 80);
+        assert!(!out.contains(&b'\n'));
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        let encoded = cmd("", &data).unwrap();
+        assert_eq!(cmd("-d", &encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        assert!(cmd("-d", b"Zm9v!").unwrap_err().contains("invalid input"));
+        assert_eq!(cmd("-d -i", b"Zm9v!").unwrap(), b"foo");
+        assert!(cmd("-d", b"Zm9=v").is_err());
+    }
+
+    #[test]
+    fn string_interface() {
+        assert_eq!(run("", Some("hi".into())).unwrap(), "aGk=\n");
+        assert_eq!(run("-d", Some("aGk=\n".into())).unwrap(), "hi");
+        let err = run("-d", Some("//79".into())).unwrap_err();
+        assert!(err.contains("not valid UTF-8"));
+    }
+
+    #[test]
+    fn encode_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("b.bin");
+        std::fs::write(&path, [1u8, 2, 3]).unwrap();
+        let out = run_bytes(&path.display().to_string(), None).unwrap();
+        assert_eq!(out, b"AQID\n");
+    }
+}
@@ -0,0 +1,66 @@
+//! Helpers for utilities that work on raw bytes, so binary data can pass
+//! through them, through pipes and through redirects unchanged. Such
+//! utilities implement `run_bytes` and derive their string `run` from it.
+
+/// Splits `data` into lines like [`str::lines`]: on `\n`, dropping a
+/// final `\r`, with no empty line after a trailing newline.
+pub fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = (!data.is_empty()).then_some(data);
+    std::iter::from_fn(move || {
+        let chunk = rest?;
+        let (line, next) = match chunk.iter().position(|&b| b == b'\n') {
+            Some(i) => (&chunk[..i], (i + 1 < chunk.len()).then(|| &chunk[i + 1..])),
+            None => (chunk, None),
+        };
+        rest = next;
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    })
+}
+
+/// Converts the output of a byte utility for the string interfaces.
+pub fn into_text(cmd: &str, output: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(output)
+        .map_err(|_| format!("{cmd}: output is not valid UTF-8; use the bytes interface"))
+}
+
+/// Converts byte input for a utility that only handles text.
+pub fn from_utf8(cmd: &str, input: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(input).map_err(|_| format!("{cmd}: input is not valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(data: &[u8]) -> Vec<&[u8]> {
+        lines(data).collect()
+    }
+
+    #[test]
+    fn lines_match_str_lines() {
+        for text in ["", "\n", "a", "a\n", "a\nb", "a\r\nb\r\n", "\n\nx\n"] {
+            let expected: Vec<&[u8]> = text.lines().map(str::as_bytes).collect();
+            assert_eq!(split(text.as_bytes()), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn lines_keep_invalid_utf8() {
+        assert_eq!(split(b"\xff\xfe\n\x00"), [&b"\xff\xfe"[..], b"\x00"]);
+    }
+
+    #[test]
+    fn text_conversion_errors() {
+        assert_eq!(into_text("cat", b"ok".to_vec()).unwrap(), "ok");
+        assert!(
+            into_text("cat", vec![0xff])
+                .unwrap_err()
+                .contains("bytes interface")
+        );
+        assert!(
+            from_utf8("grep", vec![0xff])
+                .unwrap_err()
+                .starts_with("grep: input")
+        );
+    }
+}
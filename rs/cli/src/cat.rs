@@ -1,3 +1,5 @@
+use crate::args::{Arg, Parser};
+use crate::bytes;
 use crate::fs_ops;
 
 struct Opts {
@@ -5,58 +7,62 @@ struct Opts {
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
         number: false,
         paths: Vec::new(),
     };
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 {
-            for c in token[1..].chars() {
-                match c {
-                    'n' => opts.number = true,
-                    _ => {}
-                }
-            }
-        } else {
-            opts.paths.push(token.to_string());
+    let mut parser = Parser::new("cat", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('n') => opts.number = true,
+            Arg::Long(l) if l == "number" => opts.number = true,
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
-    opts
+    Ok(opts)
 }
 
-pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
 
     if opts.paths.is_empty() {
         let input = stdin.unwrap_or_default();
-        return Ok(maybe_number(&input, opts.number));
+        return Ok(maybe_number(input, opts.number));
     }
 
-    let mut output = String::new();
+    let mut output = Vec::new();
     for path in &opts.paths {
-        let content = fs_ops::read_to_string(path)
-            .map_err(|e| format!("cat: {path}: {e}"))?;
-        output.push_str(&content);
+        let content = fs_ops::read(path).map_err(|e| format!("cat: {path}: {e}"))?;
+        output.extend(content);
     }
-    Ok(maybe_number(&output, opts.number))
+    Ok(maybe_number(output, opts.number))
 }
 
-fn maybe_number(text: &str, number: bool) -> String {
+fn maybe_number(data: Vec<u8>, number: bool) -> Vec<u8> {
     if !number {
-        return text.to_string();
+        return data;
     }
-    text.lines()
-        .enumerate()
-        .map(|(i, line)| format!("{:>6}\t{line}", i + 1))
-        .collect::<Vec<_>>()
-        .join("\n")
+    let mut output = Vec::new();
+    for (i, line) in bytes::lines(&data).enumerate() {
+        if i > 0 {
+            output.push(b'\n');
+        }
+        output.extend(format!("{:>6}\t", i + 1).as_bytes());
+        output.extend(line);
+    }
+    output
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("cat", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
     fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
         run(args, stdin.map(String::from))
     }
@@ -114,4 +120,11 @@ mod tests {
         let err = cmd("/no/such/file.txt", None).unwrap_err();
         assert!(err.contains("cat:"));
     }
+
+    #[test]
+    fn filename_with_spaces() {
+        let (_dir, path) = tmp("a file.txt", "x\n");
+        let out = cmd(&format!("\"{path}\""), None).unwrap();
+        assert_eq!(out, "x\n");
+    }
 }
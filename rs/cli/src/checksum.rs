@@ -0,0 +1,124 @@
+//! Shared driver for the `*sum` utilities: prints `DIGEST  NAME` lines for
+//! files (or `-` for stdin), or verifies such lines with `-c`.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+pub type Digest = fn(&[u8]) -> Vec<u8>;
+
+struct Opts {
+    check: bool,
+    quiet: bool,
+    status: bool,
+    paths: Vec<String>,
+}
+
+fn parse_opts(cmd: &'static str, args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        check: false,
+        quiet: false,
+        status: false,
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new(cmd, args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('c') => opts.check = true,
+            // Binary and text mode read the same bytes here.
+            Arg::Short('b' | 't') => {}
+            Arg::Long(l) => match l.as_str() {
+                "check" => opts.check = true,
+                "quiet" => opts.quiet = true,
+                "status" => opts.status = true,
+                "binary" | "text" => {}
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.paths.is_empty() {
+        opts.paths.push("-".into());
+    }
+    Ok(opts)
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn run_bytes(
+    cmd: &'static str,
+    digest: Digest,
+    args: &str,
+    stdin: Option<Vec<u8>>,
+) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(cmd, args)?;
+    let mut stdin = stdin;
+    let mut read = |path: &str| match path {
+        "-" => Ok(stdin.take().unwrap_or_default()),
+        _ => fs_ops::read(path).map_err(|e| format!("{cmd}: {path}: {e}")),
+    };
+    let mut output = String::new();
+    if !opts.check {
+        for path in &opts.paths {
+            let data = read(path)?;
+            output.push_str(&format!("{}  {path}\n", hex(&digest(&data))));
+        }
+        return Ok(output.into_bytes());
+    }
+
+    let mut failed = 0;
+    let mut unreadable = 0;
+    for list in &opts.paths {
+        let text = read(list)?;
+        let text =
+            String::from_utf8(text).map_err(|_| format!("{cmd}: {list}: not a checksum list"))?;
+        let mut checked = 0;
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            // `HASH  NAME`, or `HASH *NAME` for binary mode.
+            let Some((expected, name)) = line.split_once(' ') else {
+                continue;
+            };
+            let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+            checked += 1;
+            let status = match fs_ops::read(name) {
+                Ok(data) if hex(&digest(&data)).eq_ignore_ascii_case(expected) => "OK",
+                Ok(_) => {
+                    failed += 1;
+                    "FAILED"
+                }
+                Err(_) => {
+                    unreadable += 1;
+                    "FAILED open or read"
+                }
+            };
+            if !opts.status && (status != "OK" || !opts.quiet) {
+                output.push_str(&format!("{name}: {status}\n"));
+            }
+        }
+        if checked == 0 {
+            return Err(format!(
+                "{output}{cmd}: {list}: no properly formatted checksum lines found"
+            ));
+        }
+    }
+    let mut warnings = Vec::new();
+    if unreadable > 0 {
+        let s = if unreadable == 1 { "" } else { "s" };
+        warnings.push(format!(
+            "{cmd}: WARNING: {unreadable} listed file{s} could not be read"
+        ));
+    }
+    if failed > 0 {
+        let s = if failed == 1 { "" } else { "s" };
+        warnings.push(format!(
+            "{cmd}: WARNING: {failed} computed checksum{s} did NOT match"
+        ));
+    }
+    match warnings.is_empty() {
+        true => Ok(output.into_bytes()),
+        false if opts.status => Err(format!("{cmd}: exit status 1")),
+        false => Err(format!("{output}{}", warnings.join("\n"))),
+    }
+}
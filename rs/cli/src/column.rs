@@ -0,0 +1,250 @@
+//! `column` lays out its input in columns. With `-t` each line is split
+//! into cells that are aligned into a table; otherwise whole lines are
+//! filled into as many tab-aligned columns as fit the width (`-c`), down
+//! the columns first or across rows with `-x`, as util-linux column does.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+const TAB: usize = 8;
+
+struct Opts {
+    table: bool,
+    separators: Option<String>,
+    output_separator: String,
+    width: usize,
+    fill_rows: bool,
+    /// Column headers for `-t`.
+    names: Option<Vec<String>>,
+    /// 1-based columns to right-align in `-t` mode.
+    right: Vec<usize>,
+    paths: Vec<String>,
+}
+
+fn parse_columns(list: &str) -> Result<Vec<usize>, String> {
+    list.split(',')
+        .map(|n| match n.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("column: invalid column number: '{n}'")),
+        })
+        .collect()
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        table: false,
+        separators: None,
+        output_separator: "  ".into(),
+        width: 80,
+        fill_rows: false,
+        names: None,
+        right: Vec::new(),
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new("column", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('t') => opts.table = true,
+            Arg::Short('s') => opts.separators = Some(parser.value()?),
+            Arg::Short('o') => opts.output_separator = parser.value()?,
+            Arg::Short('c') => opts.width = parser.number()?,
+            Arg::Short('x') => opts.fill_rows = true,
+            Arg::Short('N') => {
+                opts.names = Some(parser.value()?.split(',').map(String::from).collect());
+            }
+            Arg::Short('R') => opts.right = parse_columns(&parser.value()?)?,
+            Arg::Long(l) => match l.as_str() {
+                "table" => opts.table = true,
+                "separator" => opts.separators = Some(parser.value()?),
+                "output-separator" => opts.output_separator = parser.value()?,
+                "output-width" => opts.width = parser.number()?,
+                "fillrows" => opts.fill_rows = true,
+                "table-columns" => {
+                    opts.names = Some(parser.value()?.split(',').map(String::from).collect());
+                }
+                "table-right" => opts.right = parse_columns(&parser.value()?)?,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    Ok(opts)
+}
+
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn table(opts: &Opts, lines: &[&str]) -> String {
+    let is_separator = |c: char| match &opts.separators {
+        Some(seps) => seps.contains(c),
+        None => c == ' ' || c == '\t',
+    };
+    let mut rows: Vec<Vec<&str>> = Vec::new();
+    if let Some(names) = &opts.names {
+        rows.push(names.iter().map(String::as_str).collect());
+    }
+    // Like strtok, runs of separators count as one.
+    rows.extend(
+        lines
+            .iter()
+            .map(|line| line.split(is_separator).filter(|c| !c.is_empty()).collect()),
+    );
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            let cells = rows.iter().filter_map(|row| row.get(i));
+            cells.map(|cell| width(cell)).max().unwrap_or(0)
+        })
+        .collect();
+
+    let mut output = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            if i > 0 {
+                line.push_str(&opts.output_separator);
+            }
+            let pad = " ".repeat(widths[i] - width(cell));
+            if opts.right.contains(&(i + 1)) {
+                line.push_str(&pad);
+                line.push_str(cell);
+            } else {
+                line.push_str(cell);
+                // The last column is not padded.
+                if i + 1 < row.len() {
+                    line.push_str(&pad);
+                }
+            }
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+/// Pads with tabs from `used` characters up to the column ending at `end`.
+fn tab_to(output: &mut String, mut used: usize, end: usize) {
+    loop {
+        let next = (used + TAB) & !(TAB - 1);
+        if next > end {
+            break;
+        }
+        output.push('\t');
+        used = next;
+    }
+}
+
+fn fill(opts: &Opts, entries: &[&str]) -> String {
+    let mut output = String::new();
+    let longest = entries.iter().map(|e| width(e)).max().unwrap_or(0);
+    if longest >= opts.width {
+        for entry in entries {
+            output.push_str(entry);
+            output.push('\n');
+        }
+        return output;
+    }
+    let column_width = (longest + TAB) & !(TAB - 1);
+    let columns = (opts.width / column_width).max(1);
+    if opts.fill_rows {
+        for row in entries.chunks(columns) {
+            let mut used = 0;
+            for (col, entry) in row.iter().enumerate() {
+                output.push_str(entry);
+                if col + 1 < row.len() {
+                    tab_to(&mut output, used + width(entry), (col + 1) * column_width);
+                }
+                used = (col + 1) * column_width;
+            }
+            output.push('\n');
+        }
+        return output;
+    }
+    let rows = entries.len().div_ceil(columns);
+    for row in 0..rows {
+        let mut used = 0;
+        let mut index = row;
+        for col in 0..columns {
+            let entry = entries[index];
+            output.push_str(entry);
+            index += rows;
+            if index >= entries.len() {
+                break;
+            }
+            tab_to(&mut output, used + width(entry), (col + 1) * column_width);
+            used = (col + 1) * column_width;
+        }
+        output.push('\n');
+    }
+    output
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let input = if opts.paths.is_empty() {
+        stdin.unwrap_or_default()
+    } else {
+        let mut combined = String::new();
+        for path in &opts.paths {
+            let content =
+                fs_ops::read_to_string(path).map_err(|e| format!("column: {path}: {e}"))?;
+            combined.push_str(&content);
+        }
+        combined
+    };
+    // Empty lines are ignored.
+    let lines: Vec<&str> = input.lines().filter(|l| !l.trim().is_empty()).collect();
+    match opts.table {
+        true => Ok(table(&opts, &lines)),
+        false => Ok(fill(&opts, &lines)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn table_aligns_cells() {
+        let out = cmd("-t", Some("name age city\nalice 30 paris\nbob 4 rome\n")).unwrap();
+        assert_eq!(
+            out,
+            "name   age  city\nalice  30   paris\nbob    4    rome\n"
+        );
+    }
+
+    #[test]
+    fn table_separators_and_headers() {
+        let out = cmd("-t -s , -o ' | ' -N k,v", Some("a,1\nlonger,,22\n\n")).unwrap();
+        assert_eq!(out, "k      | v\na      | 1\nlonger | 22\n");
+        let out = cmd("-t -R 2", Some("a 1\nb 100\n")).unwrap();
+        assert_eq!(out, "a    1\nb  100\n");
+    }
+
+    #[test]
+    fn fills_columns_first() {
+        let input: String = (1..=10).map(|n| format!("item{n}\n")).collect();
+        let out = cmd("-c 30", Some(&input)).unwrap();
+        assert_eq!(
+            out,
+            "item1\titem5\titem9\nitem2\titem6\titem10\nitem3\titem7\nitem4\titem8\n"
+        );
+        let out = cmd("-x -c 30", Some(&input)).unwrap();
+        assert_eq!(
+            out,
+            "item1\titem2\titem3\nitem4\titem5\titem6\nitem7\titem8\titem9\nitem10\n"
+        );
+    }
+
+    #[test]
+    fn long_entries_one_per_line() {
+        let out = cmd("-c 5", Some("abcdef\nx\n")).unwrap();
+        assert_eq!(out, "abcdef\nx\n");
+    }
+}
@@ -0,0 +1,205 @@
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CheckOrder {
+    /// Only once a line has turned out to be unpairable, as GNU comm does.
+    Default,
+    Enabled,
+    Disabled,
+}
+
+struct Opts {
+    /// Whether to show lines only in file 1, only in file 2, and in both.
+    show: [bool; 3],
+    delimiter: String,
+    total: bool,
+    check_order: CheckOrder,
+    paths: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        show: [true; 3],
+        delimiter: "\t".into(),
+        total: false,
+        check_order: CheckOrder::Default,
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new("comm", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            // `-1`, `-23` and so on arrive as numbers.
+            Arg::Number(n) if n.chars().all(|c| matches!(c, '1'..='3')) => {
+                for c in n.chars() {
+                    opts.show[c as usize - '1' as usize] = false;
+                }
+            }
+            Arg::Long(l) => match l.as_str() {
+                "output-delimiter" => opts.delimiter = parser.value()?,
+                "total" => opts.total = true,
+                "check-order" => opts.check_order = CheckOrder::Enabled,
+                "nocheck-order" => opts.check_order = CheckOrder::Disabled,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    match opts.paths.len() {
+        0 => Err("comm: missing operand".into()),
+        1 => Err(format!("comm: missing operand after '{}'", opts.paths[0])),
+        2 => Ok(opts),
+        _ => Err(format!("comm: extra operand '{}'", opts.paths[2])),
+    }
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let mut stdin = stdin;
+    let mut files = Vec::new();
+    for path in &opts.paths {
+        let content = match path.as_str() {
+            "-" => stdin.take().unwrap_or_default(),
+            path => fs_ops::read_to_string(path).map_err(|e| format!("comm: {path}: {e}"))?,
+        };
+        files.push(content);
+    }
+    let lines: Vec<Vec<&str>> = files.iter().map(|f| f.lines().collect()).collect();
+
+    let mut output = String::new();
+    let mut messages = Vec::new();
+    let mut counts = [0usize; 3];
+    let mut pos = [0usize; 2];
+    let mut seen_unpairable = false;
+    let mut warned = [false; 2];
+    // Column prefixes: each shown column before this one adds a delimiter.
+    let prefix = |column: usize| {
+        opts.delimiter
+            .repeat(opts.show[..column].iter().filter(|&&s| s).count())
+    };
+    while pos[0] < lines[0].len() || pos[1] < lines[1].len() {
+        let order = match (lines[0].get(pos[0]), lines[1].get(pos[1])) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        let (column, advance) = match order {
+            Ordering::Less => (0, [true, false]),
+            Ordering::Greater => (1, [false, true]),
+            Ordering::Equal => (2, [true, true]),
+        };
+        let line = if column == 1 {
+            lines[1][pos[1]]
+        } else {
+            lines[0][pos[0]]
+        };
+        counts[column] += 1;
+        if column < 2 {
+            seen_unpairable = true;
+        }
+        if opts.show[column] {
+            output.push_str(&prefix(column));
+            output.push_str(line);
+            output.push('\n');
+        }
+        for file in 0..2 {
+            if !advance[file] {
+                continue;
+            }
+            pos[file] += 1;
+            let checking = match opts.check_order {
+                CheckOrder::Default => seen_unpairable,
+                CheckOrder::Enabled => true,
+                CheckOrder::Disabled => false,
+            };
+            let (prev, next) = (lines[file][pos[file] - 1], lines[file].get(pos[file]));
+            if checking && !warned[file] && next.is_some_and(|next| prev > *next) {
+                warned[file] = true;
+                messages.push(format!("comm: file {} is not in sorted order", file + 1));
+                if opts.check_order == CheckOrder::Enabled {
+                    return Err(format!("{output}{}", messages.join("\n")));
+                }
+            }
+        }
+    }
+    if opts.total {
+        let d = &opts.delimiter;
+        output.push_str(&format!(
+            "{}{d}{}{d}{}{d}total\n",
+            counts[0], counts[1], counts[2]
+        ));
+    }
+    if messages.is_empty() {
+        return Ok(output);
+    }
+    messages.push("comm: input is not in sorted order".into());
+    Err(format!("{output}{}", messages.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp(a: &str, b: &str) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), a).unwrap();
+        std::fs::write(dir.path().join("b"), b).unwrap();
+        let base = dir.path().display().to_string();
+        (dir, format!("{base}/a {base}/b"))
+    }
+
+    #[test]
+    fn three_columns() {
+        let (_dir, files) = tmp("apple\nbanana\ncherry\n", "banana\ndate\n");
+        let out = run(&files, None).unwrap();
+        assert_eq!(out, "apple\n\t\tbanana\ncherry\n\tdate\n");
+    }
+
+    #[test]
+    fn suppressed_columns() {
+        let (_dir, files) = tmp("a\nb\nc\n", "b\nc\nd\n");
+        assert_eq!(run(&format!("-12 {files}"), None).unwrap(), "b\nc\n");
+        assert_eq!(run(&format!("-3 {files}"), None).unwrap(), "a\n\td\n");
+        let out = run(&format!("-1 --output-delimiter=| {files}"), None).unwrap();
+        assert_eq!(out, "|b\n|c\nd\n");
+    }
+
+    #[test]
+    fn total_and_stdin() {
+        let (dir, _) = tmp("", "");
+        let b = dir.path().join("b");
+        std::fs::write(&b, "x\ny\n").unwrap();
+        let args = format!("--total - {}", b.display());
+        let out = run(&args, Some("w\nx\n".into())).unwrap();
+        assert_eq!(out, "w\n\t\tx\n\ty\n1\t1\t1\ttotal\n");
+    }
+
+    #[test]
+    fn unsorted_input() {
+        let (_dir, files) = tmp("b\na\n", "a\n");
+        let err = run(&files, None).unwrap_err();
+        assert_eq!(
+            err,
+            "\ta\nb\na\ncomm: file 1 is not in sorted order\ncomm: input is not in sorted order"
+        );
+        assert_eq!(
+            run(&format!("--check-order {files}"), None).unwrap_err(),
+            "\ta\nb\ncomm: file 1 is not in sorted order"
+        );
+        assert!(run(&format!("--nocheck-order {files}"), None).is_ok());
+        // Without unpairable lines, disorder goes unnoticed by default.
+        let (_dir, files) = tmp("b\na\n", "b\na\n");
+        assert!(run(&files, None).is_ok());
+    }
+
+    #[test]
+    fn operand_errors() {
+        assert_eq!(run("", None).unwrap_err(), "comm: missing operand");
+        assert_eq!(
+            run("a", None).unwrap_err(),
+            "comm: missing operand after 'a'"
+        );
+    }
+}
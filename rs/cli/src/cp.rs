@@ -1,3 +1,4 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 use std::path::Path;
 
@@ -10,16 +11,13 @@ struct Opts {
 fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut recursive = false;
     let mut paths = Vec::new();
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 {
-            for c in token[1..].chars() {
-                match c {
-                    'r' | 'R' => recursive = true,
-                    _ => {}
-                }
-            }
-        } else {
-            paths.push(token.to_string());
+    let mut parser = Parser::new("cp", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('r' | 'R') => recursive = true,
+            Arg::Long(l) if l == "recursive" => recursive = true,
+            Arg::Value(path) => paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
     if paths.len() < 2 {
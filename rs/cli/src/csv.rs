@@ -0,0 +1,763 @@
+//! `csv` queries comma- or tab-separated data with RFC 4180 quoting, so
+//! fields may hold delimiters, quotes and newlines. Rows are filtered
+//! (`-w`), grouped and counted (`-g`), sorted (`-S`), limited (`-n`) and
+//! their columns selected (`-s`), in that order, then written back as CSV,
+//! as a JSON array (`--json`) or as an aligned table (`--table`). Columns
+//! are named by their header or numbered from 1. `--from-json` reads a JSON
+//! array of objects or arrays instead, so data converts both ways.
+
+use crate::args::{Arg, Parser};
+use crate::{fs_ops, json};
+use hifijson::token::Lex;
+use jaq_json::Val;
+use regex::Regex;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+enum Output {
+    Csv,
+    Json,
+    Table,
+    Count,
+    Headers,
+}
+
+struct Opts {
+    delimiter: Option<char>,
+    /// Whether the first row names the columns.
+    header: bool,
+    from_json: bool,
+    /// Column lists for `-s`, `-S` and `-g`, each comma-separated.
+    select: Vec<String>,
+    sort: Vec<String>,
+    group_by: Vec<String>,
+    reverse: bool,
+    filters: Vec<String>,
+    limit: Option<usize>,
+    output: Output,
+    paths: Vec<String>,
+}
+
+fn parse_delimiter(spec: &str) -> Result<char, String> {
+    let mut chars = spec.chars();
+    match (chars.next(), chars.as_str()) {
+        (Some('\\'), "t") => Ok('\t'),
+        (Some(c), "") if c != '"' && c != '\n' && c != '\r' => Ok(c),
+        _ => Err(format!(
+            "csv: invalid delimiter '{spec}': expected a single character"
+        )),
+    }
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        delimiter: None,
+        header: true,
+        from_json: false,
+        select: Vec::new(),
+        sort: Vec::new(),
+        group_by: Vec::new(),
+        reverse: false,
+        filters: Vec::new(),
+        limit: None,
+        output: Output::Csv,
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new("csv", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('d') => opts.delimiter = Some(parse_delimiter(&parser.value()?)?),
+            Arg::Short('t') => opts.delimiter = Some('\t'),
+            Arg::Short('H') => opts.header = false,
+            Arg::Short('s') => opts.select.push(parser.value()?),
+            Arg::Short('w') => opts.filters.push(parser.value()?),
+            Arg::Short('S') => opts.sort.push(parser.value()?),
+            Arg::Short('r') => opts.reverse = true,
+            Arg::Short('g') => opts.group_by.push(parser.value()?),
+            Arg::Short('n') => opts.limit = Some(parser.number()?),
+            Arg::Short('c') => opts.output = Output::Count,
+            Arg::Long(l) => match l.as_str() {
+                "delimiter" => opts.delimiter = Some(parse_delimiter(&parser.value()?)?),
+                "tsv" => opts.delimiter = Some('\t'),
+                "no-header" => opts.header = false,
+                "select" => opts.select.push(parser.value()?),
+                "where" => opts.filters.push(parser.value()?),
+                "sort" => opts.sort.push(parser.value()?),
+                "reverse" => opts.reverse = true,
+                "group-by" => opts.group_by.push(parser.value()?),
+                "limit" => opts.limit = Some(parser.number()?),
+                "count" => opts.output = Output::Count,
+                "headers" => opts.output = Output::Headers,
+                "json" => opts.output = Output::Json,
+                "table" => opts.output = Output::Table,
+                "from-json" => opts.from_json = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    Ok(opts)
+}
+
+/// Splits `text` into records of fields. Quoted fields may contain the
+/// delimiter, newlines and doubled quotes; a quote elsewhere in a field is
+/// kept as is. Blank lines are skipped and a leading BOM is dropped.
+fn parse_records(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    // Whether the current record has a field yet, even an empty one.
+    let mut started = false;
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => return Err(format!("line {start}: unterminated quoted field")),
+                    }
+                }
+                started = true;
+                quoted = true;
+            }
+            c if c == delimiter => {
+                record.push(std::mem::take(&mut field));
+                started = true;
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if started || !field.is_empty() {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                started = false;
+                quoted = false;
+                line += 1;
+            }
+            c => {
+                field.push(c);
+                started = true;
+            }
+        }
+    }
+    if started || !field.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// `field` quoted for output if it contains the delimiter, a quote or a
+/// line break.
+fn quote(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+struct Table {
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+}
+
+fn cell(row: &[String], column: usize) -> &str {
+    row.get(column).map_or("", String::as_str)
+}
+
+/// The text of a JSON value as a field: strings without quotes, `null` as
+/// an empty field and anything else as compact JSON.
+fn json_field(value: &Val) -> String {
+    match value {
+        Val::Null => String::new(),
+        Val::Str(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+/// Builds a table from JSON values that are rows, or arrays of rows, where
+/// a row is an object (keys become the header) or an array of fields.
+fn json_table(text: &str, header: bool) -> Result<Table, String> {
+    let mut lexer = hifijson::SliceLexer::new(text.as_bytes());
+    let mut rows = Vec::new();
+    while let Some(token) = lexer.ws_token() {
+        let value =
+            Val::parse(token, &mut lexer).map_err(|e| format!("csv: invalid JSON: {e:?}"))?;
+        match value {
+            Val::Arr(items)
+                if !items.is_empty()
+                    && items.iter().all(|v| matches!(v, Val::Arr(_) | Val::Obj(_))) =>
+            {
+                rows.extend(items.iter().cloned());
+            }
+            Val::Arr(items) if items.is_empty() => {}
+            value => rows.push(value),
+        }
+    }
+    if !rows.iter().any(|row| matches!(row, Val::Obj(_))) {
+        let mut rows = rows
+            .iter()
+            .map(|row| match row {
+                Val::Arr(fields) => Ok(fields.iter().map(json_field).collect()),
+                _ => Err("csv: expected JSON objects or arrays".to_string()),
+            })
+            .collect::<Result<Vec<Vec<String>>, String>>()?;
+        let header = (header && !rows.is_empty()).then(|| rows.remove(0));
+        return Ok(Table { header, rows });
+    }
+    let mut keys: Vec<String> = Vec::new();
+    let mut objects = Vec::new();
+    for row in &rows {
+        let Val::Obj(members) = row else {
+            return Err("csv: expected only JSON objects or only arrays".into());
+        };
+        for key in members.keys() {
+            if !keys.iter().any(|k| k == key.as_str()) {
+                keys.push(key.to_string());
+            }
+        }
+        objects.push(members);
+    }
+    let rows = objects
+        .iter()
+        .map(|members| {
+            keys.iter()
+                .map(|key| {
+                    members
+                        .iter()
+                        .find(|(k, _)| k.as_str() == key)
+                        .map_or(String::new(), |(_, v)| json_field(v))
+                })
+                .collect()
+        })
+        .collect();
+    Ok(Table {
+        header: header.then_some(keys),
+        rows,
+    })
+}
+
+/// Reads every input into one table, with the header taken from the first
+/// and required to match in the rest. Returns the output delimiter too.
+fn read_input(opts: &Opts, stdin: Option<String>) -> Result<(Table, char), String> {
+    let mut sources = Vec::new();
+    if opts.paths.is_empty() {
+        let input = stdin.ok_or("csv: no input provided")?;
+        sources.push((None, input));
+    }
+    for path in &opts.paths {
+        let text = fs_ops::read_to_string(path).map_err(|e| format!("csv: {path}: {e}"))?;
+        sources.push((Some(path.as_str()), text));
+    }
+    // Files named `.tsv` are tab-separated unless a delimiter is given.
+    let delimiter_for = |path: Option<&str>| match (opts.delimiter, path) {
+        (Some(delimiter), _) => delimiter,
+        (None, Some(path)) if path.ends_with(".tsv") && !opts.from_json => '\t',
+        _ => ',',
+    };
+    let delimiter = delimiter_for(sources[0].0);
+    if opts.from_json {
+        let text: Vec<&str> = sources.iter().map(|(_, text)| text.as_str()).collect();
+        return Ok((json_table(&text.join("\n"), opts.header)?, delimiter));
+    }
+
+    let mut table = Table {
+        header: None,
+        rows: Vec::new(),
+    };
+    let mut first: Option<&str> = None;
+    for (path, text) in &sources {
+        let mut records = parse_records(text, delimiter_for(*path)).map_err(|e| match path {
+            Some(path) => format!("csv: {path}: {e}"),
+            None => format!("csv: {e}"),
+        })?;
+        if opts.header && !records.is_empty() {
+            let header = records.remove(0);
+            match &table.header {
+                Some(expected) if *expected != header => {
+                    return Err(format!(
+                        "csv: {}: header differs from {}",
+                        path.unwrap_or("-"),
+                        first.unwrap_or("-")
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    table.header = Some(header);
+                    first = *path;
+                }
+            }
+        }
+        table.rows.extend(records);
+    }
+    Ok((table, delimiter))
+}
+
+/// Resolves a column by header name, or by number from 1.
+fn column(name: &str, header: Option<&[String]>) -> Result<usize, String> {
+    let by_name = |name: &str| header.and_then(|h| h.iter().position(|n| n == name));
+    if let Some(i) = by_name(name).or_else(|| by_name(name.trim())) {
+        return Ok(i);
+    }
+    match name.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("csv: unknown column '{name}'")),
+    }
+}
+
+/// Resolves comma-separated lists of columns, where `N-M` is a range of
+/// column numbers.
+fn columns(lists: &[String], header: Option<&[String]>) -> Result<Vec<usize>, String> {
+    let mut result = Vec::new();
+    for name in lists.iter().flat_map(|list| list.split(',')) {
+        let range = name
+            .split_once('-')
+            .and_then(|(a, b)| Some((a.trim().parse::<usize>().ok()?, b.trim().parse().ok()?)));
+        match range {
+            Some((a, b)) if column(name, header).is_err() => {
+                if a == 0 || b < a {
+                    return Err(format!("csv: invalid column range '{name}'"));
+                }
+                result.extend(a - 1..b);
+            }
+            _ => result.push(column(name, header)?),
+        }
+    }
+    Ok(result)
+}
+
+fn number(s: &str) -> Option<f64> {
+    let n: f64 = s.trim().parse().ok()?;
+    n.is_finite().then_some(n)
+}
+
+/// Orders numbers numerically and before other text, which is ordered by
+/// code point.
+fn compare(a: &str, b: &str) -> Ordering {
+    match (number(a), number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches(Regex),
+    NotMatches(Regex),
+}
+
+struct Filter {
+    column: usize,
+    op: Op,
+    value: String,
+}
+
+/// Parses `COLUMN OP VALUE` for `-w`, with OP one of `=`, `!=`, `<`, `<=`,
+/// `>`, `>=`, `~` (regex match) and `!~`.
+fn parse_filter(expr: &str, header: Option<&[String]>) -> Result<Filter, String> {
+    let invalid = || format!("csv: invalid filter '{expr}': expected COLUMN OP VALUE");
+    let at = expr.find(['=', '!', '<', '>', '~']).ok_or_else(invalid)?;
+    let (name, rest) = expr.split_at(at);
+    let ops = ["!=", "!~", "<=", ">=", "==", "=", "<", ">", "~"];
+    let op = ops
+        .into_iter()
+        .find(|op| rest.starts_with(op))
+        .ok_or_else(invalid)?;
+    if name.trim().is_empty() {
+        return Err(invalid());
+    }
+    let value = rest[op.len()..].to_string();
+    let regex = || Regex::new(&value).map_err(|e| format!("csv: invalid regex '{value}': {e}"));
+    let op = match op {
+        "!=" => Op::Ne,
+        "<" => Op::Lt,
+        "<=" => Op::Le,
+        ">" => Op::Gt,
+        ">=" => Op::Ge,
+        "~" => Op::Matches(regex()?),
+        "!~" => Op::NotMatches(regex()?),
+        _ => Op::Eq,
+    };
+    Ok(Filter {
+        column: column(name, header)?,
+        op,
+        value,
+    })
+}
+
+impl Filter {
+    fn matches(&self, row: &[String]) -> bool {
+        let field = cell(row, self.column);
+        let order = compare(field, &self.value);
+        // Numbers and text only compare by order with their own kind, so
+        // `score>50` skips rows with an empty or non-numeric score.
+        let same_kind = number(field).is_some() == number(&self.value).is_some();
+        match &self.op {
+            Op::Eq => order.is_eq(),
+            Op::Ne => order.is_ne(),
+            Op::Lt => same_kind && order.is_lt(),
+            Op::Le => same_kind && order.is_le(),
+            Op::Gt => same_kind && order.is_gt(),
+            Op::Ge => same_kind && order.is_ge(),
+            Op::Matches(regex) => regex.is_match(field),
+            Op::NotMatches(regex) => !regex.is_match(field),
+        }
+    }
+}
+
+/// One row per distinct combination of `keys`, in order of first
+/// appearance, followed by how many rows had it.
+fn group(table: Table, keys: &[usize]) -> Table {
+    let mut groups: Vec<(Vec<String>, usize)> = Vec::new();
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    for row in &table.rows {
+        let key: Vec<String> = keys.iter().map(|&k| cell(row, k).to_string()).collect();
+        match index.get(&key) {
+            Some(&i) => groups[i].1 += 1,
+            None => {
+                index.insert(key.clone(), groups.len());
+                groups.push((key, 1));
+            }
+        }
+    }
+    let header = table.header.map(|header| {
+        let mut names: Vec<String> = keys.iter().map(|&k| cell(&header, k).to_string()).collect();
+        names.push("count".into());
+        names
+    });
+    let rows = groups
+        .into_iter()
+        .map(|(mut key, count)| {
+            key.push(count.to_string());
+            key
+        })
+        .collect();
+    Table { header, rows }
+}
+
+fn write_csv(table: &Table, delimiter: char) -> String {
+    let mut output = String::new();
+    for row in table.header.iter().chain(&table.rows) {
+        let fields: Vec<String> = row.iter().map(|f| quote(f, delimiter)).collect();
+        output.push_str(&fields.join(&delimiter.to_string()));
+        output.push('\n');
+    }
+    output
+}
+
+/// Rows as JSON objects keyed by the header, or as arrays without one.
+/// Fields stay strings so that converting back loses nothing.
+fn write_json(table: &Table) -> String {
+    let items: Vec<Value> = table
+        .rows
+        .iter()
+        .map(|row| match &table.header {
+            Some(header) => Value::Object(
+                header
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| (name.clone(), cell(row, i).into()))
+                    .collect(),
+            ),
+            None => row.iter().map(|f| Value::from(f.as_str())).collect(),
+        })
+        .collect();
+    json::array(&items)
+}
+
+/// Aligns the fields into columns two spaces apart, with the header
+/// underlined and numeric columns right-aligned. Line breaks and tabs in
+/// fields are shown escaped.
+fn write_table(table: &Table) -> String {
+    let escape = |f: &str| {
+        f.replace('\n', "\\n")
+            .replace('\r', "\\r")
+            .replace('\t', "\\t")
+    };
+    let rows: Vec<Vec<String>> = table
+        .header
+        .iter()
+        .chain(&table.rows)
+        .map(|row| row.iter().map(|f| escape(f)).collect())
+        .collect();
+    let count = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..count)
+        .map(|i| {
+            rows.iter()
+                .map(|row| cell(row, i).chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let numeric: Vec<bool> = (0..count)
+        .map(|i| {
+            let mut fields = table
+                .rows
+                .iter()
+                .map(|row| cell(row, i))
+                .filter(|f| !f.is_empty());
+            fields.clone().next().is_some() && fields.all(|f| number(f).is_some())
+        })
+        .collect();
+
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let fields: Vec<String> = (0..count)
+                .map(|i| match numeric[i] {
+                    true => format!("{:>1$}", cell(row, i), widths[i]),
+                    false => format!("{:<1$}", cell(row, i), widths[i]),
+                })
+                .collect();
+            fields.join("  ").trim_end().to_string()
+        })
+        .collect();
+    if table.header.is_some() {
+        let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+        lines.insert(1, rule.join("  "));
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let (mut table, delimiter) = read_input(&opts, stdin)?;
+
+    let filters = opts
+        .filters
+        .iter()
+        .map(|expr| parse_filter(expr, table.header.as_deref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    table
+        .rows
+        .retain(|row| filters.iter().all(|filter| filter.matches(row)));
+    if !opts.group_by.is_empty() {
+        let keys = columns(&opts.group_by, table.header.as_deref())?;
+        table = group(table, &keys);
+    }
+    if !opts.sort.is_empty() {
+        let keys = columns(&opts.sort, table.header.as_deref())?;
+        table.rows.sort_by(|a, b| {
+            let order = keys
+                .iter()
+                .map(|&k| compare(cell(a, k), cell(b, k)))
+                .find(|order| order.is_ne())
+                .unwrap_or(Ordering::Equal);
+            if opts.reverse { order.reverse() } else { order }
+        });
+    }
+    if let Some(limit) = opts.limit {
+        table.rows.truncate(limit);
+    }
+    if !opts.select.is_empty() {
+        let selected = columns(&opts.select, table.header.as_deref())?;
+        let pick = |row: &Vec<String>| -> Vec<String> {
+            selected.iter().map(|&i| cell(row, i).to_string()).collect()
+        };
+        table.header = table.header.as_ref().map(pick);
+        table.rows = table.rows.iter().map(pick).collect();
+    }
+
+    Ok(match opts.output {
+        Output::Csv => write_csv(&table, delimiter),
+        Output::Json => write_json(&table),
+        Output::Table => write_table(&table),
+        Output::Count => format!("{}\n", table.rows.len()),
+        Output::Headers => {
+            let names = table.header.as_ref().or(table.rows.first());
+            let names = names.map_or(&[][..], Vec::as_slice);
+            names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| format!("{}\t{name}\n", i + 1))
+                .collect()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: &str) -> Result<String, String> {
+        run(args, Some(stdin.into()))
+    }
+
+    const PEOPLE: &str = "name,age,city\n\
+                          Ann,31,Oslo\n\
+                          Bob,27,\"Paris, France\"\n\
+                          Cid,45,Oslo\n\
+                          Dee,9,\"Rome\"\n";
+
+    #[test]
+    fn parses_rfc_4180_quoting() {
+        let records = parse_records(
+            "a,\"b,c\",\"d \"\"e\"\"\"\r\n\"multi\nline\",,x\"y\n\n",
+            ',',
+        )
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["a", "b,c", "d \"e\""], vec!["multi\nline", "", "x\"y"]]
+        );
+        assert_eq!(parse_records("a\tb\n", '\t').unwrap(), vec![vec!["a", "b"]]);
+        assert_eq!(
+            parse_records("a\n\"open,\nb", ',').unwrap_err(),
+            "line 2: unterminated quoted field"
+        );
+        assert_eq!(
+            cmd("", "x,y\n\"1,5\",\"say \"\"hi\"\"\"\n").unwrap(),
+            "x,y\n\"1,5\",\"say \"\"hi\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn selects_columns() {
+        assert_eq!(
+            cmd("-s city,name", PEOPLE).unwrap(),
+            "city,name\nOslo,Ann\n\"Paris, France\",Bob\nOslo,Cid\nRome,Dee\n"
+        );
+        assert_eq!(cmd("-s 2-3 -n 1", PEOPLE).unwrap(), "age,city\n31,Oslo\n");
+        assert_eq!(cmd("-H -s 2", "a,b\nc,d\n").unwrap(), "b\nd\n");
+        assert_eq!(
+            cmd("-s nope", PEOPLE).unwrap_err(),
+            "csv: unknown column 'nope'"
+        );
+        assert_eq!(
+            cmd("--headers", PEOPLE).unwrap(),
+            "1\tname\n2\tage\n3\tcity\n"
+        );
+    }
+
+    #[test]
+    fn filters_rows() {
+        assert_eq!(
+            cmd("-w age>30 -s name", PEOPLE).unwrap(),
+            "name\nAnn\nCid\n"
+        );
+        assert_eq!(
+            cmd("-w city=Oslo -w 'age<=31' -s name", PEOPLE).unwrap(),
+            "name\nAnn\n"
+        );
+        assert_eq!(cmd("-w 'city~^P' -s name", PEOPLE).unwrap(), "name\nBob\n");
+        assert_eq!(cmd("-w 'city!~Oslo' -c", PEOPLE).unwrap(), "2\n");
+        assert_eq!(cmd("-w age=31.0 -c", PEOPLE).unwrap(), "1\n");
+        assert_eq!(cmd("-w 'b>1' -c", "a,b\nx,2\ny,\nz,n/a\n").unwrap(), "1\n");
+        assert!(
+            cmd("-w age", PEOPLE)
+                .unwrap_err()
+                .contains("invalid filter")
+        );
+    }
+
+    #[test]
+    fn sorts_and_groups() {
+        assert_eq!(
+            cmd("-S age -s name", PEOPLE).unwrap(),
+            "name\nDee\nBob\nAnn\nCid\n"
+        );
+        assert_eq!(
+            cmd("-S city,name -r -s name", PEOPLE).unwrap(),
+            "name\nDee\nBob\nCid\nAnn\n"
+        );
+        assert_eq!(
+            cmd("-g city", PEOPLE).unwrap(),
+            "city,count\nOslo,2\n\"Paris, France\",1\nRome,1\n"
+        );
+        assert_eq!(
+            cmd("-g city -S count,city -n 2", PEOPLE).unwrap(),
+            "city,count\n\"Paris, France\",1\nRome,1\n"
+        );
+    }
+
+    #[test]
+    fn converts_to_and_from_json() {
+        let json = cmd("-w city=Oslo -s name,age --json", PEOPLE).unwrap();
+        assert_eq!(
+            json,
+            "[\n  {\"name\":\"Ann\",\"age\":\"31\"},\n  {\"name\":\"Cid\",\"age\":\"45\"}\n]\n"
+        );
+        assert_eq!(
+            cmd("--from-json", &json).unwrap(),
+            "name,age\nAnn,31\nCid,45\n"
+        );
+        assert_eq!(
+            cmd(
+                "--from-json",
+                "{\"a\":1,\"b\":\"x,y\"}\n{\"c\":null,\"a\":[2]}"
+            )
+            .unwrap(),
+            "a,b,c\n1,\"x,y\",\n[2],,\n"
+        );
+        assert_eq!(
+            cmd("-H --json", "a,b\n").unwrap(),
+            "[\n  [\"a\",\"b\"]\n]\n"
+        );
+        assert_eq!(
+            cmd("--from-json -t", "[[\"a\",\"b\"],[1,2]]").unwrap(),
+            "a\tb\n1\t2\n"
+        );
+        assert!(cmd("--from-json", "1").is_err());
+    }
+
+    #[test]
+    fn prints_tables() {
+        assert_eq!(
+            cmd("--table -s name,age,city", PEOPLE).unwrap(),
+            "name  age  city\n\
+             ----  ---  -------------\n\
+             Ann    31  Oslo\n\
+             Bob    27  Paris, France\n\
+             Cid    45  Oslo\n\
+             Dee     9  Rome\n"
+        );
+        assert_eq!(cmd("-H --table", "a,\"b\nc\"\n").unwrap(), "a  b\\nc\n");
+    }
+
+    #[test]
+    fn reads_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, t) = (
+            dir.path().join("a.csv"),
+            dir.path().join("b.csv"),
+            dir.path().join("c.tsv"),
+        );
+        std::fs::write(&a, "k,v\n1,x\n").unwrap();
+        std::fs::write(&b, "k,v\n2,y\n").unwrap();
+        std::fs::write(&t, "k\tval\n3\t\"z\"\n").unwrap();
+        let (a, b, t) = (a.display(), b.display(), t.display());
+        assert_eq!(run(&format!("{a} {b}"), None).unwrap(), "k,v\n1,x\n2,y\n");
+        assert_eq!(run(&format!("-s val {t}"), None).unwrap(), "val\nz\n");
+        assert_eq!(
+            run(&format!("{a} {t}"), None).unwrap_err(),
+            format!("csv: {t}: header differs from {a}")
+        );
+        assert_eq!(run("", None).unwrap_err(), "csv: no input provided");
+    }
+}
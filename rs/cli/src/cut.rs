@@ -1,3 +1,4 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
 struct Opts {
@@ -12,29 +13,20 @@ fn parse_opts(args: &str) -> Result<Opts, String> {
         fields: Vec::new(),
         paths: Vec::new(),
     };
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        match tokens[i] {
-            "-d" => {
-                i += 1;
-                let d = tokens.get(i).ok_or("cut: missing argument to -d")?;
-                opts.delimiter = d.chars().next().unwrap_or('\t');
+    let mut parser = Parser::new("cut", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('d') => {
+                opts.delimiter = parser.value()?.chars().next().unwrap_or('\t');
             }
-            "-f" => {
-                i += 1;
-                let spec = tokens.get(i).ok_or("cut: missing argument to -f")?;
-                opts.fields = parse_fields(spec)?;
+            Arg::Long(l) if l == "delimiter" => {
+                opts.delimiter = parser.value()?.chars().next().unwrap_or('\t');
             }
-            t if t.starts_with("-d") => {
-                opts.delimiter = t[2..].chars().next().unwrap_or('\t');
-            }
-            t if t.starts_with("-f") => {
-                opts.fields = parse_fields(&t[2..])?;
-            }
-            _ => opts.paths.push(tokens[i].to_string()),
+            Arg::Short('f') => opts.fields = parse_fields(&parser.value()?)?,
+            Arg::Long(l) if l == "fields" => opts.fields = parse_fields(&parser.value()?)?,
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
     if opts.fields.is_empty() {
         return Err("cut: you must specify a list of fields".into());
@@ -0,0 +1,99 @@
+//! DEFLATE (RFC 1951) compression and decompression plus CRC-32, shared by
+//! `gzip`, `tar -z` and `zip`.
+
+use flate2::Compression;
+use flate2::bufread::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+/// Decompresses a raw DEFLATE stream, returning the data and the number of
+/// input bytes the stream occupied.
+pub fn decompress(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut decoder = DeflateDecoder::new(data);
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out).map_err(|e| e.to_string())?;
+    let rest = decoder.into_inner();
+    Ok((out, data.len() - rest.len()))
+}
+
+/// Compresses `data` into a raw DEFLATE stream. Level 0 stores the data,
+/// 1 is fastest and 9 smallest.
+pub fn compress(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    // Writing to a Vec cannot fail.
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        for level in [0, 1, 6, 9] {
+            let compressed = compress(data, level);
+            let (out, used) = decompress(&compressed).unwrap();
+            assert_eq!(out, data, "level {level}");
+            assert_eq!(used, compressed.len());
+        }
+    }
+
+    #[test]
+    fn crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"hello hello hello hello world");
+        let text: Vec<u8> = (0..5000)
+            .flat_map(|i| format!("line {} of {}\n", i % 97, i % 13).into_bytes())
+            .collect();
+        round_trip(&text);
+        // Pseudo-random bytes, which do not compress.
+        let mut x = 12345u32;
+        let noise: Vec<u8> = (0..70000)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect();
+        round_trip(&noise);
+        round_trip(&vec![0u8; 100000]);
+    }
+
+    #[test]
+    fn levels_trade_size() {
+        let text: Vec<u8> = (0..3000)
+            .flat_map(|i| format!("{} {}\n", i * 7 % 1000, i % 10).into_bytes())
+            .collect();
+        let stored = compress(&text, 0).len();
+        let fast = compress(&text, 1).len();
+        let best = compress(&text, 9).len();
+        assert!(stored > text.len());
+        assert!(fast < stored / 2);
+        assert!(best <= fast);
+    }
+
+    #[test]
+    fn decodes_fixed_block() {
+        // "abc" compressed with the fixed codes by zlib.
+        let (out, used) = decompress(&[0x4b, 0x4c, 0x4a, 0x06, 0x00]).unwrap();
+        assert_eq!(out, b"abc");
+        assert_eq!(used, 5);
+    }
+
+    #[test]
+    fn rejects_corrupt_data() {
+        assert!(decompress(&[0x07]).is_err());
+        assert!(decompress(&[0x01, 0x05, 0x00, 0x00, 0x00]).is_err());
+        assert!(decompress(&[]).is_err());
+    }
+}
@@ -1,70 +1,291 @@
+//! `diff` compares two files line by line, or two directories entry by
+//! entry (recursively with `-r`). Lines are matched on keys that drop what
+//! `-i`, `-w` and `-b` ignore, while the output shows them as they are.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::args::{self, Arg, Parser};
 use crate::fs_ops;
 
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Normal,
+    Unified,
+    /// `-y`: the two files in columns.
+    SideBySide,
+    /// `--word-diff`: hunks with the changed words marked `[-old-]{+new+}`
+    /// inside their lines, for reviewing edits to prose.
+    Words,
+}
+
+/// Differences left out of the comparison.
+#[derive(Default)]
+struct Ignore {
+    case: bool,
+    all_space: bool,
+    space_change: bool,
+    /// `-B`: changes that only add or remove blank lines.
+    blank_lines: bool,
+}
+
+impl Ignore {
+    /// What a line is compared by.
+    fn key<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut key = Cow::Borrowed(line);
+        if self.all_space {
+            key = Cow::Owned(key.split_whitespace().collect());
+        } else if self.space_change {
+            // Runs of whitespace count as one space, and trailing ones as none.
+            let mut collapsed = String::new();
+            let mut space = false;
+            for c in key.trim_end().chars() {
+                if c.is_whitespace() {
+                    space = true;
+                    continue;
+                }
+                if space {
+                    collapsed.push(' ');
+                    space = false;
+                }
+                collapsed.push(c);
+            }
+            key = Cow::Owned(collapsed);
+        }
+        if self.case {
+            key = Cow::Owned(key.to_lowercase());
+        }
+        key
+    }
+}
+
 struct Opts {
-    unified: bool,
+    format: Format,
     context_lines: usize,
+    brief: bool,
+    recursive: bool,
+    ignore: Ignore,
+    /// Total line width for `-y`.
+    width: usize,
+    suppress_common_lines: bool,
+    /// The options as given, repeated in the `diff` line that heads each
+    /// pair of files compared inside directories.
+    switches: String,
     file_a: String,
     file_b: String,
 }
 
+fn set_format(opts: &mut Opts, format: Format) -> Result<(), String> {
+    match (opts.format, format) {
+        // Word diffs are unified hunks too, and take their context from `-U`.
+        (Format::Words, Format::Unified) => {}
+        (Format::Normal | Format::Unified, Format::Words) => opts.format = format,
+        (current, format) if current == Format::Normal || current == format => opts.format = format,
+        _ => return Err("diff: conflicting output style options".into()),
+    }
+    Ok(())
+}
+
 fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
-        unified: false,
+        format: Format::Normal,
         context_lines: 3,
+        brief: false,
+        recursive: false,
+        ignore: Ignore::default(),
+        width: 130,
+        suppress_common_lines: false,
+        switches: String::new(),
         file_a: String::new(),
         file_b: String::new(),
     };
     let mut positional = Vec::new();
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        if tokens[i] == "-u" {
-            opts.unified = true;
-        } else if tokens[i].starts_with("-U") {
-            opts.unified = true;
-            let n = &tokens[i][2..];
-            if n.is_empty() {
-                i += 1;
-                opts.context_lines = tokens
-                    .get(i)
-                    .ok_or("diff: missing argument to -U")?
-                    .parse()
-                    .map_err(|_| "diff: invalid number for -U")?;
-            } else {
-                opts.context_lines = n.parse().map_err(|_| "diff: invalid number for -U")?;
+    let mut parser = Parser::new("diff", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('u') => set_format(&mut opts, Format::Unified)?,
+            Arg::Short('U') => {
+                set_format(&mut opts, Format::Unified)?;
+                opts.context_lines = parser.number()?;
             }
-        } else {
-            positional.push(tokens[i].to_string());
+            Arg::Short('y') => set_format(&mut opts, Format::SideBySide)?,
+            Arg::Short('W') => opts.width = positive_width(&parser.value()?)?,
+            Arg::Short('q') => opts.brief = true,
+            Arg::Short('r') => opts.recursive = true,
+            Arg::Short('i') => opts.ignore.case = true,
+            Arg::Short('w') => opts.ignore.all_space = true,
+            Arg::Short('b') => opts.ignore.space_change = true,
+            Arg::Short('B') => opts.ignore.blank_lines = true,
+            Arg::Long(l) => match l.as_str() {
+                "unified" => {
+                    set_format(&mut opts, Format::Unified)?;
+                    opts.context_lines = parser.number()?;
+                }
+                "side-by-side" => set_format(&mut opts, Format::SideBySide)?,
+                "word-diff" => set_format(&mut opts, Format::Words)?,
+                "width" => opts.width = positive_width(&parser.value()?)?,
+                "suppress-common-lines" => opts.suppress_common_lines = true,
+                "brief" => opts.brief = true,
+                "recursive" => opts.recursive = true,
+                "ignore-case" => opts.ignore.case = true,
+                "ignore-all-space" => opts.ignore.all_space = true,
+                "ignore-space-change" => opts.ignore.space_change = true,
+                "ignore-blank-lines" => opts.ignore.blank_lines = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => positional.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
     if positional.len() != 2 {
         return Err("diff: requires exactly two files".into());
     }
+    opts.switches = args::split(args)?
+        .iter()
+        .filter(|word| !positional.contains(word))
+        .map(|word| format!(" {}", args::quote(word)))
+        .collect();
     opts.file_a = positional.remove(0);
     opts.file_b = positional.remove(0);
     Ok(opts)
 }
 
+fn positive_width(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(width) if width > 0 => Ok(width),
+        _ => Err(format!("diff: invalid width '{value}'")),
+    }
+}
+
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
     let opts = parse_opts(args)?;
-    let a = fs_ops::read_to_string(&opts.file_a)
-        .map_err(|e| format!("diff: {}: {e}", opts.file_a))?;
-    let b = fs_ops::read_to_string(&opts.file_b)
-        .map_err(|e| format!("diff: {}: {e}", opts.file_b))?;
-    let lines_a: Vec<&str> = a.lines().collect();
-    let lines_b: Vec<&str> = b.lines().collect();
-    let edit = myers_diff(&lines_a, &lines_b);
+    let (a, b) = (opts.file_a.as_str(), opts.file_b.as_str());
+    let is_dir = |path: &str| {
+        fs_ops::stat(path)
+            .map(|meta| meta.is_dir())
+            .map_err(|e| format!("diff: {path}: {e}"))
+    };
+    match (is_dir(a)?, is_dir(b)?) {
+        (true, true) => compare_dirs(&opts, a, b),
+        // A file and a directory compare the file with its namesake there.
+        (true, false) => compare_files(&opts, &join(a, file_name(b)), b, false),
+        (false, true) => compare_files(&opts, a, &join(b, file_name(a)), false),
+        (false, false) => compare_files(&opts, a, b, false),
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{dir}{name}")
+    } else {
+        format!("{dir}/{name}")
+    }
+}
 
-    if edit.iter().all(|e| matches!(e, Edit::Equal(_))) {
+fn file_name(path: &str) -> &str {
+    let path = path.trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Compares two directories entry by entry in name order, like GNU diff:
+/// files are diffed under a `diff` line, and subdirectories only with `-r`.
+fn compare_dirs(opts: &Opts, a: &str, b: &str) -> Result<String, String> {
+    let entries = |dir: &str| -> Result<BTreeMap<String, bool>, String> {
+        let entries = fs_ops::ls(dir, false).map_err(|e| format!("diff: {dir}: {e}"))?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| {
+                let is_dir = entry.is_dir();
+                (entry.name, is_dir)
+            })
+            .collect())
+    };
+    let (entries_a, entries_b) = (entries(a)?, entries(b)?);
+    let names: BTreeSet<&String> = entries_a.keys().chain(entries_b.keys()).collect();
+    let mut output = String::new();
+    for name in names {
+        let (path_a, path_b) = (join(a, name), join(b, name));
+        match (entries_a.get(name).copied(), entries_b.get(name).copied()) {
+            (Some(_), None) => output.push_str(&format!("Only in {a}: {name}\n")),
+            (None, _) => output.push_str(&format!("Only in {b}: {name}\n")),
+            (Some(true), Some(true)) if opts.recursive => {
+                output.push_str(&compare_dirs(opts, &path_a, &path_b)?);
+            }
+            (Some(true), Some(true)) => {
+                output.push_str(&format!("Common subdirectories: {path_a} and {path_b}\n"))
+            }
+            (Some(false), Some(false)) => {
+                output.push_str(&compare_files(opts, &path_a, &path_b, true)?);
+            }
+            (Some(dir_a), Some(_)) => {
+                let kind = |dir| if dir { "directory" } else { "regular file" };
+                output.push_str(&format!(
+                    "File {path_a} is a {} while file {path_b} is a {}\n",
+                    kind(dir_a),
+                    kind(!dir_a)
+                ));
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Compares two files, heading any differences with a `diff` line when
+/// they were found inside directories.
+fn compare_files(opts: &Opts, a: &str, b: &str, in_dir: bool) -> Result<String, String> {
+    let data_a = fs_ops::read(a).map_err(|e| format!("diff: {a}: {e}"))?;
+    let data_b = fs_ops::read(b).map_err(|e| format!("diff: {b}: {e}"))?;
+    let (text_a, text_b) = match (text(&data_a), text(&data_b)) {
+        (Some(text_a), Some(text_b)) => (text_a, text_b),
+        _ if data_a == data_b => return Ok(String::new()),
+        _ if opts.brief => return Ok(format!("Files {a} and {b} differ\n")),
+        _ => return Ok(format!("Binary files {a} and {b} differ\n")),
+    };
+    let lines_a: Vec<&str> = text_a.lines().collect();
+    let lines_b: Vec<&str> = text_b.lines().collect();
+    let edits = diff_lines(&lines_a, &lines_b, &opts.ignore);
+    let blocks = changes(&edits, &opts.ignore);
+    // Side by side shows identical files in full, unless among others.
+    if blocks.is_empty() && (in_dir || opts.brief || opts.format != Format::SideBySide) {
         return Ok(String::new());
     }
+    if opts.brief {
+        return Ok(format!("Files {a} and {b} differ\n"));
+    }
+    let mut output = String::new();
+    if in_dir {
+        output.push_str(&format!("diff{} {a} {b}\n", opts.switches));
+    }
+    output.push_str(&match opts.format {
+        Format::Normal => format_normal(&edits, &blocks),
+        Format::Unified => format_unified(a, b, opts.context_lines, &edits, &blocks),
+        Format::SideBySide => format_side_by_side(&edits, opts.width, opts.suppress_common_lines),
+        Format::Words => format_words(a, b, opts.context_lines, &edits, &blocks, &opts.ignore),
+    });
+    Ok(output)
+}
 
-    if opts.unified {
-        Ok(format_unified(&opts, &edit))
-    } else {
-        Ok(format_normal(&edit))
+/// The content of a text file; files with NUL bytes or invalid UTF-8 are
+/// binary.
+fn text(data: &[u8]) -> Option<&str> {
+    std::str::from_utf8(data)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+}
+
+/// Returns the unified diff between `a` and `b` with `context` lines
+/// around each change, or an empty string if they are identical.
+pub fn unified(label_a: &str, label_b: &str, a: &str, b: &str, context: usize) -> String {
+    let lines_a: Vec<&str> = a.lines().collect();
+    let lines_b: Vec<&str> = b.lines().collect();
+    let ignore = Ignore::default();
+    let edits = diff_lines(&lines_a, &lines_b, &ignore);
+    let blocks = changes(&edits, &ignore);
+    if blocks.is_empty() {
+        return String::new();
     }
+    format_unified(label_a, label_b, context, &edits, &blocks)
 }
 
 #[derive(Clone)]
@@ -74,7 +295,14 @@ enum Edit {
     Insert(String),
 }
 
-fn myers_diff(a: &[&str], b: &[&str]) -> Vec<Edit> {
+fn diff_lines(a: &[&str], b: &[&str], ignore: &Ignore) -> Vec<Edit> {
+    let key_a: Vec<Cow<str>> = a.iter().map(|line| ignore.key(line)).collect();
+    let key_b: Vec<Cow<str>> = b.iter().map(|line| ignore.key(line)).collect();
+    myers_diff(a, b, &key_a, &key_b)
+}
+
+/// The shortest edit script from `a` to `b`, matching entries by their keys.
+fn myers_diff<K: PartialEq>(a: &[&str], b: &[&str], key_a: &[K], key_b: &[K]) -> Vec<Edit> {
     let n = a.len();
     let m = b.len();
     let max = n + m;
@@ -96,7 +324,7 @@ fn myers_diff(a: &[&str], b: &[&str]) -> Vec<Edit> {
                 v[idx - 1] + 1
             };
             let mut y = x - k;
-            while (x as usize) < n && (y as usize) < m && a[x as usize] == b[y as usize] {
+            while (x as usize) < n && (y as usize) < m && key_a[x as usize] == key_b[y as usize] {
                 x += 1;
                 y += 1;
             }
@@ -116,11 +344,12 @@ fn myers_diff(a: &[&str], b: &[&str]) -> Vec<Edit> {
         let v = &trace[d];
         let d = d as isize;
         let k = x - y;
-        let prev_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
-            k + 1
-        } else {
-            k - 1
-        };
+        let prev_k =
+            if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+                k + 1
+            } else {
+                k - 1
+            };
         let prev_x = v[(prev_k + offset) as usize];
         let prev_y = prev_x - prev_k;
 
@@ -143,79 +372,70 @@ fn myers_diff(a: &[&str], b: &[&str]) -> Vec<Edit> {
     edits
 }
 
-fn format_normal(edits: &[Edit]) -> String {
-    let mut output = String::new();
-    let mut a_line = 1usize;
-    let mut b_line = 1usize;
+/// The runs of changed lines in `edits`, leaving out those `-B` ignores.
+fn changes(edits: &[Edit], ignore: &Ignore) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
     let mut i = 0;
     while i < edits.len() {
-        match &edits[i] {
-            Edit::Equal(_) => {
-                a_line += 1;
-                b_line += 1;
-                i += 1;
-            }
-            Edit::Delete(_) => {
-                let start_a = a_line;
-                let mut dels = Vec::new();
-                while i < edits.len() {
-                    if let Edit::Delete(l) = &edits[i] {
-                        dels.push(l.as_str());
-                        a_line += 1;
-                        i += 1;
-                    } else {
-                        break;
-                    }
-                }
-                // check for change (delete + insert)
-                let mut ins = Vec::new();
-                let start_b = b_line;
-                while i < edits.len() {
-                    if let Edit::Insert(l) = &edits[i] {
-                        ins.push(l.as_str());
-                        b_line += 1;
-                        i += 1;
-                    } else {
-                        break;
-                    }
-                }
-                let a_range = range_str(start_a, a_line - 1);
-                if ins.is_empty() {
-                    output.push_str(&format!("{}d{}\n", a_range, b_line - 1));
-                    for l in &dels {
-                        output.push_str(&format!("< {l}\n"));
-                    }
-                } else {
-                    let b_range = range_str(start_b, b_line - 1);
-                    output.push_str(&format!("{a_range}c{b_range}\n"));
-                    for l in &dels {
-                        output.push_str(&format!("< {l}\n"));
-                    }
-                    output.push_str("---\n");
-                    for l in &ins {
-                        output.push_str(&format!("> {l}\n"));
-                    }
-                }
-            }
-            Edit::Insert(_) => {
-                let start_b = b_line;
-                let mut ins = Vec::new();
-                while i < edits.len() {
-                    if let Edit::Insert(l) = &edits[i] {
-                        ins.push(l.as_str());
-                        b_line += 1;
-                        i += 1;
-                    } else {
-                        break;
-                    }
-                }
-                let b_range = range_str(start_b, b_line - 1);
-                output.push_str(&format!("{}a{b_range}\n", a_line - 1));
-                for l in &ins {
-                    output.push_str(&format!("> {l}\n"));
-                }
+        if matches!(edits[i], Edit::Equal(_)) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < edits.len() && !matches!(edits[i], Edit::Equal(_)) {
+            i += 1;
+        }
+        let blank = edits[start..i].iter().all(|edit| match edit {
+            Edit::Delete(line) | Edit::Insert(line) => ignore.key(line).is_empty(),
+            Edit::Equal(_) => true,
+        });
+        if !(ignore.blank_lines && blank) {
+            blocks.push(start..i);
+        }
+    }
+    blocks
+}
+
+/// How many lines of each file `edits` cover.
+fn counts(edits: &[Edit]) -> (usize, usize) {
+    edits.iter().fold((0, 0), |(a, b), edit| match edit {
+        Edit::Equal(_) => (a + 1, b + 1),
+        Edit::Delete(_) => (a + 1, b),
+        Edit::Insert(_) => (a, b + 1),
+    })
+}
+
+fn format_normal(edits: &[Edit], blocks: &[Range<usize>]) -> String {
+    let mut output = String::new();
+    for block in blocks {
+        let (a_line, b_line) = counts(&edits[..block.start]);
+        let mut dels = Vec::new();
+        let mut ins = Vec::new();
+        for edit in &edits[block.clone()] {
+            match edit {
+                Edit::Delete(l) => dels.push(l.as_str()),
+                Edit::Insert(l) => ins.push(l.as_str()),
+                Edit::Equal(_) => {}
             }
         }
+        let a_range = range_str(a_line + 1, a_line + dels.len());
+        let b_range = range_str(b_line + 1, b_line + ins.len());
+        if ins.is_empty() {
+            output.push_str(&format!("{a_range}d{b_line}\n"));
+        } else if dels.is_empty() {
+            output.push_str(&format!("{a_line}a{b_range}\n"));
+        } else {
+            output.push_str(&format!("{a_range}c{b_range}\n"));
+        }
+        for l in &dels {
+            output.push_str(&format!("< {l}\n"));
+        }
+        if !dels.is_empty() && !ins.is_empty() {
+            output.push_str("---\n");
+        }
+        for l in &ins {
+            output.push_str(&format!("> {l}\n"));
+        }
     }
     output
 }
@@ -239,84 +459,278 @@ fn run_with_strings(args: &str, a: &str, b: &str) -> Result<String, String> {
     run(&full_args, None)
 }
 
-fn format_unified(opts: &Opts, edits: &[Edit]) -> String {
+/// Groups changes into hunks with `ctx` lines of context, merging those
+/// close enough for their context to touch.
+fn hunks(edits: &[Edit], blocks: &[Range<usize>], ctx: usize) -> Vec<Range<usize>> {
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+    for block in blocks {
+        let start = block.start.saturating_sub(ctx);
+        let end = (block.end + ctx).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+    hunks
+}
+
+fn hunk_header(edits: &[Edit], hunk: &Range<usize>) -> String {
+    let (a_before, b_before) = counts(&edits[..hunk.start]);
+    let (a_count, b_count) = counts(&edits[hunk.clone()]);
+    // An empty range names the line before it, as in GNU diff.
+    let a_start = if a_count == 0 { a_before } else { a_before + 1 };
+    let b_start = if b_count == 0 { b_before } else { b_before + 1 };
+    format!("@@ -{a_start},{a_count} +{b_start},{b_count} @@\n")
+}
+
+fn format_unified(
+    label_a: &str,
+    label_b: &str,
+    ctx: usize,
+    edits: &[Edit],
+    blocks: &[Range<usize>],
+) -> String {
     let mut output = String::new();
-    output.push_str(&format!("--- {}\n", opts.file_a));
-    output.push_str(&format!("+++ {}\n", opts.file_b));
+    output.push_str(&format!("--- {label_a}\n"));
+    output.push_str(&format!("+++ {label_b}\n"));
+    for hunk in hunks(edits, blocks, ctx) {
+        output.push_str(&hunk_header(edits, &hunk));
+        for e in &edits[hunk] {
+            let (prefix, l) = match e {
+                Edit::Equal(l) => (' ', l),
+                Edit::Delete(l) => ('-', l),
+                Edit::Insert(l) => ('+', l),
+            };
+            output.push(prefix);
+            output.push_str(l);
+            output.push('\n');
+        }
+    }
+    output
+}
 
-    // collect hunks
-    let ctx = opts.context_lines;
-    let mut hunks: Vec<(usize, usize)> = Vec::new(); // (start, end) indices into edits
-    let mut i = 0;
-    while i < edits.len() {
-        if !matches!(&edits[i], Edit::Equal(_)) {
-            let start = i.saturating_sub(ctx);
-            let mut end = i;
-            // extend through nearby changes
-            loop {
-                // skip current change block
-                while end < edits.len() && !matches!(&edits[end], Edit::Equal(_)) {
-                    end += 1;
-                }
-                // count following equal lines
-                let eq_start = end;
-                while end < edits.len() && matches!(&edits[end], Edit::Equal(_)) {
-                    end += 1;
+/// Hunks like `-u` without line prefixes, where each run of changed lines
+/// is shown once with the words that changed marked in place.
+fn format_words(
+    label_a: &str,
+    label_b: &str,
+    ctx: usize,
+    edits: &[Edit],
+    blocks: &[Range<usize>],
+    ignore: &Ignore,
+) -> String {
+    let mut output = String::new();
+    output.push_str(&format!("--- {label_a}\n"));
+    output.push_str(&format!("+++ {label_b}\n"));
+    for hunk in hunks(edits, blocks, ctx) {
+        output.push_str(&hunk_header(edits, &hunk));
+        let mut i = hunk.start;
+        while i < hunk.end {
+            if let Edit::Equal(l) = &edits[i] {
+                output.push_str(l);
+                output.push('\n');
+                i += 1;
+                continue;
+            }
+            let (mut old, mut new) = (Vec::new(), Vec::new());
+            while i < hunk.end {
+                match &edits[i] {
+                    Edit::Delete(l) => old.push(l.as_str()),
+                    Edit::Insert(l) => new.push(l.as_str()),
+                    Edit::Equal(_) => break,
                 }
-                let eq_count = end - eq_start;
-                if end < edits.len() && eq_count <= ctx * 2 {
-                    // merge with next change
+                i += 1;
+            }
+            output.push_str(&word_diff(&old.join("\n"), &new.join("\n"), ignore));
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Splits text into words (runs of non-blank characters, as in `git diff
+/// --word-diff`), runs of blanks and newlines.
+fn words(text: &str) -> Vec<&str> {
+    let class = |c: char| match c {
+        '\n' => 0,
+        c if c.is_whitespace() => 1,
+        _ => 2,
+    };
+    let mut words = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = class(c);
+        if kind != 0 {
+            while chars.next_if(|&(_, next)| class(next) == kind).is_some() {}
+        }
+        let end = chars.peek().map_or(text.len(), |&(i, _)| i);
+        words.push(&text[start..end]);
+    }
+    words
+}
+
+/// `new` with the words that differ from `old` marked `[-old-]{+new+}`.
+fn word_diff(old: &str, new: &str, ignore: &Ignore) -> String {
+    let (words_a, words_b) = (words(old), words(new));
+    // Newlines stay distinct from the blanks that `-w` and `-b` equate.
+    let key = |word: &str| match word {
+        "\n" => "\n".to_string(),
+        word => ignore.key(word).into_owned(),
+    };
+    let key_a: Vec<String> = words_a.iter().map(|word| key(word)).collect();
+    let key_b: Vec<String> = words_b.iter().map(|word| key(word)).collect();
+    let edits = myers_diff(&words_a, &words_b, &key_a, &key_b);
+
+    let mut output = String::new();
+    let (mut deleted, mut inserted) = (String::new(), String::new());
+    for (i, edit) in edits.iter().enumerate() {
+        match edit {
+            Edit::Delete(word) => deleted.push_str(word),
+            Edit::Insert(word) => inserted.push_str(word),
+            Edit::Equal(word) => {
+                // A blank between two replacements joins them into one.
+                let joins = !deleted.is_empty()
+                    && !inserted.is_empty()
+                    && word.trim().is_empty()
+                    && !word.contains('\n')
+                    && matches!(edits.get(i + 1), Some(Edit::Delete(_) | Edit::Insert(_)));
+                if joins {
+                    deleted.push_str(word);
+                    inserted.push_str(word);
                     continue;
                 }
-                // trim trailing context
-                end = std::cmp::min(eq_start + ctx, end);
-                break;
+                mark_change(&mut output, &mut deleted, &mut inserted);
+                output.push_str(word);
             }
-            hunks.push((start, end));
-            i = end;
-        } else {
-            i += 1;
         }
     }
+    mark_change(&mut output, &mut deleted, &mut inserted);
+    output
+}
+
+fn mark_change(output: &mut String, deleted: &mut String, inserted: &mut String) {
+    if !deleted.is_empty() {
+        output.push_str(&format!("[-{deleted}-]"));
+        deleted.clear();
+    }
+    if !inserted.is_empty() {
+        output.push_str(&format!("{{+{inserted}+}}"));
+        inserted.clear();
+    }
+}
+
+const TAB: usize = 8;
+/// The least room between the columns of `-y`, for the marker.
+const GUTTER: usize = 3;
 
-    for (start, end) in hunks {
-        let mut a_start = 1usize;
-        let mut b_start = 1usize;
-        for e in &edits[..start] {
-            match e {
-                Edit::Equal(_) | Edit::Delete(_) => a_start += 1,
-                Edit::Insert(_) => b_start += 1,
+/// Where the columns of `-y` go, computed from the width as GNU diff does.
+struct Columns {
+    half: usize,
+    right: usize,
+}
+
+/// The files side by side, with `|`, `<` or `>` marking changed lines.
+/// Columns are reached with tabs, as GNU diff does without `-t`.
+fn format_side_by_side(edits: &[Edit], width: usize, suppress_common: bool) -> String {
+    let off = (width + TAB + GUTTER) / (2 * TAB) * TAB;
+    let half = off.saturating_sub(GUTTER).min(width.saturating_sub(off));
+    let columns = Columns {
+        half,
+        right: if half > 0 { off } else { width },
+    };
+    let mut output = String::new();
+    let mut i = 0;
+    while i < edits.len() {
+        if let Edit::Equal(l) = &edits[i] {
+            if !suppress_common {
+                side_line(&mut output, &columns, Some(l), ' ', Some(l));
             }
+            i += 1;
+            continue;
         }
-        let mut a_count = 0usize;
-        let mut b_count = 0usize;
-        let mut lines = Vec::new();
-        for e in &edits[start..end] {
-            match e {
-                Edit::Equal(l) => {
-                    lines.push(format!(" {l}"));
-                    a_count += 1;
-                    b_count += 1;
-                }
-                Edit::Delete(l) => {
-                    lines.push(format!("-{l}"));
-                    a_count += 1;
-                }
-                Edit::Insert(l) => {
-                    lines.push(format!("+{l}"));
-                    b_count += 1;
-                }
+        let (mut dels, mut ins) = (Vec::new(), Vec::new());
+        while i < edits.len() {
+            match &edits[i] {
+                Edit::Delete(l) => dels.push(l.as_str()),
+                Edit::Insert(l) => ins.push(l.as_str()),
+                Edit::Equal(_) => break,
             }
+            i += 1;
         }
-        output.push_str(&format!("@@ -{a_start},{a_count} +{b_start},{b_count} @@\n"));
-        for l in &lines {
-            output.push_str(l);
-            output.push('\n');
+        for row in 0..dels.len().max(ins.len()) {
+            let (left, right) = (dels.get(row).copied(), ins.get(row).copied());
+            let sep = match (left, right) {
+                (Some(_), Some(_)) => '|',
+                (Some(_), None) => '<',
+                (None, _) => '>',
+            };
+            side_line(&mut output, &columns, left, sep, right);
         }
     }
     output
 }
 
+fn side_line(
+    output: &mut String,
+    columns: &Columns,
+    left: Option<&str>,
+    sep: char,
+    right: Option<&str>,
+) {
+    let mut col = 0;
+    if let Some(left) = left {
+        col = half_line(output, left, 0, columns.half);
+    }
+    if sep != ' ' {
+        let gutter = (columns.half + columns.right - 1) / 2;
+        tab_to(output, col, gutter);
+        output.push(sep);
+        col = gutter + 1;
+    }
+    if let Some(right) = right.filter(|right| !right.is_empty()) {
+        tab_to(output, col, columns.right);
+        half_line(output, right, columns.right, columns.half);
+    }
+    output.push('\n');
+}
+
+/// Prints as much of `line` as fits in `width` columns from `indent`,
+/// returning the column reached.
+fn half_line(output: &mut String, line: &str, indent: usize, width: usize) -> usize {
+    let bound = indent + width;
+    let (mut in_col, mut out_col) = (indent, indent);
+    for c in line.chars() {
+        if c == '\t' {
+            let tab_stop = in_col + TAB - in_col % TAB;
+            if in_col == out_col && tab_stop < bound {
+                output.push('\t');
+                out_col = tab_stop;
+            }
+            in_col = tab_stop;
+        } else {
+            if in_col < bound {
+                output.push(c);
+                out_col = in_col + 1;
+            }
+            in_col += 1;
+        }
+    }
+    out_col
+}
+
+fn tab_to(output: &mut String, mut from: usize, to: usize) {
+    let mut tab_stop = from + TAB - from % TAB;
+    while tab_stop <= to {
+        output.push('\t');
+        from = tab_stop;
+        tab_stop += TAB;
+    }
+    while from < to {
+        output.push(' ');
+        from += 1;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -396,6 +810,15 @@ mod tests {
         assert!(!out.contains(" 7\n"));
     }
 
+    #[test]
+    fn unified_hunk_positions() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let b = "1\nnew\n2\n3\n4\n5\n6\n7\n9\n";
+        let out = run_with_strings("-U0", a, b).unwrap();
+        assert!(out.contains("@@ -1,0 +2,1 @@\n+new\n"));
+        assert!(out.contains("@@ -8,1 +8,0 @@\n-8\n"));
+    }
+
     #[test]
     fn both_empty() {
         let out = diff("", "").unwrap();
@@ -434,4 +857,120 @@ mod tests {
         let out = diff("old\n", "new\n").unwrap();
         assert!(out.contains("---\n"));
     }
+
+    #[test]
+    fn ignore_case_and_whitespace() {
+        let (a, b) = ("Hello World\nfoo  bar\n", "hello world\nfoo bar \n");
+        assert_eq!(
+            run_with_strings("-i", a, b).unwrap(),
+            "2c2\n< foo  bar\n---\n> foo bar \n"
+        );
+        assert_eq!(run_with_strings("-ib", a, b).unwrap(), "");
+        assert_eq!(run_with_strings("-iw", "a b\n", "ab\n").unwrap(), "");
+        assert!(!run_with_strings("-b", "a b\n", "ab\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn ignore_blank_lines() {
+        assert_eq!(
+            run_with_strings("-B", "a\n\nb\n", "a\nb\n\n\n").unwrap(),
+            ""
+        );
+        assert_eq!(
+            run_with_strings("-B", "a\n\nb\nc\n", "a\nb\nC\n").unwrap(),
+            "4c3\n< c\n---\n> C\n"
+        );
+        // Whitespace-only lines are blank only when whitespace is ignored.
+        assert!(
+            !run_with_strings("-B", "a\n\n", "a\n  \n")
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(run_with_strings("-Bw", "a\n\n", "a\n  \n").unwrap(), "");
+    }
+
+    #[test]
+    fn side_by_side() {
+        let a = "one\ntwo\nthree\n\tfive\n";
+        let b = "one\nTWO\nthree\nsix\n\tfive\nseven\n";
+        assert_eq!(
+            run_with_strings("-y -W 40", a, b).unwrap(),
+            "one\t\t\tone\ntwo\t\t   |\tTWO\nthree\t\t\tthree\n\t\t   >\tsix\n\
+             \tfive\t\t\tfive\n\t\t   >\tseven\n"
+        );
+        assert_eq!(
+            run_with_strings("-y --suppress-common-lines", "a\nb\n", "a\n").unwrap(),
+            "b\t\t\t\t\t\t\t      <\n"
+        );
+        // Lines are cut to fit their column.
+        assert_eq!(
+            run_with_strings("-y -W 20", "abcdefghijkl\n", "x\n").unwrap(),
+            "abcde |\tx\n"
+        );
+        assert_eq!(
+            run_with_strings("-y -W 20", "same\n", "same\n").unwrap(),
+            "same\tsame\n"
+        );
+        assert_eq!(
+            run_with_strings("-y -u", "", "").unwrap_err(),
+            "diff: conflicting output style options"
+        );
+    }
+
+    #[test]
+    fn word_level_changes() {
+        let a = "Title\n\nThe quick brown fox jumps over the lazy dog.\nEnd.\n";
+        let b = "Title\n\nThe slow red fox jumps over the lazy cat!\nNew line.\nEnd.\n";
+        let out = run_with_strings("--word-diff -U1", a, b).unwrap();
+        assert!(out.ends_with(
+            "@@ -2,3 +2,4 @@\n\nThe [-quick brown-]{+slow red+} fox jumps over the lazy \
+             [-dog.-]{+cat!\nNew line.+}\nEnd.\n"
+        ));
+        assert_eq!(
+            word_diff("a b", "a  b c", &Ignore::default()),
+            "a[- -]{+  +}b{+ c+}"
+        );
+    }
+
+    #[test]
+    fn directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().display().to_string();
+        let write = |path: &str, content: &[u8]| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("a/f", b"1\n2\n");
+        write("b/f", b"1\nX\n");
+        write("a/same", b"s\n");
+        write("b/same", b"s\n");
+        write("a/sub/g", b"x\n");
+        write("b/sub/g", b"y\n");
+        write("a/only", b"o\n");
+        write("a/bin", b"a\0b");
+        write("b/bin", b"a\0c");
+        let (a, b) = (format!("{root}/a"), format!("{root}/b"));
+        assert_eq!(
+            run(&format!("{a} {b}"), None).unwrap(),
+            format!(
+                "Binary files {a}/bin and {b}/bin differ\ndiff {a}/f {b}/f\n2c2\n< 2\n---\n> X\n\
+                 Only in {a}: only\nCommon subdirectories: {a}/sub and {b}/sub\n"
+            )
+        );
+        assert_eq!(
+            run(&format!("-r -q {a} {b}"), None).unwrap(),
+            format!(
+                "Files {a}/bin and {b}/bin differ\nFiles {a}/f and {b}/f differ\n\
+                 Only in {a}: only\nFiles {a}/sub/g and {b}/sub/g differ\n"
+            )
+        );
+        let out = run(&format!("-ru {a} {b}"), None).unwrap();
+        assert!(out.contains(&format!("diff -ru {a}/sub/g {b}/sub/g\n--- {a}/sub/g\n")));
+        // A file is compared with its namesake in a directory.
+        assert_eq!(
+            run(&format!("{a}/f {b}"), None).unwrap(),
+            "2c2\n< 2\n---\n> X\n"
+        );
+    }
 }
@@ -0,0 +1,363 @@
+//! `edit` makes one targeted change to a file and returns the unified diff
+//! of that change: replacing an exact string (which must be unique unless
+//! `--all` is given), or replacing, inserting or deleting whole lines.
+//! The replacement text comes from `--new`, or from stdin when omitted.
+
+use crate::args::{Arg, Parser};
+use crate::diff;
+use crate::fs_ops;
+
+enum Op {
+    Replace {
+        old: String,
+        all: bool,
+    },
+    ReplaceLines(usize, usize),
+    /// Insert after the given line; 0 inserts at the top.
+    Insert(usize),
+    DeleteLines(usize, usize),
+}
+
+struct Opts {
+    op: Op,
+    new: Option<String>,
+    dry_run: bool,
+    context_lines: usize,
+    path: String,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut ops = Vec::new();
+    let mut all = false;
+    let mut new = None;
+    let mut dry_run = false;
+    let mut context_lines = 3;
+    let mut paths = Vec::new();
+    let mut parser = Parser::new("edit", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('U') => context_lines = parser.number()?,
+            Arg::Long(l) => match l.as_str() {
+                "old" => ops.push(Op::Replace {
+                    old: parser.value()?,
+                    all: false,
+                }),
+                "new" => new = Some(parser.value()?),
+                "all" => all = true,
+                "lines" => {
+                    let (start, end) = parse_range(&parser.value()?)?;
+                    ops.push(Op::ReplaceLines(start, end));
+                }
+                "insert" => ops.push(Op::Insert(parser.number()?)),
+                "delete" => {
+                    let (start, end) = parse_range(&parser.value()?)?;
+                    ops.push(Op::DeleteLines(start, end));
+                }
+                "dry-run" => dry_run = true,
+                "unified" => context_lines = parser.number()?,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) if paths.is_empty() => paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    let path = paths.pop().ok_or("edit: missing file operand")?;
+    if ops.len() != 1 {
+        return Err("edit: specify exactly one of --old, --lines, --insert or --delete".into());
+    }
+    let mut op = ops.remove(0);
+    if let Op::Replace { all: a, .. } = &mut op {
+        *a = all;
+    }
+    Ok(Opts {
+        op,
+        new,
+        dry_run,
+        context_lines,
+        path,
+    })
+}
+
+/// Parses `N`, `N:M` or `N-M` as an inclusive 1-based line range.
+fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("edit: invalid line range: {s}");
+    let (start, end) = match s.split_once([':', '-']) {
+        Some((start, end)) => (start, end),
+        None => (s, s),
+    };
+    let start: usize = start.parse().map_err(|_| invalid())?;
+    let end: usize = end.parse().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let path = &opts.path;
+    let before = fs_ops::read_to_string(path).map_err(|e| format!("edit: {path}: {e}"))?;
+    let new_text = || {
+        opts.new
+            .clone()
+            .or_else(|| stdin.clone())
+            .ok_or_else(|| "edit: missing replacement text: use --new or stdin".to_string())
+    };
+    let after = match &opts.op {
+        Op::Replace { old, all } => replace(path, &before, old, &new_text()?, *all)?,
+        Op::ReplaceLines(start, end) => {
+            let new = new_text()?;
+            splice_lines(path, &before, *start - 1, *end, Some(&new))?
+        }
+        Op::Insert(line) => {
+            let new = new_text()?;
+            splice_lines(path, &before, *line, *line, Some(&new))?
+        }
+        Op::DeleteLines(start, end) => splice_lines(path, &before, *start - 1, *end, None)?,
+    };
+    if !opts.dry_run && after != before {
+        fs_ops::write(path, after.as_bytes()).map_err(|e| format!("edit: {path}: {e}"))?;
+    }
+    Ok(diff::unified(
+        path,
+        path,
+        &before,
+        &after,
+        opts.context_lines,
+    ))
+}
+
+fn replace(path: &str, text: &str, old: &str, new: &str, all: bool) -> Result<String, String> {
+    if old.is_empty() {
+        return Err("edit: --old must not be empty".into());
+    }
+    match text.matches(old).count() {
+        0 => Err(format!("edit: {path}: old string not found")),
+        1 => Ok(text.replacen(old, new, 1)),
+        _ if all => Ok(text.replace(old, new)),
+        n => Err(format!(
+            "edit: {path}: old string found {n} times; add surrounding context to make it unique or use --all"
+        )),
+    }
+}
+
+/// Replaces lines `start..end` (0-based, exclusive) with the lines of `new`,
+/// or removes them when `new` is `None`. Untouched lines keep their own
+/// line endings, and new lines get the file's: `\r\n` if its first line
+/// ends with one, `\n` otherwise.
+fn splice_lines(
+    path: &str,
+    text: &str,
+    start: usize,
+    end: usize,
+    new: Option<&str>,
+) -> Result<String, String> {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+    let appending = start == end && end == lines.len();
+    if end > lines.len() {
+        return Err(format!(
+            "edit: {path}: line {end} is past the end of the file ({} lines)",
+            lines.len()
+        ));
+    }
+    let eol = match lines.first() {
+        Some(line) if line.ends_with("\r\n") => "\r\n",
+        _ => "\n",
+    };
+    // Appending past an unterminated last line ends it first.
+    let unterminated = lines.last_mut().filter(|last| !last.ends_with('\n'));
+    if let Some(last) = unterminated.filter(|_| appending) {
+        last.push_str(eol);
+    }
+    let replacement: Vec<String> = match new {
+        Some(new) => new
+            .strip_suffix('\n')
+            .unwrap_or(new)
+            .split('\n')
+            .map(|line| format!("{}{eol}", line.strip_suffix('\r').unwrap_or(line)))
+            .collect(),
+        None => Vec::new(),
+    };
+    lines.splice(start..end, replacement);
+    let mut out = lines.concat();
+    // Keep an unterminated file unterminated, unless appending to it.
+    if !text.is_empty() && !text.ends_with('\n') && !appending {
+        let len = out.trim_end_matches(['\r', '\n']).len();
+        out.truncate(len);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    fn tmp(name: &str, content: &str) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        (dir, path.to_str().unwrap().to_string())
+    }
+
+    fn read(path: &str) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    const SRC: &str = "fn main() {\n    let x = 1;\n    println!(\"{x}\");\n}\n";
+
+    #[test]
+    fn replace_unique_string() {
+        let (_dir, path) = tmp("main.rs", SRC);
+        let out = cmd(&format!("{path} --old 'x = 1' --new 'x = 2'"), None).unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "--- {path}\n+++ {path}\n@@ -1,4 +1,4 @@\n fn main() {{\n-    let x = 1;\n+    let x = 2;\n     println!(\"{{x}}\");\n }}\n"
+            )
+        );
+        assert!(read(&path).contains("let x = 2;"));
+    }
+
+    #[test]
+    fn replace_rejects_ambiguous_match() {
+        let (_dir, path) = tmp("f.txt", "a b a\n");
+        let err = cmd(&format!("{path} --old a --new c"), None).unwrap_err();
+        assert!(err.contains("found 2 times"));
+        assert_eq!(read(&path), "a b a\n");
+        cmd(&format!("{path} --old a --new c --all"), None).unwrap();
+        assert_eq!(read(&path), "c b c\n");
+    }
+
+    #[test]
+    fn replace_not_found() {
+        let (_dir, path) = tmp("f.txt", "abc\n");
+        let err = cmd(&format!("{path} --old zzz --new y"), None).unwrap_err();
+        assert!(err.contains("old string not found"));
+    }
+
+    #[test]
+    fn replace_multiline_from_stdin() {
+        let (_dir, path) = tmp("main.rs", SRC);
+        let new = "    let x = 1;\n    let y = 2;";
+        cmd(&format!("{path} --old '    let x = 1;'"), Some(new)).unwrap();
+        assert_eq!(
+            read(&path),
+            "fn main() {\n    let x = 1;\n    let y = 2;\n    println!(\"{x}\");\n}\n"
+        );
+    }
+
+    #[test]
+    fn replace_lines() {
+        let (_dir, path) = tmp("f.txt", "1\n2\n3\n4\n");
+        let out = cmd(
+            &format!("{path} --lines 2:3 --new 'two\nthree\nextra'"),
+            None,
+        )
+        .unwrap();
+        assert!(out.contains("-2\n-3\n+two\n+three\n+extra\n"));
+        assert_eq!(read(&path), "1\ntwo\nthree\nextra\n4\n");
+    }
+
+    #[test]
+    fn insert_lines() {
+        let (_dir, path) = tmp("f.txt", "a\nb\n");
+        cmd(&format!("{path} --insert 0 --new top"), None).unwrap();
+        cmd(&format!("{path} --insert 3 --new bottom"), None).unwrap();
+        assert_eq!(read(&path), "top\na\nb\nbottom\n");
+    }
+
+    #[test]
+    fn insert_after_unterminated_last_line() {
+        let (_dir, path) = tmp("f.txt", "a\nb");
+        cmd(&format!("{path} --insert 2 --new c"), None).unwrap();
+        assert_eq!(read(&path), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn delete_lines() {
+        let (_dir, path) = tmp("f.txt", "1\n2\n3\n4\n");
+        let out = cmd(&format!("{path} --delete 2-3"), None).unwrap();
+        assert!(out.contains("-2\n-3\n"));
+        assert_eq!(read(&path), "1\n4\n");
+        cmd(&format!("{path} --delete 1"), None).unwrap();
+        assert_eq!(read(&path), "4\n");
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let (_dir, path) = tmp("f.txt", "a\r\nb\r\nc\r\n");
+        let out = cmd(&format!("{path} --delete 2"), None).unwrap();
+        assert!(out.contains("\n-b"));
+        assert_eq!(read(&path), "a\r\nc\r\n");
+        cmd(&format!("{path} --insert 1 --new 'x\ny'"), None).unwrap();
+        assert_eq!(read(&path), "a\r\nx\r\ny\r\nc\r\n");
+        cmd(&format!("{path} --lines 4 --new z"), None).unwrap();
+        assert_eq!(read(&path), "a\r\nx\r\ny\r\nz\r\n");
+    }
+
+    #[test]
+    fn keeps_mixed_and_missing_line_endings() {
+        let (_dir, path) = tmp("f.txt", "a\nb\r\nc");
+        cmd(&format!("{path} --lines 1 --new x"), None).unwrap();
+        assert_eq!(read(&path), "x\nb\r\nc");
+        cmd(&format!("{path} --delete 3"), None).unwrap();
+        assert_eq!(read(&path), "x\nb");
+    }
+
+    #[test]
+    fn line_range_out_of_bounds() {
+        let (_dir, path) = tmp("f.txt", "1\n2\n");
+        let err = cmd(&format!("{path} --delete 2:5"), None).unwrap_err();
+        assert!(err.contains("past the end of the file (2 lines)"));
+        let err = cmd(&format!("{path} --delete 3:2"), None).unwrap_err();
+        assert!(err.contains("invalid line range"));
+    }
+
+    #[test]
+    fn dry_run_leaves_file() {
+        let (_dir, path) = tmp("f.txt", "old\n");
+        let out = cmd(&format!("{path} --old old --new new --dry-run"), None).unwrap();
+        assert!(out.contains("-old\n+new\n"));
+        assert_eq!(read(&path), "old\n");
+    }
+
+    #[test]
+    fn context_lines() {
+        let (_dir, path) = tmp("f.txt", "1\n2\n3\n4\n5\n");
+        let out = cmd(&format!("-U0 {path} --lines 3 --new x"), None).unwrap();
+        assert!(out.ends_with("@@ -3,1 +3,1 @@\n-3\n+x\n"));
+    }
+
+    #[test]
+    fn no_change_gives_empty_diff() {
+        let (_dir, path) = tmp("f.txt", "same\n");
+        let out = cmd(&format!("{path} --old same --new same"), None).unwrap();
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn requires_one_operation() {
+        let (_dir, path) = tmp("f.txt", "x\n");
+        let err = cmd(&format!("{path} --new y"), None).unwrap_err();
+        assert!(err.contains("exactly one of"));
+        let err = cmd(&format!("{path} --old x --delete 1"), None).unwrap_err();
+        assert!(err.contains("exactly one of"));
+    }
+
+    #[test]
+    fn missing_replacement_text() {
+        let (_dir, path) = tmp("f.txt", "x\n");
+        let err = cmd(&format!("{path} --old x"), None).unwrap_err();
+        assert!(err.contains("missing replacement text"));
+    }
+
+    #[test]
+    fn missing_file() {
+        let err = cmd("/no/such.txt --old a --new b", None).unwrap_err();
+        assert!(err.starts_with("edit: /no/such.txt:"));
+    }
+}
@@ -1,172 +1,451 @@
-use crate::fs_ops;
+//! `find` walks one or more roots and evaluates an expression of tests
+//! (`-name`, `-size`, ...), actions (`-print`, `-delete`, ...) and
+//! operators (`!`, `-a`, `-o`, parentheses) against every entry.
+
+use crate::args;
+use crate::fs_ops::{self, EntryKind, Metadata};
+use crate::glob;
+use crate::json;
+use serde_json::Value;
 
 struct Opts {
-    root: String,
-    name: Option<String>,
-    file_type: Option<char>, // 'f' or 'd'
+    roots: Vec<String>,
+    expr: Expr,
+    mindepth: usize,
     maxdepth: Option<usize>,
+    /// Visit directory contents before the directory itself; implied by
+    /// `-delete` so directories are empty by the time they are removed.
+    depth_first: bool,
+    /// `--json`: `-print` adds the entry to a JSON array instead.
+    json: bool,
 }
 
-fn parse_opts(args: &str) -> Result<Opts, String> {
-    let mut opts = Opts {
-        root: ".".into(),
-        name: None,
-        file_type: None,
-        maxdepth: None,
-    };
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    // first non-flag token is the root path
-    if !tokens.is_empty() && !tokens[0].starts_with('-') {
-        opts.root = tokens[0].to_string();
-        i = 1;
-    }
-    while i < tokens.len() {
-        match tokens[i] {
-            "-name" => {
-                i += 1;
-                opts.name = Some(
-                    tokens
-                        .get(i)
-                        .ok_or("find: missing argument to -name")?
-                        .to_string(),
-                );
-            }
-            "-type" => {
-                i += 1;
-                let t = *tokens.get(i).ok_or("find: missing argument to -type")?;
-                match t {
-                    "f" | "d" => opts.file_type = Some(t.chars().next().unwrap()),
-                    _ => return Err(format!("find: unknown type: {t}")),
-                }
-            }
-            "-maxdepth" => {
-                i += 1;
-                let n: usize = tokens
-                    .get(i)
-                    .ok_or("find: missing argument to -maxdepth")?
-                    .parse()
-                    .map_err(|_| "find: invalid number for -maxdepth")?;
-                opts.maxdepth = Some(n);
-            }
-            _ => return Err(format!("find: unknown option: {}", tokens[i])),
+enum Expr {
+    Name {
+        pattern: String,
+        ignore_case: bool,
+    },
+    Path {
+        pattern: String,
+        ignore_case: bool,
+    },
+    Type {
+        dir: bool,
+    },
+    /// `-size`, compared in units of `unit` bytes, rounding up.
+    Size {
+        cmp: Cmp,
+        unit: u64,
+    },
+    /// `-mtime`/`-mmin`, the age in whole units of `unit` seconds.
+    Age {
+        cmp: Cmp,
+        unit: u64,
+    },
+    /// `-newer`: modified after the given time.
+    Newer(u64),
+    Print,
+    Print0,
+    Delete,
+    Prune,
+    True,
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn has_action(&self) -> bool {
+        match self {
+            Expr::Print | Expr::Print0 | Expr::Delete => true,
+            Expr::Not(e) => e.has_action(),
+            Expr::And(a, b) | Expr::Or(a, b) => a.has_action() || b.has_action(),
+            _ => false,
+        }
+    }
+
+    fn has_delete(&self) -> bool {
+        match self {
+            Expr::Delete => true,
+            Expr::Not(e) => e.has_delete(),
+            Expr::And(a, b) | Expr::Or(a, b) => a.has_delete() || b.has_delete(),
+            _ => false,
         }
-        i += 1;
     }
-    Ok(opts)
 }
 
-pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args)?;
-    let mut output = Vec::new();
-    // Check the root itself
-    let root_meta = fs_ops::stat(&opts.root)
-        .map_err(|e| format!("find: {}: {e}", opts.root))?;
-    if matches_opts(&opts.root, root_meta.is_dir(), &opts) {
-        output.push(opts.root.clone());
-    }
-    walk(&opts.root, &opts, 0, &mut output)?;
-    Ok(output.join("\n") + if output.is_empty() { "" } else { "\n" })
+/// A numeric argument: `+N` is more than N, `-N` less than N, `N` exactly N.
+#[derive(Clone, Copy)]
+enum Cmp {
+    Less(u64),
+    Equal(u64),
+    Greater(u64),
 }
 
-fn walk(
-    dir: &str,
-    opts: &Opts,
-    depth: usize,
-    output: &mut Vec<String>,
-) -> Result<(), String> {
-    if let Some(max) = opts.maxdepth {
-        if depth >= max {
-            return Ok(());
+impl Cmp {
+    fn parse(predicate: &str, s: &str) -> Result<Self, String> {
+        let (make, digits): (fn(u64) -> Cmp, &str) = match s.as_bytes().first() {
+            Some(b'+') => (Cmp::Greater, &s[1..]),
+            Some(b'-') => (Cmp::Less, &s[1..]),
+            _ => (Cmp::Equal, s),
+        };
+        digits
+            .parse()
+            .map(make)
+            .map_err(|_| format!("find: invalid argument to {predicate}: {s}"))
+    }
+
+    fn matches(self, value: u64) -> bool {
+        match self {
+            Cmp::Less(n) => value < n,
+            Cmp::Equal(n) => value == n,
+            Cmp::Greater(n) => value > n,
         }
     }
+}
 
-    let entries = match fs_ops::ls(dir, false) {
-        Ok(e) => e,
-        Err(_) => return Ok(()), // skip unreadable dirs
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    // find's predicates are single-dash words, so only split here.
+    let tokens = args::split(args).map_err(|e| format!("find: {e}"))?;
+    let roots: Vec<String> = tokens
+        .iter()
+        .take_while(|t| !t.starts_with('-') && *t != "(" && *t != "!")
+        .cloned()
+        .collect();
+    let mut parser = ExprParser {
+        tokens: &tokens[roots.len()..],
+        pos: 0,
+        mindepth: 0,
+        maxdepth: None,
+        json: false,
+    };
+    let expr = if parser.tokens.is_empty() {
+        Expr::True
+    } else {
+        parser.or()?
     };
+    if let Some(token) = parser.peek() {
+        return Err(format!("find: unexpected {token}"));
+    }
+    let depth_first = expr.has_delete();
+    let expr = if expr.has_action() {
+        expr
+    } else {
+        Expr::And(Box::new(expr), Box::new(Expr::Print))
+    };
+    Ok(Opts {
+        roots: if roots.is_empty() {
+            vec![".".into()]
+        } else {
+            roots
+        },
+        expr,
+        mindepth: parser.mindepth,
+        maxdepth: parser.maxdepth,
+        depth_first,
+        json: parser.json,
+    })
+}
 
-    for entry in entries {
-        let path = format!("{dir}/{}", entry.name);
-        let is_dir = entry.is_dir();
+struct ExprParser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+    mindepth: usize,
+    maxdepth: Option<usize>,
+    json: bool,
+}
 
-        if matches_opts_by_name(&entry.name, is_dir, opts) {
-            output.push(path.clone());
-        }
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
 
-        if is_dir {
-            walk(&path, opts, depth + 1, output)?;
-        }
+    fn arg(&mut self, predicate: &str) -> Result<String, String> {
+        let value = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| format!("find: missing argument to {predicate}"))?;
+        self.pos += 1;
+        Ok(value.clone())
     }
-    Ok(())
-}
 
-fn matches_opts(path: &str, is_dir: bool, opts: &Opts) -> bool {
-    if let Some(t) = opts.file_type {
-        match t {
-            'f' if is_dir => return false,
-            'd' if !is_dir => return false,
-            _ => {}
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while matches!(self.peek(), Some("-o" | "-or")) {
+            self.pos += 1;
+            let right = self.and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
         }
+        Ok(left)
     }
-    if let Some(ref pattern) = opts.name {
-        let name = std::path::Path::new(path)
-            .file_name()
-            .map(|n| n.to_string_lossy().into_owned())
-            .unwrap_or_default();
-        if !glob_match(pattern, &name) {
-            return false;
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some("-a" | "-and") => self.pos += 1,
+                None | Some("-o" | "-or" | ")") => return Ok(left),
+                Some(_) => {}
+            }
+            let right = self.unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
         }
     }
-    true
-}
 
-fn matches_opts_by_name(name: &str, is_dir: bool, opts: &Opts) -> bool {
-    if let Some(t) = opts.file_type {
-        match t {
-            'f' if is_dir => return false,
-            'd' if !is_dir => return false,
-            _ => {}
+    fn unary(&mut self) -> Result<Expr, String> {
+        let token = match self.peek() {
+            Some(token) => token.to_string(),
+            None => return Err("find: expected an expression".into()),
+        };
+        self.pos += 1;
+        match token.as_str() {
+            "!" | "-not" => Ok(Expr::Not(Box::new(self.unary()?))),
+            "(" => {
+                let expr = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err("find: missing ')'".into());
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            _ => self.primary(&token),
         }
     }
-    if let Some(ref pattern) = opts.name {
-        if !glob_match(pattern, name) {
-            return false;
-        }
+
+    fn primary(&mut self, token: &str) -> Result<Expr, String> {
+        let expr = match token {
+            "-name" | "-iname" => Expr::Name {
+                pattern: self.arg(token)?,
+                ignore_case: token == "-iname",
+            },
+            "-path" | "-ipath" | "-wholename" => Expr::Path {
+                pattern: self.arg(token)?,
+                ignore_case: token == "-ipath",
+            },
+            "-type" => {
+                let t = self.arg(token)?;
+                match t.as_str() {
+                    "f" => Expr::Type { dir: false },
+                    "d" => Expr::Type { dir: true },
+                    _ => return Err(format!("find: unknown type: {t}")),
+                }
+            }
+            "-size" => {
+                let arg = self.arg(token)?;
+                let (number, unit) = match arg.char_indices().last() {
+                    Some((i, c)) if c.is_ascii_alphabetic() => {
+                        let unit = match c {
+                            'c' => 1,
+                            'w' => 2,
+                            'b' => 512,
+                            'k' => 1 << 10,
+                            'M' => 1 << 20,
+                            'G' => 1 << 30,
+                            _ => return Err(format!("find: invalid size unit: {arg}")),
+                        };
+                        (&arg[..i], unit)
+                    }
+                    _ => (arg.as_str(), 512),
+                };
+                Expr::Size {
+                    cmp: Cmp::parse(token, number)?,
+                    unit,
+                }
+            }
+            "-mtime" | "-mmin" => Expr::Age {
+                cmp: Cmp::parse(token, &self.arg(token)?)?,
+                unit: if token == "-mtime" { 86400 } else { 60 },
+            },
+            "-newer" => {
+                let reference = self.arg(token)?;
+                let meta =
+                    fs_ops::stat(&reference).map_err(|e| format!("find: {reference}: {e}"))?;
+                let modified = meta
+                    .last_modified
+                    .ok_or_else(|| format!("find: {reference}: no modification time"))?;
+                Expr::Newer(modified)
+            }
+            "-mindepth" | "-maxdepth" => {
+                let n: usize = self
+                    .arg(token)?
+                    .parse()
+                    .map_err(|_| format!("find: invalid number for {token}"))?;
+                if token == "-mindepth" {
+                    self.mindepth = n;
+                } else {
+                    self.maxdepth = Some(n);
+                }
+                Expr::True
+            }
+            "-json" | "--json" => {
+                self.json = true;
+                Expr::True
+            }
+            "-print" => Expr::Print,
+            "-print0" => Expr::Print0,
+            "-delete" => Expr::Delete,
+            "-prune" => Expr::Prune,
+            "-true" => Expr::True,
+            "-false" => Expr::Not(Box::new(Expr::True)),
+            _ => return Err(format!("find: unknown option: {token}")),
+        };
+        Ok(expr)
     }
-    true
 }
 
-fn glob_match(pattern: &str, name: &str) -> bool {
-    glob_match_inner(pattern.as_bytes(), name.as_bytes())
+/// The entry currently being evaluated.
+struct Entry<'a> {
+    path: &'a str,
+    name: &'a str,
+    is_dir: bool,
+    size: u64,
 }
 
-fn glob_match_inner(pat: &[u8], name: &[u8]) -> bool {
-    let mut pi = 0;
-    let mut ni = 0;
-    let mut star_pi = usize::MAX;
-    let mut star_ni = 0;
-
-    while ni < name.len() {
-        if pi < pat.len() && (pat[pi] == b'?' || pat[pi] == name[ni]) {
-            pi += 1;
-            ni += 1;
-        } else if pi < pat.len() && pat[pi] == b'*' {
-            star_pi = pi;
-            star_ni = ni;
-            pi += 1;
-        } else if star_pi != usize::MAX {
-            pi = star_pi + 1;
-            star_ni += 1;
-            ni = star_ni;
-        } else {
-            return false;
+impl Entry<'_> {
+    fn modified(&self) -> Option<u64> {
+        fs_ops::stat(self.path).ok()?.last_modified
+    }
+}
+
+struct Finder<'a> {
+    opts: &'a Opts,
+    now: u64,
+    output: String,
+    /// JSON objects for the entries printed with `--json`.
+    entries: Vec<Value>,
+    /// Set by `-prune` to stop descent into the current directory.
+    pruned: bool,
+}
+
+pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut finder = Finder {
+        opts: &opts,
+        now,
+        output: String::new(),
+        entries: Vec::new(),
+        pruned: false,
+    };
+    for root in &opts.roots {
+        let meta = fs_ops::stat(root).map_err(|e| format!("find: {root}: {e}"))?;
+        let name = match root.trim_end_matches('/').rsplit('/').next() {
+            Some("") | None => "/",
+            Some(name) => name,
+        };
+        let entry = Entry {
+            path: root,
+            name,
+            is_dir: meta.is_dir(),
+            size: meta.size,
+        };
+        finder.visit(&entry, 0)?;
+    }
+    if opts.json {
+        finder.output.push_str(&json::array(&finder.entries));
+    }
+    Ok(finder.output)
+}
+
+impl Finder<'_> {
+    fn visit(&mut self, entry: &Entry, depth: usize) -> Result<(), String> {
+        let opts = self.opts;
+        let evaluate = depth >= opts.mindepth;
+        self.pruned = false;
+        if evaluate && !opts.depth_first {
+            self.eval(&opts.expr, entry)?;
+        }
+        let descend = entry.is_dir && !self.pruned && opts.maxdepth.is_none_or(|max| depth < max);
+        if descend {
+            // Unreadable directories are skipped.
+            let entries = fs_ops::ls(entry.path, false).unwrap_or_default();
+            for child in entries {
+                let path = if entry.path.ends_with('/') {
+                    format!("{}{}", entry.path, child.name)
+                } else {
+                    format!("{}/{}", entry.path, child.name)
+                };
+                let child = Entry {
+                    path: &path,
+                    name: &child.name,
+                    is_dir: child.is_dir(),
+                    size: child.size,
+                };
+                self.visit(&child, depth + 1)?;
+            }
+        }
+        if evaluate && opts.depth_first {
+            self.eval(&opts.expr, entry)?;
         }
+        Ok(())
+    }
+
+    fn eval(&mut self, expr: &Expr, entry: &Entry) -> Result<bool, String> {
+        Ok(match expr {
+            Expr::Name {
+                pattern,
+                ignore_case,
+            } => glob_matches(pattern, entry.name, *ignore_case),
+            Expr::Path {
+                pattern,
+                ignore_case,
+            } => glob_matches(pattern, entry.path, *ignore_case),
+            Expr::Type { dir } => entry.is_dir == *dir,
+            Expr::Size { cmp, unit } => cmp.matches(entry.size.div_ceil(*unit)),
+            Expr::Age { cmp, unit } => entry
+                .modified()
+                .is_some_and(|t| cmp.matches(self.now.saturating_sub(t) / unit)),
+            Expr::Newer(time) => entry.modified().is_some_and(|t| t > *time),
+            Expr::Print if self.opts.json => {
+                let meta = Metadata {
+                    size: entry.size,
+                    kind: if entry.is_dir {
+                        EntryKind::Directory
+                    } else {
+                        EntryKind::File
+                    },
+                    last_modified: entry.modified(),
+                };
+                self.entries
+                    .push(json::entry(entry.name, entry.path, &meta));
+                true
+            }
+            Expr::Print => {
+                self.output.push_str(entry.path);
+                self.output.push('\n');
+                true
+            }
+            Expr::Print0 => {
+                self.output.push_str(entry.path);
+                self.output.push('\0');
+                true
+            }
+            Expr::Delete => {
+                fs_ops::rm(entry.path, false)
+                    .map_err(|e| format!("find: cannot delete {}: {e}", entry.path))?;
+                true
+            }
+            Expr::Prune => {
+                self.pruned = true;
+                true
+            }
+            Expr::True => true,
+            Expr::Not(e) => !self.eval(e, entry)?,
+            Expr::And(a, b) => self.eval(a, entry)? && self.eval(b, entry)?,
+            Expr::Or(a, b) => self.eval(a, entry)? || self.eval(b, entry)?,
+        })
     }
-    while pi < pat.len() && pat[pi] == b'*' {
-        pi += 1;
+}
+
+fn glob_matches(pattern: &str, name: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        glob::matches(&pattern.to_lowercase(), &name.to_lowercase())
+    } else {
+        glob::matches(pattern, name)
     }
-    pi == pat.len()
 }
 
 #[cfg(test)]
@@ -253,7 +532,11 @@ mod tests {
         let out = cmd(&format!("{} -name ?.txt", dir.path().display())).unwrap();
         let l = lines(&out);
         assert!(l.iter().all(|p| {
-            let name = std::path::Path::new(p).file_name().unwrap().to_str().unwrap();
+            let name = std::path::Path::new(p)
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap();
             name.len() == 5 && name.ends_with(".txt")
         }));
     }
@@ -286,22 +569,162 @@ mod tests {
         assert_eq!(out, "");
     }
 
+    fn names(out: &str) -> Vec<String> {
+        let mut names: Vec<String> = out
+            .lines()
+            .map(|p| p.rsplit('/').next().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn iname() {
+        let dir = setup();
+        std::fs::write(dir.path().join("E.TXT"), "").unwrap();
+        let out = cmd(&format!("{} -iname '*.txt'", dir.path().display())).unwrap();
+        assert_eq!(names(&out), ["E.TXT", "a.txt", "c.txt", "d.txt"]);
+    }
+
+    #[test]
+    fn path_pattern() {
+        let dir = setup();
+        let out = cmd(&format!("{} -path '*/sub/*' -type f", dir.path().display())).unwrap();
+        assert_eq!(names(&out), ["c.txt", "d.txt"]);
+    }
+
+    #[test]
+    fn size() {
+        let dir = setup();
+        std::fs::write(dir.path().join("big.bin"), vec![0u8; 2000]).unwrap();
+        let root = dir.path().display();
+        assert_eq!(
+            names(&cmd(&format!("{root} -size 2000c")).unwrap()),
+            ["big.bin"]
+        );
+        assert_eq!(
+            names(&cmd(&format!("{root} -size +1k -type f")).unwrap()),
+            ["big.bin"]
+        );
+        let out = cmd(&format!("{root} -size -1 -type f")).unwrap();
+        assert_eq!(names(&out), ["a.txt", "b.rs", "c.txt", "d.txt"]);
+    }
+
     #[test]
-    fn glob_star() {
-        assert!(glob_match("*.txt", "hello.txt"));
-        assert!(!glob_match("*.txt", "hello.rs"));
-        assert!(glob_match("*", "anything"));
+    fn mtime_and_newer() {
+        let dir = setup();
+        let old = std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("a.txt"))
+            .unwrap();
+        let three_days = std::time::Duration::from_secs(3 * 86400);
+        let times =
+            std::fs::FileTimes::new().set_modified(std::time::SystemTime::now() - three_days);
+        old.set_times(times).unwrap();
+        let root = dir.path().display();
+        assert_eq!(
+            names(&cmd(&format!("{root} -mtime +1")).unwrap()),
+            ["a.txt"]
+        );
+        let out = cmd(&format!("{root} -type f -newer {root}/a.txt")).unwrap();
+        assert_eq!(names(&out), ["b.rs", "c.txt", "d.txt"]);
+        let out = cmd(&format!("{root} -type f -mmin -10")).unwrap();
+        assert_eq!(names(&out), ["b.rs", "c.txt", "d.txt"]);
     }
 
     #[test]
-    fn glob_question() {
-        assert!(glob_match("?.txt", "a.txt"));
-        assert!(!glob_match("?.txt", "ab.txt"));
+    fn mindepth() {
+        let dir = setup();
+        let out = cmd(&format!("{} -mindepth 2", dir.path().display())).unwrap();
+        assert_eq!(names(&out), ["c.txt", "d.txt", "deep"]);
+        let out = cmd(&format!("{} -mindepth 1 -maxdepth 1", dir.path().display())).unwrap();
+        assert_eq!(names(&out), ["a.txt", "b.rs", "sub"]);
     }
 
     #[test]
-    fn glob_exact() {
-        assert!(glob_match("exact", "exact"));
-        assert!(!glob_match("exact", "other"));
+    fn or_and_not() {
+        let dir = setup();
+        let root = dir.path().display();
+        let out = cmd(&format!("{root} -name a.txt -o -name b.rs")).unwrap();
+        assert_eq!(names(&out), ["a.txt", "b.rs"]);
+        let out = cmd(&format!("{root} -type f ! -name '*.txt'")).unwrap();
+        assert_eq!(names(&out), ["b.rs"]);
+    }
+
+    #[test]
+    fn parentheses() {
+        let dir = setup();
+        let root = dir.path().display();
+        let args = format!("{root} -type f ( -name c.txt -o -name d.txt )");
+        assert_eq!(names(&cmd(&args).unwrap()), ["c.txt", "d.txt"]);
+        let err = cmd(&format!("{root} ( -name c.txt")).unwrap_err();
+        assert!(err.contains("missing ')'"));
+    }
+
+    #[test]
+    fn prune() {
+        let dir = setup();
+        let args = format!(
+            "{} -name sub -prune -o -type f -print",
+            dir.path().display()
+        );
+        assert_eq!(names(&cmd(&args).unwrap()), ["a.txt", "b.rs"]);
+    }
+
+    #[test]
+    fn explicit_print_suppresses_default() {
+        let dir = setup();
+        let args = format!("{} -name a.txt -print -o -name b.rs", dir.path().display());
+        assert_eq!(names(&cmd(&args).unwrap()), ["a.txt"]);
+    }
+
+    #[test]
+    fn print0() {
+        let dir = setup();
+        let out = cmd(&format!("{} -name '?.txt' -print0", dir.path().display())).unwrap();
+        assert_eq!(out.matches('\0').count(), 3);
+        assert!(!out.contains('\n'));
+    }
+
+    #[test]
+    fn delete() {
+        let dir = setup();
+        let out = cmd(&format!("{} -name '*.txt' -delete", dir.path().display())).unwrap();
+        assert_eq!(out, "");
+        assert!(!dir.path().join("sub/deep/d.txt").exists());
+        assert!(dir.path().join("b.rs").exists());
+        cmd(&format!("{}/sub -delete", dir.path().display())).unwrap();
+        assert!(!dir.path().join("sub").exists());
+    }
+
+    #[test]
+    fn multiple_roots() {
+        let dir = setup();
+        let root = dir.path().display();
+        let out = cmd(&format!("{root}/sub {root}/b.rs -maxdepth 0")).unwrap();
+        assert_eq!(out, format!("{root}/sub\n{root}/b.rs\n"));
+    }
+
+    #[test]
+    fn invalid_size() {
+        let dir = setup();
+        let err = cmd(&format!("{} -size 10x", dir.path().display())).unwrap_err();
+        assert!(err.contains("invalid size unit"));
+    }
+
+    #[test]
+    fn json_output() {
+        let dir = setup();
+        let root = dir.path().display();
+        let out = cmd(&format!("{root} -name *.txt --json")).unwrap();
+        assert!(out.starts_with("[\n") && out.ends_with("\n]\n"));
+        assert_eq!(out.matches("\"kind\":\"file\"").count(), 3);
+        assert!(out.contains(&format!(
+            "{{\"name\":\"c.txt\",\"path\":\"{root}/sub/c.txt\",\"kind\":\"file\",\"size\":0,"
+        )));
+        let out = cmd(&format!("{root} -json -type d -name deep")).unwrap();
+        assert_eq!(out.lines().count(), 3);
+        assert!(out.contains("\"name\":\"deep\"") && out.contains("\"kind\":\"directory\""));
+        assert_eq!(cmd(&format!("{root} -name none --json")).unwrap(), "[]\n");
     }
 }
@@ -0,0 +1,167 @@
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+struct Opts {
+    width: usize,
+    spaces: bool,
+    bytes: bool,
+    paths: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        width: 80,
+        spaces: false,
+        bytes: false,
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new("fold", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('w') => opts.width = parser.number()?,
+            Arg::Short('s') => opts.spaces = true,
+            Arg::Short('b') => opts.bytes = true,
+            Arg::Number(n) => {
+                opts.width = n
+                    .parse()
+                    .map_err(|_| format!("fold: invalid number of columns: {n}"))?;
+            }
+            Arg::Long(l) => match l.as_str() {
+                "width" => opts.width = parser.number()?,
+                "spaces" => opts.spaces = true,
+                "bytes" => opts.bytes = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.width == 0 {
+        return Err("fold: invalid number of columns: 0".into());
+    }
+    Ok(opts)
+}
+
+/// The column after printing `c` at `column`. Tabs, backspaces and
+/// carriage returns move the cursor like a terminal unless counting bytes.
+fn advance(opts: &Opts, column: usize, c: char) -> usize {
+    if opts.bytes {
+        return column + c.len_utf8();
+    }
+    match c {
+        '\x08' => column.saturating_sub(1),
+        '\r' => 0,
+        '\t' => column + 8 - column % 8,
+        _ => column + 1,
+    }
+}
+
+fn fold_line(opts: &Opts, line: &str, output: &mut String) {
+    let mut pending: Vec<char> = Vec::new();
+    let mut column = 0;
+    for c in line.chars() {
+        loop {
+            let next = advance(opts, column, c);
+            if next <= opts.width {
+                column = next;
+                pending.push(c);
+                break;
+            }
+            let blank = match opts.spaces {
+                true => pending.iter().rposition(|c| c.is_whitespace()),
+                false => None,
+            };
+            if let Some(blank) = blank {
+                let rest = pending.split_off(blank + 1);
+                output.extend(pending.iter());
+                output.push('\n');
+                pending = rest;
+                column = pending.iter().fold(0, |col, &c| advance(opts, col, c));
+                continue;
+            }
+            // A character wider than the whole line still goes somewhere.
+            if pending.is_empty() {
+                column = next;
+                pending.push(c);
+                break;
+            }
+            output.extend(pending.drain(..));
+            output.push('\n');
+            column = 0;
+        }
+    }
+    output.extend(pending);
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let input = if opts.paths.is_empty() {
+        stdin.unwrap_or_default()
+    } else {
+        let mut combined = String::new();
+        for path in &opts.paths {
+            let content = fs_ops::read_to_string(path).map_err(|e| format!("fold: {path}: {e}"))?;
+            combined.push_str(&content);
+        }
+        combined
+    };
+
+    let mut output = String::new();
+    for line in input.split_inclusive('\n') {
+        match line.strip_suffix('\n') {
+            Some(text) => {
+                fold_line(&opts, text, &mut output);
+                output.push('\n');
+            }
+            None => fold_line(&opts, line, &mut output),
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn hard_wrap() {
+        assert_eq!(
+            cmd("-w 4", Some("abcdefghij\nxy\n")).unwrap(),
+            "abcd\nefgh\nij\nxy\n"
+        );
+        assert_eq!(cmd("-3", Some("abcdefg")).unwrap(), "abc\ndef\ng");
+    }
+
+    #[test]
+    fn break_at_spaces() {
+        let out = cmd("-s -w 10", Some("the quick brown fox jumps\n")).unwrap();
+        assert_eq!(out, "the quick \nbrown fox \njumps\n");
+        // Words longer than the width are still split.
+        let out = cmd("-sw 4", Some("ab abcdefgh\n")).unwrap();
+        assert_eq!(out, "ab \nabcd\nefgh\n");
+    }
+
+    #[test]
+    fn tabs_count_to_the_next_stop() {
+        assert_eq!(cmd("-w 10", Some("\tabcd\n")).unwrap(), "\tab\ncd\n");
+        assert_eq!(cmd("-b -w 3", Some("\tabcd\n")).unwrap(), "\tab\ncd\n");
+    }
+
+    #[test]
+    fn invalid_width() {
+        assert!(
+            cmd("-w 0", Some("x"))
+                .unwrap_err()
+                .contains("invalid number of columns")
+        );
+        assert!(
+            cmd("-w x", Some("x"))
+                .unwrap_err()
+                .contains("invalid number")
+        );
+    }
+}
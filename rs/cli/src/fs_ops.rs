@@ -61,6 +61,10 @@ mod imp {
         wit_fs::read(path)
     }
 
+    pub fn read_range(path: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+        wit_fs::read_range(path, offset, length)
+    }
+
     pub fn write(path: &str, data: &[u8]) -> Result<(), String> {
         wit_fs::write(path, data)
     }
@@ -112,6 +116,18 @@ mod imp {
         fs::read(path).map_err(|e| e.to_string())
     }
 
+    pub fn read_range(path: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+        use std::io::{Read, Seek, SeekFrom};
+        let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+        let mut data = Vec::new();
+        file.take(length)
+            .read_to_end(&mut data)
+            .map_err(|e| e.to_string())?;
+        Ok(data)
+    }
+
     pub fn write(path: &str, data: &[u8]) -> Result<(), String> {
         if let Some(parent) = std::path::Path::new(path).parent() {
             if !parent.exists() {
@@ -277,6 +293,12 @@ pub fn read(path: &str) -> Result<Vec<u8>, String> {
     imp::read(path)
 }
 
+/// Reads up to `length` bytes starting at `offset`, without fetching the
+/// rest of the file.
+pub fn read_range(path: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+    imp::read_range(path, offset, length)
+}
+
 pub fn read_to_string(path: &str) -> Result<String, String> {
     let bytes = imp::read(path)?;
     String::from_utf8(bytes).map_err(|e| format!("{path}: invalid UTF-8: {e}"))
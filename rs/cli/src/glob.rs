@@ -0,0 +1,417 @@
+//! Shell-style wildcard matching shared by the cli utilities, and the
+//! expansion of wildcards and braces in their arguments.
+
+use crate::args;
+use crate::fs_ops;
+
+/// Matches `name` against a glob `pattern`: `*` matches any run of
+/// characters, `?` a single character, `[abc]`, `[a-z]` and `[!abc]`
+/// character classes, and `\` escapes the next character.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pat: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let mut pi = 0;
+    let mut ni = 0;
+    // Where to resume after the last `*` if the rest fails to match.
+    let mut star: Option<(usize, usize)> = None;
+    while ni < name.len() {
+        if pat.get(pi) == Some(&'*') {
+            star = Some((pi, ni));
+            pi += 1;
+            continue;
+        }
+        if let Some(len) = match_one(&pat[pi..], name[ni]) {
+            pi += len;
+            ni += 1;
+            continue;
+        }
+        match star {
+            Some((star_pi, star_ni)) => {
+                pi = star_pi + 1;
+                ni = star_ni + 1;
+                star = Some((star_pi, star_ni + 1));
+            }
+            None => return false,
+        }
+    }
+    pat[pi..].iter().all(|c| *c == '*')
+}
+
+/// Matches the pattern element at the start of `pat` against `c`,
+/// returning the element's length on success.
+fn match_one(pat: &[char], c: char) -> Option<usize> {
+    match pat.first()? {
+        '?' => Some(1),
+        '\\' if pat.len() > 1 => (pat[1] == c).then_some(2),
+        '[' => match class_end(pat) {
+            Some(end) => class_matches(&pat[1..end], c).then_some(end + 1),
+            None => (c == '[').then_some(1),
+        },
+        p => (*p == c).then_some(1),
+    }
+}
+
+/// Returns the index of the `]` closing the class that starts `pat`.
+fn class_end(pat: &[char]) -> Option<usize> {
+    let mut i = 1;
+    if matches!(pat.get(i), Some('!' | '^')) {
+        i += 1;
+    }
+    // A `]` right after the opening bracket is literal.
+    if pat.get(i) == Some(&']') {
+        i += 1;
+    }
+    while i < pat.len() {
+        if pat[i] == ']' {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negate, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    matched != negate
+}
+
+/// Expands the unquoted braces and wildcards in an argument string the
+/// way a shell does before running a command. `{a,b}` and `{1..3}` expand
+/// first, regardless of files; then each word with `*`, `?` or `[...]`
+/// becomes the sorted paths it matches through [`fs_ops::ls`], where `**`
+/// matches any number of directories. Words that match nothing are left
+/// as written, and so are arguments that fail to split, for the utility
+/// to report.
+pub fn expand_args(args: &str) -> String {
+    let Ok(words) = args::words(args) else {
+        return args.to_string();
+    };
+    let mut expanded = Vec::new();
+    for word in words {
+        if !has_special(&word.pattern, "*?[{") {
+            expanded.push(word.raw);
+            continue;
+        }
+        for pattern in braces(&word.pattern) {
+            let paths = match has_special(&pattern, "*?[") {
+                true => expand_path(&pattern),
+                false => Vec::new(),
+            };
+            if paths.is_empty() {
+                expanded.push(args::quote(&unescape(&pattern)));
+            }
+            expanded.extend(paths.iter().map(|path| args::quote(path)));
+        }
+    }
+    expanded.join(" ")
+}
+
+/// Whether `pattern` has any of `chars` outside `\` escapes.
+fn has_special(pattern: &str, chars: &str) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if chars.contains(c) {
+            return true;
+        }
+    }
+    false
+}
+
+fn unescape(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Expands the first brace group in `pattern` and, recursively, the rest.
+fn braces(pattern: &str) -> Vec<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if let Some((end, alternatives)) = brace_group(&chars, i) {
+                    let prefix: String = chars[..i].iter().collect();
+                    let suffix: String = chars[end + 1..].iter().collect();
+                    return alternatives
+                        .iter()
+                        .flat_map(|alt| braces(&format!("{prefix}{alt}{suffix}")))
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    vec![pattern.to_string()]
+}
+
+/// The end and alternatives of the brace group opening at `start`: a
+/// comma-separated list, or a `{1..5}` or `{a..e}` sequence. Braces with
+/// neither stay literal, as in bash.
+fn brace_group(chars: &[char], start: usize) -> Option<(usize, Vec<String>)> {
+    let mut alternatives = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                current.push('\\');
+                current.push(chars[i + 1]);
+                i += 1;
+            }
+            '{' => {
+                depth += 1;
+                current.push('{');
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                current.push('}');
+            }
+            '}' => {
+                if alternatives.is_empty() {
+                    return sequence(&current).map(|items| (i, items));
+                }
+                alternatives.push(current);
+                return Some((i, alternatives));
+            }
+            ',' if depth == 0 => alternatives.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The most items a brace sequence may expand to. Longer sequences stay
+/// literal rather than exhausting memory.
+const MAX_SEQUENCE: u64 = 10_000;
+
+/// The items of a `1..5` or `a..e` brace sequence, counting down if the
+/// end is lower.
+fn sequence(body: &str) -> Option<Vec<String>> {
+    let (first, last) = body.split_once("..")?;
+    if let (Ok(first), Ok(last)) = (first.parse::<i64>(), last.parse::<i64>()) {
+        if first.abs_diff(last) >= MAX_SEQUENCE {
+            return None;
+        }
+        let items: Vec<i64> = match first <= last {
+            true => (first..=last).collect(),
+            false => (last..=first).rev().collect(),
+        };
+        return Some(items.iter().map(i64::to_string).collect());
+    }
+    let (mut first, mut last) = (first.chars(), last.chars());
+    match (first.next(), first.next(), last.next(), last.next()) {
+        (Some(first), None, Some(last), None) if first.is_ascii() && last.is_ascii() => {
+            let items: Vec<char> = match first <= last {
+                true => (first..=last).collect(),
+                false => (last..=first).rev().collect(),
+            };
+            Some(items.iter().map(char::to_string).collect())
+        }
+        _ => None,
+    }
+}
+
+/// The paths matching a wildcard pattern, one directory level per
+/// component. Names starting with `.` only match a pattern that does too.
+fn expand_path(pattern: &str) -> Vec<String> {
+    let absolute = pattern.starts_with('/');
+    let dirs_only = pattern.ends_with('/');
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    // Paths matched so far, where "" is the current directory.
+    let mut paths = vec![String::new()];
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let join = |dir: &str, name: &str| match (dir, absolute) {
+            ("", true) => format!("/{name}"),
+            ("", false) => name.to_string(),
+            (dir, _) => format!("{dir}/{name}"),
+        };
+        let list = |dir: &str, recursive: bool| {
+            let dir = match (dir, absolute) {
+                ("", true) => "/",
+                ("", false) => ".",
+                (dir, _) => dir,
+            };
+            fs_ops::ls(dir, recursive).unwrap_or_default()
+        };
+        let mut next = Vec::new();
+        for dir in &paths {
+            if *component == "**" {
+                // Zero or more directories, or with nothing after it, every
+                // path below.
+                if !last {
+                    next.push(dir.clone());
+                }
+                for entry in list(dir, true) {
+                    let hidden = entry.name.split('/').any(|name| name.starts_with('.'));
+                    if !hidden && (last || entry.is_dir()) {
+                        next.push(join(dir, &entry.name));
+                    }
+                }
+            } else if !has_special(component, "*?[") {
+                next.push(join(dir, &unescape(component)));
+            } else {
+                let hidden_ok = unescape(component).starts_with('.');
+                for entry in list(dir, false) {
+                    let wanted = (last && !dirs_only) || entry.is_dir();
+                    if wanted
+                        && (hidden_ok || !entry.name.starts_with('.'))
+                        && matches(component, &entry.name)
+                    {
+                        next.push(join(dir, &entry.name));
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+    // A literal last component has to exist, like `src/*/mod.rs`.
+    let check = components.last().is_some_and(|c| !has_special(c, "*?[")) || dirs_only;
+    if check {
+        paths.retain(|path| match fs_ops::stat(path) {
+            Ok(meta) => !dirs_only || meta.is_dir(),
+            Err(_) => false,
+        });
+    }
+    paths.sort();
+    paths.dedup();
+    if dirs_only {
+        paths.iter_mut().for_each(|path| path.push('/'));
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_star() {
+        assert!(matches("*.txt", "hello.txt"));
+        assert!(!matches("*.txt", "hello.rs"));
+        assert!(matches("*", "anything"));
+        assert!(matches("a*b*c", "axxbyyc"));
+        assert!(!matches("a*b*c", "axxbyy"));
+    }
+
+    #[test]
+    fn glob_question() {
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?.txt", "ab.txt"));
+    }
+
+    #[test]
+    fn glob_exact() {
+        assert!(matches("exact", "exact"));
+        assert!(!matches("exact", "other"));
+    }
+
+    #[test]
+    fn glob_classes() {
+        assert!(matches("[abc].rs", "b.rs"));
+        assert!(!matches("[abc].rs", "d.rs"));
+        assert!(matches("file[0-9]", "file7"));
+        assert!(!matches("file[!0-9]", "file7"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("a[", "a["));
+    }
+
+    #[test]
+    fn glob_escape() {
+        assert!(matches(r"\*.txt", "*.txt"));
+        assert!(!matches(r"\*.txt", "a.txt"));
+    }
+
+    #[test]
+    fn glob_unicode() {
+        assert!(matches("?é", "aé"));
+    }
+
+    #[test]
+    fn brace_expansion() {
+        assert_eq!(braces("a{b,c}d"), ["abd", "acd"]);
+        assert_eq!(braces("{x,y{1,2}}"), ["x", "y1", "y2"]);
+        assert_eq!(braces("f{1..3}"), ["f1", "f2", "f3"]);
+        assert_eq!(braces("{c..a}"), ["c", "b", "a"]);
+        assert_eq!(braces("{a}{b,}"), ["{a}b", "{a}"]);
+        assert_eq!(braces(r"\{a,b}"), [r"\{a,b}"]);
+        assert_eq!(braces("{a,b"), ["{a,b"]);
+    }
+
+    #[test]
+    fn long_sequences_stay_literal() {
+        assert_eq!(braces("{1..10000}").len(), 10_000);
+        assert_eq!(braces("{1..10001}"), ["{1..10001}"]);
+        assert_eq!(
+            braces("{0..9223372036854775807}"),
+            ["{0..9223372036854775807}"]
+        );
+        assert_eq!(
+            braces("{-9223372036854775808..0}"),
+            ["{-9223372036854775808..0}"]
+        );
+    }
+
+    #[test]
+    fn expands_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().display().to_string();
+        for path in [
+            "src/b.rs",
+            "src/a.rs",
+            "src/x.txt",
+            "src/.h.rs",
+            "docs/a.md",
+            "docs/deep/b.md",
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let expand = |args: &str| expand_args(&args.replace("ROOT", &root)).replace(&root, "ROOT");
+        assert_eq!(expand("-n ROOT/src/*.rs"), "-n ROOT/src/a.rs ROOT/src/b.rs");
+        assert_eq!(
+            expand("ROOT/src/.*.rs ROOT/s?c/[ax].*"),
+            "ROOT/src/.h.rs ROOT/src/a.rs ROOT/src/x.txt"
+        );
+        assert_eq!(expand("ROOT/**/*.md"), "ROOT/docs/a.md ROOT/docs/deep/b.md");
+        assert_eq!(expand("ROOT/*/"), "ROOT/docs/ ROOT/src/");
+        assert_eq!(expand("ROOT/*/a.*"), "ROOT/docs/a.md ROOT/src/a.rs");
+        assert_eq!(expand("ROOT/src/{a,c}.rs"), "ROOT/src/a.rs ROOT/src/c.rs");
+        // Quoted, escaped and unmatched wildcards stay as written.
+        assert_eq!(
+            expand("'ROOT/src/*.rs' ROOT/src/\\*.rs"),
+            "'ROOT/src/*.rs' ROOT/src/\\*.rs"
+        );
+        assert_eq!(expand("ROOT/none/*.rs 'a b'"), "'ROOT/none/*.rs' 'a b'");
+    }
+}
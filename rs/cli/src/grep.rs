@@ -1,5 +1,7 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
-use regex::RegexBuilder;
+use crate::glob;
+use regex::{Regex, RegexBuilder};
 
 struct Opts {
     ignore_case: bool,
@@ -8,7 +10,18 @@ struct Opts {
     line_number: bool,
     files_only: bool,
     recursive: bool,
-    pattern: String,
+    fixed: bool,
+    word: bool,
+    only_matching: bool,
+    /// `-H`/`-h`; by default filenames are shown when several files are searched.
+    with_filename: Option<bool>,
+    before: usize,
+    after: usize,
+    max_count: Option<usize>,
+    patterns: Vec<String>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    exclude_dir: Vec<String>,
     paths: Vec<String>,
 }
 
@@ -20,123 +33,341 @@ fn parse_opts(args: &str) -> Result<Opts, String> {
         line_number: false,
         files_only: false,
         recursive: false,
-        pattern: String::new(),
+        fixed: false,
+        word: false,
+        only_matching: false,
+        with_filename: None,
+        before: 0,
+        after: 0,
+        max_count: None,
+        patterns: Vec::new(),
+        include: Vec::new(),
+        exclude: Vec::new(),
+        exclude_dir: Vec::new(),
         paths: Vec::new(),
     };
     let mut positional = Vec::new();
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 && !token.starts_with("--") {
-            for c in token[1..].chars() {
-                match c {
-                    'i' => opts.ignore_case = true,
-                    'v' => opts.invert = true,
-                    'c' => opts.count = true,
-                    'n' => opts.line_number = true,
-                    'l' => opts.files_only = true,
-                    'r' | 'R' => opts.recursive = true,
-                    _ => {}
-                }
+    let mut explicit_patterns = false;
+    let mut parser = Parser::new("grep", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('i') => opts.ignore_case = true,
+            Arg::Short('v') => opts.invert = true,
+            Arg::Short('c') => opts.count = true,
+            Arg::Short('n') => opts.line_number = true,
+            Arg::Short('l') => opts.files_only = true,
+            Arg::Short('r' | 'R') => opts.recursive = true,
+            Arg::Short('F') => opts.fixed = true,
+            Arg::Short('E') => {}
+            Arg::Short('w') => opts.word = true,
+            Arg::Short('o') => opts.only_matching = true,
+            Arg::Short('H') => opts.with_filename = Some(true),
+            Arg::Short('h') => opts.with_filename = Some(false),
+            Arg::Short('A') => opts.after = parser.number()?,
+            Arg::Short('B') => opts.before = parser.number()?,
+            Arg::Short('C') => {
+                opts.after = parser.number()?;
+                opts.before = opts.after;
             }
-        } else {
-            positional.push(token.to_string());
+            Arg::Short('m') => opts.max_count = Some(parser.number()?),
+            Arg::Short('e') => {
+                opts.patterns.push(parser.value()?);
+                explicit_patterns = true;
+            }
+            Arg::Number(n) => {
+                opts.after = n
+                    .parse()
+                    .map_err(|_| format!("grep: invalid context length: {n}"))?;
+                opts.before = opts.after;
+            }
+            Arg::Long(l) => match l.as_str() {
+                "ignore-case" => opts.ignore_case = true,
+                "invert-match" => opts.invert = true,
+                "count" => opts.count = true,
+                "line-number" => opts.line_number = true,
+                "files-with-matches" => opts.files_only = true,
+                "recursive" => opts.recursive = true,
+                "fixed-strings" => opts.fixed = true,
+                "extended-regexp" => {}
+                "word-regexp" => opts.word = true,
+                "only-matching" => opts.only_matching = true,
+                "with-filename" => opts.with_filename = Some(true),
+                "no-filename" => opts.with_filename = Some(false),
+                "after-context" => opts.after = parser.number()?,
+                "before-context" => opts.before = parser.number()?,
+                "context" => {
+                    opts.after = parser.number()?;
+                    opts.before = opts.after;
+                }
+                "max-count" => opts.max_count = Some(parser.number()?),
+                "regexp" => {
+                    opts.patterns.push(parser.value()?);
+                    explicit_patterns = true;
+                }
+                "include" => opts.include.push(parser.value()?),
+                "exclude" => opts.exclude.push(parser.value()?),
+                "exclude-dir" => opts.exclude_dir.push(parser.value()?),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(value) => positional.push(value),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
-    if positional.is_empty() {
-        return Err("grep: missing pattern".into());
+    if !explicit_patterns {
+        if positional.is_empty() {
+            return Err("grep: missing pattern".into());
+        }
+        opts.patterns.push(positional.remove(0));
     }
-    opts.pattern = positional.remove(0);
     opts.paths = positional;
     Ok(opts)
 }
 
-pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args)?;
-    let re = RegexBuilder::new(&opts.pattern)
+/// Combines the patterns into one regex; a pattern containing newlines
+/// counts as one pattern per line, as in GNU grep.
+fn build_regex(opts: &Opts) -> Result<Regex, String> {
+    let alternatives: Vec<String> = opts
+        .patterns
+        .iter()
+        .flat_map(|p| p.split('\n'))
+        .map(|p| {
+            let p = if opts.fixed {
+                regex::escape(p)
+            } else {
+                p.to_string()
+            };
+            format!("(?:{p})")
+        })
+        .collect();
+    RegexBuilder::new(&alternatives.join("|"))
         .case_insensitive(opts.ignore_case)
         .build()
-        .map_err(|e| format!("grep: invalid pattern: {e}"))?;
-    let mut output = String::new();
+        .map_err(|e| format!("grep: invalid pattern: {e}"))
+}
 
-    if opts.paths.is_empty() {
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let re = build_regex(&opts)?;
+    let mut searcher = Searcher {
+        opts: &opts,
+        re: &re,
+        output: String::new(),
+        printed: false,
+    };
+
+    if opts.paths.is_empty() && !opts.recursive {
         let input = stdin.unwrap_or_default();
-        grep_lines(&input, None, &re, &opts, &mut output);
-    } else {
-        let mut files = Vec::new();
-        for path in &opts.paths {
-            if opts.recursive {
-                collect_files(path, &mut files)?;
-            } else {
-                files.push(path.clone());
-            }
-        }
-        let show_filename = files.len() > 1;
-        for file in &files {
-            let content = fs_ops::read_to_string(file)
-                .map_err(|e| format!("grep: {file}: {e}"))?;
-            let label = if show_filename { Some(file.as_str()) } else { None };
-            grep_lines(&content, label, &re, &opts, &mut output);
+        let label = opts.with_filename.unwrap_or(false).then_some("(stdin)");
+        searcher.search(&input, label, "(stdin)");
+        return Ok(searcher.output);
+    }
+    let mut files = Vec::new();
+    let mut searched_dir = false;
+    if opts.paths.is_empty() {
+        // `grep -r` without operands searches the working directory and
+        // reports paths relative to it.
+        collect_dir(".", "", &opts, &mut files)?;
+        searched_dir = true;
+    }
+    for path in &opts.paths {
+        let is_dir = opts.recursive
+            && fs_ops::stat(path)
+                .map_err(|e| format!("grep: {path}: {e}"))?
+                .is_dir();
+        if is_dir {
+            let prefix = format!("{}/", path.trim_end_matches('/'));
+            collect_dir(path, &prefix, &opts, &mut files)?;
+            searched_dir = true;
+        } else if included(path, &opts) {
+            files.push(path.clone());
         }
     }
-    Ok(output)
+    let show_filename = opts
+        .with_filename
+        .unwrap_or(opts.paths.len() > 1 || searched_dir);
+    for file in &files {
+        let content = fs_ops::read_to_string(file).map_err(|e| format!("grep: {file}: {e}"))?;
+        let label = if show_filename {
+            Some(file.as_str())
+        } else {
+            None
+        };
+        searcher.search(&content, label, file);
+    }
+    Ok(searcher.output)
 }
 
-fn grep_lines(
-    text: &str,
-    filename: Option<&str>,
-    re: &regex::Regex,
-    opts: &Opts,
-    output: &mut String,
-) {
-    let mut match_count = 0;
-    for (i, line) in text.lines().enumerate() {
-        let matched = re.is_match(line);
-        let matched = if opts.invert { !matched } else { matched };
-
-        if matched {
-            if opts.files_only {
-                if let Some(f) = filename {
-                    output.push_str(f);
-                } else {
-                    output.push_str("(stdin)");
+struct Searcher<'a> {
+    opts: &'a Opts,
+    re: &'a Regex,
+    output: String,
+    /// Whether any line has been printed yet, across all files, so
+    /// context groups know when to emit a `--` separator.
+    printed: bool,
+}
+
+impl Searcher<'_> {
+    fn search(&mut self, text: &str, label: Option<&str>, name: &str) {
+        let opts = self.opts;
+        let lines: Vec<&str> = text.lines().collect();
+        let show_lines = !opts.count && !opts.files_only;
+        let mut match_count = 0;
+        let mut last_printed: Option<usize> = None;
+        let mut after_left = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if opts.max_count.is_some_and(|max| match_count >= max) {
+                // Past the limit only trailing context is printed.
+                if after_left == 0 || !show_lines {
+                    break;
                 }
-                output.push('\n');
-                return;
+                self.print_line(label, i, line, '-', &mut last_printed);
+                after_left -= 1;
+                continue;
             }
-            match_count += 1;
-            if !opts.count {
-                if let Some(f) = filename {
-                    output.push_str(f);
-                    output.push(':');
+            if self.selects(line) {
+                match_count += 1;
+                if opts.files_only {
+                    self.output.push_str(label.unwrap_or(name));
+                    self.output.push('\n');
+                    return;
+                }
+                if !show_lines {
+                    continue;
                 }
-                if opts.line_number {
-                    output.push_str(&format!("{}:", i + 1));
+                if !opts.only_matching {
+                    let first = last_printed.map_or(0, |l| l + 1);
+                    let start = i.saturating_sub(opts.before).max(first);
+                    for (j, before) in lines[start..i].iter().enumerate() {
+                        self.print_line(label, start + j, before, '-', &mut last_printed);
+                    }
+                    self.print_line(label, i, line, ':', &mut last_printed);
+                    after_left = opts.after;
+                } else if !opts.invert {
+                    for (start, end) in find_matches(self.re, line, opts.word) {
+                        if start < end {
+                            self.print_prefix(label, i, ':');
+                            self.output.push_str(&line[start..end]);
+                            self.output.push('\n');
+                        }
+                    }
                 }
-                output.push_str(line);
-                output.push('\n');
+            } else if after_left > 0 && show_lines && !opts.only_matching {
+                self.print_line(label, i, line, '-', &mut last_printed);
+                after_left -= 1;
+            }
+        }
+        if opts.count {
+            if let Some(f) = label {
+                self.output.push_str(f);
+                self.output.push(':');
             }
+            self.output.push_str(&match_count.to_string());
+            self.output.push('\n');
         }
     }
-    if opts.count {
-        if let Some(f) = filename {
-            output.push_str(f);
-            output.push(':');
+
+    fn selects(&self, line: &str) -> bool {
+        let matched = if self.opts.word {
+            !find_matches(self.re, line, true).is_empty()
+        } else {
+            self.re.is_match(line)
+        };
+        matched != self.opts.invert
+    }
+
+    /// Prints line `index`, preceded by a `--` separator when context is
+    /// requested and the line does not continue the previous group.
+    fn print_line(
+        &mut self,
+        label: Option<&str>,
+        index: usize,
+        line: &str,
+        sep: char,
+        last_printed: &mut Option<usize>,
+    ) {
+        let context = self.opts.before > 0 || self.opts.after > 0;
+        let continues = last_printed.is_some_and(|l| l + 1 == index);
+        if context && self.printed && !continues {
+            self.output.push_str("--\n");
+        }
+        self.print_prefix(label, index, sep);
+        self.output.push_str(line);
+        self.output.push('\n');
+        self.printed = true;
+        *last_printed = Some(index);
+    }
+
+    fn print_prefix(&mut self, label: Option<&str>, index: usize, sep: char) {
+        if let Some(f) = label {
+            self.output.push_str(f);
+            self.output.push(sep);
+        }
+        if self.opts.line_number {
+            self.output.push_str(&format!("{}{sep}", index + 1));
         }
-        output.push_str(&match_count.to_string());
-        output.push('\n');
     }
 }
 
-fn collect_files(path: &str, files: &mut Vec<String>) -> Result<(), String> {
-    let meta = fs_ops::stat(path).map_err(|e| format!("grep: {path}: {e}"))?;
-    if meta.is_dir() {
-        let entries = fs_ops::ls(path, true).map_err(|e| format!("grep: {path}: {e}"))?;
-        for entry in entries {
-            if !entry.is_dir() {
-                files.push(format!("{path}/{}", entry.name));
-            }
+/// Returns the byte ranges of the non-overlapping matches in `line`.
+/// With `word`, a match only counts when it is not preceded or followed
+/// by a word character.
+fn find_matches(re: &Regex, line: &str, word: bool) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while pos <= line.len() {
+        let m = match re.find_at(line, pos) {
+            Some(m) => m,
+            None => break,
+        };
+        if !word || word_bounded(line, m.start(), m.end()) {
+            found.push((m.start(), m.end()));
+            pos = if m.end() > m.start() {
+                m.end()
+            } else {
+                next_char(line, m.end())
+            };
+        } else {
+            pos = next_char(line, m.start());
+        }
+    }
+    found
+}
+
+fn word_bounded(line: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    !line[..start].chars().next_back().is_some_and(is_word)
+        && !line[end..].chars().next().is_some_and(is_word)
+}
+
+fn next_char(line: &str, pos: usize) -> usize {
+    pos + line[pos..].chars().next().map_or(1, char::len_utf8)
+}
+
+/// Applies `--include`/`--exclude` to the file's basename.
+fn included(path: &str, opts: &Opts) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    (opts.include.is_empty() || opts.include.iter().any(|p| glob::matches(p, name)))
+        && !opts.exclude.iter().any(|p| glob::matches(p, name))
+}
+
+fn collect_dir(
+    path: &str,
+    prefix: &str,
+    opts: &Opts,
+    files: &mut Vec<String>,
+) -> Result<(), String> {
+    let entries = fs_ops::ls(path, true).map_err(|e| format!("grep: {path}: {e}"))?;
+    for entry in entries {
+        if entry.is_dir() {
+            continue;
+        }
+        let mut dirs = entry.name.split('/').rev().skip(1);
+        if dirs.any(|d| opts.exclude_dir.iter().any(|p| glob::matches(p, d))) {
+            continue;
+        }
+        if included(&entry.name, opts) {
+            files.push(format!("{prefix}{}", entry.name));
         }
-    } else {
-        files.push(path.to_string());
     }
     Ok(())
 }
@@ -293,4 +524,193 @@ mod tests {
         let out = cmd("-vc x", Some("x\na\nb\nx")).unwrap();
         assert_eq!(out, "2\n");
     }
+
+    #[test]
+    fn quoted_pattern_with_spaces() {
+        let out = cmd("\"foo bar\"", Some("foo bar\nfoo\nbar\n")).unwrap();
+        assert_eq!(out, "foo bar\n");
+    }
+
+    #[test]
+    fn quoted_regex_keeps_escapes() {
+        let out = cmd(r"-c '\d+\s\w'", Some("12 a\nab\n3  b\n")).unwrap();
+        assert_eq!(out, "1\n");
+    }
+
+    #[test]
+    fn filename_with_spaces() {
+        let (_dir, path) = tmp("my notes.txt", "alpha\nbeta\n");
+        let out = cmd(&format!("-n beta '{path}'"), None).unwrap();
+        assert_eq!(out, "2:beta\n");
+    }
+
+    #[test]
+    fn pattern_after_double_dash() {
+        let out = cmd("-- -v", Some("a -v b\nc\n")).unwrap();
+        assert_eq!(out, "a -v b\n");
+    }
+
+    #[test]
+    fn unknown_option() {
+        assert!(
+            cmd("-Z x", Some("x"))
+                .unwrap_err()
+                .contains("invalid option")
+        );
+    }
+
+    #[test]
+    fn after_context() {
+        let out = cmd("-A1 b", Some("a\nb\nc\nd\n")).unwrap();
+        assert_eq!(out, "b\nc\n");
+    }
+
+    #[test]
+    fn before_context_with_line_numbers() {
+        let out = cmd("-n -B1 c", Some("a\nb\nc\nd\n")).unwrap();
+        assert_eq!(out, "2-b\n3:c\n");
+    }
+
+    #[test]
+    fn context_groups_separated() {
+        let out = cmd("-C1 a", Some("x\na\ny\nz\nw\na\nq\n")).unwrap();
+        assert_eq!(out, "x\na\ny\n--\nw\na\nq\n");
+    }
+
+    #[test]
+    fn context_groups_merge() {
+        let out = cmd("-1 a", Some("a\nx\na\ny\n")).unwrap();
+        assert_eq!(out, "a\nx\na\ny\n");
+    }
+
+    #[test]
+    fn fixed_strings() {
+        let out = cmd("-F a.c", Some("abc\na.c\n")).unwrap();
+        assert_eq!(out, "a.c\n");
+    }
+
+    #[test]
+    fn word_match() {
+        let out = cmd("-w foo", Some("foobar\nfoo bar\nbarfoo\n")).unwrap();
+        assert_eq!(out, "foo bar\n");
+    }
+
+    #[test]
+    fn word_match_skips_partial_hits() {
+        let out = cmd("-w foo", Some("foobar foo\n")).unwrap();
+        assert_eq!(out, "foobar foo\n");
+    }
+
+    #[test]
+    fn only_matching() {
+        let out = cmd("-on '[0-9]+'", Some("a1b22\nnone\n333\n")).unwrap();
+        assert_eq!(out, "1:1\n1:22\n3:333\n");
+    }
+
+    #[test]
+    fn multiple_patterns() {
+        let out = cmd("-e foo -e bar", Some("foo\nbaz\nbar\n")).unwrap();
+        assert_eq!(out, "foo\nbar\n");
+    }
+
+    #[test]
+    fn explicit_pattern_makes_operands_files() {
+        let (_dir, path) = tmp("f.txt", "one\ntwo\n");
+        let out = cmd(&format!("-e two {path}"), None).unwrap();
+        assert_eq!(out, "two\n");
+    }
+
+    #[test]
+    fn max_count() {
+        let out = cmd("-m2 a", Some("a1\nb\na2\na3\n")).unwrap();
+        assert_eq!(out, "a1\na2\n");
+        let out = cmd("-c -m1 a", Some("a\na\n")).unwrap();
+        assert_eq!(out, "1\n");
+    }
+
+    #[test]
+    fn max_count_keeps_trailing_context() {
+        let out = cmd("-m1 -A1 a", Some("a\nb\na\n")).unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[test]
+    fn context_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let p1 = dir.path().join("a.txt");
+        let p2 = dir.path().join("b.txt");
+        std::fs::write(&p1, "x\nhit\n").unwrap();
+        std::fs::write(&p2, "hit\ny\n").unwrap();
+        let (p1, p2) = (p1.display(), p2.display());
+        let out = cmd(&format!("-A1 hit {p1} {p2}"), None).unwrap();
+        assert_eq!(out, format!("{p1}:hit\n--\n{p2}:hit\n{p2}-y\n"));
+    }
+
+    #[test]
+    fn filename_flags() {
+        let (_dir, path) = tmp("f.txt", "hit\n");
+        let out = cmd(&format!("-H hit {path}"), None).unwrap();
+        assert_eq!(out, format!("{path}:hit\n"));
+        let out = cmd(&format!("-h hit {path} {path}"), None).unwrap();
+        assert_eq!(out, "hit\nhit\n");
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub")).unwrap();
+        std::fs::create_dir_all(dir.path().join("skip")).unwrap();
+        std::fs::write(dir.path().join("a.txt"), "needle\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "needle\n").unwrap();
+        std::fs::write(dir.path().join("sub/c.txt"), "needle\n").unwrap();
+        std::fs::write(dir.path().join("skip/d.txt"), "needle\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn recursive_include() {
+        let dir = tree();
+        let root = dir.path().display();
+        let out = cmd(&format!("-rl --include=*.txt needle {root}"), None).unwrap();
+        let mut lines: Vec<_> = out.lines().collect();
+        lines.sort();
+        let expected = [
+            format!("{root}/a.txt"),
+            format!("{root}/skip/d.txt"),
+            format!("{root}/sub/c.txt"),
+        ];
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn recursive_exclude() {
+        let dir = tree();
+        let root = dir.path().display();
+        let args = format!("-rl --exclude '*.txt' needle {root}");
+        assert_eq!(cmd(&args, None).unwrap(), format!("{root}/b.rs\n"));
+    }
+
+    #[test]
+    fn recursive_exclude_dir() {
+        let dir = tree();
+        let root = dir.path().display();
+        let args = format!("-rc --exclude-dir=skip --exclude-dir=sub needle {root}");
+        let out = cmd(&args, None).unwrap();
+        assert!(out.contains("a.txt:1"));
+        assert!(!out.contains("skip"));
+        assert!(!out.contains("sub"));
+    }
+
+    #[test]
+    fn recursive_single_dir_shows_filenames() {
+        let dir = tree();
+        let sub = dir.path().join("sub");
+        let out = cmd(&format!("-r needle {}", sub.display()), None).unwrap();
+        assert_eq!(out, format!("{}/c.txt:needle\n", sub.display()));
+    }
+
+    #[test]
+    fn invalid_context() {
+        let err = cmd("-A x a", Some("a")).unwrap_err();
+        assert!(err.contains("invalid number"));
+    }
 }
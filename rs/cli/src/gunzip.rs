@@ -0,0 +1,38 @@
+use crate::gzip;
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    gzip::gzip("gunzip", true, args, stdin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("gunzip", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    #[test]
+    fn decompresses_by_default() {
+        let compressed = gzip::compress(b"data\n", 9, None, 0);
+        let err = run("", Some("text".into())).unwrap_err();
+        assert_eq!(err, "gunzip: stdin: not in gzip format");
+        let err = run("", Some(String::new())).unwrap_err();
+        assert_eq!(err, "gunzip: stdin: unexpected end of file");
+        assert_eq!(run_bytes("", Some(compressed)).unwrap(), b"data\n");
+    }
+
+    #[test]
+    fn restores_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt.gz");
+        std::fs::write(&path, gzip::compress(b"notes\n", 6, Some("notes.txt"), 0)).unwrap();
+        run(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("notes.txt")).unwrap(),
+            b"notes\n"
+        );
+        assert!(!path.exists());
+    }
+}
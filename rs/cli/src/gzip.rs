@@ -0,0 +1,578 @@
+//! `gzip` and `gunzip`. Files are replaced by their compressed (`.gz`) or
+//! decompressed counterparts like GNU gzip; with no files, or with `-c`,
+//! the result is the output instead.
+
+use crate::args::{Arg, Parser};
+use crate::deflate;
+use crate::fs_ops;
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// Wraps `data` in a gzip member, optionally recording the original file
+/// name and modification time.
+pub fn compress(data: &[u8], level: u32, name: Option<&str>, mtime: u32) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(8);
+    out.push(if name.is_some() { FNAME } else { 0 });
+    out.extend(mtime.to_le_bytes());
+    out.push(match level {
+        9 => 2,
+        1 => 4,
+        _ => 0,
+    });
+    // Unix.
+    out.push(3);
+    if let Some(name) = name {
+        out.extend(name.as_bytes());
+        out.push(0);
+    }
+    out.extend(deflate::compress(data, level));
+    out.extend(deflate::crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+/// The decompressed contents of a gzip file, with the first member's
+/// header details.
+struct Member {
+    data: Vec<u8>,
+    name: Option<String>,
+    /// Header and trailer bytes, which gzip leaves out of its ratios.
+    overhead: usize,
+}
+
+/// Parses the gzip header at the start of `input`, returning the stored
+/// name and the header length.
+fn parse_header(input: &[u8]) -> Result<(Option<String>, usize), String> {
+    if input.len() < 10 || input[..2] != MAGIC {
+        return Err("not in gzip format".into());
+    }
+    if input[2] != 8 {
+        return Err("unknown method".into());
+    }
+    let flags = input[3];
+    let mut pos = 10;
+    let truncated = || "unexpected end of file".to_string();
+    if flags & FEXTRA != 0 {
+        let len = input.get(pos..pos + 2).ok_or_else(truncated)?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    let mut name = None;
+    if flags & FNAME != 0 {
+        let end = input[pos.min(input.len())..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(truncated)?;
+        name = Some(String::from_utf8_lossy(&input[pos..pos + end]).into_owned());
+        pos += end + 1;
+    }
+    if flags & FCOMMENT != 0 {
+        let end = input[pos.min(input.len())..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(truncated)?;
+        pos += end + 1;
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    if pos > input.len() {
+        return Err(truncated());
+    }
+    Ok((name, pos))
+}
+
+/// Decompresses every member of a gzip file, checking each trailer.
+fn decompress_members(input: &[u8]) -> Result<Member, String> {
+    let mut member = Member {
+        data: Vec::new(),
+        name: None,
+        overhead: 0,
+    };
+    if input.is_empty() {
+        return Err("unexpected end of file".into());
+    }
+    let mut pos = 0;
+    while pos < input.len() {
+        let first = pos == 0;
+        let (name, header_len) = parse_header(&input[pos..])?;
+        pos += header_len;
+        let (data, used) = deflate::decompress(&input[pos..])?;
+        pos += used;
+        let trailer = input.get(pos..pos + 8).ok_or("unexpected end of file")?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != deflate::crc32(&data) || size != data.len() as u32 {
+            return Err("invalid compressed data--crc error".into());
+        }
+        if first {
+            member.name = name;
+            member.overhead = header_len + 8;
+        }
+        member.data.extend(data);
+        pos += 8;
+    }
+    Ok(member)
+}
+
+/// Decompresses gzip data, as for `tar -z`.
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, String> {
+    decompress_members(input).map(|member| member.data)
+}
+
+struct Opts {
+    decompress: bool,
+    stdout: bool,
+    keep: bool,
+    force: bool,
+    list: bool,
+    test: bool,
+    verbose: bool,
+    recursive: bool,
+    /// Record (compressing) or restore (decompressing) the original name.
+    name: Option<bool>,
+    level: u32,
+    suffix: String,
+    paths: Vec<String>,
+}
+
+fn parse_opts(cmd: &'static str, decompress: bool, args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        decompress,
+        stdout: false,
+        keep: false,
+        force: false,
+        list: false,
+        test: false,
+        verbose: false,
+        recursive: false,
+        name: None,
+        level: 6,
+        suffix: ".gz".into(),
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new(cmd, args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('d') => opts.decompress = true,
+            Arg::Short('c') => opts.stdout = true,
+            Arg::Short('k') => opts.keep = true,
+            Arg::Short('f') => opts.force = true,
+            Arg::Short('l') => opts.list = true,
+            Arg::Short('t') => opts.test = true,
+            Arg::Short('v') => opts.verbose = true,
+            Arg::Short('q') => opts.verbose = false,
+            Arg::Short('r') => opts.recursive = true,
+            Arg::Short('n') => opts.name = Some(false),
+            Arg::Short('N') => opts.name = Some(true),
+            Arg::Short('S') => opts.suffix = parser.value()?,
+            Arg::Short(c @ '1'..='9') => opts.level = c as u32 - '0' as u32,
+            Arg::Number(n) if n.len() == 1 && n != "0" => opts.level = n.parse().unwrap_or(6),
+            Arg::Long(l) => match l.as_str() {
+                "decompress" | "uncompress" => opts.decompress = true,
+                "stdout" | "to-stdout" => opts.stdout = true,
+                "keep" => opts.keep = true,
+                "force" => opts.force = true,
+                "list" => opts.list = true,
+                "test" => opts.test = true,
+                "verbose" => opts.verbose = true,
+                "quiet" => opts.verbose = false,
+                "recursive" => opts.recursive = true,
+                "no-name" => opts.name = Some(false),
+                "name" => opts.name = Some(true),
+                "suffix" => opts.suffix = parser.value()?,
+                "fast" => opts.level = 1,
+                "best" => opts.level = 9,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.suffix.is_empty() {
+        return Err(format!("{cmd}: invalid suffix ''"));
+    }
+    Ok(opts)
+}
+
+/// The percentage saved, counting only the compressed data, as gzip does.
+fn ratio(compressed: usize, overhead: usize, uncompressed: usize) -> String {
+    if uncompressed == 0 {
+        return format!("{:5.1}%", 0.0);
+    }
+    let saved = uncompressed as f64 - compressed.saturating_sub(overhead) as f64;
+    format!("{:5.1}%", saved * 100.0 / uncompressed as f64)
+}
+
+fn base_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    gzip("gzip", false, args, stdin)
+}
+
+pub fn gzip(
+    cmd: &'static str,
+    decompress: bool,
+    args: &str,
+    stdin: Option<Vec<u8>>,
+) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(cmd, decompress, args)?;
+    let mut gzip = Gzip {
+        cmd,
+        opts: &opts,
+        output: Vec::new(),
+        messages: Vec::new(),
+        listed: Vec::new(),
+    };
+    let mut paths = opts.paths.clone();
+    if paths.is_empty() {
+        paths.push("-".into());
+    }
+    let mut stdin = stdin;
+    for path in &paths {
+        if path == "-" {
+            let input = stdin.take().unwrap_or_default();
+            gzip.stream(input);
+            continue;
+        }
+        match fs_ops::stat(path) {
+            Ok(meta) if meta.is_dir() => {
+                if !opts.recursive {
+                    gzip.message(format!("{path} is a directory -- ignored"));
+                    continue;
+                }
+                let entries = fs_ops::ls(path, true).map_err(|e| format!("{cmd}: {path}: {e}"))?;
+                for entry in entries.iter().filter(|e| !e.is_dir()) {
+                    gzip.file(&format!("{}/{}", path.trim_end_matches('/'), entry.name));
+                }
+            }
+            Ok(_) => gzip.file(path),
+            Err(e) => gzip.message(format!("{path}: {e}")),
+        }
+    }
+    gzip.finish()
+}
+
+struct Gzip<'a> {
+    cmd: &'static str,
+    opts: &'a Opts,
+    output: Vec<u8>,
+    /// Warnings and errors, reported after the output.
+    messages: Vec<String>,
+    /// (compressed, uncompressed, overhead) sizes for the `-l` totals.
+    listed: Vec<(usize, usize, usize)>,
+}
+
+impl Gzip<'_> {
+    fn message(&mut self, message: String) {
+        self.messages.push(format!("{}: {message}", self.cmd));
+    }
+
+    fn verbose(&mut self, line: String) {
+        if self.opts.verbose {
+            self.output.extend(line.as_bytes());
+            self.output.push(b'\n');
+        }
+    }
+
+    /// Handles stdin: the result always goes to the output.
+    fn stream(&mut self, input: Vec<u8>) {
+        if !self.opts.decompress && !self.opts.list && !self.opts.test {
+            let compressed = compress(&input, self.opts.level, None, 0);
+            self.output.extend(compressed);
+            return;
+        }
+        match decompress_members(&input) {
+            Ok(member) if self.opts.list => self.list(input.len(), &member, "stdout"),
+            Ok(_) if self.opts.test => {}
+            Ok(member) => self.output.extend(member.data),
+            Err(e) => self.message(format!("stdin: {e}")),
+        }
+    }
+
+    fn file(&mut self, path: &str) {
+        if self.opts.decompress || self.opts.list || self.opts.test {
+            self.decompress_file(path);
+        } else {
+            self.compress_file(path);
+        }
+    }
+
+    fn compress_file(&mut self, path: &str) {
+        let suffix = &self.opts.suffix;
+        if path.ends_with(suffix.as_str()) && !self.opts.stdout {
+            self.message(format!("{path} already has {suffix} suffix -- unchanged"));
+            return;
+        }
+        let data = match fs_ops::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                self.message(format!("{path}: {e}"));
+                return;
+            }
+        };
+        let (name, mtime) = match self.opts.name {
+            Some(false) => (None, 0),
+            _ => {
+                let mtime = fs_ops::stat(path)
+                    .ok()
+                    .and_then(|m| m.last_modified)
+                    .unwrap_or(0);
+                (Some(base_name(path)), mtime as u32)
+            }
+        };
+        let compressed = compress(&data, self.opts.level, name, mtime);
+        if self.opts.stdout {
+            self.output.extend(compressed);
+            return;
+        }
+        let target = format!("{path}{suffix}");
+        let overhead = 18 + name.map_or(0, |n| n.len() + 1);
+        let ratio = ratio(compressed.len(), overhead, data.len());
+        if self.write(path, &target, &compressed) {
+            self.verbose(format!("{path}:\t{ratio} -- created {target}"));
+        }
+    }
+
+    fn decompress_file(&mut self, path: &str) {
+        let suffix = &self.opts.suffix;
+        let valid = |stem: &&str| !stem.is_empty() && !stem.ends_with('/');
+        let stripped = match path.strip_suffix(suffix.as_str()).filter(valid) {
+            Some(stem) => Some(stem.to_string()),
+            None => path
+                .strip_suffix(".tgz")
+                .filter(valid)
+                .map(|s| format!("{s}.tar")),
+        };
+        let writing = !(self.opts.list || self.opts.test || self.opts.stdout);
+        if stripped.is_none() && writing {
+            self.message(format!("{path}: unknown suffix -- ignored"));
+            return;
+        }
+        let member = match fs_ops::read(path).and_then(|input| {
+            let member = decompress_members(&input)?;
+            Ok((input.len(), member))
+        }) {
+            Ok(member) => member,
+            Err(e) => {
+                self.message(format!("{path}: {e}"));
+                return;
+            }
+        };
+        let (compressed, member) = member;
+        // With -N, the name stored in the header wins.
+        let target = match (&member.name, stripped) {
+            (Some(name), Some(_)) if self.opts.name == Some(true) => match path.rfind('/') {
+                Some(i) => format!("{}/{}", &path[..i], base_name(name)),
+                None => base_name(name).to_string(),
+            },
+            (_, stripped) => stripped.unwrap_or_else(|| path.to_string()),
+        };
+        if self.opts.list {
+            self.list(compressed, &member, &target);
+        } else if self.opts.test {
+            self.verbose(format!("{path}:\t OK"));
+        } else if self.opts.stdout {
+            self.output.extend(member.data);
+        } else {
+            let ratio = ratio(compressed, member.overhead, member.data.len());
+            if self.write(path, &target, &member.data) {
+                self.verbose(format!("{path}:\t{ratio} -- created {target}"));
+            }
+        }
+    }
+
+    /// Writes `target` and removes `source` unless `-k` was given.
+    fn write(&mut self, source: &str, target: &str, data: &[u8]) -> bool {
+        if !self.opts.force && fs_ops::exists(target).unwrap_or(false) {
+            self.message(format!("{target} already exists; not overwritten"));
+            return false;
+        }
+        if let Err(e) = fs_ops::write(target, data) {
+            self.message(format!("{target}: {e}"));
+            return false;
+        }
+        let removed = match self.opts.keep {
+            true => Ok(()),
+            false => fs_ops::rm(source, false),
+        };
+        if let Err(e) = removed {
+            self.message(format!("{source}: {e}"));
+        }
+        true
+    }
+
+    fn list(&mut self, compressed: usize, member: &Member, name: &str) {
+        if self.listed.is_empty() {
+            self.output
+                .extend(b"         compressed        uncompressed  ratio uncompressed_name\n");
+        }
+        let ratio = ratio(compressed, member.overhead, member.data.len());
+        self.output.extend(
+            format!(
+                "{compressed:>19} {:>19} {ratio} {name}\n",
+                member.data.len()
+            )
+            .as_bytes(),
+        );
+        self.listed
+            .push((compressed, member.data.len(), member.overhead));
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        if self.listed.len() > 1 {
+            let compressed: usize = self.listed.iter().map(|l| l.0).sum();
+            let uncompressed: usize = self.listed.iter().map(|l| l.1).sum();
+            let overhead: usize = self.listed.iter().map(|l| l.2).sum();
+            let ratio = ratio(compressed, overhead, uncompressed);
+            self.output.extend(
+                format!("{compressed:>19} {uncompressed:>19} {ratio} (totals)\n").as_bytes(),
+            );
+        }
+        match self.messages.is_empty() {
+            true => Ok(self.output),
+            false => Err(format!(
+                "{}{}",
+                String::from_utf8_lossy(&self.output),
+                self.messages.join("\n")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("gzip", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn tmp(name: &str, content: &[u8]) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        (dir, path.to_str().unwrap().to_string())
+    }
+
+    const TEXT: &[u8] = b"hello hello hello\n";
+
+    #[test]
+    fn stdin_round_trip() {
+        let compressed = run_bytes("", Some(TEXT.to_vec())).unwrap();
+        assert_eq!(compressed[..2], MAGIC);
+        assert_eq!(run_bytes("-d", Some(compressed)).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn levels() {
+        let data: Vec<u8> = (0..2000)
+            .flat_map(|i| format!("{i}\n").into_bytes())
+            .collect();
+        for level in ["-1", "-6", "--best"] {
+            let compressed = run_bytes(level, Some(data.clone())).unwrap();
+            assert!(compressed.len() < data.len() / 2);
+            assert_eq!(decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn compress_file_replaces_it() {
+        let (_dir, path) = tmp("a.txt", TEXT);
+        let out = run(&format!("-v {path}"), None).unwrap();
+        assert!(out.starts_with(&format!("{path}:\t 38.9%")));
+        assert!(out.ends_with(&format!("% -- created {path}.gz\n")));
+        assert!(!std::path::Path::new(&path).exists());
+        let compressed = std::fs::read(format!("{path}.gz")).unwrap();
+        // The original name is recorded in the header.
+        assert_eq!(&compressed[10..16], b"a.txt\0");
+
+        run(&format!("-d {path}.gz"), None).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), TEXT);
+        assert!(!std::path::Path::new(&format!("{path}.gz")).exists());
+    }
+
+    #[test]
+    fn keep_and_stdout() {
+        let (_dir, path) = tmp("a.txt", TEXT);
+        run(&format!("-k {path}"), None).unwrap();
+        assert!(std::path::Path::new(&path).exists());
+        let out = run_bytes(&format!("-dc {path}.gz"), None).unwrap();
+        assert_eq!(out, TEXT);
+        assert!(std::path::Path::new(&format!("{path}.gz")).exists());
+    }
+
+    #[test]
+    fn refuses_to_overwrite() {
+        let (_dir, path) = tmp("a.txt", TEXT);
+        run(&format!("-k {path}"), None).unwrap();
+        let err = run(&format!("-k {path}"), None).unwrap_err();
+        assert_eq!(
+            err,
+            format!("gzip: {path}.gz already exists; not overwritten")
+        );
+        run(&format!("-kf {path}"), None).unwrap();
+        let err = run(&format!("{path}.gz"), None).unwrap_err();
+        assert!(err.ends_with("already has .gz suffix -- unchanged"));
+    }
+
+    #[test]
+    fn list() {
+        let (_dir, path) = tmp("a.txt", TEXT);
+        run(&path, None).unwrap();
+        let size = std::fs::metadata(format!("{path}.gz")).unwrap().len();
+        // 18 bytes of data; the header and trailer take 24 of the total.
+        let saved = (18.0 - (size as f64 - 24.0)) * 100.0 / 18.0;
+        let out = run(&format!("-l {path}.gz"), None).unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "         compressed        uncompressed  ratio uncompressed_name\n\
+                 {size:>19} {:>19} {saved:5.1}% {path}\n",
+                18
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let err = run_bytes("-d", Some(b"plain text".to_vec())).unwrap_err();
+        assert_eq!(err, "gzip: stdin: not in gzip format");
+        let mut compressed = run_bytes("", Some(TEXT.to_vec())).unwrap();
+        let n = compressed.len();
+        compressed[n - 8] ^= 1;
+        let err = run_bytes("-t", Some(compressed)).unwrap_err();
+        assert!(err.contains("crc error"));
+        let (_dir, path) = tmp("plain", TEXT);
+        let err = run(&format!("-d {path}"), None).unwrap_err();
+        assert!(err.ends_with("unknown suffix -- ignored"));
+    }
+
+    #[test]
+    fn concatenated_members() {
+        let mut data = compress(b"one\n", 6, None, 0);
+        data.extend(compress(b"two\n", 1, None, 0));
+        assert_eq!(decompress(&data).unwrap(), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn recursive() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("a.txt"), TEXT).unwrap();
+        std::fs::write(dir.path().join("sub/b.txt"), TEXT).unwrap();
+        let root = dir.path().to_str().unwrap();
+        let err = run(root, None).unwrap_err();
+        assert!(err.ends_with("is a directory -- ignored"));
+        run(&format!("-r {root}"), None).unwrap();
+        assert!(dir.path().join("sub/b.txt.gz").exists());
+        assert!(dir.path().join("a.txt.gz").exists());
+    }
+}
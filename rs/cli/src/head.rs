@@ -1,67 +1,154 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
+/// How much of each input to print.
+#[derive(Clone, Copy)]
+enum Count {
+    Lines(usize),
+    /// `-n -N`: all but the last N lines.
+    AllButLines(usize),
+    Bytes(usize),
+    /// `-c -N`: all but the last N bytes.
+    AllButBytes(usize),
+}
+
 struct Opts {
-    lines: usize,
+    count: Count,
+    /// Whether to print `==> path <==` headers; by default only for
+    /// several files.
+    headers: Option<bool>,
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+/// Parses the value of `-n` or `-c`, where a leading `-` counts from the end.
+fn parse_count(value: &str, bytes: bool) -> Result<Count, String> {
+    let (all_but, number) = match value.strip_prefix('-') {
+        Some(number) => (true, number),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let n: usize = number.parse().map_err(|_| {
+        let unit = if bytes { "bytes" } else { "lines" };
+        format!("head: invalid number of {unit}: '{value}'")
+    })?;
+    Ok(match (bytes, all_but) {
+        (false, false) => Count::Lines(n),
+        (false, true) => Count::AllButLines(n),
+        (true, false) => Count::Bytes(n),
+        (true, true) => Count::AllButBytes(n),
+    })
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
-        lines: 10,
+        count: Count::Lines(10),
+        headers: None,
         paths: Vec::new(),
     };
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        if tokens[i] == "-n" {
-            i += 1;
-            if let Some(n) = tokens.get(i) {
-                opts.lines = n.parse().unwrap_or(10);
-            }
-        } else if tokens[i].starts_with('-') && tokens[i][1..].chars().all(|c| c.is_ascii_digit()) {
-            opts.lines = tokens[i][1..].parse().unwrap_or(10);
-        } else {
-            opts.paths.push(tokens[i].to_string());
+    let mut parser = Parser::new("head", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('n') => opts.count = parse_count(&parser.value()?, false)?,
+            Arg::Short('c') => opts.count = parse_count(&parser.value()?, true)?,
+            Arg::Short('q') => opts.headers = Some(false),
+            Arg::Short('v') => opts.headers = Some(true),
+            Arg::Long(l) => match l.as_str() {
+                "lines" => opts.count = parse_count(&parser.value()?, false)?,
+                "bytes" => opts.count = parse_count(&parser.value()?, true)?,
+                "quiet" | "silent" => opts.headers = Some(false),
+                "verbose" => opts.headers = Some(true),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Number(n) => opts.count = parse_count(&n, false)?,
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
-    opts
+    Ok(opts)
 }
 
-pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
-    let mut output = String::new();
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    let mut output = Vec::new();
     if opts.paths.is_empty() {
         let input = stdin.unwrap_or_default();
-        let taken: Vec<&str> = input.lines().take(opts.lines).collect();
-        output.push_str(&taken.join("\n"));
-        if !taken.is_empty() {
-            output.push('\n');
+        if opts.headers == Some(true) {
+            output.extend(b"==> standard input <==\n");
         }
+        select(&input, opts.count, &mut output);
     } else {
-        let multi = opts.paths.len() > 1;
+        let headers = opts.headers.unwrap_or(opts.paths.len() > 1);
         for (i, path) in opts.paths.iter().enumerate() {
-            if multi {
+            if headers {
                 if i > 0 {
-                    output.push('\n');
+                    output.push(b'\n');
                 }
-                output.push_str(&format!("==> {path} <==\n"));
-            }
-            let content = fs_ops::read_to_string(path)
-                .map_err(|e| format!("head: {path}: {e}"))?;
-            let taken: Vec<&str> = content.lines().take(opts.lines).collect();
-            output.push_str(&taken.join("\n"));
-            if !taken.is_empty() {
-                output.push('\n');
+                output.extend(format!("==> {path} <==\n").as_bytes());
             }
+            let content = match opts.count {
+                // Only the start of the file is needed.
+                Count::Bytes(n) => fs_ops::read_range(path, 0, n as u64),
+                _ => fs_ops::read(path),
+            };
+            let content = content.map_err(|e| format!("head: {path}: {e}"))?;
+            select(&content, opts.count, &mut output);
         }
     }
     Ok(output)
 }
 
+/// The start of the last `lines` lines of `data`.
+fn last_lines_start(data: &[u8], lines: usize) -> usize {
+    if lines == 0 {
+        return data.len();
+    }
+    // A trailing newline ends the last line rather than starting a new one.
+    let body = data.strip_suffix(b"\n").unwrap_or(data);
+    body.iter()
+        .enumerate()
+        .rev()
+        .filter(|&(_, &b)| b == b'\n')
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1)
+}
+
+/// Copies the selected part of `data` unchanged. Whole lines end with a
+/// newline even if the input had none; byte counts are exact.
+fn select(data: &[u8], count: Count, output: &mut Vec<u8>) {
+    let end = match count {
+        Count::Lines(lines) => {
+            let mut end = 0;
+            for _ in 0..lines {
+                match data[end..].iter().position(|&b| b == b'\n') {
+                    Some(i) => end += i + 1,
+                    None => end = data.len(),
+                }
+            }
+            end
+        }
+        Count::AllButLines(lines) => last_lines_start(data, lines),
+        Count::Bytes(n) => {
+            output.extend(&data[..n.min(data.len())]);
+            return;
+        }
+        Count::AllButBytes(n) => {
+            output.extend(&data[..data.len().saturating_sub(n)]);
+            return;
+        }
+    };
+    output.extend(&data[..end]);
+    if end > 0 && data[end - 1] != b'\n' {
+        output.push(b'\n');
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("head", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
 
     fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
         run(args, stdin.map(String::from))
@@ -69,7 +156,10 @@ mod tests {
 
     #[test]
     fn default_ten_lines() {
-        let input = (1..=20).map(|i| i.to_string()).collect::<Vec<_>>().join("\n");
+        let input = (1..=20)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
         let out = cmd("", Some(&input)).unwrap();
         assert_eq!(out.lines().count(), 10);
         assert_eq!(out.lines().next().unwrap(), "1");
@@ -122,6 +212,56 @@ mod tests {
         assert!(out.contains("b.txt"));
     }
 
+    #[test]
+    fn all_but_last_lines() {
+        assert_eq!(cmd("-n -2", Some("a\nb\nc\nd\n")).unwrap(), "a\nb\n");
+        assert_eq!(cmd("-n-1", Some("a\nb\nc")).unwrap(), "a\nb\n");
+        assert_eq!(cmd("-n -5", Some("a\nb\n")).unwrap(), "");
+        assert_eq!(cmd("-n -0", Some("a\nb")).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn byte_counts() {
+        assert_eq!(cmd("-c 3", Some("hello\n")).unwrap(), "hel");
+        assert_eq!(cmd("-c -2", Some("hello\n")).unwrap(), "hell");
+        assert_eq!(cmd("--bytes=10", Some("hi")).unwrap(), "hi");
+        assert_eq!(
+            run_bytes("-c 2", Some(vec![0xff, 0xfe, 0xfd])).unwrap(),
+            [0xff, 0xfe]
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("f.bin");
+        std::fs::write(&p, b"0123456789").unwrap();
+        assert_eq!(cmd(&format!("-c 4 {}", p.display()), None).unwrap(), "0123");
+    }
+
+    #[test]
+    fn header_options() {
+        assert_eq!(
+            cmd("-v -n 1", Some("a\nb\n")).unwrap(),
+            "==> standard input <==\na\n"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let p1 = dir.path().join("a.txt");
+        let p2 = dir.path().join("b.txt");
+        std::fs::write(&p1, "aa\n").unwrap();
+        std::fs::write(&p2, "bb\n").unwrap();
+        let out = cmd(&format!("-q {} {}", p1.display(), p2.display()), None).unwrap();
+        assert_eq!(out, "aa\nbb\n");
+    }
+
+    #[test]
+    fn invalid_count() {
+        assert_eq!(
+            cmd("-n x", Some("")).unwrap_err(),
+            "head: invalid number of lines: 'x'"
+        );
+        assert_eq!(
+            cmd("-c 1z", Some("")).unwrap_err(),
+            "head: invalid number of bytes: '1z'"
+        );
+    }
+
     #[test]
     fn missing_file() {
         let err = cmd("/no/file", None).unwrap_err();
@@ -0,0 +1,472 @@
+//! `join` pairs the lines of two files sorted on a join field, like GNU
+//! join: `-a`/`-v` print unpairable lines, `-o` picks output fields and
+//! `-e` fills missing ones. As in GNU join, input order is checked once a
+//! line has turned out to be unpairable (or always with `--check-order`).
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CheckOrder {
+    Default,
+    Enabled,
+    Disabled,
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    /// The join field, from whichever file has it.
+    Join,
+    /// A field of one of the files, both numbered from 0.
+    Of(usize, usize),
+}
+
+enum Format {
+    /// The join field, then the other fields of each line.
+    Default,
+    /// Like the default, but with as many fields as each file's first line.
+    Auto,
+    List(Vec<Field>),
+}
+
+struct Opts {
+    separator: Option<char>,
+    /// The 0-based join field of each file.
+    fields: [usize; 2],
+    unpaired: [bool; 2],
+    only_unpaired: bool,
+    empty: Option<String>,
+    format: Format,
+    ignore_case: bool,
+    header: bool,
+    check_order: CheckOrder,
+    paths: Vec<String>,
+}
+
+fn parse_field(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("join: invalid field number: '{value}'")),
+    }
+}
+
+fn parse_file_number(value: &str) -> Result<usize, String> {
+    match value {
+        "1" => Ok(0),
+        "2" => Ok(1),
+        _ => Err(format!("join: invalid file number: '{value}'")),
+    }
+}
+
+fn parse_format(spec: &str) -> Result<Format, String> {
+    if spec == "auto" {
+        return Ok(Format::Auto);
+    }
+    let mut fields = Vec::new();
+    for item in spec.split([',', ' ', '\t']).filter(|s| !s.is_empty()) {
+        let field = match item.split_once('.') {
+            None if item == "0" => Field::Join,
+            Some((file, field)) => Field::Of(parse_file_number(file)?, parse_field(field)?),
+            None => return Err(format!("join: invalid field specifier: '{item}'")),
+        };
+        fields.push(field);
+    }
+    Ok(Format::List(fields))
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        separator: None,
+        fields: [0, 0],
+        unpaired: [false, false],
+        only_unpaired: false,
+        empty: None,
+        format: Format::Default,
+        ignore_case: false,
+        header: false,
+        check_order: CheckOrder::Default,
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new("join", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('t') => {
+                let value = parser.value()?;
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => opts.separator = Some(c),
+                    _ => return Err(format!("join: multi-character tab '{value}'")),
+                }
+            }
+            // `-1 FIELD` and `-2 FIELD` arrive as numbers.
+            Arg::Number(n) if n == "1" || n == "2" => {
+                let file = parse_file_number(&n)?;
+                opts.fields[file] = parse_field(&parser.value()?)?;
+            }
+            Arg::Short('j') => {
+                let field = parse_field(&parser.value()?)?;
+                opts.fields = [field, field];
+            }
+            Arg::Short('a') => opts.unpaired[parse_file_number(&parser.value()?)?] = true,
+            Arg::Short('v') => {
+                opts.unpaired[parse_file_number(&parser.value()?)?] = true;
+                opts.only_unpaired = true;
+            }
+            Arg::Short('e') => opts.empty = Some(parser.value()?),
+            Arg::Short('o') => opts.format = parse_format(&parser.value()?)?,
+            Arg::Short('i') => opts.ignore_case = true,
+            Arg::Long(l) => match l.as_str() {
+                "ignore-case" => opts.ignore_case = true,
+                "header" => opts.header = true,
+                "check-order" => opts.check_order = CheckOrder::Enabled,
+                "nocheck-order" => opts.check_order = CheckOrder::Disabled,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    match opts.paths.len() {
+        0 => Err("join: missing operand".into()),
+        1 => Err(format!("join: missing operand after '{}'", opts.paths[0])),
+        2 => Ok(opts),
+        _ => Err(format!("join: extra operand '{}'", opts.paths[2])),
+    }
+}
+
+struct Line<'a> {
+    text: &'a str,
+    fields: Vec<&'a str>,
+}
+
+fn split_fields<'a>(opts: &Opts, text: &'a str) -> Vec<&'a str> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    match opts.separator {
+        Some(sep) => text.split(sep).collect(),
+        None => text
+            .split([' ', '\t'])
+            .filter(|field| !field.is_empty())
+            .collect(),
+    }
+}
+
+struct Join<'a> {
+    opts: &'a Opts,
+    lines: [Vec<Line<'a>>; 2],
+    /// Field counts of each file's first line, for `-o auto`.
+    auto_counts: [usize; 2],
+    output: String,
+    messages: Vec<String>,
+    warned: [bool; 2],
+    seen_unpairable: bool,
+}
+
+impl Join<'_> {
+    fn key(&self, file: usize, index: usize) -> &str {
+        let field = self.opts.fields[file];
+        self.lines[file][index]
+            .fields
+            .get(field)
+            .copied()
+            .unwrap_or("")
+    }
+
+    fn compare(&self, a: (usize, usize), b: (usize, usize)) -> Ordering {
+        let (a, b) = (self.key(a.0, a.1), self.key(b.0, b.1));
+        match self.opts.ignore_case {
+            true => a.to_lowercase().cmp(&b.to_lowercase()),
+            false => a.cmp(b),
+        }
+    }
+
+    /// Moves past line `index` of `file`, checking that the next line does
+    /// not sort before it. Returns the next index.
+    fn advance(&mut self, file: usize, index: usize) -> Result<usize, String> {
+        let next = index + 1;
+        let checking = match self.opts.check_order {
+            CheckOrder::Default => self.seen_unpairable,
+            CheckOrder::Enabled => true,
+            CheckOrder::Disabled => false,
+        };
+        if checking
+            && !self.warned[file]
+            && next < self.lines[file].len()
+            && self.compare((file, index), (file, next)) == Ordering::Greater
+        {
+            self.warned[file] = true;
+            let path = &self.opts.paths[file];
+            let text = self.lines[file][next].text;
+            self.messages
+                .push(format!("join: {path}:{}: is not sorted: {text}", next + 1));
+            if self.opts.check_order == CheckOrder::Enabled {
+                return Err(format!("{}{}", self.output, self.messages.join("\n")));
+            }
+        }
+        Ok(next)
+    }
+
+    fn field(&self, line: Option<&Line>, index: usize, output: &mut String) {
+        match line.and_then(|line| line.fields.get(index)) {
+            Some(field) => output.push_str(field),
+            None => output.push_str(self.opts.empty.as_deref().unwrap_or("")),
+        }
+    }
+
+    /// Prints a joined line; `None` stands for the missing side of an
+    /// unpairable line.
+    fn print(&mut self, line1: Option<usize>, line2: Option<usize>) {
+        let lines = [
+            line1.map(|i| &self.lines[0][i]),
+            line2.map(|i| &self.lines[1][i]),
+        ];
+        let sep = self.opts.separator.unwrap_or(' ');
+        let mut out = String::new();
+        match &self.opts.format {
+            Format::List(fields) => {
+                for (n, field) in fields.iter().enumerate() {
+                    if n > 0 {
+                        out.push(sep);
+                    }
+                    let (file, index) = match *field {
+                        Field::Join if lines[0].is_none() => (1, self.opts.fields[1]),
+                        Field::Join => (0, self.opts.fields[0]),
+                        Field::Of(file, index) => (file, index),
+                    };
+                    self.field(lines[file], index, &mut out);
+                }
+            }
+            format => {
+                let file = if lines[0].is_none() { 1 } else { 0 };
+                self.field(lines[file], self.opts.fields[file], &mut out);
+                for (file, &line) in lines.iter().enumerate() {
+                    let count = match (format, line) {
+                        (Format::Auto, _) => self.auto_counts[file],
+                        (_, Some(line)) => line.fields.len(),
+                        (_, None) => 0,
+                    };
+                    for index in (0..count).filter(|&i| i != self.opts.fields[file]) {
+                        out.push(sep);
+                        self.field(line, index, &mut out);
+                    }
+                }
+            }
+        }
+        self.output.push_str(&out);
+        self.output.push('\n');
+    }
+
+    fn unpaired(&mut self, file: usize, index: usize) {
+        if self.opts.unpaired[file] {
+            match file {
+                0 => self.print(Some(index), None),
+                _ => self.print(None, Some(index)),
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        let mut pos = [0, 0];
+        if self.opts.header {
+            let header = [0, 1].map(|file| (!self.lines[file].is_empty()).then_some(0));
+            if header.iter().any(Option::is_some) {
+                self.print(header[0], header[1]);
+            }
+            pos = [1, 1];
+        }
+        let pairs = !self.opts.only_unpaired;
+        let len = [self.lines[0].len(), self.lines[1].len()];
+        while pos[0] < len[0] && pos[1] < len[1] {
+            match self.compare((0, pos[0]), (1, pos[1])) {
+                Ordering::Less => {
+                    self.unpaired(0, pos[0]);
+                    pos[0] = self.advance(0, pos[0])?;
+                    self.seen_unpairable = true;
+                }
+                Ordering::Greater => {
+                    self.unpaired(1, pos[1]);
+                    pos[1] = self.advance(1, pos[1])?;
+                    self.seen_unpairable = true;
+                }
+                Ordering::Equal => {
+                    let mut end1 = pos[0];
+                    loop {
+                        end1 = self.advance(0, end1)?;
+                        if end1 >= len[0] || self.compare((0, end1), (1, pos[1])) != Ordering::Equal
+                        {
+                            break;
+                        }
+                    }
+                    let mut end2 = pos[1];
+                    loop {
+                        end2 = self.advance(1, end2)?;
+                        if end2 >= len[1] || self.compare((0, pos[0]), (1, end2)) != Ordering::Equal
+                        {
+                            break;
+                        }
+                    }
+                    if pairs {
+                        for i in pos[0]..end1 {
+                            for j in pos[1]..end2 {
+                                self.print(Some(i), Some(j));
+                            }
+                        }
+                    }
+                    pos = [end1, end2];
+                }
+            }
+        }
+        // Read the rest of each file to print unpairable lines and check
+        // the order, as GNU join does.
+        for file in 0..2 {
+            let checking = self.opts.check_order != CheckOrder::Disabled
+                && !(self.warned[0] && self.warned[1]);
+            if pos[file] >= len[file] || !(self.opts.unpaired[file] || checking) {
+                continue;
+            }
+            if pos[1 - file] < len[1 - file] {
+                self.seen_unpairable = true;
+            }
+            let mut index = pos[file];
+            while index < len[file] {
+                self.unpaired(file, index);
+                if self.warned[file] && !self.opts.unpaired[file] {
+                    break;
+                }
+                index = self.advance(file, index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let mut stdin = stdin;
+    let mut contents = Vec::new();
+    for path in &opts.paths {
+        let content = match path.as_str() {
+            "-" => stdin.take().unwrap_or_default(),
+            path => fs_ops::read_to_string(path).map_err(|e| format!("join: {path}: {e}"))?,
+        };
+        contents.push(content);
+    }
+    let lines = [0, 1].map(|file| {
+        contents[file]
+            .lines()
+            .map(|text| Line {
+                text,
+                fields: split_fields(&opts, text),
+            })
+            .collect::<Vec<_>>()
+    });
+    let auto_counts = [0, 1].map(|file| lines[file].first().map_or(0, |l| l.fields.len()));
+    let mut join = Join {
+        opts: &opts,
+        lines,
+        auto_counts,
+        output: String::new(),
+        messages: Vec::new(),
+        warned: [false, false],
+        seen_unpairable: false,
+    };
+    join.run()?;
+    if join.messages.is_empty() {
+        return Ok(join.output);
+    }
+    join.messages
+        .push("join: input is not in sorted order".into());
+    Err(format!("{}{}", join.output, join.messages.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp(a: &str, b: &str) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), a).unwrap();
+        std::fs::write(dir.path().join("b"), b).unwrap();
+        let base = dir.path().display().to_string();
+        (dir, format!("{base}/a {base}/b"))
+    }
+
+    const NAMES: &str = "1 alice\n2 bob\n4 dave\n";
+    const AGES: &str = "1 30\n2 25\n2 26\n3 40\n";
+
+    #[test]
+    fn pairs_lines_on_first_field() {
+        let (_dir, files) = tmp(NAMES, AGES);
+        let out = run(&files, None).unwrap();
+        assert_eq!(out, "1 alice 30\n2 bob 25\n2 bob 26\n");
+    }
+
+    #[test]
+    fn unpairable_lines() {
+        let (_dir, files) = tmp(NAMES, AGES);
+        let out = run(&format!("-a1 -a 2 {files}"), None).unwrap();
+        assert_eq!(out, "1 alice 30\n2 bob 25\n2 bob 26\n3 40\n4 dave\n");
+        assert_eq!(run(&format!("-v 2 {files}"), None).unwrap(), "3 40\n");
+    }
+
+    #[test]
+    fn output_format_and_empty() {
+        let (_dir, files) = tmp(NAMES, AGES);
+        let out = run(&format!("-a1 -a2 -e NA -o 0,1.2,2.2 {files}"), None).unwrap();
+        assert_eq!(out, "1 alice 30\n2 bob 25\n2 bob 26\n3 NA 40\n4 dave NA\n");
+        let out = run(&format!("-a1 -e - -o auto {files}"), None).unwrap();
+        assert!(out.ends_with("4 dave -\n"));
+    }
+
+    #[test]
+    fn separator_fields_and_case() {
+        let (_dir, files) = tmp("x,Key,1\n", "key;y\n");
+        let dir_files: Vec<&str> = files.split(' ').collect();
+        let args = format!("-t, -1 2 -i {} -", dir_files[0]);
+        assert_eq!(run(&args, Some("KEY,z\n".into())).unwrap(), "Key,x,1,z\n");
+    }
+
+    #[test]
+    fn header_line() {
+        let (_dir, files) = tmp("id name\n1 a\n", "id age\n1 9\n");
+        assert_eq!(
+            run(&format!("--header {files}"), None).unwrap(),
+            "id name age\n1 a 9\n"
+        );
+    }
+
+    #[test]
+    fn unsorted_input() {
+        let (_dir, files) = tmp("b 1\na 2\n", "a x\nb y\n");
+        let a = files.split(' ').next().unwrap();
+        let err = run(&files, None).unwrap_err();
+        assert_eq!(
+            err,
+            format!("b 1 y\njoin: {a}:2: is not sorted: a 2\njoin: input is not in sorted order")
+        );
+        assert_eq!(
+            run(&format!("--nocheck-order {files}"), None).unwrap(),
+            "b 1 y\n"
+        );
+    }
+
+    #[test]
+    fn operand_errors() {
+        assert_eq!(
+            run("x", None).unwrap_err(),
+            "join: missing operand after 'x'"
+        );
+        assert!(
+            run("-t ab a b", None)
+                .unwrap_err()
+                .contains("multi-character tab")
+        );
+        assert!(
+            run("-a 3 a b", None)
+                .unwrap_err()
+                .contains("invalid file number")
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
 use hifijson::token::Lex;
 use jaq_core::load::{Arena, File, Loader};
 use jaq_core::{Compiler, Ctx, RcIter, ValT};
@@ -6,47 +8,109 @@ use jaq_json::Val;
 struct Opts {
     raw_output: bool,
     compact: bool,
+    /// The indentation unit for pretty output.
+    indent: String,
+    slurp: bool,
+    null_input: bool,
+    exit_status: bool,
+    /// `--arg`/`--argjson` bindings, names without the `$`.
+    vars: Vec<(String, Val)>,
     filter: String,
+    files: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
-    let mut raw_output = false;
-    let mut compact = false;
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        raw_output: false,
+        compact: false,
+        indent: "  ".into(),
+        slurp: false,
+        null_input: false,
+        exit_status: false,
+        vars: Vec::new(),
+        filter: String::new(),
+        files: Vec::new(),
+    };
     let mut positional = Vec::new();
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 {
-            for c in token[1..].chars() {
-                match c {
-                    'r' => raw_output = true,
-                    'c' => compact = true,
-                    _ => {}
+    let mut parser = Parser::new("jq", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('r') => opts.raw_output = true,
+            Arg::Short('c') => opts.compact = true,
+            Arg::Short('s') => opts.slurp = true,
+            Arg::Short('n') => opts.null_input = true,
+            Arg::Short('e') => opts.exit_status = true,
+            Arg::Long(l) => match l.as_str() {
+                "raw-output" => opts.raw_output = true,
+                "compact-output" => opts.compact = true,
+                "slurp" => opts.slurp = true,
+                "null-input" => opts.null_input = true,
+                "exit-status" => opts.exit_status = true,
+                "tab" => opts.indent = "\t".into(),
+                "indent" => {
+                    let n: usize = parser.number()?;
+                    if n > 7 {
+                        return Err("jq: cannot indent more than 7 characters".into());
+                    }
+                    opts.indent = " ".repeat(n);
                 }
-            }
-        } else {
-            positional.push(token.to_string());
+                "arg" => {
+                    let name = parser.value()?;
+                    let value = parser.value()?;
+                    opts.vars.push((name, Val::from(value)));
+                }
+                "argjson" => {
+                    let name = parser.value()?;
+                    let text = parser.value()?;
+                    let value = match parse_values(&text) {
+                        Ok(values) if values.len() == 1 => values.into_iter().next(),
+                        _ => None,
+                    }
+                    .ok_or_else(|| format!("jq: invalid JSON text passed to --argjson: {text}"))?;
+                    opts.vars.push((name, value));
+                }
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(value) => positional.push(value),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
-    let filter = if positional.is_empty() {
-        ".".to_string()
-    } else {
-        positional.join(" ")
-    };
-    Opts {
-        raw_output,
-        compact,
-        filter,
+    let mut positional = positional.into_iter();
+    opts.filter = positional.next().unwrap_or_else(|| ".".into());
+    opts.files = positional.collect();
+    Ok(opts)
+}
+
+/// Parses a sequence of JSON values separated by optional whitespace, as
+/// in newline-delimited JSON.
+fn parse_values(text: &str) -> Result<Vec<Val>, String> {
+    let mut lexer = hifijson::SliceLexer::new(text.as_bytes());
+    let mut values = Vec::new();
+    while let Some(token) = lexer.ws_token() {
+        let value =
+            Val::parse(token, &mut lexer).map_err(|e| format!("jq: invalid JSON: {e:?}"))?;
+        values.push(value);
     }
+    Ok(values)
 }
 
 pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
-    let input = stdin.ok_or("jq: no input provided")?;
+    let opts = parse_opts(args)?;
 
-    // Parse JSON input via hifijson.
-    let mut lexer = hifijson::SliceLexer::new(input.as_bytes());
-    let val: Val = lexer
-        .exactly_one(|token, lexer| Val::parse(token, lexer))
-        .map_err(|e| format!("jq: invalid JSON: {e:?}"))?;
+    let mut values = Vec::new();
+    if !opts.files.is_empty() {
+        for path in &opts.files {
+            let text = fs_ops::read_to_string(path).map_err(|e| format!("jq: {path}: {e}"))?;
+            values.extend(parse_values(&text)?);
+        }
+    } else if let Some(input) = stdin {
+        values = parse_values(&input)?;
+    } else if !opts.null_input {
+        return Err("jq: no input provided".into());
+    }
+    if opts.slurp {
+        values = vec![values.into_iter().collect()];
+    }
 
     // Compile jq filter.
     let program = File {
@@ -58,39 +122,91 @@ pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
     let modules = loader
         .load(&arena, program)
         .map_err(|errs| format!("jq: parse error: {errs:?}"))?;
+    let var_names: Vec<String> = opts
+        .vars
+        .iter()
+        .map(|(name, _)| format!("${name}"))
+        .collect();
     let filter = Compiler::default()
         .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .with_global_vars(var_names.iter().map(String::as_str))
         .compile(modules)
         .map_err(|errs| format!("jq: compile error: {errs:?}"))?;
 
-    let inputs = RcIter::new(core::iter::empty());
+    // Values not consumed as main inputs stay available to `input`/`inputs`.
+    let inputs = RcIter::new(values.into_iter().map(Ok));
+    let mains: Box<dyn Iterator<Item = Result<Val, String>>> = if opts.null_input {
+        Box::new(core::iter::once(Ok(Val::Null)))
+    } else {
+        Box::new(&inputs)
+    };
     let mut output = String::new();
+    let mut last: Option<Val> = None;
+    for input in mains {
+        let input = input.map_err(|e| format!("jq: {e}"))?;
+        let ctx = Ctx::new(opts.vars.iter().map(|(_, v)| v.clone()), &inputs);
+        for result in filter.run((ctx, input)) {
+            let v = result.map_err(|e| format!("jq: {e}"))?;
+            match v.as_str() {
+                Some(s) if opts.raw_output => output.push_str(s),
+                _ if opts.compact || opts.indent.is_empty() => output.push_str(&v.to_string()),
+                _ => write_pretty(&v, &opts.indent, 0, &mut output),
+            }
+            output.push('\n');
+            last = Some(v);
+        }
+    }
+    if opts.exit_status {
+        let code = match last {
+            None => 4,
+            Some(Val::Null | Val::Bool(false)) => 1,
+            Some(_) => 0,
+        };
+        if code != 0 {
+            return Err(format!("{output}jq: exit status {code}"));
+        }
+    }
+    Ok(output)
+}
 
-    for result in filter.run((Ctx::new([], &inputs), val)) {
-        match result {
-            Ok(v) => {
-                if opts.raw_output {
-                    match v.as_str() {
-                        Some(s) => output.push_str(s),
-                        None => output.push_str(&v.to_string()),
-                    }
-                } else if opts.compact {
-                    output.push_str(&v.to_string());
-                } else {
-                    // Pretty print: roundtrip through serde_json.
-                    let json: serde_json::Value =
-                        serde_json::from_str(&v.to_string()).unwrap_or(serde_json::Value::Null);
-                    output.push_str(
-                        &serde_json::to_string_pretty(&json)
-                            .unwrap_or_else(|_| v.to_string()),
-                    );
+/// Writes `v` like jq's default output: one array element or object
+/// member per line, indented by `unit` per nesting level.
+fn write_pretty(v: &Val, unit: &str, level: usize, out: &mut String) {
+    let newline = |out: &mut String, level: usize| {
+        out.push('\n');
+        for _ in 0..level {
+            out.push_str(unit);
+        }
+    };
+    match v {
+        Val::Arr(items) if !items.is_empty() => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
                 }
-                output.push('\n');
+                newline(out, level + 1);
+                write_pretty(item, unit, level + 1, out);
             }
-            Err(e) => return Err(format!("jq: {e}")),
+            newline(out, level);
+            out.push(']');
         }
+        Val::Obj(members) if !members.is_empty() => {
+            out.push('{');
+            for (i, (key, value)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                out.push_str(&Val::Str(key.clone()).to_string());
+                out.push_str(": ");
+                write_pretty(value, unit, level + 1, out);
+            }
+            newline(out, level);
+            out.push('}');
+        }
+        _ => out.push_str(&v.to_string()),
     }
-    Ok(output)
 }
 
 #[cfg(test)]
@@ -135,7 +251,11 @@ mod tests {
 
     #[test]
     fn pipe() {
-        let out = cmd(".items[] | .name", r#"{"items":[{"name":"a"},{"name":"b"}]}"#).unwrap();
+        let out = cmd(
+            "'.items[] | .name'",
+            r#"{"items":[{"name":"a"},{"name":"b"}]}"#,
+        )
+        .unwrap();
         let lines: Vec<&str> = out.lines().collect();
         assert_eq!(lines, vec!["\"a\"", "\"b\""]);
     }
@@ -167,7 +287,7 @@ mod tests {
 
     #[test]
     fn select() {
-        let out = cmd(".[] | select(. > 2)", r#"[1,2,3,4]"#).unwrap();
+        let out = cmd("'.[] | select(. > 2)'", r#"[1,2,3,4]"#).unwrap();
         let lines: Vec<&str> = out.lines().collect();
         assert_eq!(lines, vec!["3", "4"]);
     }
@@ -195,4 +315,109 @@ mod tests {
         let out = cmd(".", r#"{"a":1}"#).unwrap();
         assert!(out.contains('\n')); // pretty printed has newlines
     }
+
+    fn tmp(name: &str, content: &str) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        (dir, path.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn pretty_print_keeps_key_order() {
+        let out = cmd(".", r#"{"b":[1,{"c":null}],"a":{},"d":[]}"#).unwrap();
+        assert_eq!(
+            out,
+            "{\n  \"b\": [\n    1,\n    {\n      \"c\": null\n    }\n  ],\n  \"a\": {},\n  \"d\": []\n}\n"
+        );
+    }
+
+    #[test]
+    fn indent_and_tab() {
+        let out = cmd("--indent 4 .", "[1]").unwrap();
+        assert_eq!(out, "[\n    1\n]\n");
+        let out = cmd("--tab .", r#"{"a":1}"#).unwrap();
+        assert_eq!(out, "{\n\t\"a\": 1\n}\n");
+        let out = cmd("--indent 0 .", "[1, 2]").unwrap();
+        assert_eq!(out, "[1,2]\n");
+    }
+
+    #[test]
+    fn multiple_documents() {
+        let out = cmd("-c .a", "{\"a\":1}\n{\"a\":2} {\"a\":[3]}").unwrap();
+        assert_eq!(out, "1\n2\n[3]\n");
+    }
+
+    #[test]
+    fn slurp() {
+        let out = cmd("-c -s .", "1\n2\n3\n").unwrap();
+        assert_eq!(out, "[1,2,3]\n");
+        let out = cmd("-s length", "").unwrap();
+        assert_eq!(out, "0\n");
+    }
+
+    #[test]
+    fn null_input() {
+        let out = run("-n '1 + 1'", None).unwrap();
+        assert_eq!(out, "2\n");
+        let out = cmd("-n -c '[inputs]'", "1 2").unwrap();
+        assert_eq!(out, "[1,2]\n");
+    }
+
+    #[test]
+    fn input_builtin() {
+        let out = cmd("-c '[., input]'", "1 2 3 4").unwrap();
+        assert_eq!(out, "[1,2]\n[3,4]\n");
+    }
+
+    #[test]
+    fn file_arguments() {
+        let (dir, a) = tmp("a.json", r#"{"n":1}"#);
+        let b = dir.path().join("b.json");
+        std::fs::write(&b, "{\"n\":2}\n{\"n\":3}\n").unwrap();
+        let out = run(&format!(".n {a} {}", b.display()), None).unwrap();
+        assert_eq!(out, "1\n2\n3\n");
+        let out = run(&format!("-s 'map(.n) | add' {a} {}", b.display()), None).unwrap();
+        assert_eq!(out, "6\n");
+    }
+
+    #[test]
+    fn missing_file() {
+        let err = run(". /no/such.json", None).unwrap_err();
+        assert!(err.starts_with("jq: /no/such.json:"));
+    }
+
+    #[test]
+    fn arg_and_argjson() {
+        let out = run(
+            "-n -c --arg name bob --argjson n '{\"x\":[1]}' '[$name, $n.x]'",
+            None,
+        );
+        assert_eq!(out.unwrap(), "[\"bob\",[1]]\n");
+        let out = cmd("-r --arg k b '.[$k]'", r#"{"a":"1","b":"2"}"#).unwrap();
+        assert_eq!(out, "2\n");
+    }
+
+    #[test]
+    fn invalid_argjson() {
+        let err = run("-n --argjson x '{bad' .", None).unwrap_err();
+        assert!(err.contains("invalid JSON text passed to --argjson"));
+    }
+
+    #[test]
+    fn exit_status() {
+        assert_eq!(cmd("-e .a", r#"{"a":1}"#).unwrap(), "1\n");
+        let err = cmd("-e .a", r#"{"a":false}"#).unwrap_err();
+        assert_eq!(err, "false\njq: exit status 1");
+        let err = cmd("-e .a", r#"{"a":null}"#).unwrap_err();
+        assert!(err.ends_with("jq: exit status 1"));
+        let err = cmd("-e empty", "1").unwrap_err();
+        assert_eq!(err, "jq: exit status 4");
+    }
+
+    #[test]
+    fn large_numbers_survive() {
+        let out = cmd(".", "12345678901234567890").unwrap();
+        assert_eq!(out, "12345678901234567890\n");
+    }
 }
@@ -0,0 +1,52 @@
+//! JSON output for the `--json` flags of `ls`, `stat`, `find` and `wc`, so
+//! that programs and `jq` get typed fields rather than text columns.
+
+use crate::fs_ops::{EntryKind, Metadata};
+use serde_json::{Value, json};
+
+/// A file system entry as an object with its `name`, `path`, `kind`
+/// (`"file"` or `"directory"`), `size` in bytes and `modified` time in
+/// seconds since the Unix epoch, or `null` where unknown.
+pub fn entry(name: &str, path: &str, meta: &Metadata) -> Value {
+    let kind = match meta.kind {
+        EntryKind::File => "file",
+        EntryKind::Directory => "directory",
+    };
+    json!({
+        "name": name,
+        "path": path,
+        "kind": kind,
+        "size": meta.size,
+        "modified": meta.last_modified,
+    })
+}
+
+/// The given JSON values as an array, one compact value per line.
+pub fn array(items: &[Value]) -> String {
+    if items.is_empty() {
+        return "[]\n".into();
+    }
+    let items: Vec<String> = items.iter().map(Value::to_string).collect();
+    format!("[\n  {}\n]\n", items.join(",\n  "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_and_arrays() {
+        let meta = Metadata {
+            size: 3,
+            kind: EntryKind::File,
+            last_modified: None,
+        };
+        let entry = entry("a\"b.txt", "dir/a\"b.txt", &meta);
+        assert_eq!(
+            entry.to_string(),
+            "{\"name\":\"a\\\"b.txt\",\"path\":\"dir/a\\\"b.txt\",\"kind\":\"file\",\"size\":3,\"modified\":null}"
+        );
+        assert_eq!(array(&[]), "[]\n");
+        assert_eq!(array(&[json!(1), json!("2")]), "[\n  1,\n  \"2\"\n]\n");
+    }
+}
@@ -1,31 +1,64 @@
+use crate::bindings::exports::asterai::cli::bytes::Guest as BytesGuest;
+use crate::bindings::exports::asterai::cli::command::Guest as CommandGuest;
 use crate::bindings::exports::asterai::cli::common::Guest;
 
 #[allow(dead_code)]
 mod fs_ops;
 
+mod args;
 mod awk;
+mod base64;
+mod bytes;
 mod cat;
+mod checksum;
+mod column;
+mod comm;
 mod cp;
+mod csv;
 mod cut;
+mod deflate;
 mod diff;
+mod edit;
 mod find;
+mod fold;
+mod glob;
 mod grep;
+mod gunzip;
+mod gzip;
 mod head;
+mod join;
 mod jq;
+mod json;
 mod ls;
+mod md5sum;
 mod mkdir;
 mod mv;
+mod nl;
+mod od;
+mod paste;
+mod patch;
+mod printf;
+mod rev;
 mod rm;
 mod sed;
+mod seq;
+mod sha256sum;
+mod shell;
 mod sort;
+mod split;
 mod stat;
 mod tail;
+mod tar;
 mod tee;
 mod touch;
 mod tr;
 mod tree;
 mod uniq;
+mod unzip;
 mod wc;
+mod xargs;
+mod xxd;
+mod zip;
 
 #[allow(warnings)]
 mod bindings {
@@ -38,76 +71,218 @@ mod bindings {
 
 struct Component;
 
+impl CommandGuest for Component {
+    fn run(args: String, stdin: Option<String>) -> Result<String, String> {
+        shell::run(&args, stdin)
+    }
+}
+
 impl Guest for Component {
     fn awk(args: String, stdin: Option<String>) -> Result<String, String> {
-        awk::run(&args, stdin)
+        common("awk", &args, stdin)
     }
     fn ls(args: String, stdin: Option<String>) -> Result<String, String> {
-        ls::run(&args, stdin)
+        common("ls", &args, stdin)
     }
     fn cat(args: String, stdin: Option<String>) -> Result<String, String> {
-        cat::run(&args, stdin)
+        common("cat", &args, stdin)
     }
     fn cp(args: String, stdin: Option<String>) -> Result<String, String> {
-        cp::run(&args, stdin)
+        common("cp", &args, stdin)
     }
     fn mv(args: String, stdin: Option<String>) -> Result<String, String> {
-        mv::run(&args, stdin)
+        common("mv", &args, stdin)
     }
     fn rm(args: String, stdin: Option<String>) -> Result<String, String> {
-        rm::run(&args, stdin)
+        common("rm", &args, stdin)
     }
     fn mkdir(args: String, stdin: Option<String>) -> Result<String, String> {
-        mkdir::run(&args, stdin)
+        common("mkdir", &args, stdin)
     }
     fn touch(args: String, stdin: Option<String>) -> Result<String, String> {
-        touch::run(&args, stdin)
+        common("touch", &args, stdin)
     }
     fn grep(args: String, stdin: Option<String>) -> Result<String, String> {
-        grep::run(&args, stdin)
+        common("grep", &args, stdin)
     }
     fn sed(args: String, stdin: Option<String>) -> Result<String, String> {
-        sed::run(&args, stdin)
+        common("sed", &args, stdin)
     }
     fn sort(args: String, stdin: Option<String>) -> Result<String, String> {
-        sort::run(&args, stdin)
+        common("sort", &args, stdin)
     }
     fn stat(args: String, stdin: Option<String>) -> Result<String, String> {
-        stat::run(&args, stdin)
+        common("stat", &args, stdin)
     }
     fn jq(args: String, stdin: Option<String>) -> Result<String, String> {
-        jq::run(&args, stdin)
+        common("jq", &args, stdin)
     }
     fn cut(args: String, stdin: Option<String>) -> Result<String, String> {
-        cut::run(&args, stdin)
+        common("cut", &args, stdin)
     }
     fn diff(args: String, stdin: Option<String>) -> Result<String, String> {
-        diff::run(&args, stdin)
+        common("diff", &args, stdin)
+    }
+    fn edit(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("edit", &args, stdin)
+    }
+    fn patch(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("patch", &args, stdin)
     }
     fn find(args: String, stdin: Option<String>) -> Result<String, String> {
-        find::run(&args, stdin)
+        common("find", &args, stdin)
     }
     fn head(args: String, stdin: Option<String>) -> Result<String, String> {
-        head::run(&args, stdin)
+        common("head", &args, stdin)
     }
     fn tail(args: String, stdin: Option<String>) -> Result<String, String> {
-        tail::run(&args, stdin)
+        common("tail", &args, stdin)
     }
     fn tr(args: String, stdin: Option<String>) -> Result<String, String> {
-        tr::run(&args, stdin)
+        common("tr", &args, stdin)
     }
     fn tree(args: String, stdin: Option<String>) -> Result<String, String> {
-        tree::run(&args, stdin)
+        common("tree", &args, stdin)
     }
     fn tee(args: String, stdin: Option<String>) -> Result<String, String> {
-        tee::run(&args, stdin)
+        common("tee", &args, stdin)
     }
     fn uniq(args: String, stdin: Option<String>) -> Result<String, String> {
-        uniq::run(&args, stdin)
+        common("uniq", &args, stdin)
     }
     fn wc(args: String, stdin: Option<String>) -> Result<String, String> {
-        wc::run(&args, stdin)
+        common("wc", &args, stdin)
+    }
+    fn base64(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("base64", &args, stdin)
+    }
+    fn xxd(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("xxd", &args, stdin)
+    }
+    fn od(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("od", &args, stdin)
+    }
+    fn sha256sum(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("sha256sum", &args, stdin)
+    }
+    fn md5sum(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("md5sum", &args, stdin)
+    }
+    fn tar(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("tar", &args, stdin)
+    }
+    fn zip(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("zip", &args, stdin)
+    }
+    fn unzip(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("unzip", &args, stdin)
+    }
+    fn gzip(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("gzip", &args, stdin)
+    }
+    fn gunzip(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("gunzip", &args, stdin)
+    }
+    fn paste(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("paste", &args, stdin)
+    }
+    fn join(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("join", &args, stdin)
+    }
+    fn comm(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("comm", &args, stdin)
+    }
+    fn split(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("split", &args, stdin)
+    }
+    fn nl(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("nl", &args, stdin)
+    }
+    fn column(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("column", &args, stdin)
+    }
+    fn fold(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("fold", &args, stdin)
+    }
+    fn rev(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("rev", &args, stdin)
+    }
+    fn seq(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("seq", &args, stdin)
+    }
+    fn printf(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("printf", &args, stdin)
     }
+    fn xargs(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("xargs", &args, stdin)
+    }
+    fn csv(args: String, stdin: Option<String>) -> Result<String, String> {
+        common("csv", &args, stdin)
+    }
+}
+
+impl BytesGuest for Component {
+    fn run(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::run_bytes(&args, stdin)
+    }
+    fn cat(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("cat", &args, stdin)
+    }
+    fn head(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("head", &args, stdin)
+    }
+    fn tail(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("tail", &args, stdin)
+    }
+    fn tee(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("tee", &args, stdin)
+    }
+    fn wc(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("wc", &args, stdin)
+    }
+    fn base64(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("base64", &args, stdin)
+    }
+    fn xxd(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("xxd", &args, stdin)
+    }
+    fn od(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("od", &args, stdin)
+    }
+    fn sha256sum(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("sha256sum", &args, stdin)
+    }
+    fn md5sum(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("md5sum", &args, stdin)
+    }
+    fn tar(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("tar", &args, stdin)
+    }
+    fn zip(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("zip", &args, stdin)
+    }
+    fn unzip(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("unzip", &args, stdin)
+    }
+    fn gzip(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("gzip", &args, stdin)
+    }
+    fn gunzip(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("gunzip", &args, stdin)
+    }
+    fn split(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("split", &args, stdin)
+    }
+    fn printf(args: String, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+        shell::dispatch("printf", &args, stdin)
+    }
+}
+
+/// Runs a utility of the `common` interface through the same dispatch
+/// as the shell, so its arguments are expanded the same way.
+fn common(name: &str, args: &str, stdin: Option<String>) -> Result<String, String> {
+    let output = shell::dispatch(name, args, stdin.map(String::into_bytes))?;
+    bytes::into_text(name, output)
 }
 
 bindings::export!(Component with_types_in bindings);
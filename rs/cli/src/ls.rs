@@ -1,4 +1,7 @@
-use crate::fs_ops;
+use crate::args::{Arg, Parser};
+use crate::fs_ops::{self, EntryKind, Metadata};
+use crate::json;
+use serde_json::Value;
 use std::path::Path;
 
 struct Opts {
@@ -7,39 +10,43 @@ struct Opts {
     recursive: bool,
     sort_size: bool,
     sort_time: bool,
+    json: bool,
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
         long: false,
         all: false,
         recursive: false,
         sort_size: false,
         sort_time: false,
+        json: false,
         paths: Vec::new(),
     };
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 && !token.starts_with("--") {
-            for c in token[1..].chars() {
-                match c {
-                    'l' => opts.long = true,
-                    'a' => opts.all = true,
-                    'R' => opts.recursive = true,
-                    'S' => opts.sort_size = true,
-                    't' => opts.sort_time = true,
-                    '1' => {} // one-per-line is the default
-                    _ => {}
-                }
-            }
-        } else {
-            opts.paths.push(token.to_string());
+    let mut parser = Parser::new("ls", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('l') => opts.long = true,
+            Arg::Short('a') => opts.all = true,
+            Arg::Short('R') => opts.recursive = true,
+            Arg::Short('S') => opts.sort_size = true,
+            Arg::Short('t') => opts.sort_time = true,
+            Arg::Short('1') | Arg::Number(_) => {} // one-per-line is the default
+            Arg::Long(l) => match l.as_str() {
+                "all" => opts.all = true,
+                "recursive" => opts.recursive = true,
+                "json" => opts.json = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
     if opts.paths.is_empty() {
         opts.paths.push(".".into());
     }
-    opts
+    Ok(opts)
 }
 
 struct ItemInfo {
@@ -50,32 +57,42 @@ struct ItemInfo {
 }
 
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+    let opts = parse_opts(args)?;
     let mut output = String::new();
     let multi = opts.paths.len() > 1 || opts.recursive;
+    let mut entries = Vec::new();
 
     for (i, path) in opts.paths.iter().enumerate() {
-        if multi {
+        if multi && !opts.json {
             if i > 0 {
                 output.push('\n');
             }
             output.push_str(&format!("{path}:\n"));
         }
-        list_dir(path, &opts, &mut output, multi)?;
+        list_dir(path, &opts, &mut output, &mut entries)?;
+    }
+    if opts.json {
+        return Ok(json::array(&entries));
     }
     Ok(output)
 }
 
-fn list_dir(dir: &str, opts: &Opts, output: &mut String, _multi: bool) -> Result<(), String> {
-    let entries = fs_ops::ls(dir, false).map_err(|e| format!("ls: {dir}: {e}"))?;
+/// Lists `dir` into `output`, or into `entries` as JSON with `--json`.
+fn list_dir(
+    dir: &str,
+    opts: &Opts,
+    output: &mut String,
+    entries: &mut Vec<Value>,
+) -> Result<(), String> {
+    let listing = fs_ops::ls(dir, false).map_err(|e| format!("ls: {dir}: {e}"))?;
     let mut items: Vec<ItemInfo> = Vec::new();
 
-    for entry in entries {
+    for entry in listing {
         if !opts.all && entry.name.starts_with('.') {
             continue;
         }
         let full_path = format!("{dir}/{}", entry.name);
-        let modified = if opts.long || opts.sort_time {
+        let modified = if opts.long || opts.sort_time || opts.json {
             fs_ops::stat(&full_path).ok().and_then(|m| m.last_modified)
         } else {
             None
@@ -98,7 +115,22 @@ fn list_dir(dir: &str, opts: &Opts, output: &mut String, _multi: bool) -> Result
     }
 
     for item in &items {
-        if opts.long {
+        if opts.json {
+            let meta = Metadata {
+                size: item.size,
+                kind: if item.is_dir {
+                    EntryKind::Directory
+                } else {
+                    EntryKind::File
+                },
+                last_modified: item.modified,
+            };
+            entries.push(json::entry(
+                &item.name,
+                &format!("{dir}/{}", item.name),
+                &meta,
+            ));
+        } else if opts.long {
             let kind = if item.is_dir { 'd' } else { '-' };
             let date = match item.modified {
                 Some(ts) => format_date(ts),
@@ -119,8 +151,10 @@ fn list_dir(dir: &str, opts: &Opts, output: &mut String, _multi: bool) -> Result
             if item.is_dir {
                 let child = Path::new(dir).join(&item.name);
                 let child_str = child.to_string_lossy();
-                output.push_str(&format!("\n{child_str}:\n"));
-                list_dir(&child_str, opts, output, true)?;
+                if !opts.json {
+                    output.push_str(&format!("\n{child_str}:\n"));
+                }
+                list_dir(&child_str, opts, output, entries)?;
             }
         }
     }
@@ -242,4 +276,26 @@ mod tests {
         let out = cmd(&dir.path().display().to_string()).unwrap();
         assert_eq!(out, "");
     }
+
+    #[test]
+    fn json_output() {
+        let dir = setup();
+        std::fs::write(dir.path().join("sub/child.txt"), "c").unwrap();
+        let root = dir.path().display();
+        let out = cmd(&format!("--json {root}")).unwrap();
+        assert!(out.starts_with("[\n  {\"name\":\"alpha.txt\","));
+        assert!(out.contains(&format!(
+            "{{\"name\":\"beta.txt\",\"path\":\"{root}/beta.txt\",\"kind\":\"file\",\"size\":2,\"modified\":"
+        )));
+        assert!(out.contains("\"name\":\"sub\",") && out.contains("\"kind\":\"directory\""));
+        assert!(!out.contains("child.txt") && !out.contains(".hidden"));
+        let out = cmd(&format!("-R --json {root}")).unwrap();
+        assert!(out.contains(&format!("\"path\":\"{root}/sub/child.txt\"")));
+        assert!(!out.contains("sub:"));
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(
+            cmd(&format!("--json {}", empty.path().display())).unwrap(),
+            "[]\n"
+        );
+    }
 }
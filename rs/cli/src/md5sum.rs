@@ -0,0 +1,53 @@
+use crate::checksum;
+use md5::{Digest, Md5};
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    checksum::run_bytes("md5sum", |data| Md5::digest(data).to_vec(), args, stdin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("md5sum", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn known_digests() {
+        for (input, digest) in [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                "The quick brown fox jumps over the lazy dog",
+                "9e107d9d372bb6826bd81d3542a419d6",
+            ),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "8215ef0796a20bcaaae116d3876c664a",
+            ),
+        ] {
+            assert_eq!(cmd("", Some(input)).unwrap(), format!("{digest}  -\n"));
+        }
+    }
+
+    #[test]
+    fn binary_file_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("b.bin");
+        std::fs::write(&path, [0u8, 0xff, 0x80]).unwrap();
+        let path = path.display().to_string();
+        let sums = cmd(&path, None).unwrap();
+        assert_eq!(cmd("-c", Some(&sums)).unwrap(), format!("{path}: OK\n"));
+        let binary_mode = sums.replace("  ", " *");
+        assert_eq!(
+            cmd("-c", Some(&binary_mode)).unwrap(),
+            format!("{path}: OK\n")
+        );
+    }
+}
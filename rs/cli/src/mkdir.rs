@@ -1,18 +1,16 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
     let mut parents = false;
     let mut paths = Vec::new();
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 {
-            for c in token[1..].chars() {
-                match c {
-                    'p' => parents = true,
-                    _ => {}
-                }
-            }
-        } else {
-            paths.push(token);
+    let mut parser = Parser::new("mkdir", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('p') => parents = true,
+            Arg::Long(l) if l == "parents" => parents = true,
+            Arg::Value(path) => paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
     if paths.is_empty() {
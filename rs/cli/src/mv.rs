@@ -1,15 +1,16 @@
+use crate::args::operands;
 use crate::fs_ops;
 use std::path::Path;
 
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
-    let mut paths: Vec<&str> = args.split_whitespace().collect();
+    let mut paths = operands("mv", args)?;
     if paths.len() < 2 {
         return Err("mv: missing operand".into());
     }
     let dst = paths.pop().unwrap();
-    let dst_is_dir = fs_ops::exists(dst)
+    let dst_is_dir = fs_ops::exists(&dst)
         .unwrap_or(false)
-        && fs_ops::stat(dst)
+        && fs_ops::stat(&dst)
             .map(|m| m.is_dir())
             .unwrap_or(false);
 
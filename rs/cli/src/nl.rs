@@ -0,0 +1,178 @@
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+use regex::Regex;
+
+/// Which lines get a number (`-b`).
+enum Style {
+    All,
+    NonEmpty,
+    None,
+    Matching(Regex),
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Left,
+    Right,
+    RightZeros,
+}
+
+struct Opts {
+    style: Style,
+    format: Format,
+    width: usize,
+    separator: String,
+    start: i64,
+    increment: i64,
+    paths: Vec<String>,
+}
+
+fn parse_style(value: &str) -> Result<Style, String> {
+    match value {
+        "a" => Ok(Style::All),
+        "t" => Ok(Style::NonEmpty),
+        "n" => Ok(Style::None),
+        _ => match value.strip_prefix('p') {
+            Some(pattern) => Regex::new(pattern)
+                .map(Style::Matching)
+                .map_err(|e| format!("nl: invalid pattern: {e}")),
+            None => Err(format!("nl: invalid body numbering style: '{value}'")),
+        },
+    }
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "ln" => Ok(Format::Left),
+        "rn" => Ok(Format::Right),
+        "rz" => Ok(Format::RightZeros),
+        _ => Err(format!("nl: invalid line numbering format: '{value}'")),
+    }
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        style: Style::NonEmpty,
+        format: Format::Right,
+        width: 6,
+        separator: "\t".into(),
+        start: 1,
+        increment: 1,
+        paths: Vec::new(),
+    };
+    let mut parser = Parser::new("nl", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('b') => opts.style = parse_style(&parser.value()?)?,
+            Arg::Short('n') => opts.format = parse_format(&parser.value()?)?,
+            Arg::Short('w') => opts.width = parser.number()?,
+            Arg::Short('s') => opts.separator = parser.value()?,
+            Arg::Short('v') => opts.start = parser.number()?,
+            Arg::Short('i') => opts.increment = parser.number()?,
+            Arg::Long(l) => match l.as_str() {
+                "body-numbering" => opts.style = parse_style(&parser.value()?)?,
+                "number-format" => opts.format = parse_format(&parser.value()?)?,
+                "number-width" => opts.width = parser.number()?,
+                "number-separator" => opts.separator = parser.value()?,
+                "starting-line-number" => opts.start = parser.number()?,
+                "line-increment" => opts.increment = parser.number()?,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.width == 0 {
+        return Err("nl: invalid line number field width: '0'".into());
+    }
+    Ok(opts)
+}
+
+fn format_number(opts: &Opts, n: i64) -> String {
+    let width = opts.width;
+    match opts.format {
+        Format::Left => format!("{n:<width$}"),
+        Format::Right => format!("{n:>width$}"),
+        Format::RightZeros => format!("{n:0width$}"),
+    }
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let input = if opts.paths.is_empty() {
+        stdin.unwrap_or_default()
+    } else {
+        let mut combined = String::new();
+        for path in &opts.paths {
+            let content = fs_ops::read_to_string(path).map_err(|e| format!("nl: {path}: {e}"))?;
+            combined.push_str(&content);
+        }
+        combined
+    };
+
+    let mut output = String::new();
+    let mut number = opts.start;
+    // Unnumbered lines are indented to line up with numbered ones.
+    let blank = " ".repeat(opts.width + opts.separator.chars().count());
+    for line in input.lines() {
+        let numbered = match &opts.style {
+            Style::All => true,
+            Style::NonEmpty => !line.is_empty(),
+            Style::None => false,
+            Style::Matching(re) => re.is_match(line),
+        };
+        if numbered {
+            output.push_str(&format_number(&opts, number));
+            output.push_str(&opts.separator);
+            number += opts.increment;
+        } else {
+            output.push_str(&blank);
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn numbers_non_empty_lines() {
+        let out = cmd("", Some("a\n\nb\n")).unwrap();
+        assert_eq!(out, "     1\ta\n       \n     2\tb\n");
+    }
+
+    #[test]
+    fn styles_and_formats() {
+        let out = cmd("-ba -nrz -w3 -s:", Some("a\n\nb\n")).unwrap();
+        assert_eq!(out, "001:a\n002:\n003:b\n");
+        let out = cmd("-b 'p^#' -n ln -w 2 -s ' '", Some("# one\ntwo\n# three\n")).unwrap();
+        assert_eq!(out, "1  # one\n   two\n2  # three\n");
+    }
+
+    #[test]
+    fn start_and_increment() {
+        let out = cmd("-v 10 -i 5 -w 2", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "10\ta\n15\tb\n");
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(
+            cmd("-b x", Some(""))
+                .unwrap_err()
+                .contains("invalid body numbering style")
+        );
+        assert!(
+            cmd("-n xx", Some(""))
+                .unwrap_err()
+                .contains("invalid line numbering format")
+        );
+    }
+}
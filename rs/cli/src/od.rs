@@ -0,0 +1,392 @@
+//! `od` dumps bytes in octal, hexadecimal, decimal or as characters. Each
+//! `-t` type gets its own row per line, with columns aligned across rows
+//! the way GNU `od` does, and repeated lines collapse into `*`.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+#[derive(Clone, Copy)]
+enum Radix {
+    Octal,
+    Decimal,
+    Hex,
+    None,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Char,
+    Signed,
+    Unsigned,
+    Octal,
+    Hex,
+}
+
+#[derive(Clone, Copy)]
+struct Format {
+    kind: Kind,
+    size: usize,
+    /// Append the printable characters of the line, as in `-t x1z`.
+    text: bool,
+}
+
+impl Format {
+    /// The width of one field, not counting the space before it.
+    fn width(&self) -> usize {
+        match (self.kind, self.size) {
+            (Kind::Char, _) => 3,
+            (Kind::Hex, size) => size * 2,
+            (Kind::Octal, 1) => 3,
+            (Kind::Octal, 2) => 6,
+            (Kind::Octal, 4) => 11,
+            (Kind::Octal, _) => 22,
+            (Kind::Unsigned, 1) => 3,
+            (Kind::Unsigned, 2) => 5,
+            (Kind::Unsigned, 4) => 10,
+            (Kind::Unsigned, _) => 20,
+            (Kind::Signed, 1) => 4,
+            (Kind::Signed, 2) => 6,
+            (Kind::Signed, 4) => 11,
+            (Kind::Signed, _) => 20,
+        }
+    }
+
+    fn field(&self, chunk: &[u8]) -> String {
+        if self.kind == Kind::Char {
+            return char_field(chunk[0]);
+        }
+        // Words are little-endian; a partial final word is zero-padded.
+        let mut buf = [0u8; 8];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let value = u64::from_le_bytes(buf);
+        match self.kind {
+            Kind::Hex => format!("{value:0width$x}", width = self.width()),
+            Kind::Octal => format!("{value:0width$o}", width = self.width()),
+            Kind::Unsigned => value.to_string(),
+            _ => {
+                let shift = 64 - 8 * self.size as u32;
+                ((value << shift) as i64 >> shift).to_string()
+            }
+        }
+    }
+}
+
+fn char_field(byte: u8) -> String {
+    match byte {
+        0 => "\\0".into(),
+        7 => "\\a".into(),
+        8 => "\\b".into(),
+        b'\t' => "\\t".into(),
+        b'\n' => "\\n".into(),
+        11 => "\\v".into(),
+        12 => "\\f".into(),
+        b'\r' => "\\r".into(),
+        0x20..=0x7e => (byte as char).to_string(),
+        _ => format!("{byte:03o}"),
+    }
+}
+
+fn parse_types(spec: &str) -> Result<Vec<Format>, String> {
+    let invalid = || format!("od: invalid type string '{spec}'");
+    let mut formats = Vec::new();
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        let kind = match c {
+            'c' => Kind::Char,
+            'd' => Kind::Signed,
+            'u' => Kind::Unsigned,
+            'o' => Kind::Octal,
+            'x' => Kind::Hex,
+            _ => return Err(invalid()),
+        };
+        let mut digits = String::new();
+        while let Some(d) = chars.next_if(char::is_ascii_digit) {
+            digits.push(d);
+        }
+        let size = match (kind, digits.as_str()) {
+            (Kind::Char, "") => 1,
+            (Kind::Char, _) => return Err(invalid()),
+            (_, "") => 4,
+            (_, "1") => 1,
+            (_, "2") => 2,
+            (_, "4") => 4,
+            (_, "8") => 8,
+            _ => return Err(invalid()),
+        };
+        let text = chars.next_if_eq(&'z').is_some();
+        formats.push(Format { kind, size, text });
+    }
+    if formats.is_empty() {
+        return Err(invalid());
+    }
+    Ok(formats)
+}
+
+struct Opts {
+    radix: Radix,
+    formats: Vec<Format>,
+    skip: usize,
+    limit: Option<usize>,
+    width: usize,
+    verbose: bool,
+    path: Option<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        radix: Radix::Octal,
+        formats: Vec::new(),
+        skip: 0,
+        limit: None,
+        width: 16,
+        verbose: false,
+        path: None,
+    };
+    let short = |kind, size| Format {
+        kind,
+        size,
+        text: false,
+    };
+    let mut parser = Parser::new("od", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('A') => {
+                opts.radix = match parser.value()?.as_str() {
+                    "o" => Radix::Octal,
+                    "d" => Radix::Decimal,
+                    "x" => Radix::Hex,
+                    "n" => Radix::None,
+                    other => return Err(format!("od: invalid output address radix '{other}'")),
+                }
+            }
+            Arg::Short('t') => opts.formats.extend(parse_types(&parser.value()?)?),
+            Arg::Short('b') => opts.formats.push(short(Kind::Octal, 1)),
+            Arg::Short('c') => opts.formats.push(short(Kind::Char, 1)),
+            Arg::Short('d') => opts.formats.push(short(Kind::Unsigned, 2)),
+            Arg::Short('o') => opts.formats.push(short(Kind::Octal, 2)),
+            Arg::Short('x') => opts.formats.push(short(Kind::Hex, 2)),
+            Arg::Short('j') => opts.skip = parser.number()?,
+            Arg::Short('N') => opts.limit = Some(parser.number()?),
+            Arg::Short('w') => opts.width = parser.number()?,
+            Arg::Short('v') => opts.verbose = true,
+            Arg::Long(l) => match l.as_str() {
+                "format" => opts.formats.extend(parse_types(&parser.value()?)?),
+                "skip-bytes" => opts.skip = parser.number()?,
+                "read-bytes" => opts.limit = Some(parser.number()?),
+                "width" => opts.width = parser.number()?,
+                "output-duplicates" => opts.verbose = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) if opts.path.is_none() && path != "-" => opts.path = Some(path),
+            Arg::Value(path) if path == "-" => {}
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.formats.is_empty() {
+        opts.formats.push(short(Kind::Octal, 2));
+    }
+    let max_size = opts.formats.iter().map(|f| f.size).max().unwrap_or(1);
+    if opts.width == 0 || !opts.width.is_multiple_of(max_size) {
+        return Err(format!("od: invalid line width: {}", opts.width));
+    }
+    Ok(opts)
+}
+
+fn address(radix: Radix, offset: usize) -> String {
+    match radix {
+        Radix::Octal => format!("{offset:07o}"),
+        Radix::Decimal => format!("{offset:07}"),
+        Radix::Hex => format!("{offset:06x}"),
+        Radix::None => String::new(),
+    }
+}
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    let input = match &opts.path {
+        Some(path) => fs_ops::read(path).map_err(|e| format!("od: {path}: {e}"))?,
+        None => stdin.unwrap_or_default(),
+    };
+    let start = opts.skip.min(input.len());
+    let end = match opts.limit {
+        Some(limit) => (start + limit).min(input.len()),
+        None => input.len(),
+    };
+    let data = &input[start..end];
+
+    // Every row spans the same width: pad each format's fields so that
+    // columns line up with the widest row.
+    let line_width = opts
+        .formats
+        .iter()
+        .map(|f| opts.width / f.size * (f.width() + 1))
+        .max()
+        .unwrap_or(0);
+    let address_width = address(opts.radix, 0).len();
+
+    let mut output = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut starred = false;
+    for (i, line) in data.chunks(opts.width).enumerate() {
+        if !opts.verbose && previous == Some(line) {
+            if !starred {
+                output.push_str("*\n");
+                starred = true;
+            }
+            continue;
+        }
+        previous = Some(line);
+        starred = false;
+        for (row, format) in opts.formats.iter().enumerate() {
+            let mut text = match row {
+                0 => address(opts.radix, start + i * opts.width),
+                _ => " ".repeat(address_width),
+            };
+            let fields = opts.width / format.size;
+            let pad = line_width - fields * (format.width() + 1);
+            for (j, chunk) in line.chunks(format.size).enumerate() {
+                let extra = pad * (j + 1) / fields - pad * j / fields;
+                let width = format.width() + 1 + extra;
+                text.push_str(&format!("{:>width$}", format.field(chunk)));
+            }
+            if format.text {
+                let used = text.len() - address_width;
+                text.push_str(&" ".repeat(line_width - used));
+                let printable: String = line
+                    .iter()
+                    .map(|&b| match b {
+                        0x20..=0x7e => b as char,
+                        _ => '.',
+                    })
+                    .collect();
+                text.push_str(&format!("  >{printable}<"));
+            }
+            output.push_str(&text);
+            output.push('\n');
+        }
+    }
+    if !matches!(opts.radix, Radix::None) {
+        output.push_str(&address(opts.radix, end));
+        output.push('\n');
+    }
+    Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: &[u8]) -> Result<String, String> {
+        run_bytes(args, Some(stdin.to_vec())).map(|out| String::from_utf8(out).unwrap())
+    }
+
+    const DATA: &[u8] = b"Hello, world!\nbinary\x00\x01\xff end";
+
+    #[test]
+    fn default_octal_words() {
+        assert_eq!(
+            cmd("", DATA).unwrap(),
+            "0000000 062510 066154 026157 073440 071157 062154 005041 064542\n\
+             0000020 060556 074562 000400 020377 067145 000144\n\
+             0000033\n"
+        );
+    }
+
+    #[test]
+    fn chars() {
+        assert_eq!(
+            cmd("-c", DATA).unwrap(),
+            "0000000   H   e   l   l   o   ,       w   o   r   l   d   !  \\n   b   i\n\
+             0000020   n   a   r   y  \\0 001 377       e   n   d\n\
+             0000033\n"
+        );
+    }
+
+    #[test]
+    fn hex_bytes_with_text() {
+        assert_eq!(
+            cmd("-A x -t x1z", DATA).unwrap(),
+            "000000 48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 62 69  >Hello, world!.bi<\n\
+             000010 6e 61 72 79 00 01 ff 20 65 6e 64                 >nary... end<\n\
+             00001b\n"
+        );
+    }
+
+    #[test]
+    fn multiple_types_align() {
+        let out = cmd("-t d2 -t u1", &DATA[..16]).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "0000000   25928   27756   11375   30496   29295   25708    2593   26978"
+        );
+        assert_eq!(
+            lines[1],
+            "         72 101 108 108 111  44  32 119 111 114 108 100  33  10  98 105"
+        );
+    }
+
+    #[test]
+    fn wide_words_pad_partial() {
+        assert_eq!(
+            cmd("-t x4", DATA).unwrap(),
+            "0000000 6c6c6548 77202c6f 646c726f 69620a21\n\
+             0000020 7972616e 20ff0100 00646e65\n\
+             0000033\n"
+        );
+    }
+
+    #[test]
+    fn signed_values() {
+        assert_eq!(
+            cmd("-A n -t d1", &[0x80, 0x7f, 0xff]).unwrap(),
+            " -128  127   -1\n"
+        );
+    }
+
+    #[test]
+    fn skip_and_limit() {
+        assert_eq!(
+            cmd("-A n -t d1 -N 4 -j 14", DATA).unwrap(),
+            "   98  105  110   97\n"
+        );
+        assert_eq!(
+            cmd("-A d -c -j 14 -N 2", DATA).unwrap(),
+            "0000014   b   i\n0000016\n"
+        );
+    }
+
+    #[test]
+    fn duplicates_collapse() {
+        let data = [b'a'; 50];
+        assert_eq!(
+            cmd("-c", &data).unwrap(),
+            "0000000   a   a   a   a   a   a   a   a   a   a   a   a   a   a   a   a\n\
+             *\n\
+             0000060   a   a\n\
+             0000062\n"
+        );
+        assert_eq!(cmd("-v -c", &data).unwrap().lines().count(), 5);
+    }
+
+    #[test]
+    fn width() {
+        let out = cmd("-b -w 8", &DATA[..8]).unwrap();
+        assert_eq!(out, "0000000 110 145 154 154 157 054 040 167\n0000010\n");
+        assert!(
+            cmd("-t x4 -w 6", DATA)
+                .unwrap_err()
+                .contains("invalid line width")
+        );
+    }
+
+    #[test]
+    fn invalid_type() {
+        assert!(
+            cmd("-t q", DATA)
+                .unwrap_err()
+                .contains("invalid type string")
+        );
+        assert!(cmd("-t x3", DATA).is_err());
+    }
+}
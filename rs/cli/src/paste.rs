@@ -0,0 +1,193 @@
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+struct Opts {
+    delimiters: Vec<String>,
+    serial: bool,
+    paths: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut delimiters = "\t".to_string();
+    let mut serial = false;
+    let mut paths = Vec::new();
+    let mut parser = Parser::new("paste", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('d') => delimiters = parser.value()?,
+            Arg::Short('s') => serial = true,
+            Arg::Long(l) => match l.as_str() {
+                "delimiters" => delimiters = parser.value()?,
+                "serial" => serial = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("-".into());
+    }
+    Ok(Opts {
+        delimiters: parse_delimiters(&delimiters)?,
+        serial,
+        paths,
+    })
+}
+
+/// Expands the `-d` list, in which `\0` stands for no delimiter.
+fn parse_delimiters(list: &str) -> Result<Vec<String>, String> {
+    let mut delimiters = Vec::new();
+    let mut chars = list.chars();
+    while let Some(c) = chars.next() {
+        let delimiter = match c {
+            '\\' => match chars.next() {
+                Some('n') => "\n".to_string(),
+                Some('t') => "\t".to_string(),
+                Some('0') => String::new(),
+                Some(c) => c.to_string(),
+                None => {
+                    return Err(format!(
+                        "paste: delimiter list ends with an unescaped backslash: {list}"
+                    ));
+                }
+            },
+            c => c.to_string(),
+        };
+        delimiters.push(delimiter);
+    }
+    if delimiters.is_empty() {
+        delimiters.push(String::new());
+    }
+    Ok(delimiters)
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    // Every `-` operand reads from the same stdin, so `paste - -` pairs
+    // up its lines.
+    let stdin_lines: Vec<String> = stdin
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect();
+    let mut files = Vec::new();
+    for path in &opts.paths {
+        let lines = match path.as_str() {
+            "-" => None,
+            path => {
+                let content =
+                    fs_ops::read_to_string(path).map_err(|e| format!("paste: {path}: {e}"))?;
+                Some(content.lines().map(String::from).collect::<Vec<_>>())
+            }
+        };
+        files.push(lines);
+    }
+
+    let mut output = String::new();
+    if opts.serial {
+        let mut stdin_lines = Some(stdin_lines);
+        for file in files {
+            let lines = file.or_else(|| stdin_lines.take()).unwrap_or_default();
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(&opts.delimiters[(i - 1) % opts.delimiters.len()]);
+                }
+                output.push_str(line);
+            }
+            output.push('\n');
+        }
+        return Ok(output);
+    }
+
+    let mut positions = vec![0; files.len()];
+    let mut stdin_pos = 0;
+    loop {
+        let mut row = String::new();
+        let mut any = false;
+        for (i, file) in files.iter().enumerate() {
+            if i > 0 {
+                row.push_str(&opts.delimiters[(i - 1) % opts.delimiters.len()]);
+            }
+            let line = match file {
+                Some(lines) => {
+                    positions[i] += 1;
+                    lines.get(positions[i] - 1)
+                }
+                None => {
+                    stdin_pos += 1;
+                    stdin_lines.get(stdin_pos - 1)
+                }
+            };
+            if let Some(line) = line {
+                row.push_str(line);
+                any = true;
+            }
+        }
+        if !any {
+            break;
+        }
+        output.push_str(&row);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    fn tmp(files: &[(&str, &str)]) -> (tempfile::TempDir, Vec<String>) {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files
+            .iter()
+            .map(|(name, content)| {
+                let p = dir.path().join(name);
+                std::fs::write(&p, content).unwrap();
+                p.display().to_string()
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    #[test]
+    fn merges_files_side_by_side() {
+        let (_dir, p) = tmp(&[("a", "1\n2\n3\n"), ("b", "x\ny\n")]);
+        let out = cmd(&format!("{} {}", p[0], p[1]), None).unwrap();
+        assert_eq!(out, "1\tx\n2\ty\n3\t\n");
+    }
+
+    #[test]
+    fn stdin_operands_share_lines() {
+        let out = cmd("- -", Some("a\nb\nc\n")).unwrap();
+        assert_eq!(out, "a\tb\nc\t\n");
+        let out = cmd("-d,", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[test]
+    fn serial_with_cycling_delimiters() {
+        let out = cmd("-s -d ',;'", Some("1\n2\n3\n4\n")).unwrap();
+        assert_eq!(out, "1,2;3,4\n");
+        let (_dir, p) = tmp(&[("a", "1\n2\n"), ("b", "x\n")]);
+        let out = cmd(&format!("-s {} {}", p[0], p[1]), None).unwrap();
+        assert_eq!(out, "1\t2\nx\n");
+    }
+
+    #[test]
+    fn delimiter_escapes() {
+        let out = cmd(r"-d '\0' - -", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "ab\n");
+        let out = cmd(r"-s -d '\n'", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "a\nb\n");
+        assert!(
+            cmd(r"-d '\'", Some(""))
+                .unwrap_err()
+                .contains("unescaped backslash")
+        );
+    }
+}
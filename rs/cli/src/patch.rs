@@ -0,0 +1,706 @@
+//! `patch` applies unified diffs to files. Hunks that no longer match at
+//! their recorded line are searched for nearby (offset) and, failing that,
+//! retried with up to `--fuzz` context lines ignored at each end. Hunks
+//! that still do not apply are reported and saved to `<file>.rej`.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+struct Opts {
+    strip: Option<usize>,
+    fuzz: usize,
+    dry_run: bool,
+    reverse: bool,
+    ignore_whitespace: bool,
+    input: Option<String>,
+    /// A file to patch instead of the names in the diff headers.
+    target: Option<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        strip: None,
+        fuzz: 2,
+        dry_run: false,
+        reverse: false,
+        ignore_whitespace: false,
+        input: None,
+        target: None,
+    };
+    let mut parser = Parser::new("patch", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('p') => opts.strip = Some(parser.number()?),
+            Arg::Short('F') => opts.fuzz = parser.number()?,
+            Arg::Short('R') => opts.reverse = true,
+            Arg::Short('l') => opts.ignore_whitespace = true,
+            Arg::Short('i') => opts.input = Some(parser.value()?),
+            Arg::Long(l) => match l.as_str() {
+                "strip" => opts.strip = Some(parser.number()?),
+                "fuzz" => opts.fuzz = parser.number()?,
+                "dry-run" | "check" => opts.dry_run = true,
+                "reverse" => opts.reverse = true,
+                "ignore-whitespace" => opts.ignore_whitespace = true,
+                "input" => opts.input = Some(parser.value()?),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(value) if opts.target.is_none() => opts.target = Some(value),
+            Arg::Value(value) if opts.input.is_none() => opts.input = Some(value),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    Ok(opts)
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let text = match &opts.input {
+        Some(path) => fs_ops::read_to_string(path).map_err(|e| format!("patch: {path}: {e}"))?,
+        None => stdin.ok_or("patch: no patch input")?,
+    };
+    let mut patches = parse_patch(&text)?;
+    if patches.is_empty() {
+        return Err("patch: only garbage was found in the patch input".into());
+    }
+    if opts.reverse {
+        patches.iter_mut().for_each(FilePatch::reverse);
+    }
+    let mut output = String::new();
+    let mut failed = false;
+    for patch in &patches {
+        failed |= !apply_file(patch, &opts, &mut output)?;
+    }
+    if failed {
+        return Err(format!("{output}patch: exit status 1"));
+    }
+    Ok(output)
+}
+
+const DEV_NULL: &str = "/dev/null";
+
+struct FilePatch {
+    old: String,
+    new: String,
+    hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.old, &mut self.new);
+        for hunk in &mut self.hunks {
+            std::mem::swap(&mut hunk.old_start, &mut hunk.new_start);
+            std::mem::swap(&mut hunk.old_len, &mut hunk.new_len);
+            std::mem::swap(&mut hunk.old_no_eol, &mut hunk.new_no_eol);
+            for line in &mut hunk.lines {
+                line.0 = match line.0 {
+                    '-' => '+',
+                    '+' => '-',
+                    c => c,
+                };
+            }
+        }
+    }
+}
+
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+    /// Each line's marker (`' '`, `'-'` or `'+'`) and text.
+    lines: Vec<(char, String)>,
+    old_no_eol: bool,
+    new_no_eol: bool,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.side('+')
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.side('-')
+    }
+
+    fn side(&self, skip: char) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter(|(c, _)| *c != skip)
+            .map(|(_, l)| l.as_str())
+            .collect()
+    }
+
+    /// The number of context lines before the first and after the last change.
+    fn context(&self) -> (usize, usize) {
+        let leading = self.lines.iter().take_while(|(c, _)| *c == ' ').count();
+        let trailing = self
+            .lines
+            .iter()
+            .rev()
+            .take_while(|(c, _)| *c == ' ')
+            .count();
+        if leading == self.lines.len() {
+            (leading, 0)
+        } else {
+            (leading, trailing)
+        }
+    }
+
+    fn format(&self) -> String {
+        let mut out = format!(
+            "@@ -{},{} +{},{} @@\n",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        );
+        for (c, line) in &self.lines {
+            out.push(*c);
+            out.push_str(line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let next = lines.get(i + 1).copied().unwrap_or("");
+        if let (Some(old), Some(new)) = (line.strip_prefix("--- "), next.strip_prefix("+++ ")) {
+            patches.push(FilePatch {
+                old: header_name(old),
+                new: header_name(new),
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+        if line.starts_with("@@ ") {
+            let patch = patches
+                .last_mut()
+                .ok_or_else(|| format!("patch: hunk without file header at line {}", i + 1))?;
+            let (hunk, used) = parse_hunk(&lines[i..], i + 1)?;
+            patch.hunks.push(hunk);
+            i += used;
+            continue;
+        }
+        i += 1;
+    }
+    Ok(patches)
+}
+
+/// Strips the timestamp some diff tools append after a tab.
+fn header_name(header: &str) -> String {
+    header
+        .split('\t')
+        .next()
+        .unwrap_or("")
+        .trim_end()
+        .to_string()
+}
+
+/// Parses the hunk starting at `lines[0]`, returning it and the number of
+/// lines it spans.
+fn parse_hunk(lines: &[&str], line_no: usize) -> Result<(Hunk, usize), String> {
+    let malformed = || {
+        format!(
+            "patch: malformed hunk header at line {line_no}: {}",
+            lines[0]
+        )
+    };
+    let ranges = lines[0][3..].split(" @@").next().ok_or_else(malformed)?;
+    let (old, new) = ranges.split_once(' ').ok_or_else(malformed)?;
+    let (old_start, old_len) =
+        parse_range(old.strip_prefix('-').ok_or_else(malformed)?).ok_or_else(malformed)?;
+    let (new_start, new_len) =
+        parse_range(new.strip_prefix('+').ok_or_else(malformed)?).ok_or_else(malformed)?;
+    let mut hunk = Hunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+        lines: Vec::new(),
+        old_no_eol: false,
+        new_no_eol: false,
+    };
+    let (mut old_seen, mut new_seen) = (0, 0);
+    let mut i = 1;
+    while old_seen < old_len || new_seen < new_len {
+        let line = lines
+            .get(i)
+            .ok_or_else(|| format!("patch: unexpected end of hunk at line {line_no}"))?;
+        // Some editors strip the space from empty context lines.
+        let (marker, text) = match line.chars().next() {
+            None => (' ', ""),
+            Some(c @ (' ' | '-' | '+')) => (c, &line[1..]),
+            Some('\\') => {
+                i += 1;
+                continue;
+            }
+            Some(_) => {
+                return Err(format!(
+                    "patch: malformed hunk at line {}: {line}",
+                    line_no + i
+                ));
+            }
+        };
+        if marker != '+' {
+            old_seen += 1;
+        }
+        if marker != '-' {
+            new_seen += 1;
+        }
+        hunk.lines.push((marker, text.to_string()));
+        i += 1;
+    }
+    // A trailing "\ No newline at end of file" applies to the last line.
+    if lines.get(i).is_some_and(|l| l.starts_with('\\')) {
+        match hunk.lines.last().map(|(c, _)| *c) {
+            Some('-') => hunk.old_no_eol = true,
+            Some('+') => hunk.new_no_eol = true,
+            _ => {
+                hunk.old_no_eol = true;
+                hunk.new_no_eol = true;
+            }
+        }
+        i += 1;
+    }
+    // The marker may also follow the last removed line, before the additions.
+    for (j, line) in lines[1..i].iter().enumerate() {
+        if line.starts_with('\\') && lines[j].starts_with('-') {
+            hunk.old_no_eol = true;
+        }
+    }
+    Ok((hunk, i))
+}
+
+/// Parses `start,len` or `start` (a length of one).
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Applies one file's hunks, returning whether all of them succeeded.
+fn apply_file(patch: &FilePatch, opts: &Opts, output: &mut String) -> Result<bool, String> {
+    let creating = patch.old == DEV_NULL;
+    let deleting = patch.new == DEV_NULL;
+    let path = match &opts.target {
+        Some(target) => target.clone(),
+        None => target_path(patch, opts.strip)?,
+    };
+    let verb = if opts.dry_run { "checking" } else { "patching" };
+    output.push_str(&format!("{verb} file {path}\n"));
+
+    let original = match fs_ops::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) if creating => String::new(),
+        Err(e) => {
+            output.push_str(&format!("patch: {path}: {e}\n"));
+            return Ok(false);
+        }
+    };
+    let mut lines: Vec<String> = original.lines().map(String::from).collect();
+    let mut trailing_newline = original.is_empty() || original.ends_with('\n');
+
+    let mut rejects = Vec::new();
+    // Lines added minus lines removed by the hunks applied so far.
+    let mut delta: isize = 0;
+    let mut last_offset: isize = 0;
+    // Hunks may not overlap lines already patched.
+    let mut min_pos = 0;
+    for (n, hunk) in patch.hunks.iter().enumerate() {
+        let n = n + 1;
+        // A hunk without old lines inserts after line `old_start`.
+        let start = if hunk.old_len == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.max(1) - 1
+        };
+        let expected = start as isize + delta;
+        let found = locate_hunk(&lines, hunk, expected + last_offset, min_pos, opts);
+        let (pos, fuzz) = match found {
+            Some(found) => found,
+            None => {
+                let at = (expected.max(0) + 1).max(1);
+                output.push_str(&format!("Hunk #{n} FAILED at {at}.\n"));
+                rejects.push(hunk);
+                continue;
+            }
+        };
+        let old = hunk.old_lines();
+        let new = hunk.new_lines();
+        let (leading, trailing) = hunk.context();
+        let lead = fuzz.min(leading);
+        let trail = fuzz.min(trailing);
+        let old = &old[lead..old.len() - trail];
+        let new = &new[lead..new.len() - trail];
+        let offset = pos as isize - (expected + lead as isize);
+        let replacement: Vec<String> = new.iter().map(|l| l.to_string()).collect();
+        let at_eof = pos + old.len() == lines.len();
+        lines.splice(pos..pos + old.len(), replacement);
+        if at_eof && (hunk.old_no_eol || hunk.new_no_eol) {
+            trailing_newline = !hunk.new_no_eol;
+        }
+        delta += new.len() as isize - old.len() as isize;
+        last_offset = offset;
+        min_pos = pos + new.len();
+
+        if offset != 0 || fuzz > 0 {
+            let mut msg = format!("Hunk #{n} succeeded at {}", pos + 1 - lead);
+            if fuzz > 0 {
+                msg.push_str(&format!(" with fuzz {fuzz}"));
+            }
+            if offset != 0 {
+                let unit = if offset.abs() == 1 { "line" } else { "lines" };
+                msg.push_str(&format!(" (offset {offset} {unit})"));
+            }
+            output.push_str(&msg);
+            output.push_str(".\n");
+        }
+    }
+
+    if !opts.dry_run {
+        if deleting && rejects.is_empty() && lines.is_empty() {
+            fs_ops::rm(&path, false).map_err(|e| format!("patch: {path}: {e}"))?;
+        } else {
+            let mut content = lines.join("\n");
+            if trailing_newline && !lines.is_empty() {
+                content.push('\n');
+            }
+            fs_ops::write(&path, content.as_bytes()).map_err(|e| format!("patch: {path}: {e}"))?;
+        }
+    }
+    if rejects.is_empty() {
+        return Ok(true);
+    }
+    let total = patch.hunks.len();
+    let noun = if total == 1 { "hunk" } else { "hunks" };
+    output.push_str(&format!("{} out of {total} {noun} FAILED", rejects.len()));
+    if opts.dry_run {
+        output.push('\n');
+    } else {
+        let rej_path = format!("{path}.rej");
+        let mut rej = format!("--- {}\n+++ {}\n", patch.old, patch.new);
+        for hunk in &rejects {
+            rej.push_str(&hunk.format());
+        }
+        fs_ops::write(&rej_path, rej.as_bytes()).map_err(|e| format!("patch: {rej_path}: {e}"))?;
+        output.push_str(&format!(" -- saving rejects to file {rej_path}\n"));
+    }
+    Ok(false)
+}
+
+/// Picks the file to patch from the diff headers: the new name when
+/// creating, otherwise the old name if it exists and the new one if not.
+fn target_path(patch: &FilePatch, strip: Option<usize>) -> Result<String, String> {
+    let old = (patch.old != DEV_NULL).then(|| strip_path(&patch.old, strip));
+    let new = (patch.new != DEV_NULL).then(|| strip_path(&patch.new, strip));
+    match (old, new) {
+        (Some(old), Some(new)) => {
+            let old = old?;
+            if fs_ops::exists(&old).unwrap_or(false) {
+                Ok(old)
+            } else {
+                new
+            }
+        }
+        (Some(name), None) | (None, Some(name)) => name,
+        (None, None) => Err("patch: no file name in patch header".into()),
+    }
+}
+
+/// Removes `strip` leading path components. Without `-p`, the name is
+/// used as-is when its directory exists and reduced to its basename
+/// otherwise.
+fn strip_path(name: &str, strip: Option<usize>) -> Result<String, String> {
+    let n = match strip {
+        Some(n) => n,
+        None => {
+            let name = match name.rsplit_once('/') {
+                Some((dir, base)) if !fs_ops::exists(dir).unwrap_or(false) => base,
+                _ => name,
+            };
+            return Ok(name.to_string());
+        }
+    };
+    let mut rest = name;
+    for _ in 0..n {
+        match rest.split_once('/') {
+            Some((_, tail)) => rest = tail.trim_start_matches('/'),
+            None => return Err(format!("patch: cannot strip {n} components from {name}")),
+        }
+    }
+    Ok(rest.to_string())
+}
+
+/// Finds where the hunk applies, returning the position of its (possibly
+/// fuzz-trimmed) old lines and the fuzz used. Positions closest to `hint`
+/// are tried first.
+fn locate_hunk(
+    lines: &[String],
+    hunk: &Hunk,
+    hint: isize,
+    min_pos: usize,
+    opts: &Opts,
+) -> Option<(usize, usize)> {
+    let old = hunk.old_lines();
+    let (leading, trailing) = hunk.context();
+    for fuzz in 0..=opts.fuzz {
+        let lead = fuzz.min(leading);
+        let trail = fuzz.min(trailing);
+        if fuzz > 0 && lead == 0 && trail == 0 {
+            break;
+        }
+        let wanted = &old[lead..old.len() - trail];
+        if wanted.len() > lines.len() {
+            continue;
+        }
+        let last = lines.len() - wanted.len();
+        let hint =
+            (hint + lead as isize).clamp(min_pos as isize, last.max(min_pos) as isize) as usize;
+        let matches_at = |pos: usize| {
+            pos >= min_pos
+                && pos <= last
+                && wanted
+                    .iter()
+                    .zip(&lines[pos..])
+                    .all(|(w, l)| lines_equal(w, l, opts.ignore_whitespace))
+        };
+        for distance in 0..=lines.len() {
+            if matches_at(hint + distance) {
+                return Some((hint + distance, fuzz));
+            }
+            if distance > 0 && distance <= hint && matches_at(hint - distance) {
+                return Some((hint - distance, fuzz));
+            }
+        }
+    }
+    None
+}
+
+fn lines_equal(a: &str, b: &str, ignore_whitespace: bool) -> bool {
+    if ignore_whitespace {
+        a.split_whitespace().eq(b.split_whitespace())
+    } else {
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: &str) -> Result<String, String> {
+        run(args, Some(stdin.to_string()))
+    }
+
+    fn tmp(name: &str, content: &str) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        (dir, path.to_str().unwrap().to_string())
+    }
+
+    fn read(path: &str) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn diff(path: &str, hunks: &str) -> String {
+        format!("--- {path}\n+++ {path}\n{hunks}")
+    }
+
+    const FIVE: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+    #[test]
+    fn applies_simple_hunk() {
+        let (_dir, path) = tmp("f.txt", FIVE);
+        let patch = diff(&path, "@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n");
+        let out = cmd("", &patch).unwrap();
+        assert_eq!(out, format!("patching file {path}\n"));
+        assert_eq!(read(&path), "one\ntwo\nTHREE\nfour\nfive\n");
+    }
+
+    #[test]
+    fn applies_multiple_hunks() {
+        let (_dir, path) = tmp("f.txt", "a\nb\nc\nd\ne\nf\ng\nh\n");
+        let patch = diff(
+            &path,
+            "@@ -1,2 +1,3 @@\n a\n+a2\n b\n@@ -7,2 +8,1 @@\n g\n-h\n",
+        );
+        cmd("", &patch).unwrap();
+        assert_eq!(read(&path), "a\na2\nb\nc\nd\ne\nf\ng\n");
+    }
+
+    #[test]
+    fn reports_offset() {
+        let (_dir, path) = tmp("f.txt", &format!("new1\nnew2\n{FIVE}"));
+        let patch = diff(&path, "@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n");
+        let out = cmd("", &patch).unwrap();
+        assert!(out.contains("Hunk #1 succeeded at 4 (offset 2 lines)."));
+        assert_eq!(read(&path), "new1\nnew2\none\ntwo\nTHREE\nfour\nfive\n");
+    }
+
+    #[test]
+    fn applies_with_fuzz() {
+        let (_dir, path) = tmp("f.txt", "one\nTWO\nthree\nfour\nfive\n");
+        let patch = diff(&path, "@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n");
+        let out = cmd("", &patch).unwrap();
+        assert!(out.contains("Hunk #1 succeeded at 2 with fuzz 1."));
+        assert_eq!(read(&path), "one\nTWO\nTHREE\nfour\nfive\n");
+    }
+
+    #[test]
+    fn fuzz_zero_rejects() {
+        let (_dir, path) = tmp("f.txt", "one\nTWO\nthree\nfour\nfive\n");
+        let patch = diff(&path, "@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n");
+        let err = cmd("-F0", &patch).unwrap_err();
+        assert!(err.contains("Hunk #1 FAILED at 2."));
+    }
+
+    #[test]
+    fn rejected_hunks_saved() {
+        let (_dir, path) = tmp("f.txt", FIVE);
+        let patch = diff(
+            &path,
+            "@@ -1,1 +1,1 @@\n-one\n+ONE\n@@ -4,1 +4,1 @@\n-missing\n+x\n",
+        );
+        let err = cmd("", &patch).unwrap_err();
+        assert!(err.contains("Hunk #2 FAILED at 4."));
+        assert!(err.contains(&format!(
+            "1 out of 2 hunks FAILED -- saving rejects to file {path}.rej"
+        )));
+        assert!(err.ends_with("patch: exit status 1"));
+        assert_eq!(read(&path), "ONE\ntwo\nthree\nfour\nfive\n");
+        let rej = read(&format!("{path}.rej"));
+        assert!(rej.contains("@@ -4,1 +4,1 @@\n-missing\n+x\n"));
+    }
+
+    #[test]
+    fn dry_run_leaves_file() {
+        let (_dir, path) = tmp("f.txt", FIVE);
+        let patch = diff(&path, "@@ -1,1 +1,1 @@\n-one\n+ONE\n");
+        let out = cmd("--dry-run", &patch).unwrap();
+        assert_eq!(out, format!("checking file {path}\n"));
+        assert_eq!(read(&path), FIVE);
+    }
+
+    #[test]
+    fn dry_run_reports_failures() {
+        let (_dir, path) = tmp("f.txt", FIVE);
+        let patch = diff(&path, "@@ -1,1 +1,1 @@\n-nope\n+x\n");
+        let err = cmd("--dry-run", &patch).unwrap_err();
+        assert!(err.contains("1 out of 1 hunk FAILED\n"));
+        assert!(!std::path::Path::new(&format!("{path}.rej")).exists());
+    }
+
+    #[test]
+    fn strip_components() {
+        // A relative directory, so the stripped header names resolve.
+        let dir = tempfile::tempdir_in(".").unwrap();
+        let name = dir.path().file_name().unwrap().to_str().unwrap();
+        let path = format!("{name}/f.txt");
+        std::fs::write(&path, FIVE).unwrap();
+        let patch = format!(
+            "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -1 +1 @@\n-one\n+ONE\n"
+        );
+        let out = cmd("-p1", &patch).unwrap();
+        assert_eq!(out, format!("patching file {path}\n"));
+        assert!(read(&path).starts_with("ONE\n"));
+        let err = cmd("-p99", &patch).unwrap_err();
+        assert!(err.contains("cannot strip 99 components"));
+    }
+
+    #[test]
+    fn explicit_target_file() {
+        let (_dir, path) = tmp("f.txt", FIVE);
+        let patch = "--- old.txt\n+++ new.txt\n@@ -5 +5 @@\n-five\n+5\n";
+        cmd(&format!("'{path}'"), patch).unwrap();
+        assert!(read(&path).ends_with("four\n5\n"));
+    }
+
+    #[test]
+    fn patch_from_input_file() {
+        let (dir, path) = tmp("f.txt", FIVE);
+        let patch_path = dir.path().join("change.diff");
+        std::fs::write(&patch_path, diff(&path, "@@ -1 +1 @@\n-one\n+1\n")).unwrap();
+        run(&format!("-i {}", patch_path.display()), None).unwrap();
+        assert!(read(&path).starts_with("1\ntwo\n"));
+    }
+
+    #[test]
+    fn reverse() {
+        let (_dir, path) = tmp("f.txt", "one\nTWO\nthree\n");
+        let patch = diff(&path, "@@ -1,3 +1,3 @@\n one\n-two\n+TWO\n three\n");
+        cmd("-R", &patch).unwrap();
+        assert_eq!(read(&path), "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn creates_and_deletes_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.txt");
+        let path = path.display();
+        let create = format!("--- /dev/null\n+++ {path}\n@@ -0,0 +1,2 @@\n+hello\n+world\n");
+        cmd("", &create).unwrap();
+        assert_eq!(read(&path.to_string()), "hello\nworld\n");
+        let delete = format!("--- {path}\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-hello\n-world\n");
+        cmd("", &delete).unwrap();
+        assert!(!dir.path().join("new.txt").exists());
+    }
+
+    #[test]
+    fn no_newline_at_end_of_file() {
+        let (_dir, path) = tmp("f.txt", "a\nb\n");
+        let patch = diff(&path, "@@ -2 +2 @@\n-b\n+c\n\\ No newline at end of file\n");
+        cmd("", &patch).unwrap();
+        assert_eq!(read(&path), "a\nc");
+        let patch = diff(&path, "@@ -2 +2 @@\n-c\n\\ No newline at end of file\n+d\n");
+        cmd("", &patch).unwrap();
+        assert_eq!(read(&path), "a\nd\n");
+    }
+
+    #[test]
+    fn ignore_whitespace() {
+        let (_dir, path) = tmp("f.txt", "fn  main() {\n    body();\n}\n");
+        let patch = diff(
+            &path,
+            "@@ -1,3 +1,3 @@\n fn main() {\n-  body();\n+  other();\n }\n",
+        );
+        assert!(cmd("-F0", &patch).is_err());
+        cmd("-l -F0", &patch).unwrap();
+        assert!(read(&path).contains("  other();"));
+    }
+
+    #[test]
+    fn round_trips_diff_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n").unwrap();
+        std::fs::write(&b, "1\n2\nthree\n4\n5\n6\n7\n8\nnine\n10\n11\n").unwrap();
+        let diff = crate::diff::run(&format!("-u {} {}", a.display(), b.display()), None).unwrap();
+        run(&format!("{}", a.display()), Some(diff)).unwrap();
+        assert_eq!(read(a.to_str().unwrap()), read(b.to_str().unwrap()));
+    }
+
+    #[test]
+    fn missing_file_reported() {
+        let patch = "--- /no/such.txt\n+++ /no/such.txt\n@@ -1 +1 @@\n-a\n+b\n";
+        let err = cmd("", patch).unwrap_err();
+        assert!(err.contains("patch: such.txt:"));
+    }
+
+    #[test]
+    fn garbage_input() {
+        let err = cmd("", "not a diff\n").unwrap_err();
+        assert!(err.contains("only garbage"));
+    }
+
+    #[test]
+    fn no_input() {
+        assert!(run("", None).unwrap_err().contains("no patch input"));
+    }
+}
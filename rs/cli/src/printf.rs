@@ -0,0 +1,573 @@
+//! `printf FORMAT [ARGUMENT]...` as in GNU coreutils. The format is reused
+//! until the arguments run out; missing arguments count as empty or zero.
+//! Numeric arguments may be decimal, `0x` hex, `0` octal, or `'c` for the
+//! code of character `c`. Output is bytes, so `\NNN` and `\xHH` escapes
+//! can produce any byte.
+
+use crate::args;
+
+/// A parsed `%` conversion specification.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+/// Formatting state: arguments still to be consumed and errors so far.
+struct Printer<'a> {
+    args: &'a [String],
+    next: usize,
+    messages: Vec<String>,
+}
+
+impl Printer<'_> {
+    fn arg(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.next)?;
+        self.next += 1;
+        Some(arg)
+    }
+
+    fn invalid(&mut self, arg: &str, what: &str) {
+        self.messages.push(format!("printf: '{arg}': {what}"));
+    }
+
+    /// Parses an integer argument the way strtoimax does, warning about
+    /// arguments that are not (completely) numeric.
+    fn integer(&mut self) -> i128 {
+        let Some(arg) = self.arg() else { return 0 };
+        let arg = arg.to_string();
+        if let Some(code) = char_code(&arg) {
+            return code as i128;
+        }
+        let trimmed = arg.trim_start();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (radix, digits) = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+            Some(hex) => (16, hex),
+            None if digits.starts_with('0') && digits.len() > 1 => (8, &digits[1..]),
+            None => (10, digits),
+        };
+        let end = digits
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(digits.len());
+        let value = match i128::from_str_radix(&digits[..end], radix) {
+            Ok(value) if negative => -value,
+            Ok(value) => value,
+            Err(_) if radix == 8 && end == 0 => 0,
+            Err(_) => {
+                self.invalid(&arg, "expected a numeric value");
+                return 0;
+            }
+        };
+        if end < digits.len() {
+            self.invalid(&arg, "value not completely converted");
+        }
+        value
+    }
+
+    fn float(&mut self) -> f64 {
+        let Some(arg) = self.arg() else { return 0.0 };
+        let arg = arg.to_string();
+        if let Some(code) = char_code(&arg) {
+            return code as f64;
+        }
+        let trimmed = arg.trim();
+        if let Ok(value) = trimmed.parse::<f64>() {
+            return value;
+        }
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let hex = digits.strip_prefix("0x").or(digits.strip_prefix("0X"));
+        if let Some(Ok(value)) = hex.map(|hex| u64::from_str_radix(hex, 16)) {
+            return if negative {
+                -(value as f64)
+            } else {
+                value as f64
+            };
+        }
+        // The longest prefix that parses, like strtod.
+        let prefix = (1..trimmed.len())
+            .rev()
+            .filter(|&i| trimmed.is_char_boundary(i))
+            .find_map(|i| trimmed[..i].parse::<f64>().ok());
+        match prefix {
+            Some(value) => {
+                self.invalid(&arg, "value not completely converted");
+                value
+            }
+            None => {
+                self.invalid(&arg, "expected a numeric value");
+                0.0
+            }
+        }
+    }
+}
+
+/// The value of a `'c` or `"c` argument.
+fn char_code(arg: &str) -> Option<u32> {
+    let rest = arg.strip_prefix('\'').or(arg.strip_prefix('"'))?;
+    Some(rest.chars().next().map_or(0, u32::from))
+}
+
+/// Appends `c` encoded as UTF-8.
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+/// Expands the escape at the start of `chars` (just after a backslash).
+/// `%b` arguments write octal escapes as `\0NNN`. Returns false for `\c`,
+/// which ends all output.
+fn escape(chars: &mut std::iter::Peekable<std::str::Chars>, out: &mut Vec<u8>, in_b: bool) -> bool {
+    let take_digits = |chars: &mut std::iter::Peekable<std::str::Chars>, radix: u32, max: usize| {
+        let mut value = 0u32;
+        let mut count = 0;
+        while count < max {
+            match chars.peek().and_then(|c| c.to_digit(radix)) {
+                Some(d) => {
+                    value = value * radix + d;
+                    chars.next();
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        (value, count)
+    };
+    let Some(c) = chars.next() else {
+        out.push(b'\\');
+        return true;
+    };
+    match c {
+        'a' => out.push(0x07),
+        'b' => out.push(0x08),
+        'c' => return false,
+        'e' => out.push(0x1b),
+        'f' => out.push(0x0c),
+        'n' => out.push(b'\n'),
+        'r' => out.push(b'\r'),
+        't' => out.push(b'\t'),
+        'v' => out.push(0x0b),
+        '\\' => out.push(b'\\'),
+        '"' if !in_b => out.push(b'"'),
+        '0' if in_b => {
+            let (value, _) = take_digits(chars, 8, 3);
+            out.push(value as u8);
+        }
+        '0'..='7' => {
+            let (rest, count) = take_digits(chars, 8, 2);
+            let value = (c as u32 - '0' as u32) * 8u32.pow(count as u32) + rest;
+            out.push(value as u8);
+        }
+        'x' => match take_digits(chars, 16, 2) {
+            (_, 0) => out.extend_from_slice(b"\\x"),
+            (value, _) => out.push(value as u8),
+        },
+        'u' | 'U' => {
+            let len = if c == 'u' { 4 } else { 8 };
+            let (value, count) = take_digits(chars, 16, len);
+            match char::from_u32(value) {
+                Some(ch) if count == len => push_char(out, ch),
+                _ => {
+                    out.push(b'\\');
+                    push_char(out, c);
+                }
+            }
+        }
+        c => {
+            out.push(b'\\');
+            push_char(out, c);
+        }
+    }
+    true
+}
+
+/// Pads `body` to the spec's width. With `0`, zeros go after `sign`.
+fn pad(out: &mut Vec<u8>, spec: &Spec, sign: &str, body: &str, zero: bool) {
+    let len = sign.chars().count() + body.chars().count();
+    let fill = spec.width.saturating_sub(len);
+    if spec.left {
+        out.extend_from_slice(sign.as_bytes());
+        out.extend_from_slice(body.as_bytes());
+        out.extend(std::iter::repeat_n(b' ', fill));
+    } else if zero {
+        out.extend_from_slice(sign.as_bytes());
+        out.extend(std::iter::repeat_n(b'0', fill));
+        out.extend_from_slice(body.as_bytes());
+    } else {
+        out.extend(std::iter::repeat_n(b' ', fill));
+        out.extend_from_slice(sign.as_bytes());
+        out.extend_from_slice(body.as_bytes());
+    }
+}
+
+fn sign(spec: &Spec, negative: bool) -> &'static str {
+    match (negative, spec.plus, spec.space) {
+        (true, _, _) => "-",
+        (false, true, _) => "+",
+        (false, false, true) => " ",
+        _ => "",
+    }
+}
+
+fn format_integer(out: &mut Vec<u8>, spec: &Spec, value: i128) {
+    let (negative, digits, prefix) = match spec.conversion {
+        'd' | 'i' => {
+            let value = value.clamp(i64::MIN as i128, i64::MAX as i128);
+            (value < 0, value.unsigned_abs().to_string(), "")
+        }
+        c => {
+            // Negative values wrap around, as in C.
+            let value = value as i64 as u64;
+            let digits = match c {
+                'o' => format!("{value:o}"),
+                'x' => format!("{value:x}"),
+                'X' => format!("{value:X}"),
+                _ => value.to_string(),
+            };
+            let prefix = match c {
+                'x' if spec.alternate && value != 0 => "0x",
+                'X' if spec.alternate && value != 0 => "0X",
+                _ => "",
+            };
+            (false, digits, prefix)
+        }
+    };
+    let mut digits = match spec.precision {
+        Some(0) if digits == "0" => String::new(),
+        Some(precision) if digits.len() < precision => {
+            format!("{}{digits}", "0".repeat(precision - digits.len()))
+        }
+        _ => digits,
+    };
+    if spec.conversion == 'o' && spec.alternate && !digits.starts_with('0') {
+        digits.insert(0, '0');
+    }
+    let sign = format!("{}{prefix}", self::sign(spec, negative));
+    pad(
+        out,
+        spec,
+        &sign,
+        &digits,
+        spec.zero && spec.precision.is_none(),
+    );
+}
+
+/// `%e` style with at least two exponent digits: `1.500000e+02`.
+fn exponential(value: f64, precision: usize, upper: bool) -> String {
+    let formatted = format!("{value:.precision$e}");
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{mantissa}{e}{sign}{:02}", exponent.abs())
+}
+
+/// Formats a float conversion: `%f`, `%e`, `%g` or an upper-case form.
+fn format_float(out: &mut Vec<u8>, spec: &Spec, value: f64) {
+    let upper = spec.conversion.is_ascii_uppercase();
+    let negative = value.is_sign_negative() && !value.is_nan();
+    let magnitude = value.abs();
+    if !value.is_finite() {
+        let body = match (value.is_nan(), upper) {
+            (true, false) => "nan",
+            (true, true) => "NAN",
+            (false, false) => "inf",
+            (false, true) => "INF",
+        };
+        return pad(out, spec, sign(spec, negative), body, false);
+    }
+    let precision = spec.precision.unwrap_or(6);
+    let body = match spec.conversion.to_ascii_lowercase() {
+        'e' => exponential(magnitude, precision, upper),
+        'g' => {
+            let precision = precision.max(1);
+            let probe = exponential(magnitude, precision - 1, false);
+            let exponent: i32 = probe
+                .rsplit_once('e')
+                .and_then(|(_, e)| e.parse().ok())
+                .unwrap_or(0);
+            let mut body = if exponent < -4 || exponent >= precision as i32 {
+                exponential(magnitude, precision - 1, upper)
+            } else {
+                let decimals = (precision as i32 - 1 - exponent) as usize;
+                format!("{magnitude:.decimals$}")
+            };
+            if !spec.alternate && body.contains('.') {
+                let split = body.find(['e', 'E']).unwrap_or(body.len());
+                let (mantissa, exponent) = body.split_at(split);
+                let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+                body = format!("{mantissa}{exponent}");
+            }
+            body
+        }
+        _ => {
+            let mut body = format!("{magnitude:.precision$}");
+            if spec.alternate && precision == 0 {
+                body.push('.');
+            }
+            body
+        }
+    };
+    pad(out, spec, sign(spec, negative), &body, spec.zero);
+}
+
+/// Parses the conversion specification after a `%`, reading `*` widths
+/// and precisions from the arguments. `None` means an invalid one.
+fn parse_spec(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    printer: &mut Printer,
+) -> Option<Spec> {
+    let mut spec = Spec::default();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '-' => spec.left = true,
+            '+' => spec.plus = true,
+            ' ' => spec.space = true,
+            '#' => spec.alternate = true,
+            '0' => spec.zero = true,
+            _ => break,
+        }
+        chars.next();
+    }
+    let number = |chars: &mut std::iter::Peekable<std::str::Chars>, printer: &mut Printer| {
+        if chars.next_if_eq(&'*').is_some() {
+            return Some(printer.integer());
+        }
+        let mut digits = String::new();
+        while let Some(c) = chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+        }
+        digits.parse::<i128>().ok()
+    };
+    if let Some(width) = number(chars, printer) {
+        if width < 0 {
+            spec.left = true;
+        }
+        spec.width = width.unsigned_abs().min(1 << 20) as usize;
+    }
+    if chars.next_if_eq(&'.').is_some() {
+        // A negative precision counts as none.
+        let precision = number(chars, printer).unwrap_or(0);
+        spec.precision = (precision >= 0).then(|| precision.min(1 << 20) as usize);
+    }
+    // Length modifiers make no difference here.
+    while chars.next_if(|c| "hlLqjzt".contains(*c)).is_some() {}
+    spec.conversion = chars.next()?;
+    Some(spec)
+}
+
+/// Formats `format` once, consuming arguments from `printer`. Returns
+/// false once `\c` has ended the output.
+fn format_once(format: &str, printer: &mut Printer, out: &mut Vec<u8>) -> Result<bool, String> {
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if !escape(&mut chars, out, false) {
+                    return Ok(false);
+                }
+            }
+            '%' if chars.next_if_eq(&'%').is_some() => out.push(b'%'),
+            '%' => {
+                let start = format.len() - chars.clone().map(char::len_utf8).sum::<usize>();
+                let spec = parse_spec(&mut chars, printer);
+                let spec = match spec {
+                    Some(spec) if "diouxXfFeEgGcsb".contains(spec.conversion) => spec,
+                    _ => {
+                        let end = format.len() - chars.map(char::len_utf8).sum::<usize>();
+                        let directive = &format[start - 1..end];
+                        return Err(format!(
+                            "printf: {directive}: invalid conversion specification"
+                        ));
+                    }
+                };
+                match spec.conversion {
+                    'd' | 'i' | 'o' | 'u' | 'x' | 'X' => {
+                        let value = printer.integer();
+                        format_integer(out, &spec, value);
+                    }
+                    'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                        let value = printer.float();
+                        format_float(out, &spec, value);
+                    }
+                    'c' => {
+                        let arg = printer.arg().unwrap_or_default();
+                        let body: String = arg.chars().take(1).collect();
+                        pad(out, &spec, "", &body, false);
+                    }
+                    's' => {
+                        let arg = printer.arg().unwrap_or_default();
+                        let body: String = match spec.precision {
+                            Some(precision) => arg.chars().take(precision).collect(),
+                            None => arg.to_string(),
+                        };
+                        pad(out, &spec, "", &body, false);
+                    }
+                    _ => {
+                        let arg = printer.arg().unwrap_or_default().to_string();
+                        let mut expanded = Vec::new();
+                        let mut chars = arg.chars().peekable();
+                        let mut more = true;
+                        while let Some(c) = chars.next() {
+                            match c {
+                                '\\' => {
+                                    more = escape(&mut chars, &mut expanded, true);
+                                    if !more {
+                                        break;
+                                    }
+                                }
+                                c => push_char(&mut expanded, c),
+                            }
+                        }
+                        if let Some(precision) = spec.precision {
+                            expanded.truncate(precision);
+                        }
+                        let fill = spec.width.saturating_sub(expanded.len());
+                        if !spec.left {
+                            out.extend(std::iter::repeat_n(b' ', fill));
+                        }
+                        out.extend_from_slice(&expanded);
+                        if spec.left {
+                            out.extend(std::iter::repeat_n(b' ', fill));
+                        }
+                        if !more {
+                            return Ok(false);
+                        }
+                    }
+                }
+            }
+            c => push_char(out, c),
+        }
+    }
+    Ok(true)
+}
+
+/// Formats `format` with `args`, reusing the format while arguments
+/// remain. Returns the output and any warnings about bad numbers.
+/// `seq -f` formats its numbers with this too.
+pub(crate) fn format(format: &str, args: &[String]) -> Result<(Vec<u8>, Vec<String>), String> {
+    let mut printer = Printer {
+        args,
+        next: 0,
+        messages: Vec::new(),
+    };
+    let mut out = Vec::new();
+    loop {
+        let start = printer.next;
+        if !format_once(format, &mut printer, &mut out)? {
+            break;
+        }
+        if printer.next == start || printer.next >= args.len() {
+            break;
+        }
+    }
+    Ok((out, printer.messages))
+}
+
+pub fn run_bytes(args: &str, _stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let mut words = args::split(args).map_err(|e| format!("printf: {e}"))?;
+    if words.first().is_some_and(|w| w == "--") {
+        words.remove(0);
+    }
+    if words.is_empty() {
+        return Err("printf: missing operand".into());
+    }
+    let (output, messages) = format(&words[0], &words[1..])?;
+    if messages.is_empty() {
+        return Ok(output);
+    }
+    let mut output = output;
+    output.extend_from_slice(messages.join("\n").as_bytes());
+    Err(String::from_utf8_lossy(&output).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("printf", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn cmd(args: &str) -> Result<String, String> {
+        run(args, None)
+    }
+
+    #[test]
+    fn strings_and_escapes() {
+        assert_eq!(cmd(r"'%s-%s\n' a b c").unwrap(), "a-b\nc-\n");
+        assert_eq!(
+            cmd(r"'[%5s][%-5s][%.2s]\n' ab cd xyz").unwrap(),
+            "[   ab][cd   ][xy]\n"
+        );
+        assert_eq!(cmd(r"'\t\x41\101é\\%%'").unwrap(), "\tAAé\\%");
+        assert_eq!(cmd(r"'%b|%s\n' 'a\tb' 'a\tb'").unwrap(), "a\tb|a\\tb\n");
+        assert_eq!(cmd(r"'x%by\n' 'stop\cignored'").unwrap(), "xstop");
+        assert_eq!(run_bytes(r"'\0377\377'", None).unwrap(), b"\x1f7\xff");
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            cmd("'%d %i %5d|%-5d|%05d\\n' 42 -7 3 3 -3").unwrap(),
+            "42 -7     3|3    |-0003\n"
+        );
+        assert_eq!(
+            cmd("'%x %X %o %#x %#o %u\\n' 255 255 8 255 8 -1").unwrap(),
+            "ff FF 10 0xff 010 18446744073709551615\n"
+        );
+        assert_eq!(
+            cmd("'%+d % d %.3d %*d\\n' 5 5 7 4 1").unwrap(),
+            "+5  5 007    1\n"
+        );
+        assert_eq!(cmd("'%d %d %d\\n' 0x10 010 \"'A\"").unwrap(), "16 8 65\n");
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(
+            cmd("'%f %.2f %8.3f %-8.1f|\\n' 3.14159 2.5 1 -1").unwrap(),
+            "3.141590 2.50    1.000 -1.0    |\n"
+        );
+        assert_eq!(
+            cmd("'%e %E %.0e\\n' 1234.5 0.000123 5").unwrap(),
+            "1.234500e+03 1.230000E-04 5e+00\n"
+        );
+        assert_eq!(
+            cmd("'%g %g %g %g %G\\n' 100000 1000000 0.0001 0.00001 1e-10").unwrap(),
+            "100000 1e+06 0.0001 1e-05 1E-10\n"
+        );
+        assert_eq!(
+            cmd("'%g %#g %.3g\\n' 2.5 2.5 3.14159").unwrap(),
+            "2.5 2.50000 3.14\n"
+        );
+        assert_eq!(cmd("'%f %F\\n' inf -inf").unwrap(), "inf -INF\n");
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert_eq!(
+            cmd("'%d|%d\\n' abc 12x").unwrap_err(),
+            "0|12\nprintf: 'abc': expected a numeric value\nprintf: '12x': value not completely converted"
+        );
+        assert_eq!(
+            cmd("'%z'").unwrap_err(),
+            "printf: %z: invalid conversion specification"
+        );
+        assert_eq!(cmd("").unwrap_err(), "printf: missing operand");
+        // Without conversions the format is printed once.
+        assert_eq!(cmd("hi x y").unwrap(), "hi");
+    }
+}
@@ -0,0 +1,62 @@
+use crate::args;
+use crate::fs_ops;
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let paths = args::operands("rev", args)?;
+    let input = if paths.is_empty() {
+        stdin.unwrap_or_default()
+    } else {
+        let mut combined = String::new();
+        for path in &paths {
+            let content = fs_ops::read_to_string(path).map_err(|e| format!("rev: {path}: {e}"))?;
+            combined.push_str(&content);
+        }
+        combined
+    };
+
+    let mut output = String::new();
+    for line in input.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        output.extend(text.chars().rev());
+        output.push_str(newline);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn reverses_each_line() {
+        assert_eq!(
+            cmd("", Some("abc\nhello world\n")).unwrap(),
+            "cba\ndlrow olleh\n"
+        );
+    }
+
+    #[test]
+    fn keeps_missing_final_newline_and_unicode() {
+        assert_eq!(cmd("", Some("a\nnaïve")).unwrap(), "a\nevïan");
+    }
+
+    #[test]
+    fn files() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("f.txt");
+        std::fs::write(&p, "12\n34\n").unwrap();
+        assert_eq!(cmd(&p.display().to_string(), None).unwrap(), "21\n43\n");
+        assert!(
+            cmd("/no/such/file", None)
+                .unwrap_err()
+                .starts_with("rev: /no/such/file:")
+        );
+    }
+}
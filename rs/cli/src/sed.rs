@@ -1,92 +1,751 @@
+//! sed implementation: `-e` expressions and `;`/newline separated scripts,
+//! line, `$` and regex addresses with ranges (`1,5`, `/a/,/b/`, `3,+2`),
+//! `!` negation, `{}` blocks and the `s`, `d`, `p`, `a`, `i`, `c`, `y`,
+//! `q` and `=` commands. Regexes are POSIX basic by default, extended
+//! with `-E`; replacements support `&` and `\1`..`\9`.
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 
 struct Opts {
     in_place: bool,
-    expression: String,
+    quiet: bool,
+    extended: bool,
+    script: String,
     paths: Vec<String>,
 }
 
 fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut in_place = false;
+    let mut quiet = false;
+    let mut extended = false;
+    let mut expressions = Vec::new();
     let mut positional = Vec::new();
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        let token = tokens[i];
-        if token == "-i" {
-            in_place = true;
-        } else if token.starts_with('-') && token.len() > 1 {
-            for c in token[1..].chars() {
-                match c {
-                    'i' => in_place = true,
-                    _ => {}
-                }
-            }
-        } else {
-            positional.push(token.to_string());
+    let mut parser = Parser::new("sed", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('i') => in_place = true,
+            Arg::Short('n') => quiet = true,
+            Arg::Short('E' | 'r') => extended = true,
+            Arg::Short('e') => expressions.push(parser.value()?),
+            Arg::Long(l) => match l.as_str() {
+                "in-place" => in_place = true,
+                "quiet" | "silent" => quiet = true,
+                "regexp-extended" => extended = true,
+                "expression" => expressions.push(parser.value()?),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(value) => positional.push(value),
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
-    if positional.is_empty() {
-        return Err("sed: missing expression".into());
+    if expressions.is_empty() {
+        if positional.is_empty() {
+            return Err("sed: missing expression".into());
+        }
+        expressions.push(positional.remove(0));
     }
-    let expression = positional.remove(0);
     Ok(Opts {
         in_place,
-        expression,
+        quiet,
+        extended,
+        script: expressions.join("\n"),
         paths: positional,
     })
 }
 
+enum Addr {
+    Line(usize),
+    Last,
+    Regex(Regex),
+}
+
+enum RangeEnd {
+    Addr(Addr),
+    /// `addr1,+N`: the N lines after the first match.
+    Relative(usize),
+}
+
+struct Address {
+    start: Addr,
+    end: Option<RangeEnd>,
+    negate: bool,
+}
+
+enum Part {
+    Literal(String),
+    /// `&` (group 0) or `\1`..`\9`.
+    Group(usize),
+}
+
 struct Sub {
     re: Regex,
-    replacement: String,
+    replacement: Vec<Part>,
     global: bool,
+    /// Replace only from the Nth match on.
+    nth: usize,
+    print: bool,
 }
 
-fn parse_sub(expr: &str) -> Result<Sub, String> {
-    // s/pattern/replacement/flags
-    if !expr.starts_with('s') || expr.len() < 4 {
-        return Err(format!("sed: unsupported expression: {expr}"));
-    }
-    let delim = expr.as_bytes()[1] as char;
-    let rest = &expr[2..];
-    let parts: Vec<&str> = rest.splitn(3, delim).collect();
-    if parts.len() < 2 {
-        return Err(format!("sed: invalid substitution: {expr}"));
-    }
-    let pattern = parts[0];
-    let replacement = parts[1];
-    let flags = if parts.len() > 2 { parts[2] } else { "" };
-    let global = flags.contains('g');
-    let case_insensitive = flags.contains('i') || flags.contains('I');
-
-    let re = regex::RegexBuilder::new(pattern)
-        .case_insensitive(case_insensitive)
-        .build()
-        .map_err(|e| format!("sed: invalid pattern: {e}"))?;
-
-    Ok(Sub {
-        re,
-        replacement: replacement.to_string(),
-        global,
-    })
+enum Kind {
+    /// `{`, with the index of the command after the matching `}`.
+    Block(usize),
+    EndBlock,
+    Subst(Box<Sub>),
+    Delete,
+    Print,
+    Append(String),
+    Insert(String),
+    Change(String),
+    Translate(Vec<(char, char)>),
+    Quit(i32),
+    LineNumber,
 }
 
-fn apply(text: &str, sub: &Sub) -> String {
-    let mut output = String::new();
-    for line in text.lines() {
-        let replaced = if sub.global {
-            sub.re.replace_all(line, sub.replacement.as_str())
-        } else {
-            sub.re.replace(line, sub.replacement.as_str())
+struct Command {
+    address: Option<Address>,
+    kind: Kind,
+}
+
+struct ScriptParser {
+    chars: Vec<char>,
+    pos: usize,
+    extended: bool,
+    last_regex: Option<Regex>,
+}
+
+impl ScriptParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Command>, String> {
+        let mut commands: Vec<Command> = Vec::new();
+        let mut blocks = Vec::new();
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\n' | ';')) {
+                self.pos += 1;
+            }
+            let Some(c) = self.peek() else {
+                break;
+            };
+            if c == '#' {
+                while !matches!(self.next(), Some('\n') | None) {}
+                continue;
+            }
+            if c == '}' {
+                self.pos += 1;
+                let start: usize = blocks.pop().ok_or("sed: unexpected `}'")?;
+                commands.push(Command {
+                    address: None,
+                    kind: Kind::EndBlock,
+                });
+                commands[start].kind = Kind::Block(commands.len());
+                self.end_command()?;
+                continue;
+            }
+            let address = self.parse_address()?;
+            self.skip_spaces();
+            let c = self.next().ok_or("sed: missing command")?;
+            let kind = match c {
+                '{' => {
+                    blocks.push(commands.len());
+                    commands.push(Command {
+                        address,
+                        kind: Kind::Block(0),
+                    });
+                    continue;
+                }
+                's' => Kind::Subst(Box::new(self.parse_sub()?)),
+                'y' => Kind::Translate(self.parse_translate()?),
+                'd' => Kind::Delete,
+                'p' => Kind::Print,
+                '=' => Kind::LineNumber,
+                'a' => Kind::Append(self.parse_text()),
+                'i' => Kind::Insert(self.parse_text()),
+                'c' => Kind::Change(self.parse_text()),
+                'q' => {
+                    self.skip_spaces();
+                    let mut code = String::new();
+                    while let Some(d) = self.peek().filter(char::is_ascii_digit) {
+                        code.push(d);
+                        self.pos += 1;
+                    }
+                    Kind::Quit(code.parse().unwrap_or(0))
+                }
+                c => return Err(format!("sed: unsupported command: {c}")),
+            };
+            commands.push(Command { address, kind });
+            self.end_command()?;
+        }
+        if !blocks.is_empty() {
+            return Err("sed: unmatched `{'".into());
+        }
+        Ok(commands)
+    }
+
+    /// Checks that a command is followed by a separator.
+    fn end_command(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        match self.peek() {
+            None | Some(';' | '\n' | '}' | '#') => Ok(()),
+            Some(c) => Err(format!("sed: extra characters after command: {c}")),
+        }
+    }
+
+    fn parse_address(&mut self) -> Result<Option<Address>, String> {
+        let Some(start) = self.parse_addr()? else {
+            self.skip_spaces();
+            if self.peek() == Some('!') {
+                return Err("sed: missing address before `!'".into());
+            }
+            return Ok(None);
+        };
+        let mut end = None;
+        if self.peek() == Some(',') {
+            self.pos += 1;
+            self.skip_spaces();
+            if self.peek() == Some('+') {
+                self.pos += 1;
+                end = Some(RangeEnd::Relative(self.parse_number()?));
+            } else {
+                let addr = self.parse_addr()?.ok_or("sed: unexpected `,'")?;
+                end = Some(RangeEnd::Addr(addr));
+            }
+        }
+        self.skip_spaces();
+        let mut negate = false;
+        while self.peek() == Some('!') {
+            self.pos += 1;
+            negate = true;
+            self.skip_spaces();
+        }
+        Ok(Some(Address { start, end, negate }))
+    }
+
+    fn parse_number(&mut self) -> Result<usize, String> {
+        let mut digits = String::new();
+        while let Some(d) = self.peek().filter(char::is_ascii_digit) {
+            digits.push(d);
+            self.pos += 1;
+        }
+        digits
+            .parse()
+            .map_err(|_| "sed: expected a line number".to_string())
+    }
+
+    fn parse_addr(&mut self) -> Result<Option<Addr>, String> {
+        let delim = match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let line = self.parse_number()?;
+                if line == 0 {
+                    return Err("sed: invalid usage of line address 0".into());
+                }
+                return Ok(Some(Addr::Line(line)));
+            }
+            Some('$') => {
+                self.pos += 1;
+                return Ok(Some(Addr::Last));
+            }
+            Some('/') => '/',
+            Some('\\') => {
+                self.pos += 1;
+                self.next().ok_or("sed: unexpected end of script")?
+            }
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        let pattern = self.read_regex(delim, "unterminated address regex")?;
+        let mut case_insensitive = false;
+        while matches!(self.peek(), Some('I')) {
+            self.pos += 1;
+            case_insensitive = true;
+        }
+        Ok(Some(Addr::Regex(self.compile(&pattern, case_insensitive)?)))
+    }
+
+    /// Compiles a translated pattern; an empty pattern reuses the last regex.
+    fn compile(&mut self, pattern: &str, case_insensitive: bool) -> Result<Regex, String> {
+        if pattern.is_empty() {
+            return self
+                .last_regex
+                .clone()
+                .ok_or_else(|| "sed: no previous regular expression".into());
+        }
+        let re = RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| format!("sed: invalid pattern: {e}"))?;
+        self.last_regex = Some(re.clone());
+        Ok(re)
+    }
+
+    /// Reads a regex up to `delim` and translates it from POSIX basic (or
+    /// extended) syntax into `regex` crate syntax.
+    fn read_regex(&mut self, delim: char, unterminated: &str) -> Result<String, String> {
+        let mut out = String::new();
+        // At the start of the regex or a group, where `*` is literal in BRE.
+        let mut at_start = true;
+        loop {
+            let c = self.next().ok_or_else(|| format!("sed: {unterminated}"))?;
+            if c == delim {
+                return Ok(out);
+            }
+            let was_start = at_start;
+            at_start = false;
+            match c {
+                '\\' => {
+                    let e = self.next().ok_or_else(|| format!("sed: {unterminated}"))?;
+                    match e {
+                        e if e == delim => out.push_str(&regex::escape(&e.to_string())),
+                        'n' => out.push_str("\\n"),
+                        't' => out.push_str("\\t"),
+                        '1'..='9' => {
+                            return Err("sed: backreferences in patterns are not supported".into());
+                        }
+                        '(' | '|' if !self.extended => {
+                            out.push(e);
+                            at_start = true;
+                        }
+                        ')' | '{' | '}' | '+' | '?' if !self.extended => out.push(e),
+                        'w' | 'W' | 's' | 'S' | 'b' | 'B' | '<' | '>' => {
+                            out.push('\\');
+                            out.push(e);
+                        }
+                        '`' => out.push_str("\\A"),
+                        '\'' => out.push_str("\\z"),
+                        e => out.push_str(&regex::escape(&e.to_string())),
+                    }
+                }
+                '[' => self.read_bracket(delim, &mut out)?,
+                '(' | ')' | '{' | '}' | '|' | '+' | '?' if !self.extended => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                '(' | '|' => {
+                    out.push(c);
+                    at_start = true;
+                }
+                '*' if was_start => out.push_str("\\*"),
+                '^' if was_start => {
+                    out.push('^');
+                    at_start = true;
+                }
+                '^' if !self.extended => out.push_str("\\^"),
+                '$' if !self.extended && !self.at_regex_end(delim) => out.push_str("\\$"),
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Whether a BRE `$` here is an anchor: at the end of the regex or a group.
+    fn at_regex_end(&self, delim: char) -> bool {
+        match self.peek() {
+            Some(c) if c == delim => true,
+            Some('\\') => self.chars.get(self.pos + 1) == Some(&')'),
+            _ => false,
+        }
+    }
+
+    fn read_bracket(&mut self, delim: char, out: &mut String) -> Result<(), String> {
+        let unmatched = || "sed: invalid pattern: unmatched [".to_string();
+        out.push('[');
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            out.push('^');
+        }
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            out.push_str("\\]");
+        }
+        loop {
+            let c = self.next().ok_or_else(unmatched)?;
+            match c {
+                ']' => {
+                    out.push(']');
+                    return Ok(());
+                }
+                '[' if matches!(self.peek(), Some(':' | '=' | '.')) => {
+                    let kind = self.next().ok_or_else(unmatched)?;
+                    out.push('[');
+                    out.push(kind);
+                    loop {
+                        let c = self.next().ok_or_else(unmatched)?;
+                        out.push(c);
+                        if c == kind && self.peek() == Some(']') {
+                            self.pos += 1;
+                            out.push(']');
+                            break;
+                        }
+                    }
+                }
+                '\\' => match self.peek() {
+                    Some('n') => {
+                        self.pos += 1;
+                        out.push_str("\\n");
+                    }
+                    Some('t') => {
+                        self.pos += 1;
+                        out.push_str("\\t");
+                    }
+                    Some('\\') => {
+                        self.pos += 1;
+                        out.push_str("\\\\");
+                    }
+                    Some(c) if c == delim => {
+                        self.pos += 1;
+                        out.push_str(&regex::escape(&c.to_string()));
+                    }
+                    _ => out.push_str("\\\\"),
+                },
+                '[' | '&' | '~' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn parse_sub(&mut self) -> Result<Sub, String> {
+        let delim = match self.next() {
+            Some(c) if c != '\\' && c != '\n' => c,
+            _ => return Err("sed: unterminated `s' command".into()),
+        };
+        let pattern = self.read_regex(delim, "unterminated `s' command")?;
+        let replacement = self.read_replacement(delim)?;
+        let mut global = false;
+        let mut nth = 1;
+        let mut print = false;
+        let mut case_insensitive = false;
+        loop {
+            match self.peek() {
+                Some('g') => global = true,
+                Some('p') => print = true,
+                Some('i' | 'I') => case_insensitive = true,
+                Some(c) if c.is_ascii_digit() => {
+                    nth = self.parse_number()?;
+                    if nth == 0 {
+                        return Err("sed: number option to `s' command may not be zero".into());
+                    }
+                    continue;
+                }
+                Some(c @ ('w' | 'e' | 'm' | 'M')) => {
+                    return Err(format!("sed: unsupported `s' flag: {c}"));
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let re = self.compile(&pattern, case_insensitive)?;
+        let max_group = replacement.iter().filter_map(|part| match part {
+            Part::Group(n) => Some(*n),
+            Part::Literal(_) => None,
+        });
+        if let Some(n) = max_group.max().filter(|n| *n >= re.captures_len()) {
+            return Err(format!("sed: invalid reference \\{n} on `s' command's RHS"));
+        }
+        Ok(Sub {
+            re,
+            replacement,
+            global,
+            nth,
+            print,
+        })
+    }
+
+    fn read_replacement(&mut self, delim: char) -> Result<Vec<Part>, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        loop {
+            let c = self.next().ok_or("sed: unterminated `s' command")?;
+            match c {
+                c if c == delim => break,
+                '&' => {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    parts.push(Part::Group(0));
+                }
+                '\\' => match self.next() {
+                    Some(d @ '0'..='9') => {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                        parts.push(Part::Group(d as usize - '0' as usize));
+                    }
+                    Some('n') => literal.push('\n'),
+                    Some('t') => literal.push('\t'),
+                    Some(other) => literal.push(other),
+                    None => return Err("sed: unterminated `s' command".into()),
+                },
+                c => literal.push(c),
+            }
+        }
+        parts.push(Part::Literal(literal));
+        parts.retain(|part| !matches!(part, Part::Literal(s) if s.is_empty()));
+        Ok(parts)
+    }
+
+    fn parse_translate(&mut self) -> Result<Vec<(char, char)>, String> {
+        let delim = self.next().ok_or("sed: unterminated `y' command")?;
+        let read = |parser: &mut Self| -> Result<Vec<char>, String> {
+            let mut chars = Vec::new();
+            loop {
+                match parser.next().ok_or("sed: unterminated `y' command")? {
+                    c if c == delim => return Ok(chars),
+                    '\\' => match parser.next() {
+                        Some('n') => chars.push('\n'),
+                        Some('t') => chars.push('\t'),
+                        Some(c) => chars.push(c),
+                        None => return Err("sed: unterminated `y' command".into()),
+                    },
+                    c => chars.push(c),
+                }
+            }
         };
-        output.push_str(&replaced);
-        output.push('\n');
+        let from = read(self)?;
+        let to = read(self)?;
+        if from.len() != to.len() {
+            return Err("sed: strings for `y' command are different lengths".into());
+        }
+        Ok(from.into_iter().zip(to).collect())
     }
+
+    /// Reads the text of `a`, `i` or `c`: either `a text` or `a\` followed
+    /// by lines, where a trailing backslash continues onto the next line.
+    fn parse_text(&mut self) -> String {
+        self.skip_spaces();
+        if self.peek() == Some('\\') {
+            self.pos += 1;
+            if self.peek() == Some('\n') {
+                self.pos += 1;
+            }
+        }
+        let mut text = String::new();
+        while let Some(c) = self.peek().filter(|c| *c != '\n') {
+            self.pos += 1;
+            match c {
+                '\\' => match self.next() {
+                    Some('t') => text.push('\t'),
+                    Some(c) => text.push(c),
+                    None => break,
+                },
+                c => text.push(c),
+            }
+        }
+        text
+    }
+}
+
+fn parse_script(script: &str, extended: bool) -> Result<Vec<Command>, String> {
+    let parser = ScriptParser {
+        chars: script.chars().collect(),
+        pos: 0,
+        extended,
+        last_regex: None,
+    };
+    parser.parse()
+}
+
+impl Sub {
+    /// Returns the substituted text, or `None` if nothing was replaced.
+    fn apply(&self, text: &str) -> Option<String> {
+        let mut out = String::new();
+        let mut last = 0;
+        let mut replaced = false;
+        for (i, caps) in self.re.captures_iter(text).enumerate() {
+            if i + 1 < self.nth {
+                continue;
+            }
+            let m = caps.get(0)?;
+            out.push_str(&text[last..m.start()]);
+            for part in &self.replacement {
+                match part {
+                    Part::Literal(s) => out.push_str(s),
+                    Part::Group(n) => out.push_str(caps.get(*n).map_or("", |g| g.as_str())),
+                }
+            }
+            last = m.end();
+            replaced = true;
+            if !self.global {
+                break;
+            }
+        }
+        if !replaced {
+            return None;
+        }
+        out.push_str(&text[last..]);
+        Some(out)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct RangeState {
+    active: bool,
+    /// Last line of an `addr1,+N` range.
+    end_line: usize,
+}
+
+struct Editor<'a> {
+    commands: &'a [Command],
+    quiet: bool,
+    ranges: Vec<RangeState>,
+    line: usize,
+    quit: Option<i32>,
+}
+
+impl<'a> Editor<'a> {
+    fn new(commands: &'a [Command], quiet: bool) -> Self {
+        Self {
+            commands,
+            quiet,
+            ranges: vec![RangeState::default(); commands.len()],
+            line: 0,
+            quit: None,
+        }
+    }
+
+    fn addr_matches(&self, addr: &Addr, text: &str, last: bool) -> bool {
+        match addr {
+            Addr::Line(n) => self.line == *n,
+            Addr::Last => last,
+            Addr::Regex(re) => re.is_match(text),
+        }
+    }
+
+    fn selected(&mut self, index: usize, text: &str, last: bool) -> bool {
+        let Some(address) = &self.commands[index].address else {
+            return true;
+        };
+        let matched = match &address.end {
+            None => self.addr_matches(&address.start, text, last),
+            Some(end) => {
+                let mut state = self.ranges[index];
+                let matched = if state.active {
+                    state.active = match end {
+                        RangeEnd::Addr(Addr::Line(n)) => self.line < *n,
+                        RangeEnd::Addr(addr) => !self.addr_matches(addr, text, last),
+                        RangeEnd::Relative(_) => self.line < state.end_line,
+                    };
+                    true
+                } else if self.addr_matches(&address.start, text, last) {
+                    state.active = match end {
+                        RangeEnd::Addr(Addr::Line(n)) => self.line < *n,
+                        RangeEnd::Addr(_) => !last,
+                        RangeEnd::Relative(n) => {
+                            state.end_line = self.line + n;
+                            *n > 0
+                        }
+                    };
+                    true
+                } else {
+                    false
+                };
+                self.ranges[index] = state;
+                matched
+            }
+        };
+        matched != address.negate
+    }
+
+    /// Runs the script over `lines`, appending to `output`.
+    fn run(&mut self, lines: &[&str], output: &mut String) {
+        for (i, line) in lines.iter().enumerate() {
+            self.line += 1;
+            let last = i + 1 == lines.len();
+            let mut space = line.to_string();
+            let mut appended = String::new();
+            let mut autoprint = !self.quiet;
+            let mut pc = 0;
+            while pc < self.commands.len() {
+                let command = &self.commands[pc];
+                if !self.selected(pc, &space, last) {
+                    pc = match command.kind {
+                        Kind::Block(end) => end,
+                        _ => pc + 1,
+                    };
+                    continue;
+                }
+                match &command.kind {
+                    Kind::Block(_) | Kind::EndBlock => {}
+                    Kind::Subst(sub) => {
+                        if let Some(result) = sub.apply(&space) {
+                            space = result;
+                            if sub.print {
+                                output.push_str(&space);
+                                output.push('\n');
+                            }
+                        }
+                    }
+                    Kind::Delete => {
+                        autoprint = false;
+                        break;
+                    }
+                    Kind::Print => {
+                        output.push_str(&space);
+                        output.push('\n');
+                    }
+                    Kind::Append(text) => {
+                        appended.push_str(text);
+                        appended.push('\n');
+                    }
+                    Kind::Insert(text) => {
+                        output.push_str(text);
+                        output.push('\n');
+                    }
+                    Kind::Change(text) => {
+                        // A range is replaced as a whole, at its last line.
+                        let negate = command.address.as_ref().is_some_and(|a| a.negate);
+                        if negate || !self.ranges[pc].active {
+                            output.push_str(text);
+                            output.push('\n');
+                        }
+                        autoprint = false;
+                        break;
+                    }
+                    Kind::Translate(map) => {
+                        space = space
+                            .chars()
+                            .map(|c| map.iter().find(|(from, _)| *from == c).map_or(c, |m| m.1))
+                            .collect();
+                    }
+                    Kind::Quit(code) => {
+                        self.quit = Some(*code);
+                        break;
+                    }
+                    Kind::LineNumber => {
+                        output.push_str(&format!("{}\n", self.line));
+                    }
+                }
+                pc += 1;
+            }
+            if autoprint {
+                output.push_str(&space);
+                output.push('\n');
+            }
+            output.push_str(&appended);
+            if self.quit.is_some() {
+                return;
+            }
+        }
+    }
+}
+
+fn edit(editor: &mut Editor, text: &str) -> String {
+    let lines: Vec<&str> = text.split_terminator('\n').collect();
+    let mut output = String::new();
+    editor.run(&lines, &mut output);
     // Preserve missing trailing newline.
-    if !text.ends_with('\n') && output.ends_with('\n') {
+    if !text.is_empty() && !text.ends_with('\n') && output.ends_with('\n') {
         output.pop();
     }
     output
@@ -94,26 +753,38 @@ fn apply(text: &str, sub: &Sub) -> String {
 
 pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
     let opts = parse_opts(args)?;
-    let sub = parse_sub(&opts.expression)?;
+    let commands = parse_script(&opts.script, opts.extended)?;
+    let mut editor = Editor::new(&commands, opts.quiet);
 
-    if opts.paths.is_empty() {
-        let input = stdin.unwrap_or_default();
-        return Ok(apply(&input, &sub));
-    }
-
-    let mut output = String::new();
-    for path in &opts.paths {
-        let content = fs_ops::read_to_string(path)
-            .map_err(|e| format!("sed: {path}: {e}"))?;
-        let result = apply(&content, &sub);
-        if opts.in_place {
-            fs_ops::write(path, result.as_bytes())
-                .map_err(|e| format!("sed: {path}: {e}"))?;
-        } else {
-            output.push_str(&result);
+    let output = if opts.paths.is_empty() {
+        edit(&mut editor, &stdin.unwrap_or_default())
+    } else if opts.in_place {
+        for path in &opts.paths {
+            let content = fs_ops::read_to_string(path).map_err(|e| format!("sed: {path}: {e}"))?;
+            // Each file is edited separately, with its own line numbers.
+            editor = Editor::new(&commands, opts.quiet);
+            let result = edit(&mut editor, &content);
+            fs_ops::write(path, result.as_bytes()).map_err(|e| format!("sed: {path}: {e}"))?;
+            if editor.quit.is_some() {
+                break;
+            }
         }
+        String::new()
+    } else {
+        let mut input = String::new();
+        for path in &opts.paths {
+            let content = fs_ops::read_to_string(path).map_err(|e| format!("sed: {path}: {e}"))?;
+            if !input.is_empty() && !input.ends_with('\n') {
+                input.push('\n');
+            }
+            input.push_str(&content);
+        }
+        edit(&mut editor, &input)
+    };
+    match editor.quit {
+        Some(code) if code != 0 => Err(format!("{output}sed: exit status {code}")),
+        _ => Ok(output),
     }
-    Ok(output)
 }
 
 #[cfg(test)]
@@ -168,7 +839,7 @@ mod tests {
 
     #[test]
     fn regex_pattern() {
-        let out = cmd("s/[0-9]+/NUM/g", Some("abc 123 def 456")).unwrap();
+        let out = cmd("-E s/[0-9]+/NUM/g", Some("abc 123 def 456")).unwrap();
         assert_eq!(out, "abc NUM def NUM");
     }
 
@@ -224,7 +895,7 @@ mod tests {
 
     #[test]
     fn unsupported_expression() {
-        let err = cmd("d", Some("x")).unwrap_err();
+        let err = cmd("x", Some("x")).unwrap_err();
         assert!(err.contains("unsupported"));
     }
 
@@ -236,7 +907,205 @@ mod tests {
 
     #[test]
     fn capture_group_replacement() {
-        let out = cmd("s/([a-z]+)@([a-z]+)/$2=$1/", Some("foo@bar")).unwrap();
+        let out = cmd(r"-E 's/([a-z]+)@([a-z]+)/\2=\1/'", Some("foo@bar")).unwrap();
         assert_eq!(out, "bar=foo");
     }
+
+    #[test]
+    fn quoted_expression_with_spaces() {
+        let out = cmd("'s/foo bar/baz qux/g'", Some("foo bar, foo bar")).unwrap();
+        assert_eq!(out, "baz qux, baz qux");
+    }
+
+    #[test]
+    fn basic_regex_groups_and_ampersand() {
+        let out = cmd(r"'s/\(foo\)-\(bar\)/[\2 \1] &/'", Some("foo-bar")).unwrap();
+        assert_eq!(out, "[bar foo] foo-bar");
+    }
+
+    #[test]
+    fn basic_regex_literal_operators() {
+        let out = cmd("'s/a+b?(c)/x/'", Some("a+b?(c) aabc")).unwrap();
+        assert_eq!(out, "x aabc");
+        let out = cmd(r"'s/a\+/x/'", Some("caaat")).unwrap();
+        assert_eq!(out, "cxt");
+    }
+
+    #[test]
+    fn escaped_ampersand_and_newline() {
+        let out = cmd(r"'s/ /\&\n/g'", Some("a b")).unwrap();
+        assert_eq!(out, "a&\nb");
+    }
+
+    #[test]
+    fn nth_occurrence() {
+        let out = cmd("s/a/x/2", Some("aaaa")).unwrap();
+        assert_eq!(out, "axaa");
+        let out = cmd("s/a/x/2g", Some("aaaa")).unwrap();
+        assert_eq!(out, "axxx");
+    }
+
+    #[test]
+    fn multiple_expressions() {
+        let out = cmd("-e s/a/b/ -e s/b/c/", Some("a\n")).unwrap();
+        assert_eq!(out, "c\n");
+    }
+
+    #[test]
+    fn semicolon_separated_script() {
+        let out = cmd("'s/a/b/; s/c/d/g'", Some("ac cc\n")).unwrap();
+        assert_eq!(out, "bd dd\n");
+    }
+
+    #[test]
+    fn delete_lines() {
+        let out = cmd("2d", Some("1\n2\n3\n")).unwrap();
+        assert_eq!(out, "1\n3\n");
+        let out = cmd("'/^#/d'", Some("# c\nx\n#y\n")).unwrap();
+        assert_eq!(out, "x\n");
+    }
+
+    #[test]
+    fn quiet_print() {
+        let out = cmd("-n '/b/p'", Some("a\nb\nc\nab\n")).unwrap();
+        assert_eq!(out, "b\nab\n");
+        let out = cmd("-n 's/a/x/p'", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "x\n");
+    }
+
+    #[test]
+    fn print_without_quiet_duplicates() {
+        let out = cmd("1p", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "a\na\nb\n");
+    }
+
+    #[test]
+    fn line_ranges() {
+        let out = cmd("-n 2,4p", Some("1\n2\n3\n4\n5\n")).unwrap();
+        assert_eq!(out, "2\n3\n4\n");
+        let out = cmd("'3,$d'", Some("1\n2\n3\n4\n")).unwrap();
+        assert_eq!(out, "1\n2\n");
+        let out = cmd("-n 2,+1p", Some("1\n2\n3\n4\n")).unwrap();
+        assert_eq!(out, "2\n3\n");
+    }
+
+    #[test]
+    fn regex_ranges() {
+        let out = cmd("'/start/,/end/d'", Some("a\nstart\nb\nend\nc\n")).unwrap();
+        assert_eq!(out, "a\nc\n");
+        let out = cmd("-n '/b/,3p'", Some("a\nb\nc\nd\n")).unwrap();
+        assert_eq!(out, "b\nc\n");
+    }
+
+    #[test]
+    fn last_line_address() {
+        let out = cmd("'$s/$/!/'", Some("a\nb\n")).unwrap();
+        assert_eq!(out, "a\nb!\n");
+    }
+
+    #[test]
+    fn negated_address() {
+        let out = cmd("'/keep/!d'", Some("keep 1\ndrop\nkeep 2\n")).unwrap();
+        assert_eq!(out, "keep 1\nkeep 2\n");
+    }
+
+    #[test]
+    fn blocks() {
+        let out = cmd("-n '/x/{s/x/y/;p}'", Some("ax\nb\nxx\n")).unwrap();
+        assert_eq!(out, "ay\nyx\n");
+    }
+
+    #[test]
+    fn append_insert_change() {
+        let out = cmd(
+            "-e '2i before' -e '2a after' -e '$c last'",
+            Some("1\n2\n3\n"),
+        )
+        .unwrap();
+        assert_eq!(out, "1\nbefore\n2\nafter\nlast\n");
+    }
+
+    #[test]
+    fn append_multiline_text() {
+        let out = cmd("'1a\\\nfirst\\\nsecond'", Some("x\n")).unwrap();
+        assert_eq!(out, "x\nfirst\nsecond\n");
+    }
+
+    #[test]
+    fn change_range() {
+        let out = cmd("'2,3c replaced'", Some("1\n2\n3\n4\n")).unwrap();
+        assert_eq!(out, "1\nreplaced\n4\n");
+    }
+
+    #[test]
+    fn translate() {
+        let out = cmd("y/abc/xyz/", Some("aabbcc\n")).unwrap();
+        assert_eq!(out, "xxyyzz\n");
+        let err = cmd("y/ab/x/", Some("a")).unwrap_err();
+        assert!(err.contains("different lengths"));
+    }
+
+    #[test]
+    fn quit() {
+        let out = cmd("2q", Some("1\n2\n3\n")).unwrap();
+        assert_eq!(out, "1\n2\n");
+        let err = cmd("'/b/q 5'", Some("a\nb\nc\n")).unwrap_err();
+        assert_eq!(err, "a\nb\nsed: exit status 5");
+    }
+
+    #[test]
+    fn line_number() {
+        let out = cmd("-n '$='", Some("a\nb\nc\n")).unwrap();
+        assert_eq!(out, "3\n");
+    }
+
+    #[test]
+    fn case_insensitive_address() {
+        let out = cmd("'/abc/Id'", Some("ABC\nx\n")).unwrap();
+        assert_eq!(out, "x\n");
+    }
+
+    #[test]
+    fn custom_address_delimiter() {
+        let out = cmd(r"-n '\,/usr,p'", Some("/usr/bin\n/bin\n")).unwrap();
+        assert_eq!(out, "/usr/bin\n");
+    }
+
+    #[test]
+    fn empty_regex_reuses_last() {
+        let out = cmd("'/a/s//x/g'", Some("banana\nkiwi\n")).unwrap();
+        assert_eq!(out, "bxnxnx\nkiwi\n");
+    }
+
+    #[test]
+    fn anchors_in_basic_regex() {
+        let out = cmd("'s/^a/x/; s/a$/y/; s/b^c/z/'", Some("aba\nb^c\n")).unwrap();
+        assert_eq!(out, "xby\nz\n");
+    }
+
+    #[test]
+    fn invalid_group_reference() {
+        let err = cmd(r"'s/a/\1/'", Some("a")).unwrap_err();
+        assert!(err.contains("invalid reference"));
+    }
+
+    #[test]
+    fn multiple_files_share_line_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "1\n2\n").unwrap();
+        std::fs::write(&b, "3\n4\n").unwrap();
+        let out = cmd(&format!("-n '3p;$p' {} {}", a.display(), b.display()), None).unwrap();
+        assert_eq!(out, "3\n4\n");
+    }
+
+    #[test]
+    fn in_place_preserves_crlf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("f.txt");
+        std::fs::write(&path, "a\r\nb\r\n").unwrap();
+        cmd(&format!("-i 1d {}", path.display()), None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "b\r\n");
+    }
 }
@@ -0,0 +1,263 @@
+use crate::args::{Arg, Parser};
+
+struct Opts {
+    format: Option<String>,
+    separator: String,
+    equal_width: bool,
+    operands: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        format: None,
+        separator: "\n".into(),
+        equal_width: false,
+        operands: Vec::new(),
+    };
+    let mut parser = Parser::new("seq", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('f') => opts.format = Some(parser.value()?),
+            Arg::Short('s') => opts.separator = parser.value()?,
+            Arg::Short('w') => opts.equal_width = true,
+            Arg::Long(l) => match l.as_str() {
+                "format" => opts.format = Some(parser.value()?),
+                "separator" => opts.separator = parser.value()?,
+                "equal-width" => opts.equal_width = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            // Negative operands look like options: `-5` arrives as a
+            // number, `-1.5` as `-1` with `.5` left in the cluster.
+            Arg::Number(n) => opts.operands.push(format!("-{n}")),
+            Arg::Short(c) if c.is_ascii_digit() || c == '.' => {
+                opts.operands.push(format!("-{c}{}", parser.value()?));
+            }
+            Arg::Value(v) => opts.operands.push(v),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.format.is_some() && opts.equal_width {
+        return Err(
+            "seq: format string may not be specified when printing equal width strings".into(),
+        );
+    }
+    Ok(opts)
+}
+
+/// A decimal number as an integer count of `10^-decimals`, so that
+/// stepping by `0.1` does not accumulate rounding errors.
+#[derive(Clone, Copy)]
+struct Decimal {
+    value: i128,
+    decimals: u32,
+}
+
+impl Decimal {
+    fn parse(s: &str) -> Option<Decimal> {
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let plain = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if int.is_empty() && frac.is_empty() || !plain(int) || !plain(frac) {
+            // Exponent forms such as `1e3` go through floating point.
+            let value: f64 = s.parse().ok()?;
+            if !value.is_finite() || s.contains(['i', 'I', 'n', 'N']) {
+                return None;
+            }
+            return Decimal::parse(&value.to_string());
+        }
+        let digits: i128 = format!("0{int}{frac}").parse().ok()?;
+        Some(Decimal {
+            value: if negative { -digits } else { digits },
+            decimals: frac.len() as u32,
+        })
+    }
+
+    /// The value in units of `10^-decimals`, truncating extra decimals.
+    fn scaled(self, decimals: u32) -> i128 {
+        match decimals.checked_sub(self.decimals) {
+            Some(more) => self.value * 10i128.pow(more),
+            None => self.value / 10i128.pow(self.decimals - decimals),
+        }
+    }
+}
+
+/// Formats `value` (in units of `10^-decimals`) as a decimal string.
+fn format_decimal(value: i128, decimals: u32) -> String {
+    let digits = format!(
+        "{:0>width$}",
+        value.unsigned_abs(),
+        width = decimals as usize + 1
+    );
+    let sign = if value < 0 { "-" } else { "" };
+    match decimals {
+        0 => format!("{sign}{digits}"),
+        d => {
+            let (int, frac) = digits.split_at(digits.len() - d as usize);
+            format!("{sign}{int}.{frac}")
+        }
+    }
+}
+
+/// Checks that a `-f` format has exactly one floating point conversion.
+fn check_format(format: &str) -> Result<(), String> {
+    let mut directives = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            continue;
+        }
+        while chars.next_if(|c| "-+ #0'".contains(*c)).is_some() {}
+        while chars.next_if(|c| c.is_ascii_digit() || *c == '.').is_some() {}
+        match chars.next() {
+            Some(c) if "eEfFgGaA".contains(c) => directives += 1,
+            _ => return Err(format!("seq: invalid format string: '{format}'")),
+        }
+    }
+    match directives {
+        0 => Err(format!("seq: format '{format}' has no % directive")),
+        1 => Ok(()),
+        _ => Err(format!("seq: format '{format}' has too many % directives")),
+    }
+}
+
+pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    if let Some(format) = &opts.format {
+        check_format(format)?;
+    }
+    let numbers = opts
+        .operands
+        .iter()
+        .map(|s| {
+            Decimal::parse(s).ok_or_else(|| format!("seq: invalid floating point argument: '{s}'"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let one = Decimal {
+        value: 1,
+        decimals: 0,
+    };
+    let (first, increment, last) = match numbers[..] {
+        [] => return Err("seq: missing operand".into()),
+        [last] => (one, one, last),
+        [first, last] => (first, one, last),
+        [first, increment, last] => (first, increment, last),
+        _ => return Err(format!("seq: extra operand '{}'", opts.operands[3])),
+    };
+    if increment.value == 0 {
+        let operand = &opts.operands[1];
+        return Err(format!("seq: invalid Zero increment value: '{operand}'"));
+    }
+
+    // Output has as many decimals as FIRST and INCREMENT; LAST only
+    // bounds the sequence.
+    let decimals = first.decimals.max(increment.decimals);
+    let scale = decimals.max(last.decimals);
+    let (start, step, end) = (
+        first.scaled(scale),
+        increment.scaled(scale),
+        last.scaled(scale),
+    );
+    let unscale = 10i128.pow(scale - decimals);
+    let width = match opts.equal_width {
+        true => [first, last]
+            .map(|n| format_decimal(n.scaled(decimals), decimals).len())
+            .into_iter()
+            .max()
+            .unwrap_or(0),
+        false => 0,
+    };
+
+    let mut output = String::new();
+    let mut current = start;
+    while (step > 0 && current <= end) || (step < 0 && current >= end) {
+        if current != start {
+            output.push_str(&opts.separator);
+        }
+        let number = format_decimal(current / unscale, decimals);
+        match &opts.format {
+            Some(format) => {
+                let (formatted, _) = crate::printf::format(format, &[number])
+                    .map_err(|e| e.replacen("printf", "seq", 1))?;
+                output.push_str(&String::from_utf8_lossy(&formatted));
+            }
+            None => {
+                let (sign, digits) = match number.strip_prefix('-') {
+                    Some(digits) => ("-", digits),
+                    None => ("", number.as_str()),
+                };
+                let pad = width.saturating_sub(number.len());
+                output.push_str(sign);
+                output.push_str(&"0".repeat(pad));
+                output.push_str(digits);
+            }
+        }
+        current += step;
+    }
+    if current != start {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str) -> Result<String, String> {
+        run(args, None)
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(cmd("3").unwrap(), "1\n2\n3\n");
+        assert_eq!(cmd("2 4").unwrap(), "2\n3\n4\n");
+        assert_eq!(cmd("10 -3 1").unwrap(), "10\n7\n4\n1\n");
+        assert_eq!(cmd("-2 0").unwrap(), "-2\n-1\n0\n");
+        assert_eq!(cmd("5 1").unwrap(), "");
+    }
+
+    #[test]
+    fn decimals_are_exact() {
+        assert_eq!(cmd("0 0.1 0.3").unwrap(), "0.0\n0.1\n0.2\n0.3\n");
+        assert_eq!(cmd("1 0.5 2.25").unwrap(), "1.0\n1.5\n2.0\n");
+        assert_eq!(cmd("-1.5 1").unwrap(), "-1.5\n-0.5\n0.5\n");
+        assert_eq!(cmd("1e1 1e1").unwrap(), "10\n");
+    }
+
+    #[test]
+    fn separator_width_and_format() {
+        assert_eq!(cmd("-s , 3").unwrap(), "1,2,3\n");
+        assert_eq!(cmd("-w 8 10").unwrap(), "08\n09\n10\n");
+        assert_eq!(cmd("-w -1 1").unwrap(), "-1\n00\n01\n");
+        assert_eq!(cmd("-f %03.1f 2").unwrap(), "1.0\n2.0\n");
+        assert_eq!(cmd("-f 'n=%g' 0.5 0.5 1").unwrap(), "n=0.5\nn=1\n");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(cmd("").unwrap_err(), "seq: missing operand");
+        assert_eq!(
+            cmd("1 0 2").unwrap_err(),
+            "seq: invalid Zero increment value: '0'"
+        );
+        assert_eq!(
+            cmd("x").unwrap_err(),
+            "seq: invalid floating point argument: 'x'"
+        );
+        assert_eq!(cmd("1 2 3 4").unwrap_err(), "seq: extra operand '4'");
+        assert_eq!(
+            cmd("-f %d 3").unwrap_err(),
+            "seq: invalid format string: '%d'"
+        );
+        assert_eq!(
+            cmd("-f x 3").unwrap_err(),
+            "seq: format 'x' has no % directive"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::checksum;
+use sha2::{Digest, Sha256};
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    checksum::run_bytes(
+        "sha256sum",
+        |data| Sha256::digest(data).to_vec(),
+        args,
+        stdin,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("sha256sum", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(
+            cmd("", Some("")).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  -\n"
+        );
+        assert_eq!(
+            cmd("", Some("abc")).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  -\n"
+        );
+        // Two padding blocks.
+        let long = "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(
+            cmd("", Some(long)).unwrap(),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1  -\n"
+        );
+    }
+
+    #[test]
+    fn files_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.bin");
+        std::fs::write(&a, "abc").unwrap();
+        std::fs::write(&b, [0xffu8, 0]).unwrap();
+        let (a, b) = (a.display().to_string(), b.display().to_string());
+        let sums = cmd(&format!("{a} {b}"), None).unwrap();
+        assert!(sums.starts_with(&format!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  {a}\n"
+        )));
+        assert_eq!(
+            cmd("-c", Some(&sums)).unwrap(),
+            format!("{a}: OK\n{b}: OK\n")
+        );
+        assert_eq!(cmd("-c --quiet", Some(&sums)).unwrap(), "");
+
+        std::fs::write(&b, "changed").unwrap();
+        let err = cmd("-c", Some(&sums)).unwrap_err();
+        assert_eq!(
+            err,
+            format!("{a}: OK\n{b}: FAILED\nsha256sum: WARNING: 1 computed checksum did NOT match")
+        );
+        assert_eq!(
+            cmd("-c --status", Some(&sums)).unwrap_err(),
+            "sha256sum: exit status 1"
+        );
+    }
+
+    #[test]
+    fn check_missing_file_and_bad_list() {
+        let list = format!("{}  /no/such/file\n", "0".repeat(64));
+        let err = cmd("-c", Some(&list)).unwrap_err();
+        assert!(err.starts_with("/no/such/file: FAILED open or read\n"));
+        assert!(err.ends_with("1 listed file could not be read"));
+        let err = cmd("-c", Some("garbage")).unwrap_err();
+        assert!(err.contains("no properly formatted checksum lines found"));
+    }
+}
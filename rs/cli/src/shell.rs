@@ -0,0 +1,480 @@
+//! Mini shell behind `command/run`. Supports quoting, pipes (`|`), command
+//! lists (`&&`, `||`, `;`) and output redirection (`>`, `>>`) into `asterai:fs`.
+//! Each command is dispatched to the matching utility module, which receives
+//! its arguments with quoting intact.
+use crate::args;
+use crate::bytes;
+use crate::fs_ops;
+use crate::glob;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A word, both as written (for passing to utilities)
+    /// and with quotes removed (for command names and redirect targets).
+    Word {
+        raw: String,
+        value: String,
+    },
+    Pipe,
+    And,
+    Or,
+    Semi,
+    Redirect {
+        append: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Connector {
+    /// `;` or the start of the line: always run.
+    Always,
+    And,
+    Or,
+}
+
+#[derive(Debug)]
+struct Command {
+    name: String,
+    args: String,
+    redirect: Option<Redirect>,
+}
+
+#[derive(Debug)]
+struct Redirect {
+    path: String,
+    append: bool,
+}
+
+type Pipeline = Vec<Command>;
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    let mut raw = String::new();
+    let mut value = String::new();
+    let mut in_word = false;
+    let flush =
+        |tokens: &mut Vec<Token>, raw: &mut String, value: &mut String, in_word: &mut bool| {
+            if *in_word {
+                tokens.push(Token::Word {
+                    raw: std::mem::take(raw),
+                    value: std::mem::take(value),
+                });
+                *in_word = false;
+            }
+        };
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                raw.push(c);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => {
+                            raw.push(c);
+                            value.push(c);
+                        }
+                        None => return Err("sh: unterminated quote".into()),
+                    }
+                }
+                raw.push('\'');
+            }
+            '"' => {
+                in_word = true;
+                raw.push(c);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            let next = chars.next().ok_or("sh: unterminated quote")?;
+                            raw.push('\\');
+                            raw.push(next);
+                            if !matches!(next, '"' | '\\' | '$' | '`') {
+                                value.push('\\');
+                            }
+                            value.push(next);
+                        }
+                        Some(c) => {
+                            raw.push(c);
+                            value.push(c);
+                        }
+                        None => return Err("sh: unterminated quote".into()),
+                    }
+                }
+                raw.push('"');
+            }
+            '\\' => {
+                in_word = true;
+                raw.push(c);
+                if let Some(next) = chars.next() {
+                    raw.push(next);
+                    value.push(next);
+                }
+            }
+            '|' | '&' | ';' | '>' => {
+                flush(&mut tokens, &mut raw, &mut value, &mut in_word);
+                let doubled = chars.peek() == Some(&c);
+                let token = match (c, doubled) {
+                    ('|', true) => Token::Or,
+                    ('|', false) => Token::Pipe,
+                    ('&', true) => Token::And,
+                    ('&', false) => return Err("sh: background jobs are not supported".into()),
+                    (';', _) => Token::Semi,
+                    (_, append) => Token::Redirect { append },
+                };
+                if doubled && c != ';' {
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+            c if c.is_whitespace() => {
+                flush(&mut tokens, &mut raw, &mut value, &mut in_word);
+            }
+            c => {
+                in_word = true;
+                raw.push(c);
+                value.push(c);
+            }
+        }
+    }
+    flush(&mut tokens, &mut raw, &mut value, &mut in_word);
+    Ok(tokens)
+}
+
+fn parse(tokens: Vec<Token>) -> Result<Vec<(Connector, Pipeline)>, String> {
+    let mut list = Vec::new();
+    let mut connector = Connector::Always;
+    let mut pipeline: Pipeline = Vec::new();
+    let mut words: Vec<(String, String)> = Vec::new();
+    let mut redirect = None;
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word { raw, value } => words.push((raw, value)),
+            Token::Redirect { append } => match tokens.next() {
+                Some(Token::Word { value, .. }) => {
+                    redirect = Some(Redirect {
+                        path: value,
+                        append,
+                    })
+                }
+                _ => return Err("sh: missing redirect target".into()),
+            },
+            Token::Pipe => {
+                pipeline.push(build_command(std::mem::take(&mut words), redirect.take())?);
+            }
+            Token::And | Token::Or | Token::Semi => {
+                if words.is_empty() && pipeline.is_empty() && token == Token::Semi {
+                    continue;
+                }
+                pipeline.push(build_command(std::mem::take(&mut words), redirect.take())?);
+                list.push((connector, std::mem::take(&mut pipeline)));
+                connector = match token {
+                    Token::And => Connector::And,
+                    Token::Or => Connector::Or,
+                    _ => Connector::Always,
+                };
+            }
+        }
+    }
+    if !words.is_empty() || redirect.is_some() || !pipeline.is_empty() {
+        pipeline.push(build_command(words, redirect)?);
+        list.push((connector, pipeline));
+    } else if connector != Connector::Always {
+        return Err("sh: syntax error: unexpected end of input".into());
+    }
+    Ok(list)
+}
+
+fn build_command(
+    words: Vec<(String, String)>,
+    redirect: Option<Redirect>,
+) -> Result<Command, String> {
+    let mut words = words.into_iter();
+    let (_, name) = words.next().ok_or("sh: syntax error: missing command")?;
+    let args = words.map(|(raw, _)| raw).collect::<Vec<_>>().join(" ");
+    Ok(Command {
+        name,
+        args,
+        redirect,
+    })
+}
+
+/// Runs one command, after expanding the wildcards in its arguments.
+/// Byte-oriented utilities see their input unchanged; the rest need it to
+/// be UTF-8 text.
+pub(crate) fn dispatch(name: &str, args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let args = &glob::expand_args(args);
+    match name {
+        "base64" => return crate::base64::run_bytes(args, stdin),
+        "cat" => return crate::cat::run_bytes(args, stdin),
+        "gunzip" => return crate::gunzip::run_bytes(args, stdin),
+        "gzip" => return crate::gzip::run_bytes(args, stdin),
+        "head" => return crate::head::run_bytes(args, stdin),
+        "md5sum" => return crate::md5sum::run_bytes(args, stdin),
+        "od" => return crate::od::run_bytes(args, stdin),
+        "printf" => return crate::printf::run_bytes(args, stdin),
+        "sha256sum" => return crate::sha256sum::run_bytes(args, stdin),
+        "split" => return crate::split::run_bytes(args, stdin),
+        "tail" => return crate::tail::run_bytes(args, stdin),
+        "tar" => return crate::tar::run_bytes(args, stdin),
+        "tee" => return crate::tee::run_bytes(args, stdin),
+        "unzip" => return crate::unzip::run_bytes(args, stdin),
+        "wc" => return crate::wc::run_bytes(args, stdin),
+        "xxd" => return crate::xxd::run_bytes(args, stdin),
+        "zip" => return crate::zip::run_bytes(args, stdin),
+        _ => {}
+    }
+    let stdin = stdin
+        .map(|input| bytes::from_utf8(name, input))
+        .transpose()?;
+    let output = match name {
+        "awk" => crate::awk::run(args, stdin),
+        "column" => crate::column::run(args, stdin),
+        "comm" => crate::comm::run(args, stdin),
+        "cp" => crate::cp::run(args, stdin),
+        "csv" => crate::csv::run(args, stdin),
+        "cut" => crate::cut::run(args, stdin),
+        "diff" => crate::diff::run(args, stdin),
+        "edit" => crate::edit::run(args, stdin),
+        "find" => crate::find::run(args, stdin),
+        "fold" => crate::fold::run(args, stdin),
+        "grep" => crate::grep::run(args, stdin),
+        "join" => crate::join::run(args, stdin),
+        "jq" => crate::jq::run(args, stdin),
+        "ls" => crate::ls::run(args, stdin),
+        "mkdir" => crate::mkdir::run(args, stdin),
+        "mv" => crate::mv::run(args, stdin),
+        "nl" => crate::nl::run(args, stdin),
+        "paste" => crate::paste::run(args, stdin),
+        "patch" => crate::patch::run(args, stdin),
+        "rev" => crate::rev::run(args, stdin),
+        "rm" => crate::rm::run(args, stdin),
+        "sed" => crate::sed::run(args, stdin),
+        "seq" => crate::seq::run(args, stdin),
+        "sort" => crate::sort::run(args, stdin),
+        "stat" => crate::stat::run(args, stdin),
+        "touch" => crate::touch::run(args, stdin),
+        "tr" => crate::tr::run(args, stdin),
+        "tree" => crate::tree::run(args, stdin),
+        "uniq" => crate::uniq::run(args, stdin),
+        "xargs" => crate::xargs::run(args, stdin),
+        "echo" => echo(args),
+        "true" => Ok(String::new()),
+        "false" => Err(String::new()),
+        _ => Err(format!("sh: {name}: command not found")),
+    }?;
+    Ok(output.into_bytes())
+}
+
+fn echo(args: &str) -> Result<String, String> {
+    let words = args::split(args).map_err(|e| format!("echo: {e}"))?;
+    match words.first().map(String::as_str) {
+        Some("-n") => Ok(words[1..].join(" ")),
+        _ => Ok(format!("{}\n", words.join(" "))),
+    }
+}
+
+/// Runs a pipeline, feeding each command's output to the next.
+/// The pipeline stops at the first failing command.
+fn run_pipeline(pipeline: &[Command], stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let mut input = stdin;
+    for command in pipeline {
+        let output = dispatch(&command.name, &command.args, input.take())?;
+        input = Some(match &command.redirect {
+            Some(redirect) => {
+                let result = match redirect.append {
+                    true => fs_ops::append(&redirect.path, &output),
+                    false => fs_ops::write(&redirect.path, &output),
+                };
+                result.map_err(|e| format!("sh: {}: {e}", redirect.path))?;
+                Vec::new()
+            }
+            None => output,
+        });
+    }
+    Ok(input.unwrap_or_default())
+}
+
+/// Runs a command line. The output of every pipeline that ran is
+/// concatenated. If the last pipeline that ran failed, its error is
+/// returned after any output produced before it.
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    bytes::into_text("sh", run_bytes(args, stdin.map(String::into_bytes))?)
+}
+
+/// Like [`run`], with binary-safe input and output.
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let list = parse(tokenize(args)?)?;
+    let mut output = Vec::new();
+    let mut last: Result<(), String> = Ok(());
+    let mut stdin = stdin;
+    for (connector, pipeline) in &list {
+        let skip = match connector {
+            Connector::Always => false,
+            Connector::And => last.is_err(),
+            Connector::Or => last.is_ok(),
+        };
+        if skip {
+            continue;
+        }
+        // Only the first pipeline reads the command's stdin.
+        last = run_pipeline(pipeline, stdin.take()).map(|out| output.extend(out));
+    }
+    match last {
+        Ok(()) => Ok(output),
+        Err(e) => Err(format!("{}{e}", String::from_utf8_lossy(&output))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
+        run(args, stdin.map(String::from))
+    }
+
+    #[test]
+    fn tokenize_quotes_and_operators() {
+        let tokens = tokenize(r#"grep 'a b' "c\"d"|sort>>out.txt"#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word {
+                    raw: "grep".into(),
+                    value: "grep".into()
+                },
+                Token::Word {
+                    raw: "'a b'".into(),
+                    value: "a b".into()
+                },
+                Token::Word {
+                    raw: r#""c\"d""#.into(),
+                    value: "c\"d".into()
+                },
+                Token::Pipe,
+                Token::Word {
+                    raw: "sort".into(),
+                    value: "sort".into()
+                },
+                Token::Redirect { append: true },
+                Token::Word {
+                    raw: "out.txt".into(),
+                    value: "out.txt".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn operators_inside_quotes_are_literal() {
+        let out = cmd("echo 'a | b && c; d > e'", None).unwrap();
+        assert_eq!(out, "a | b && c; d > e\n");
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        assert!(cmd("echo 'abc", None).is_err());
+    }
+
+    #[test]
+    fn pipe() {
+        let out = cmd("sort | uniq | head -n 2", Some("b\na\nb\nc\n")).unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[test]
+    fn and_or_semi() {
+        assert_eq!(cmd("echo a && echo b", None).unwrap(), "a\nb\n");
+        assert_eq!(cmd("false && echo b", None).unwrap_err(), "");
+        assert_eq!(cmd("false || echo b", None).unwrap(), "b\n");
+        assert_eq!(cmd("echo a || echo b", None).unwrap(), "a\n");
+        assert_eq!(cmd("echo a; echo b;", None).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn error_keeps_earlier_output() {
+        let err = cmd("echo a; nope", None).unwrap_err();
+        assert_eq!(err, "a\nsh: nope: command not found");
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(cmd("echo a &&", None).is_err());
+        assert!(cmd("| sort", None).is_err());
+        assert!(cmd("echo a >", None).is_err());
+        assert!(cmd("echo a &", None).is_err());
+    }
+
+    #[test]
+    fn redirect_write_and_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("out.txt");
+        let out = cmd(&format!("echo one > {}", p.display()), None).unwrap();
+        assert_eq!(out, "");
+        cmd(&format!("echo two >> {}", p.display()), None).unwrap();
+        assert_eq!(std::fs::read_to_string(&p).unwrap(), "one\ntwo\n");
+        cmd(&format!("echo three > {}", p.display()), None).unwrap();
+        assert_eq!(std::fs::read_to_string(&p).unwrap(), "three\n");
+    }
+
+    #[test]
+    fn redirect_pipeline_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("in.txt");
+        let dst = dir.path().join("out.txt");
+        std::fs::write(&src, "b\na\n").unwrap();
+        let line = format!(
+            "cat {} | sort > \"{}\" && cat {}",
+            src.display(),
+            dst.display(),
+            dst.display()
+        );
+        assert_eq!(cmd(&line, None).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn stdin_feeds_first_pipeline_only() {
+        let out = cmd("cat; cat", Some("x\n")).unwrap();
+        assert_eq!(out, "x\n");
+    }
+
+    #[test]
+    fn binary_pipeline_and_redirect() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("in.bin");
+        let dst = dir.path().join("out.bin");
+        let data = [0u8, 0xff, 0xfe, b'\n', 0x80];
+        std::fs::write(&src, data).unwrap();
+        let line = format!("cat {} | tee {}", src.display(), dst.display());
+        run_bytes(&line, None).unwrap();
+        assert_eq!(std::fs::read(&dst).unwrap(), data);
+        let line = format!("cat {} | base64 | base64 -d | xxd -p", src.display());
+        assert_eq!(cmd(&line, None).unwrap(), "00fffe0a80\n");
+    }
+
+    #[test]
+    fn binary_output_needs_bytes_interface() {
+        let err = cmd("base64 -d", Some("/w==")).unwrap_err();
+        assert!(err.starts_with("sh: output is not valid UTF-8"));
+        assert_eq!(
+            run_bytes("base64 -d", Some(b"/w==".to_vec())).unwrap(),
+            [0xff]
+        );
+        let err = run_bytes("base64 -d | grep x", Some(b"/w==".to_vec())).unwrap_err();
+        assert_eq!(err, "grep: input is not valid UTF-8");
+    }
+
+    #[test]
+    fn expands_wildcards_before_each_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().display();
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        let out = cmd(&format!("echo two > {root}/b.txt; cat {root}/*.txt"), None).unwrap();
+        assert_eq!(out, "one\ntwo\n");
+        let out = cmd(&format!("echo '{root}/*.txt' {root}/{{c,d}}.md"), None).unwrap();
+        assert_eq!(out, format!("{root}/*.txt {root}/c.md {root}/d.md\n"));
+    }
+}
@@ -1,45 +1,628 @@
+//! `sort` with GNU semantics in the C locale. Lines are compared key by
+//! key (`-k`, or the whole line), each key with its own ordering options
+//! or else the global ones. Lines whose keys all compare equal fall back
+//! to a byte comparison of the whole line, unless `-s` or `-u` is given.
+
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
+use std::cmp::Ordering;
 
-struct Opts {
-    reverse: bool,
+/// Ordering options, global or for one key.
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Order {
+    /// `-b` on the start of a key.
+    skip_start_blanks: bool,
+    /// `-b` on the end of a key.
+    skip_end_blanks: bool,
+    dictionary: bool,
+    fold: bool,
+    ignore_nonprinting: bool,
+    general_numeric: bool,
+    human_numeric: bool,
+    month: bool,
     numeric: bool,
+    version: bool,
+    reverse: bool,
+}
+
+impl Order {
+    /// Applies one option letter, returning false for an unknown one.
+    fn set(&mut self, c: char) -> bool {
+        match c {
+            'b' => {
+                self.skip_start_blanks = true;
+                self.skip_end_blanks = true;
+            }
+            'd' => self.dictionary = true,
+            'f' => self.fold = true,
+            'i' => self.ignore_nonprinting = true,
+            'g' => self.general_numeric = true,
+            'h' => self.human_numeric = true,
+            'M' => self.month = true,
+            'n' => self.numeric = true,
+            'V' => self.version = true,
+            'r' => self.reverse = true,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The letters of the options that conflict with each other.
+    fn conflicts(&self) -> Option<String> {
+        let sorts = [
+            (self.dictionary, 'd'),
+            (self.general_numeric, 'g'),
+            (self.human_numeric, 'h'),
+            (self.ignore_nonprinting, 'i'),
+            (self.month, 'M'),
+            (self.numeric, 'n'),
+            (self.version, 'V'),
+        ];
+        let numeric = sorts
+            .iter()
+            .filter(|(on, c)| *on && "ghMnV".contains(*c))
+            .count();
+        let text = self.dictionary || self.ignore_nonprinting;
+        if numeric > 1 || (numeric == 1 && text) {
+            return Some(sorts.iter().filter(|(on, _)| *on).map(|(_, c)| c).collect());
+        }
+        None
+    }
+}
+
+/// A `-k` key: fields and characters are 0-based here, and an end
+/// character of 0 means the end of the field.
+struct Key {
+    start_field: usize,
+    start_char: usize,
+    end_field: Option<usize>,
+    end_char: usize,
+    order: Order,
+}
+
+struct Opts {
+    keys: Vec<Key>,
+    global: Order,
+    separator: Option<u8>,
+    stable: bool,
     unique: bool,
+    /// `-c` (report the first disorder) or `-C` (quietly).
+    check: Option<bool>,
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_key(spec: &str) -> Result<Key, String> {
+    let invalid = |why: &str| format!("sort: {why}: invalid field specification '{spec}'");
+    let mut order = Order::default();
+    // Parses `F[.C][OPTS]`. `b` applies to just the start or end position.
+    let mut parse_pos = |pos: &str, start: bool| -> Result<(usize, Option<usize>), String> {
+        let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let end = digits(pos);
+        let field: usize = pos[..end].parse().map_err(|_| invalid("invalid number"))?;
+        let mut rest = &pos[end..];
+        let mut char_pos = None;
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = digits(after);
+            char_pos = Some(
+                after[..end]
+                    .parse()
+                    .map_err(|_| invalid("invalid number"))?,
+            );
+            rest = &after[end..];
+        }
+        for c in rest.chars() {
+            match c {
+                'b' if start => order.skip_start_blanks = true,
+                'b' => order.skip_end_blanks = true,
+                c if order.set(c) => {}
+                c => return Err(format!("sort: invalid option -- '{c}'")),
+            }
+        }
+        if field == 0 {
+            return Err(invalid("field number is zero"));
+        }
+        Ok((field - 1, char_pos))
+    };
+    let (start, end) = match spec.split_once(',') {
+        Some((start, end)) => (start, Some(end)),
+        None => (spec, None),
+    };
+    let (start_field, start_char) = parse_pos(start, true)?;
+    if start_char == Some(0) {
+        return Err(invalid("character offset is zero"));
+    }
+    let (end_field, end_char) = match end {
+        Some(end) => {
+            let (field, end_char) = parse_pos(end, false)?;
+            (Some(field), end_char.unwrap_or(0))
+        }
+        None => (None, 0),
+    };
+    Ok(Key {
+        start_field,
+        start_char: start_char.map_or(0, |c| c - 1),
+        end_field,
+        end_char,
+        order,
+    })
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
-        reverse: false,
-        numeric: false,
+        keys: Vec::new(),
+        global: Order::default(),
+        separator: None,
+        stable: false,
         unique: false,
+        check: None,
         paths: Vec::new(),
     };
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 && !token.starts_with("--") {
-            for c in token[1..].chars() {
-                match c {
-                    'r' => opts.reverse = true,
-                    'n' => opts.numeric = true,
-                    'u' => opts.unique = true,
-                    _ => {}
+    let mut parser = Parser::new("sort", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('k') => opts.keys.push(parse_key(&parser.value()?)?),
+            Arg::Short('t') => opts.separator = Some(parse_separator(&parser.value()?)?),
+            Arg::Short('s') => opts.stable = true,
+            Arg::Short('u') => opts.unique = true,
+            Arg::Short('c') => opts.check = Some(false),
+            Arg::Short('C') => opts.check = Some(true),
+            Arg::Short(c) if opts.global.set(c) => {}
+            Arg::Long(l) => match l.as_str() {
+                "key" => opts.keys.push(parse_key(&parser.value()?)?),
+                "field-separator" => opts.separator = Some(parse_separator(&parser.value()?)?),
+                "stable" => opts.stable = true,
+                "unique" => opts.unique = true,
+                "check" => opts.check = Some(false),
+                "ignore-leading-blanks" => _ = opts.global.set('b'),
+                "dictionary-order" => _ = opts.global.set('d'),
+                "ignore-case" => _ = opts.global.set('f'),
+                "ignore-nonprinting" => _ = opts.global.set('i'),
+                "general-numeric-sort" => _ = opts.global.set('g'),
+                "human-numeric-sort" => _ = opts.global.set('h'),
+                "month-sort" => _ = opts.global.set('M'),
+                "numeric-sort" => _ = opts.global.set('n'),
+                "version-sort" => _ = opts.global.set('V'),
+                "reverse" => _ = opts.global.set('r'),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    // Keys without ordering options of their own use the global ones.
+    for key in &mut opts.keys {
+        if key.order == Order::default() {
+            key.order = opts.global;
+        }
+    }
+    for order in opts.keys.iter().map(|k| &k.order).chain([&opts.global]) {
+        if let Some(letters) = order.conflicts() {
+            return Err(format!("sort: options '-{letters}' are incompatible"));
+        }
+    }
+    if opts.check.is_some() && opts.paths.len() > 1 {
+        let extra = &opts.paths[1];
+        return Err(format!("sort: extra operand '{extra}' not allowed with -c"));
+    }
+    Ok(opts)
+}
+
+fn parse_separator(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        [b] => Ok(*b),
+        [] => Err("sort: empty tab".into()),
+        _ => Err(format!("sort: multi-character tab '{value}'")),
+    }
+}
+
+fn is_blank(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+/// The start of a key in `line`, like GNU sort's `begfield`.
+fn key_start(line: &[u8], key: &Key, separator: Option<u8>) -> usize {
+    let lim = line.len();
+    let mut ptr = 0;
+    for _ in 0..key.start_field {
+        if ptr >= lim {
+            break;
+        }
+        match separator {
+            Some(tab) => {
+                while ptr < lim && line[ptr] != tab {
+                    ptr += 1;
                 }
+                if ptr < lim {
+                    ptr += 1;
+                }
+            }
+            None => {
+                while ptr < lim && is_blank(line[ptr]) {
+                    ptr += 1;
+                }
+                while ptr < lim && !is_blank(line[ptr]) {
+                    ptr += 1;
+                }
+            }
+        }
+    }
+    if key.order.skip_start_blanks {
+        while ptr < lim && is_blank(line[ptr]) {
+            ptr += 1;
+        }
+    }
+    (ptr + key.start_char).min(lim)
+}
+
+/// The end of a key in `line`, like GNU sort's `limfield`.
+fn key_end(line: &[u8], key: &Key, separator: Option<u8>) -> usize {
+    let lim = line.len();
+    let Some(end_field) = key.end_field else {
+        return lim;
+    };
+    // Without a character position the whole end field is included.
+    let mut fields = end_field + usize::from(key.end_char == 0);
+    let mut ptr = 0;
+    while ptr < lim && fields > 0 {
+        fields -= 1;
+        match separator {
+            Some(tab) => {
+                while ptr < lim && line[ptr] != tab {
+                    ptr += 1;
+                }
+                if ptr < lim && (fields > 0 || key.end_char > 0) {
+                    ptr += 1;
+                }
+            }
+            None => {
+                while ptr < lim && is_blank(line[ptr]) {
+                    ptr += 1;
+                }
+                while ptr < lim && !is_blank(line[ptr]) {
+                    ptr += 1;
+                }
+            }
+        }
+    }
+    if key.end_char > 0 {
+        if key.order.skip_end_blanks {
+            while ptr < lim && is_blank(line[ptr]) {
+                ptr += 1;
+            }
+        }
+        ptr = (ptr + key.end_char).min(lim);
+    }
+    ptr
+}
+
+fn key_field<'a>(line: &'a [u8], key: &Key, separator: Option<u8>) -> &'a [u8] {
+    let start = key_start(line, key, separator);
+    let end = key_end(line, key, separator).max(start);
+    &line[start..end]
+}
+
+fn skip_blanks(s: &[u8]) -> &[u8] {
+    let start = s.iter().position(|&b| !is_blank(b)).unwrap_or(s.len());
+    &s[start..]
+}
+
+/// A number as its sign and digits, without leading zeros in the integer
+/// part or trailing zeros in the fraction, so it compares exactly.
+fn decimal(s: &[u8]) -> (bool, &[u8], &[u8]) {
+    let (negative, s) = match s.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, s),
+    };
+    let int_end = s
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .unwrap_or(s.len());
+    let int = &s[..int_end];
+    let frac = match s[int_end..].split_first() {
+        Some((b'.', rest)) => {
+            let end = rest
+                .iter()
+                .position(|b| !b.is_ascii_digit())
+                .unwrap_or(rest.len());
+            &rest[..end]
+        }
+        _ => &[],
+    };
+    let int_start = int.iter().position(|&b| b != b'0').unwrap_or(int.len());
+    let frac_end = frac.iter().rposition(|&b| b != b'0').map_or(0, |i| i + 1);
+    let (int, frac) = (&int[int_start..], &frac[..frac_end]);
+    // Negative zero is zero.
+    (negative && !(int.is_empty() && frac.is_empty()), int, frac)
+}
+
+fn compare_numeric(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_negative, a_int, a_frac) = decimal(skip_blanks(a));
+    let (b_negative, b_int, b_frac) = decimal(skip_blanks(b));
+    let magnitude = a_int
+        .len()
+        .cmp(&b_int.len())
+        .then_with(|| a_int.cmp(b_int))
+        .then_with(|| a_frac.cmp(b_frac));
+    match (a_negative, b_negative) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    }
+}
+
+/// The SI suffix order of a number for `-h`: none < K < M < G < ...
+fn unit_order(s: &[u8]) -> i32 {
+    let (negative, digits) = match s.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, s),
+    };
+    let mut chars = digits.iter().copied().peekable();
+    let mut nonzero = false;
+    while let Some(d) = chars.next_if(u8::is_ascii_digit) {
+        nonzero |= d != b'0';
+    }
+    if chars.next_if_eq(&b'.').is_some() {
+        while let Some(d) = chars.next_if(u8::is_ascii_digit) {
+            nonzero |= d != b'0';
+        }
+    }
+    let order = match chars.next() {
+        _ if !nonzero => 0,
+        Some(b'K' | b'k') => 1,
+        Some(b'M') => 2,
+        Some(b'G') => 3,
+        Some(b'T') => 4,
+        Some(b'P') => 5,
+        Some(b'E') => 6,
+        Some(b'Z') => 7,
+        Some(b'Y') => 8,
+        Some(b'R') => 9,
+        Some(b'Q') => 10,
+        _ => 0,
+    };
+    if negative { -order } else { order }
+}
+
+fn compare_human(a: &[u8], b: &[u8]) -> Ordering {
+    let (a, b) = (skip_blanks(a), skip_blanks(b));
+    unit_order(a)
+        .cmp(&unit_order(b))
+        .then_with(|| compare_numeric(a, b))
+}
+
+/// The longest prefix of `s` that is a floating point number, as strtod
+/// reads it.
+fn parse_float(s: &[u8]) -> Option<f64> {
+    let s = std::str::from_utf8(s).ok()?.trim_start();
+    let lower = s.to_ascii_lowercase();
+    let unsigned = lower.trim_start_matches(['+', '-']);
+    for special in ["infinity", "inf", "nan"] {
+        if unsigned.starts_with(special) && s.len() - unsigned.len() <= 1 {
+            let sign = &s[..s.len() - unsigned.len()];
+            return format!("{sign}{special}").parse().ok();
+        }
+    }
+    if let Some(hex) = unsigned.strip_prefix("0x") {
+        let end = hex
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(hex.len());
+        if let Ok(value) = u64::from_str_radix(&hex[..end], 16) {
+            let value = value as f64;
+            return Some(if s.starts_with('-') { -value } else { value });
+        }
+    }
+    (1..=s.len())
+        .rev()
+        .filter(|&i| s.is_char_boundary(i))
+        .find_map(|i| {
+            let prefix = &s[..i];
+            // Rust accepts forms strtod does not, such as a bare "e5".
+            let digit = prefix.bytes().any(|b| b.is_ascii_digit());
+            prefix.parse::<f64>().ok().filter(|_| digit)
+        })
+}
+
+fn compare_general(a: &[u8], b: &[u8]) -> Ordering {
+    // Non-numbers sort first, then NaNs, then numbers.
+    match (parse_float(a), parse_float(b)) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(a), Some(b)) => match a.partial_cmp(&b) {
+            Some(order) => order,
+            None => a.is_nan().cmp(&b.is_nan()).reverse(),
+        },
+    }
+}
+
+fn month(s: &[u8]) -> usize {
+    const MONTHS: [&[u8]; 12] = [
+        b"JAN", b"FEB", b"MAR", b"APR", b"MAY", b"JUN", b"JUL", b"AUG", b"SEP", b"OCT", b"NOV",
+        b"DEC",
+    ];
+    let s = skip_blanks(s);
+    let name = s.get(..3).map(<[u8]>::to_ascii_uppercase);
+    name.and_then(|name| MONTHS.iter().position(|m| *m == name))
+        .map_or(0, |i| i + 1)
+}
+
+/// Orders the characters of a version string for [`compare_version`]:
+/// `~` sorts before everything, even the end of the string.
+fn version_order(c: Option<&u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => *c as i32,
+        Some(b'~') => -1,
+        Some(c) => *c as i32 + 256,
+    }
+}
+
+/// Debian-style comparison of alternating non-digit and digit runs.
+fn compare_version_parts(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    let digit_at = |s: &[u8], k: usize| s.get(k).is_some_and(u8::is_ascii_digit);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let (x, y) = (version_order(a.get(i)), version_order(b.get(j)));
+            if x != y {
+                return x.cmp(&y);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while digit_at(a, i) && digit_at(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
             }
-        } else {
-            opts.paths.push(token.to_string());
+            i += 1;
+            j += 1;
+        }
+        if digit_at(a, i) {
+            return Ordering::Greater;
+        }
+        if digit_at(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
         }
     }
-    opts
+    Ordering::Equal
+}
+
+/// Where a file suffix such as `.tar.gz` starts: the first `.` after
+/// which the rest matches `(\.[A-Za-z~][A-Za-z0-9~]*)*`.
+fn suffix_start(s: &[u8]) -> usize {
+    let is_suffix = |rest: &[u8]| {
+        rest.split(|&b| b == b'.')
+            .skip(1)
+            .all(|part| match part.split_first() {
+                Some((first, tail)) => {
+                    (first.is_ascii_alphabetic() || *first == b'~')
+                        && tail.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'~')
+                }
+                None => false,
+            })
+    };
+    (0..s.len())
+        .find(|&i| s[i] == b'.' && is_suffix(&s[i..]))
+        .unwrap_or(s.len())
+}
+
+/// Version sort as in GNU's `filevercmp`.
+fn compare_version(a: &[u8], b: &[u8]) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    for special in [&b""[..], b".", b".."] {
+        if a == special {
+            return Ordering::Less;
+        }
+        if b == special {
+            return Ordering::Greater;
+        }
+    }
+    // Hidden files come first.
+    let (a, b) = match (a.strip_prefix(b"."), b.strip_prefix(b".")) {
+        (Some(a), Some(b)) => (a, b),
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => (a, b),
+    };
+    let (a_stem, b_stem) = (&a[..suffix_start(a)], &b[..suffix_start(b)]);
+    let order = match a_stem == b_stem {
+        true => compare_version_parts(a, b),
+        false => compare_version_parts(a_stem, b_stem),
+    };
+    order.then_with(|| a.cmp(b))
+}
+
+fn compare_text(a: &[u8], b: &[u8], order: &Order) -> Ordering {
+    if !(order.fold || order.dictionary || order.ignore_nonprinting) {
+        return a.cmp(b);
+    }
+    let keep = |b: &u8| {
+        let dictionary = !order.dictionary || is_blank(*b) || b.is_ascii_alphanumeric();
+        let printing = !order.ignore_nonprinting || (b' '..=b'~').contains(b);
+        dictionary && printing
+    };
+    let translate = |b: &u8| match order.fold {
+        true => b.to_ascii_uppercase(),
+        false => *b,
+    };
+    let a = a.iter().filter(|b| keep(b)).map(translate);
+    let b = b.iter().filter(|b| keep(b)).map(translate);
+    a.cmp(b)
+}
+
+fn compare_key(a: &[u8], b: &[u8], order: &Order) -> Ordering {
+    let result = if order.numeric {
+        compare_numeric(a, b)
+    } else if order.human_numeric {
+        compare_human(a, b)
+    } else if order.general_numeric {
+        compare_general(a, b)
+    } else if order.month {
+        month(a).cmp(&month(b))
+    } else if order.version {
+        compare_version(a, b)
+    } else {
+        compare_text(a, b, order)
+    };
+    if order.reverse {
+        result.reverse()
+    } else {
+        result
+    }
+}
+
+/// Compares two lines by their keys, without the last-resort comparison.
+fn compare_keys(opts: &Opts, a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if opts.keys.is_empty() {
+        return compare_key(a, b, &opts.global);
+    }
+    for key in &opts.keys {
+        let (a, b) = (
+            key_field(a, key, opts.separator),
+            key_field(b, key, opts.separator),
+        );
+        let order = compare_key(a, b, &key.order);
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+fn compare(opts: &Opts, a: &str, b: &str) -> Ordering {
+    let order = compare_keys(opts, a, b);
+    if order != Ordering::Equal || opts.stable || opts.unique {
+        return order;
+    }
+    match opts.global.reverse {
+        true => b.cmp(a),
+        false => a.cmp(b),
+    }
 }
 
 pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+    let opts = parse_opts(args)?;
     let input = if opts.paths.is_empty() {
         stdin.unwrap_or_default()
     } else {
         let mut combined = String::new();
         for path in &opts.paths {
-            let content = fs_ops::read_to_string(path)
-                .map_err(|e| format!("sort: {path}: {e}"))?;
+            let content = fs_ops::read_to_string(path).map_err(|e| format!("sort: {path}: {e}"))?;
             combined.push_str(&content);
         }
         combined
@@ -47,22 +630,28 @@ pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
 
     let mut lines: Vec<&str> = input.lines().collect();
 
-    if opts.numeric {
-        lines.sort_by(|a, b| {
-            let na = parse_num(a);
-            let nb = parse_num(b);
-            na.partial_cmp(&nb).unwrap_or(std::cmp::Ordering::Equal)
-        });
-    } else {
-        lines.sort();
+    if let Some(quiet) = opts.check {
+        let name = opts.paths.first().map_or("-", String::as_str);
+        for (i, pair) in lines.windows(2).enumerate() {
+            // With -u, equal lines are out of order too.
+            let disorder = match opts.unique {
+                true => compare(&opts, pair[0], pair[1]) != Ordering::Less,
+                false => compare(&opts, pair[0], pair[1]) == Ordering::Greater,
+            };
+            if disorder {
+                return Err(match quiet {
+                    true => "sort: exit status 1".into(),
+                    false => format!("sort: {name}:{}: disorder: {}", i + 2, pair[1]),
+                });
+            }
+        }
+        return Ok(String::new());
     }
 
-    if opts.reverse {
-        lines.reverse();
-    }
+    lines.sort_by(|a, b| compare(&opts, a, b));
 
     if opts.unique {
-        lines.dedup();
+        lines.dedup_by(|b, a| compare_keys(&opts, a, b) == Ordering::Equal);
     }
 
     let mut output = lines.join("\n");
@@ -72,15 +661,6 @@ pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
     Ok(output)
 }
 
-fn parse_num(s: &str) -> f64 {
-    s.trim()
-        .chars()
-        .take_while(|c| *c == '-' || *c == '.' || c.is_ascii_digit())
-        .collect::<String>()
-        .parse()
-        .unwrap_or(0.0)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,6 +705,125 @@ mod tests {
         assert_eq!(out, "1cherry\n2bananas\n10apples\n");
     }
 
+    #[test]
+    fn numeric_exact_and_signed() {
+        let out = cmd(
+            "-n",
+            Some("-1.5\n 3\n-0\n0.25\nx\n-10\n100000000000000000001\n100000000000000000000"),
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "-10\n-1.5\n-0\nx\n0.25\n 3\n100000000000000000000\n100000000000000000001\n"
+        );
+    }
+
+    #[test]
+    fn keys_and_separator() {
+        let csv = "bob,35,paris\nalice,9,rome\ncarol,35,oslo\n";
+        assert_eq!(
+            cmd("-t, -k2,2n -k1,1r", Some(csv)).unwrap(),
+            "alice,9,rome\ncarol,35,oslo\nbob,35,paris\n"
+        );
+        assert_eq!(
+            cmd("-t , -k 3", Some(csv)).unwrap(),
+            "carol,35,oslo\nbob,35,paris\nalice,9,rome\n"
+        );
+        // Without -t, fields include their leading blanks.
+        let out = cmd("-k2", Some("a  b\nc a\n")).unwrap();
+        assert_eq!(out, "a  b\nc a\n");
+        let out = cmd("-k2b", Some("a  b\nc a\n")).unwrap();
+        assert_eq!(out, "c a\na  b\n");
+    }
+
+    #[test]
+    fn character_positions() {
+        let out = cmd("-k1.2,1.3", Some("xcb\nyab\nzaa\n")).unwrap();
+        assert_eq!(out, "zaa\nyab\nxcb\n");
+        assert_eq!(
+            cmd("-k0", Some("")).unwrap_err(),
+            "sort: field number is zero: invalid field specification '0'"
+        );
+        assert_eq!(
+            cmd("-k1.0", Some("")).unwrap_err(),
+            "sort: character offset is zero: invalid field specification '1.0'"
+        );
+    }
+
+    #[test]
+    fn stable_and_last_resort() {
+        let input = "b 1\na 1\nc 0\n";
+        assert_eq!(cmd("-k2,2", Some(input)).unwrap(), "c 0\na 1\nb 1\n");
+        assert_eq!(cmd("-s -k2,2", Some(input)).unwrap(), "c 0\nb 1\na 1\n");
+        assert_eq!(cmd("-u -k2,2", Some(input)).unwrap(), "c 0\nb 1\n");
+    }
+
+    #[test]
+    fn human_numeric() {
+        let out = cmd("-h", Some("2G\n10K\n1M\n500\n1.5K\n0K\n-1M\n")).unwrap();
+        assert_eq!(out, "-1M\n0K\n500\n1.5K\n10K\n1M\n2G\n");
+    }
+
+    #[test]
+    fn version() {
+        let input = "file10.txt\nfile2.txt\nfile1.10\nfile1.9\n.hidden\nv1.0~rc1\nv1.0\n";
+        let out = cmd("-V", Some(input)).unwrap();
+        assert_eq!(
+            out,
+            ".hidden\nfile1.9\nfile1.10\nfile2.txt\nfile10.txt\nv1.0~rc1\nv1.0\n"
+        );
+    }
+
+    #[test]
+    fn fold_case() {
+        let out = cmd("-f", Some("b\nB\na\n_\nA\n")).unwrap();
+        assert_eq!(out, "A\na\nB\nb\n_\n");
+        let out = cmd("-fu", Some("b\nB\na\n")).unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[test]
+    fn general_numeric_and_month() {
+        let out = cmd("-g", Some("1e3\n-inf\nabc\n0x10\n2.5\nnan\n")).unwrap();
+        assert_eq!(out, "abc\nnan\n-inf\n2.5\n0x10\n1e3\n");
+        let out = cmd("-M", Some("Mar\n feb\nDEC\nxyz\n")).unwrap();
+        assert_eq!(out, "xyz\n feb\nMar\nDEC\n");
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!(cmd("-c", Some("a\nb\nb\n")).unwrap(), "");
+        assert_eq!(
+            cmd("-c", Some("a\nc\nb\n")).unwrap_err(),
+            "sort: -:3: disorder: b"
+        );
+        assert_eq!(
+            cmd("-cu", Some("a\nb\nb\n")).unwrap_err(),
+            "sort: -:3: disorder: b"
+        );
+        assert_eq!(
+            cmd("-C -n", Some("10\n9\n")).unwrap_err(),
+            "sort: exit status 1"
+        );
+        assert!(
+            cmd("-c a b", None)
+                .unwrap_err()
+                .contains("not allowed with -c")
+        );
+    }
+
+    #[test]
+    fn incompatible_options() {
+        assert_eq!(
+            cmd("-nV", Some("")).unwrap_err(),
+            "sort: options '-nV' are incompatible"
+        );
+        assert_eq!(
+            cmd("-t ab", Some("")).unwrap_err(),
+            "sort: multi-character tab 'ab'"
+        );
+    }
+
     #[test]
     fn empty_input() {
         let out = cmd("", Some("")).unwrap();
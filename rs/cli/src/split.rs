@@ -0,0 +1,379 @@
+//! `split` writes its input to a series of files (`xaa`, `xab`, ...) of
+//! `-l` lines, `-b` bytes, or `-n` equal chunks. Without `-a`, suffixes
+//! grow like GNU split's once they run out: `xyz` is followed by `xzaaa`.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+
+const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &[u8] = b"0123456789";
+const HEX: &[u8] = b"0123456789abcdef";
+
+enum Mode {
+    Lines(usize),
+    Bytes(usize),
+    /// `-n N`: N chunks of equal size.
+    Chunks(usize),
+    /// `-n l/N`: N chunks, without splitting lines.
+    LineChunks(usize),
+}
+
+struct Opts {
+    mode: Mode,
+    suffix_length: Option<usize>,
+    alphabet: &'static [u8],
+    additional_suffix: String,
+    verbose: bool,
+    input: Option<String>,
+    prefix: String,
+}
+
+/// Parses a size such as `100`, `10K` (KiB), `1MB` (10^6) or `2G`.
+fn parse_size(value: &str) -> Result<usize, String> {
+    let invalid = || format!("split: invalid number of bytes: '{value}'");
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: usize = number.parse().map_err(|_| invalid())?;
+    let multiplier: usize = match unit {
+        "" => 1,
+        "b" => 512,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        "KB" => 1000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+fn parse_chunks(value: &str) -> Result<Mode, String> {
+    let invalid = || format!("split: invalid number of chunks: '{value}'");
+    let (lines, count) = match value.strip_prefix("l/") {
+        Some(count) => (true, count),
+        None => (false, value),
+    };
+    match count.parse::<usize>() {
+        Ok(n) if n > 0 && lines => Ok(Mode::LineChunks(n)),
+        Ok(n) if n > 0 => Ok(Mode::Chunks(n)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        mode: Mode::Lines(1000),
+        suffix_length: None,
+        alphabet: LETTERS,
+        additional_suffix: String::new(),
+        verbose: false,
+        input: None,
+        prefix: "x".into(),
+    };
+    let mut operands = Vec::new();
+    let mut parser = Parser::new("split", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('l') => {
+                let value = parser.value()?;
+                match value.parse::<usize>() {
+                    Ok(n) if n > 0 => opts.mode = Mode::Lines(n),
+                    _ => return Err(format!("split: invalid number of lines: '{value}'")),
+                }
+            }
+            Arg::Short('b') => {
+                let size = parse_size(&parser.value()?)?;
+                if size == 0 {
+                    return Err("split: invalid number of bytes: '0'".into());
+                }
+                opts.mode = Mode::Bytes(size);
+            }
+            Arg::Short('n') => opts.mode = parse_chunks(&parser.value()?)?,
+            Arg::Short('a') => opts.suffix_length = Some(parser.number()?),
+            Arg::Short('d') => opts.alphabet = DIGITS,
+            Arg::Short('x') => opts.alphabet = HEX,
+            Arg::Long(l) => match l.as_str() {
+                "lines" => {
+                    let value = parser.value()?;
+                    match value.parse::<usize>() {
+                        Ok(n) if n > 0 => opts.mode = Mode::Lines(n),
+                        _ => return Err(format!("split: invalid number of lines: '{value}'")),
+                    }
+                }
+                "bytes" => opts.mode = Mode::Bytes(parse_size(&parser.value()?)?.max(1)),
+                "number" => opts.mode = parse_chunks(&parser.value()?)?,
+                "suffix-length" => opts.suffix_length = Some(parser.number()?),
+                "numeric-suffixes" => opts.alphabet = DIGITS,
+                "hex-suffixes" => opts.alphabet = HEX,
+                "additional-suffix" => {
+                    let suffix = parser.value()?;
+                    if suffix.contains('/') {
+                        return Err(format!(
+                            "split: invalid suffix '{suffix}', contains directory separator"
+                        ));
+                    }
+                    opts.additional_suffix = suffix;
+                }
+                "verbose" => opts.verbose = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(v) => operands.push(v),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    let mut operands = operands.into_iter();
+    opts.input = operands.next().filter(|input| input != "-");
+    if let Some(prefix) = operands.next() {
+        opts.prefix = prefix;
+    }
+    if let Some(extra) = operands.next() {
+        return Err(format!("split: extra operand '{extra}'"));
+    }
+    if opts.suffix_length == Some(0) {
+        return Err("split: invalid suffix length: '0'".into());
+    }
+    Ok(opts)
+}
+
+/// The suffix of output file `index`, or `None` once they run out.
+fn suffix(opts: &Opts, index: usize, fixed_length: Option<usize>) -> Option<String> {
+    let alphabet = opts.alphabet;
+    let base = alphabet.len();
+    let digits = |mut n: usize, width: usize| {
+        let mut out = vec![alphabet[0]; width];
+        for slot in out.iter_mut().rev() {
+            *slot = alphabet[n % base];
+            n /= base;
+        }
+        String::from_utf8(out).unwrap_or_default()
+    };
+    if let Some(width) = fixed_length {
+        let capacity = base.checked_pow(width as u32).unwrap_or(usize::MAX);
+        return (index < capacity).then(|| digits(index, width));
+    }
+    // Each width leaves out the names starting with the last symbol, which
+    // introduce the next, wider, set.
+    let mut index = index;
+    let mut level = 0;
+    loop {
+        let width = 2 + level;
+        let capacity = (base - 1).checked_mul(base.checked_pow(width as u32 - 1)?)?;
+        if index < capacity {
+            let last = alphabet[base - 1] as char;
+            return Some(format!(
+                "{}{}",
+                last.to_string().repeat(level),
+                digits(index, width)
+            ));
+        }
+        index -= capacity;
+        level += 1;
+    }
+}
+
+/// The byte ranges of each output file.
+fn chunks(opts: &Opts, data: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    match opts.mode {
+        Mode::Lines(n) => {
+            let mut start = 0;
+            let mut count = 0;
+            for (i, &b) in data.iter().enumerate() {
+                if b == b'\n' {
+                    count += 1;
+                    if count == n {
+                        ranges.push((start, i + 1));
+                        start = i + 1;
+                        count = 0;
+                    }
+                }
+            }
+            if start < data.len() {
+                ranges.push((start, data.len()));
+            }
+        }
+        Mode::Bytes(n) => {
+            let mut start = 0;
+            while start < data.len() {
+                let end = (start + n).min(data.len());
+                ranges.push((start, end));
+                start = end;
+            }
+        }
+        Mode::Chunks(n) => {
+            let size = data.len() / n;
+            for k in 0..n {
+                let end = if k + 1 == n {
+                    data.len()
+                } else {
+                    (k + 1) * size
+                };
+                ranges.push((k * size, end));
+            }
+        }
+        Mode::LineChunks(n) => {
+            let size = data.len() / n;
+            let mut start = 0;
+            for k in 1..=n {
+                // A chunk ends with the line holding its last byte.
+                let end = match (k * size).checked_sub(1) {
+                    _ if k == n => data.len(),
+                    Some(last) if last >= start => data[last..]
+                        .iter()
+                        .position(|&b| b == b'\n')
+                        .map_or(data.len(), |i| last + i + 1),
+                    _ => start,
+                };
+                ranges.push((start, end));
+                start = end;
+            }
+        }
+    }
+    ranges
+}
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    let data = match &opts.input {
+        Some(path) => fs_ops::read(path).map_err(|e| format!("split: {path}: {e}"))?,
+        None => stdin.unwrap_or_default(),
+    };
+    let ranges = chunks(&opts, &data);
+    // With -n the suffixes are as long as the number of chunks needs.
+    let fixed_length = match opts.mode {
+        Mode::Chunks(n) | Mode::LineChunks(n) => {
+            let mut width = 1;
+            while opts
+                .alphabet
+                .len()
+                .checked_pow(width as u32)
+                .is_some_and(|c| c < n)
+            {
+                width += 1;
+            }
+            Some(opts.suffix_length.unwrap_or(2).max(width))
+        }
+        _ => opts.suffix_length,
+    };
+    let mut output = String::new();
+    for (index, (start, end)) in ranges.into_iter().enumerate() {
+        let suffix =
+            suffix(&opts, index, fixed_length).ok_or("split: output file suffixes exhausted")?;
+        let path = format!("{}{suffix}{}", opts.prefix, opts.additional_suffix);
+        if opts.verbose {
+            output.push_str(&format!("creating file '{path}'\n"));
+        }
+        fs_ops::write(&path, &data[start..end]).map_err(|e| format!("split: {path}: {e}"))?;
+    }
+    Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("split", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn split_dir(args: &str, input: &[u8]) -> (tempfile::TempDir, Result<String, String>) {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path().join("x").display().to_string();
+        let result = run_bytes(&format!("{args} - {prefix}"), Some(input.to_vec()))
+            .map(|out| String::from_utf8(out).unwrap());
+        (dir, result)
+    }
+
+    fn files(dir: &tempfile::TempDir) -> Vec<(String, String)> {
+        let mut files: Vec<(String, String)> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                let content = std::fs::read_to_string(e.path()).unwrap();
+                (e.file_name().to_string_lossy().into_owned(), content)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn numbers(n: usize) -> String {
+        (1..=n).map(|i| format!("{i}\n")).collect()
+    }
+
+    #[test]
+    fn by_lines() {
+        let (dir, result) = split_dir("-l 4", numbers(10).as_bytes());
+        assert_eq!(result.unwrap(), "");
+        let names: Vec<(String, String)> = files(&dir);
+        assert_eq!(
+            names,
+            vec![
+                ("xaa".to_string(), "1\n2\n3\n4\n".to_string()),
+                ("xab".to_string(), "5\n6\n7\n8\n".to_string()),
+                ("xac".to_string(), "9\n10\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn by_bytes_with_numeric_suffixes() {
+        let (dir, result) = split_dir("-b 4 -d --additional-suffix=.txt --verbose", b"abcdefghij");
+        let prefix = dir.path().join("x").display().to_string();
+        assert_eq!(
+            result.unwrap(),
+            format!(
+                "creating file '{prefix}00.txt'\ncreating file '{prefix}01.txt'\ncreating file '{prefix}02.txt'\n"
+            )
+        );
+        assert_eq!(files(&dir)[2], ("x02.txt".to_string(), "ij".to_string()));
+        assert_eq!(parse_size("2K"), Ok(2048));
+        assert_eq!(parse_size("1MB"), Ok(1_000_000));
+        assert!(parse_size("3Q").is_err());
+    }
+
+    #[test]
+    fn chunks() {
+        let input = "a\nbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb\nc\n";
+        let (dir, _) = split_dir("-n 3", input.as_bytes());
+        let sizes: Vec<usize> = files(&dir).iter().map(|f| f.1.len()).collect();
+        assert_eq!(sizes, [13, 13, 15]);
+        let (dir, _) = split_dir("-n l/3", input.as_bytes());
+        let sizes: Vec<usize> = files(&dir).iter().map(|f| f.1.len()).collect();
+        assert_eq!(sizes, [39, 0, 2]);
+        let (dir, _) = split_dir("-n l/3", numbers(10).as_bytes());
+        assert_eq!(files(&dir)[1].1, "5\n6\n7\n");
+    }
+
+    #[test]
+    fn suffixes_widen() {
+        let opts = parse_opts("").unwrap();
+        assert_eq!(suffix(&opts, 0, None).unwrap(), "aa");
+        assert_eq!(suffix(&opts, 649, None).unwrap(), "yz");
+        assert_eq!(suffix(&opts, 650, None).unwrap(), "zaaa");
+        assert_eq!(suffix(&opts, 650 + 16900, None).unwrap(), "zzaaaa");
+        assert_eq!(suffix(&opts, 675, Some(2)).unwrap(), "zz");
+        assert_eq!(suffix(&opts, 676, Some(2)), None);
+        let (_dir, result) = split_dir("-l 1 -a 1", numbers(27).as_bytes());
+        assert_eq!(result.unwrap_err(), "split: output file suffixes exhausted");
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(
+            run("-l 0", None)
+                .unwrap_err()
+                .contains("invalid number of lines")
+        );
+        assert!(
+            run("-n x", None)
+                .unwrap_err()
+                .contains("invalid number of chunks")
+        );
+        assert!(run("a b c", None).unwrap_err().contains("extra operand"));
+    }
+}
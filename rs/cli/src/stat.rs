@@ -1,13 +1,33 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
+use crate::json;
 
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
-    let paths: Vec<&str> = args.split_whitespace().collect();
+    let mut json_output = false;
+    let mut paths = Vec::new();
+    let mut parser = Parser::new("stat", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Long(l) if l == "json" => json_output = true,
+            Arg::Value(path) => paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
     if paths.is_empty() {
         return Err("stat: missing operand".into());
     }
     let mut output = String::new();
+    let mut entries = Vec::new();
     for path in &paths {
         let meta = fs_ops::stat(path).map_err(|e| format!("stat: {path}: {e}"))?;
+        if json_output {
+            let name = match path.trim_end_matches('/').rsplit('/').next() {
+                Some("") | None => "/",
+                Some(name) => name,
+            };
+            entries.push(json::entry(name, path, &meta));
+            continue;
+        }
         let file_type = if meta.is_dir() {
             "directory"
         } else {
@@ -22,6 +42,9 @@ pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
         output.push_str(&format!("  Size: {:<14} {file_type}\n", meta.size));
         output.push_str(&format!("Modify: {modified}\n"));
     }
+    if json_output {
+        return Ok(json::array(&entries));
+    }
     Ok(output)
 }
 
@@ -105,4 +128,21 @@ mod tests {
         assert!(out.contains("Size: 2"));
         assert!(out.contains("Size: 3"));
     }
+
+    #[test]
+    fn json_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("f.txt");
+        std::fs::write(&p, "hello").unwrap();
+        let out = cmd(&format!("--json {} {}/", p.display(), dir.path().display())).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with(&format!(
+            "  {{\"name\":\"f.txt\",\"path\":\"{}\",\"kind\":\"file\",\"size\":5,\"modified\":1",
+            p.display()
+        )));
+        let dir_name = dir.path().file_name().unwrap().to_str().unwrap();
+        assert!(lines[2].contains(&format!("\"name\":\"{dir_name}\"")));
+        assert!(lines[2].contains("\"kind\":\"directory\""));
+    }
 }
@@ -1,71 +1,230 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
+/// How much of each input to print.
+#[derive(Clone, Copy)]
+enum Count {
+    Lines(usize),
+    /// `-n +N`: from line N on.
+    FromLine(usize),
+    Bytes(usize),
+    /// `-c +N`: from byte N on.
+    FromByte(usize),
+}
+
 struct Opts {
-    lines: usize,
+    /// `None` means the last 10 lines. Not allowed with `--offset`.
+    count: Option<Count>,
+    /// Whether to print `==> path <==` headers; by default only for
+    /// several files.
+    headers: Option<bool>,
+    /// `--offset N`: only the content after the first N bytes, for polling
+    /// a growing file.
+    offset: Option<u64>,
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+/// How far back to read at a time when looking for the last lines.
+const BLOCK: u64 = 64 * 1024;
+
+/// Parses the value of `-n` or `-c`, where a leading `+` counts from the start.
+fn parse_count(value: &str, bytes: bool) -> Result<Count, String> {
+    let (from_start, number) = match value.strip_prefix('+') {
+        Some(number) => (true, number),
+        None => (false, value.strip_prefix('-').unwrap_or(value)),
+    };
+    let n: usize = number.parse().map_err(|_| {
+        let unit = if bytes { "bytes" } else { "lines" };
+        format!("tail: invalid number of {unit}: '{value}'")
+    })?;
+    Ok(match (bytes, from_start) {
+        (false, false) => Count::Lines(n),
+        (false, true) => Count::FromLine(n),
+        (true, false) => Count::Bytes(n),
+        (true, true) => Count::FromByte(n),
+    })
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
-        lines: 10,
+        count: None,
+        headers: None,
+        offset: None,
         paths: Vec::new(),
     };
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        if tokens[i] == "-n" {
-            i += 1;
-            if let Some(n) = tokens.get(i) {
-                opts.lines = n.parse().unwrap_or(10);
-            }
-        } else if tokens[i].starts_with('-') && tokens[i][1..].chars().all(|c| c.is_ascii_digit()) {
-            opts.lines = tokens[i][1..].parse().unwrap_or(10);
-        } else {
-            opts.paths.push(tokens[i].to_string());
+    let mut parser = Parser::new("tail", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('n') => opts.count = Some(parse_count(&parser.value()?, false)?),
+            Arg::Short('c') => opts.count = Some(parse_count(&parser.value()?, true)?),
+            Arg::Short('q') => opts.headers = Some(false),
+            Arg::Short('v') => opts.headers = Some(true),
+            Arg::Long(l) => match l.as_str() {
+                "lines" => opts.count = Some(parse_count(&parser.value()?, false)?),
+                "bytes" => opts.count = Some(parse_count(&parser.value()?, true)?),
+                "quiet" | "silent" => opts.headers = Some(false),
+                "verbose" => opts.headers = Some(true),
+                "offset" => {
+                    let value = parser.value()?;
+                    let offset = value
+                        .parse()
+                        .map_err(|_| format!("tail: invalid offset: '{value}'"))?;
+                    opts.offset = Some(offset);
+                }
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Number(n) => opts.count = Some(parse_count(&n, false)?),
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
-    opts
+    if opts.offset.is_some() && opts.paths.len() != 1 {
+        return Err("tail: --offset needs exactly one file".into());
+    }
+    // Trimming the output would break the offset arithmetic of the next poll.
+    if opts.offset.is_some() && opts.count.is_some() {
+        return Err("tail: --offset cannot be combined with -n or -c".into());
+    }
+    Ok(opts)
 }
 
-pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
-    let mut output = String::new();
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    if let Some(offset) = opts.offset {
+        return since_offset(&opts.paths[0], offset);
+    }
+    let count = opts.count.unwrap_or(Count::Lines(10));
+    let mut output = Vec::new();
     if opts.paths.is_empty() {
         let input = stdin.unwrap_or_default();
-        let all: Vec<&str> = input.lines().collect();
-        let start = all.len().saturating_sub(opts.lines);
-        let taken = &all[start..];
-        output.push_str(&taken.join("\n"));
-        if !taken.is_empty() {
-            output.push('\n');
+        if opts.headers == Some(true) {
+            output.extend(b"==> standard input <==\n");
         }
+        select(&input, count, &mut output);
     } else {
-        let multi = opts.paths.len() > 1;
+        let headers = opts.headers.unwrap_or(opts.paths.len() > 1);
         for (i, path) in opts.paths.iter().enumerate() {
-            if multi {
+            if headers {
                 if i > 0 {
-                    output.push('\n');
+                    output.push(b'\n');
                 }
-                output.push_str(&format!("==> {path} <==\n"));
-            }
-            let content = fs_ops::read_to_string(path)
-                .map_err(|e| format!("tail: {path}: {e}"))?;
-            let all: Vec<&str> = content.lines().collect();
-            let start = all.len().saturating_sub(opts.lines);
-            let taken = &all[start..];
-            output.push_str(&taken.join("\n"));
-            if !taken.is_empty() {
-                output.push('\n');
+                output.extend(format!("==> {path} <==\n").as_bytes());
             }
+            tail_file(path, count, &mut output).map_err(|e| format!("tail: {path}: {e}"))?;
         }
     }
     Ok(output)
 }
 
+/// Reads only as much of the end of a file as `count` needs, with range
+/// reads so that the tail of a large (or remote) file is cheap.
+fn tail_file(path: &str, count: Count, output: &mut Vec<u8>) -> Result<(), String> {
+    let size = fs_ops::stat(path)?.size;
+    let content = match count {
+        Count::Bytes(n) => {
+            let start = size.saturating_sub(n as u64);
+            output.extend(fs_ops::read_range(path, start, size - start)?);
+            return Ok(());
+        }
+        Count::FromByte(n) => {
+            let start = (n as u64).saturating_sub(1).min(size);
+            output.extend(fs_ops::read_range(path, start, size - start)?);
+            return Ok(());
+        }
+        Count::FromLine(_) => fs_ops::read(path)?,
+        Count::Lines(lines) => {
+            let mut start = size;
+            let mut data = Vec::new();
+            // Reads backwards until the last `lines` lines are all in.
+            while start > 0 && lines > 0 && last_lines_start(&data, lines) == 0 {
+                let from = start.saturating_sub(BLOCK);
+                let mut block = fs_ops::read_range(path, from, start - from)?;
+                block.append(&mut data);
+                data = block;
+                start = from;
+            }
+            data
+        }
+    };
+    select(&content, count, output);
+    Ok(())
+}
+
+/// The content of `path` after its first `offset` bytes, exactly as it is,
+/// so the next poll can pass `offset` plus the length of this output. If
+/// the file has shrunk below `offset` it was truncated or replaced, and
+/// all of it is returned along with a warning; the next poll then passes
+/// the length of the output before the warning.
+fn since_offset(path: &str, offset: u64) -> Result<Vec<u8>, String> {
+    let size = fs_ops::stat(path)
+        .map_err(|e| format!("tail: {path}: {e}"))?
+        .size;
+    let truncated = offset > size;
+    let start = if truncated { 0 } else { offset };
+    let data =
+        fs_ops::read_range(path, start, size - start).map_err(|e| format!("tail: {path}: {e}"))?;
+    if truncated {
+        let output = String::from_utf8_lossy(&data);
+        return Err(format!("{output}tail: {path}: file truncated"));
+    }
+    Ok(data)
+}
+
+/// The start of the last `lines` lines of `data`, or 0 if it has no more
+/// lines than that.
+fn last_lines_start(data: &[u8], lines: usize) -> usize {
+    if lines == 0 {
+        return data.len();
+    }
+    // A trailing newline ends the last line rather than starting a new one.
+    let body = data.strip_suffix(b"\n").unwrap_or(data);
+    body.iter()
+        .enumerate()
+        .rev()
+        .filter(|&(_, &b)| b == b'\n')
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1)
+}
+
+/// Copies the selected part of `data` unchanged. Whole lines end with a
+/// newline even if the input had none; byte counts are exact.
+fn select(data: &[u8], count: Count, output: &mut Vec<u8>) {
+    let start = match count {
+        Count::Lines(lines) => last_lines_start(data, lines),
+        Count::FromLine(line) => {
+            let mut start = 0;
+            for _ in 1..line {
+                match data[start..].iter().position(|&b| b == b'\n') {
+                    Some(i) => start += i + 1,
+                    None => start = data.len(),
+                }
+            }
+            start
+        }
+        Count::Bytes(n) => {
+            output.extend(&data[data.len().saturating_sub(n)..]);
+            return;
+        }
+        Count::FromByte(n) => {
+            output.extend(&data[n.saturating_sub(1).min(data.len())..]);
+            return;
+        }
+    };
+    let taken = &data[start..];
+    output.extend(taken);
+    if !taken.is_empty() && !taken.ends_with(b"\n") {
+        output.push(b'\n');
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("tail", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
 
     fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
         run(args, stdin.map(String::from))
@@ -73,7 +232,10 @@ mod tests {
 
     #[test]
     fn default_ten_lines() {
-        let input = (1..=20).map(|i| i.to_string()).collect::<Vec<_>>().join("\n");
+        let input = (1..=20)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
         let out = cmd("", Some(&input)).unwrap();
         assert_eq!(out.lines().count(), 10);
         assert_eq!(out.lines().next().unwrap(), "11");
@@ -132,6 +294,84 @@ mod tests {
         assert!(out.contains("bb"));
     }
 
+    #[test]
+    fn from_line() {
+        assert_eq!(cmd("-n +3", Some("a\nb\nc\nd\n")).unwrap(), "c\nd\n");
+        assert_eq!(cmd("-n +0", Some("a\nb\n")).unwrap(), "a\nb\n");
+        assert_eq!(cmd("-n +9", Some("a\nb\n")).unwrap(), "");
+        assert_eq!(cmd("-n -1", Some("a\nb\n")).unwrap(), "b\n");
+    }
+
+    #[test]
+    fn byte_counts() {
+        assert_eq!(cmd("-c 3", Some("hello\n")).unwrap(), "lo\n");
+        assert_eq!(cmd("-c +2", Some("hello")).unwrap(), "ello");
+        assert_eq!(cmd("-c 99", Some("hi")).unwrap(), "hi");
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("f.bin");
+        std::fs::write(&p, b"0123456789").unwrap();
+        let path = p.display();
+        assert_eq!(cmd(&format!("-c 4 {path}"), None).unwrap(), "6789");
+        assert_eq!(cmd(&format!("-c +8 {path}"), None).unwrap(), "789");
+    }
+
+    #[test]
+    fn last_lines_of_large_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("big.log");
+        let content: String = (0..50_000).map(|i| format!("line {i}\n")).collect();
+        std::fs::write(&p, &content).unwrap();
+        let out = cmd(&format!("-n 2 {}", p.display()), None).unwrap();
+        assert_eq!(out, "line 49998\nline 49999\n");
+        let out = cmd(&format!("-n 20000 {}", p.display()), None).unwrap();
+        assert_eq!(out.lines().next(), Some("line 30000"));
+        assert_eq!(out.lines().count(), 20000);
+    }
+
+    #[test]
+    fn offset_polling() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("app.log");
+        let path = p.display();
+        std::fs::write(&p, "one\n").unwrap();
+        let first = cmd(&format!("--offset 0 {path}"), None).unwrap();
+        assert_eq!(first, "one\n");
+        assert_eq!(cmd(&format!("--offset 4 {path}"), None).unwrap(), "");
+        std::fs::write(&p, "one\ntwo\nthree").unwrap();
+        assert_eq!(
+            cmd(&format!("--offset 4 {path}"), None).unwrap(),
+            "two\nthree"
+        );
+        assert!(
+            cmd(&format!("--offset 4 -n 1 {path}"), None)
+                .unwrap_err()
+                .contains("cannot be combined")
+        );
+        assert!(cmd(&format!("-c 2 --offset 4 {path}"), None).is_err());
+        std::fs::write(&p, "new\n").unwrap();
+        assert_eq!(
+            cmd(&format!("--offset=13 {path}"), None).unwrap_err(),
+            format!("new\ntail: {path}: file truncated")
+        );
+        assert!(
+            cmd("--offset 1", Some("x"))
+                .unwrap_err()
+                .contains("exactly one file")
+        );
+    }
+
+    #[test]
+    fn header_options() {
+        assert_eq!(
+            cmd("-v -n 1", Some("a\nb\n")).unwrap(),
+            "==> standard input <==\nb\n"
+        );
+        assert_eq!(
+            cmd("-n x", Some("")).unwrap_err(),
+            "tail: invalid number of lines: 'x'"
+        );
+    }
+
     #[test]
     fn missing_file() {
         let err = cmd("/no/file", None).unwrap_err();
@@ -0,0 +1,677 @@
+//! `tar` creates (`-c`), lists (`-t`) and extracts (`-x`) ustar archives,
+//! gzip-compressed with `-z`. Archives are read from and written to `-f`,
+//! or stdin and the output without it. Compressed archives are detected
+//! automatically when reading.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+use crate::gzip;
+
+const BLOCK: usize = 512;
+/// Archives are padded to a whole record of 20 blocks, as GNU tar does.
+const RECORD: usize = 20 * BLOCK;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Create,
+    List,
+    Extract,
+}
+
+struct Opts {
+    mode: Mode,
+    gzip: bool,
+    verbose: bool,
+    archive: Option<String>,
+    directory: Option<String>,
+    strip: usize,
+    to_stdout: bool,
+    keep_old: bool,
+    excludes: Vec<String>,
+    members: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut mode = None;
+    let mut opts = Opts {
+        mode: Mode::List,
+        gzip: false,
+        verbose: false,
+        archive: None,
+        directory: None,
+        strip: 0,
+        to_stdout: false,
+        keep_old: false,
+        excludes: Vec::new(),
+        members: Vec::new(),
+    };
+    // Traditional syntax: the first word is an option cluster without a
+    // dash, as in `tar czf out.tar.gz dir`.
+    let trimmed = args.trim_start();
+    let args = match trimmed.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => format!("-{trimmed}"),
+        _ => trimmed.to_string(),
+    };
+    let mut parser = Parser::new("tar", &args)?;
+    let mut set_mode = |m: Mode| match mode {
+        Some(existing) if existing != m => {
+            Err("tar: You may not specify more than one '-ctx' option".to_string())
+        }
+        _ => {
+            mode = Some(m);
+            Ok(())
+        }
+    };
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('c') => set_mode(Mode::Create)?,
+            Arg::Short('t') => set_mode(Mode::List)?,
+            Arg::Short('x') => set_mode(Mode::Extract)?,
+            Arg::Short('z') => opts.gzip = true,
+            Arg::Short('v') => opts.verbose = true,
+            Arg::Short('f') => opts.archive = Some(parser.value()?),
+            Arg::Short('C') => opts.directory = Some(parser.value()?),
+            Arg::Short('O') => opts.to_stdout = true,
+            Arg::Short('k') => opts.keep_old = true,
+            Arg::Long(l) => match l.as_str() {
+                "create" => set_mode(Mode::Create)?,
+                "list" => set_mode(Mode::List)?,
+                "extract" | "get" => set_mode(Mode::Extract)?,
+                "gzip" | "gunzip" => opts.gzip = true,
+                "verbose" => opts.verbose = true,
+                "file" => opts.archive = Some(parser.value()?),
+                "directory" => opts.directory = Some(parser.value()?),
+                "strip-components" => opts.strip = parser.number()?,
+                "to-stdout" => opts.to_stdout = true,
+                "keep-old-files" => opts.keep_old = true,
+                "exclude" => opts.excludes.push(parser.value()?),
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(member) => opts.members.push(member),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    opts.mode = mode.ok_or("tar: You must specify one of the '-ctx' options")?;
+    if opts.archive.as_deref() == Some("-") {
+        opts.archive = None;
+    }
+    if opts.mode == Mode::Create && opts.members.is_empty() {
+        return Err("tar: Cowardly refusing to create an empty archive".into());
+    }
+    Ok(opts)
+}
+
+fn join(dir: Option<&str>, name: &str) -> String {
+    match dir {
+        None | Some(".") | Some("") => name.to_string(),
+        Some(dir) => format!("{}/{name}", dir.trim_end_matches('/')),
+    }
+}
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    match opts.mode {
+        Mode::Create => create(&opts),
+        Mode::List | Mode::Extract => {
+            let input = match &opts.archive {
+                Some(path) => fs_ops::read(path).map_err(|e| format!("tar: {path}: {e}"))?,
+                None => stdin.unwrap_or_default(),
+            };
+            // Compression is detected from the data, so -z is optional here.
+            let data = match input.starts_with(&[0x1f, 0x8b]) {
+                true => gzip::decompress(&input).map_err(|e| format!("tar: {e}"))?,
+                false => input,
+            };
+            read_archive(&opts, &data)
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Creation
+
+struct Header<'a> {
+    name: &'a str,
+    size: u64,
+    mtime: u64,
+    kind: u8,
+}
+
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}", width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+fn header_block(header: &Header, name: &[u8], prefix: &[u8]) -> [u8; BLOCK] {
+    let mut block = [0u8; BLOCK];
+    block[..name.len()].copy_from_slice(name);
+    let mode = if header.kind == b'5' { 0o755 } else { 0o644 };
+    octal(&mut block[100..108], mode);
+    octal(&mut block[108..116], 0);
+    octal(&mut block[116..124], 0);
+    octal(&mut block[124..136], header.size);
+    octal(&mut block[136..148], header.mtime);
+    block[156] = header.kind;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    block[345..345 + prefix.len()].copy_from_slice(prefix);
+    // The checksum is computed with its own field set to spaces.
+    block[148..156].fill(b' ');
+    let sum: u32 = block.iter().map(|&b| b as u32).sum();
+    let digits = format!("{sum:06o}\0 ");
+    block[148..156].copy_from_slice(digits.as_bytes());
+    block
+}
+
+/// Appends the header blocks for an entry. Names that do not fit the
+/// ustar name and prefix fields are stored in a GNU long name entry.
+fn write_header(out: &mut Vec<u8>, header: &Header) {
+    let name = header.name.as_bytes();
+    if name.len() <= 100 {
+        out.extend(header_block(header, name, b""));
+        return;
+    }
+    let split = (1..name.len())
+        .rev()
+        .find(|&i| name[i] == b'/' && i <= 155 && name.len() - i - 1 <= 100);
+    if let Some(i) = split {
+        out.extend(header_block(header, &name[i + 1..], &name[..i]));
+        return;
+    }
+    let long = Header {
+        name: "././@LongLink",
+        size: name.len() as u64 + 1,
+        mtime: 0,
+        kind: b'L',
+    };
+    out.extend(header_block(&long, long.name.as_bytes(), b""));
+    let mut data = name.to_vec();
+    data.push(0);
+    pad_block(&mut data);
+    out.extend(data);
+    out.extend(header_block(header, &name[..100], b""));
+}
+
+fn pad_block(data: &mut Vec<u8>) {
+    let padded = data.len().div_ceil(BLOCK) * BLOCK;
+    data.resize(padded, 0);
+}
+
+fn excluded(opts: &Opts, name: &str) -> bool {
+    let base = name
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(name);
+    opts.excludes
+        .iter()
+        .any(|pattern| crate::glob::matches(pattern, name) || crate::glob::matches(pattern, base))
+}
+
+fn create(opts: &Opts) -> Result<Vec<u8>, String> {
+    let dir = opts.directory.as_deref();
+    let mut archive = Vec::new();
+    let mut listing = String::new();
+    for member in &opts.members {
+        // Member names are stored relative, as GNU tar does.
+        let name = member.trim_start_matches('/');
+        let name = if name.is_empty() { "." } else { name };
+        let path = join(dir, member);
+        let meta = fs_ops::stat(&path).map_err(|e| format!("tar: {member}: {e}"))?;
+        let mut entries = vec![(name.trim_end_matches('/').to_string(), meta.is_dir())];
+        if meta.is_dir() {
+            let children = fs_ops::ls(&path, true).map_err(|e| format!("tar: {member}: {e}"))?;
+            let base = name.trim_end_matches('/');
+            entries.extend(
+                children
+                    .into_iter()
+                    .map(|e| (format!("{base}/{}", e.name), e.is_dir())),
+            );
+        }
+        for (entry, is_dir) in entries {
+            let source = join(dir, &entry);
+            if excluded(opts, &entry) || Some(&source) == opts.archive.as_ref() {
+                continue;
+            }
+            let mtime = fs_ops::stat(&source)
+                .ok()
+                .and_then(|m| m.last_modified)
+                .unwrap_or(0);
+            let (stored, data) = match is_dir {
+                true => (format!("{entry}/"), Vec::new()),
+                false => {
+                    let data = fs_ops::read(&source).map_err(|e| format!("tar: {entry}: {e}"))?;
+                    (entry, data)
+                }
+            };
+            let header = Header {
+                name: &stored,
+                size: data.len() as u64,
+                mtime,
+                kind: if is_dir { b'5' } else { b'0' },
+            };
+            write_header(&mut archive, &header);
+            let mut data = data;
+            pad_block(&mut data);
+            archive.extend(data);
+            if opts.verbose {
+                listing.push_str(&stored);
+                listing.push('\n');
+            }
+        }
+    }
+    archive.resize(archive.len() + 2 * BLOCK, 0);
+    archive.resize(archive.len().div_ceil(RECORD) * RECORD, 0);
+    if opts.gzip {
+        archive = gzip::compress(&archive, 6, None, 0);
+    }
+    match &opts.archive {
+        Some(path) => {
+            fs_ops::write(path, &archive).map_err(|e| format!("tar: {path}: {e}"))?;
+            Ok(listing.into_bytes())
+        }
+        None => Ok(archive),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Listing and extraction
+
+struct Entry {
+    name: String,
+    kind: u8,
+    mode: u64,
+    size: u64,
+    mtime: u64,
+    owner: String,
+}
+
+fn parse_octal(field: &[u8]) -> Result<u64, String> {
+    let text: String = field
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect();
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| "tar: This does not look like a tar archive".into())
+}
+
+fn field_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn parse_header(block: &[u8]) -> Result<Entry, String> {
+    let stored: u64 = parse_octal(&block[148..156])?;
+    let sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                b' ' as u64
+            } else {
+                b as u64
+            }
+        })
+        .sum();
+    if stored != sum {
+        return Err("tar: This does not look like a tar archive".into());
+    }
+    let mut name = field_str(&block[..100]);
+    if &block[257..262] == b"ustar" {
+        let prefix = field_str(&block[345..500]);
+        if !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+    }
+    let uname = field_str(&block[265..297]);
+    let gname = field_str(&block[297..329]);
+    let owner = match (uname.is_empty(), gname.is_empty()) {
+        (false, false) => format!("{uname}/{gname}"),
+        _ => format!(
+            "{}/{}",
+            parse_octal(&block[108..116])?,
+            parse_octal(&block[116..124])?
+        ),
+    };
+    Ok(Entry {
+        name,
+        kind: block[156],
+        mode: parse_octal(&block[100..108])?,
+        size: parse_octal(&block[124..136])?,
+        mtime: parse_octal(&block[136..148])?,
+        owner,
+    })
+}
+
+/// Parses the `path` record from a pax extended header.
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines().find_map(|record| {
+        let (_, kv) = record.split_once(' ')?;
+        kv.strip_prefix("path=").map(String::from)
+    })
+}
+
+fn permissions(kind: u8, mode: u64) -> String {
+    let mut s = String::from(match kind {
+        b'5' => 'd',
+        b'2' => 'l',
+        b'1' => 'h',
+        _ => '-',
+    });
+    for shift in [6, 3, 0] {
+        let bits = mode >> shift & 7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+    s
+}
+
+fn format_date(ts: u64) -> String {
+    let secs = ts;
+    let days = secs / 86400;
+    let time_of_day = secs % 86400;
+    let hour = time_of_day / 3600;
+    let minute = (time_of_day % 3600) / 60;
+    let z = days as i64 + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = if m <= 2 { y + 1 } else { y };
+    format!("{y}-{m:02}-{d:02} {hour:02}:{minute:02}")
+}
+
+/// Whether `name` was selected by the member operands, returning the
+/// index of the operand that matched.
+fn selected(opts: &Opts, name: &str) -> Option<Option<usize>> {
+    if opts.members.is_empty() {
+        return Some(None);
+    }
+    let name = name.trim_end_matches('/');
+    opts.members
+        .iter()
+        .map(|m| m.trim_end_matches('/'))
+        .position(|m| name == m || name.starts_with(&format!("{m}/")))
+        .map(Some)
+}
+
+/// Drops `strip` leading components, returning `None` if nothing is left.
+fn strip_components(name: &str, strip: usize) -> Option<String> {
+    let parts: Vec<&str> = name.split('/').filter(|p| !p.is_empty()).collect();
+    (parts.len() > strip).then(|| parts[strip..].join("/"))
+}
+
+fn read_archive(opts: &Opts, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    let mut messages = Vec::new();
+    let mut found = vec![false; opts.members.len()];
+    let mut long_name: Option<String> = None;
+    let mut pos = 0;
+    while pos + BLOCK <= data.len() {
+        let block = &data[pos..pos + BLOCK];
+        if block.iter().all(|&b| b == 0) {
+            break;
+        }
+        let mut entry = parse_header(block)?;
+        pos += BLOCK;
+        let size = entry.size as usize;
+        let content = data
+            .get(pos..pos + size)
+            .ok_or("tar: Unexpected EOF in archive")?;
+        pos += size.div_ceil(BLOCK) * BLOCK;
+        match entry.kind {
+            b'L' => {
+                long_name = Some(field_str(content));
+                continue;
+            }
+            b'x' => {
+                long_name = pax_path(content).or(long_name);
+                continue;
+            }
+            b'g' => continue,
+            _ => {}
+        }
+        if let Some(name) = long_name.take() {
+            entry.name = name;
+        }
+        let Some(operand) = selected(opts, &entry.name) else {
+            continue;
+        };
+        if let Some(i) = operand {
+            found[i] = true;
+        }
+        if excluded(opts, &entry.name) {
+            continue;
+        }
+        match opts.mode {
+            Mode::List => {
+                let line = match opts.verbose {
+                    true => format!(
+                        "{} {} {:>width$} {} {}\n",
+                        permissions(entry.kind, entry.mode),
+                        entry.owner,
+                        entry.size,
+                        format_date(entry.mtime),
+                        entry.name,
+                        width = 19usize.saturating_sub(entry.owner.len() + 1).max(1)
+                    ),
+                    false => format!("{}\n", entry.name),
+                };
+                output.extend(line.as_bytes());
+            }
+            Mode::Extract => {
+                if let Err(e) = extract(opts, &entry, content, &mut output) {
+                    messages.push(e);
+                }
+            }
+            Mode::Create => {}
+        }
+    }
+    for (member, found) in opts.members.iter().zip(found) {
+        if !found {
+            messages.push(format!("tar: {member}: Not found in archive"));
+        }
+    }
+    match messages.is_empty() {
+        true => Ok(output),
+        false => Err(format!(
+            "{}{}",
+            String::from_utf8_lossy(&output),
+            messages.join("\n")
+        )),
+    }
+}
+
+fn extract(opts: &Opts, entry: &Entry, content: &[u8], output: &mut Vec<u8>) -> Result<(), String> {
+    let name = &entry.name;
+    if name.split('/').any(|part| part == "..") {
+        return Err(format!("tar: {name}: Member name contains '..'"));
+    }
+    let Some(relative) = strip_components(name, opts.strip) else {
+        return Ok(());
+    };
+    if opts.to_stdout {
+        if matches!(entry.kind, b'0' | 0 | b'7') {
+            output.extend(content);
+        }
+        return Ok(());
+    }
+    if opts.verbose {
+        output.extend(format!("{name}\n").as_bytes());
+    }
+    let target = join(opts.directory.as_deref(), &relative);
+    match entry.kind {
+        b'5' => fs_ops::mkdir(&target).map_err(|e| format!("tar: {name}: {e}")),
+        b'0' | 0 | b'7' => {
+            if opts.keep_old && fs_ops::exists(&target).unwrap_or(false) {
+                return Err(format!("tar: {name}: Cannot open: File exists"));
+            }
+            fs_ops::write(&target, content).map_err(|e| format!("tar: {name}: {e}"))
+        }
+        b'1' | b'2' => Err(format!(
+            "tar: {name}: Cannot create link: links are not supported"
+        )),
+        _ => Err(format!("tar: {name}: Unknown file type; file ignored")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("tar", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn tree() -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("proj");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("README"), "read me\n").unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("src/data.bin"), [0u8, 0xff, 7]).unwrap();
+        let base = dir_path(&dir);
+        (dir, base)
+    }
+
+    fn dir_path(dir: &tempfile::TempDir) -> String {
+        dir.path().to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn create_list_extract() {
+        let (_dir, base) = tree();
+        run_bytes(&format!("-cf {base}/p.tar -C {base} proj"), None).unwrap();
+        let list = run(&format!("-tf {base}/p.tar"), None).unwrap();
+        assert_eq!(
+            list,
+            "proj/\nproj/README\nproj/src/\nproj/src/data.bin\nproj/src/main.rs\n"
+        );
+        let out = tempfile::tempdir().unwrap();
+        let out_path = dir_path(&out);
+        run(&format!("-xf {base}/p.tar -C {out_path}"), None).unwrap();
+        let extracted = out.path().join("proj/src/data.bin");
+        assert_eq!(std::fs::read(extracted).unwrap(), [0u8, 0xff, 7]);
+        assert_eq!(
+            std::fs::read_to_string(out.path().join("proj/README")).unwrap(),
+            "read me\n"
+        );
+    }
+
+    #[test]
+    fn archive_layout() {
+        let (_dir, base) = tree();
+        let archive = run_bytes(&format!("-c -C {base} proj/README"), None).unwrap();
+        assert_eq!(archive.len(), RECORD);
+        assert_eq!(&archive[..11], b"proj/README");
+        assert_eq!(&archive[257..263], b"ustar\0");
+        assert_eq!(&archive[124..136], b"00000000010\0");
+        assert_eq!(&archive[BLOCK..BLOCK + 8], b"read me\n");
+    }
+
+    #[test]
+    fn gzip_and_stdin() {
+        let (_dir, base) = tree();
+        let archive = run_bytes(&format!("czf - -C {base} proj/src"), None).unwrap();
+        assert_eq!(archive[..2], [0x1f, 0x8b]);
+        // Compression is detected without -z.
+        let list = run_bytes("-t", Some(archive.clone())).unwrap();
+        assert_eq!(list, b"proj/src/\nproj/src/data.bin\nproj/src/main.rs\n");
+        let out = run_bytes("-xzO proj/src/main.rs", Some(archive)).unwrap();
+        assert_eq!(out, b"fn main() {}\n");
+    }
+
+    #[test]
+    fn verbose_listing() {
+        let (_dir, base) = tree();
+        let archive = run_bytes(&format!("-c -C {base} proj/README"), None).unwrap();
+        let list = run_bytes("-tv", Some(archive)).unwrap();
+        let list = String::from_utf8(list).unwrap();
+        assert!(
+            list.starts_with("-rw-r--r-- 0/0               8 "),
+            "{list}"
+        );
+        assert!(list.ends_with(" proj/README\n"));
+    }
+
+    #[test]
+    fn members_and_strip_components() {
+        let (_dir, base) = tree();
+        let archive = run_bytes(&format!("-c -C {base} proj"), None).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let out_path = dir_path(&out);
+        let args = format!("-x -C {out_path} --strip-components 1 proj/src");
+        run_bytes(&args, Some(archive.clone())).unwrap();
+        assert!(out.path().join("src/main.rs").exists());
+        assert!(!out.path().join("README").exists());
+        let err = run_bytes("-t nope", Some(archive)).unwrap_err();
+        assert_eq!(err, "tar: nope: Not found in archive");
+    }
+
+    #[test]
+    fn exclude() {
+        let (_dir, base) = tree();
+        let archive = run_bytes(&format!("-c -C {base} --exclude '*.bin' proj"), None).unwrap();
+        let list = run_bytes("-t", Some(archive)).unwrap();
+        assert_eq!(list, b"proj/\nproj/README\nproj/src/\nproj/src/main.rs\n");
+    }
+
+    #[test]
+    fn long_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir_path(&dir);
+        let deep = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        let single = "n".repeat(150);
+        std::fs::create_dir_all(dir.path().join("d".repeat(120))).unwrap();
+        std::fs::write(dir.path().join(&deep), "deep").unwrap();
+        std::fs::write(dir.path().join(&single), "long").unwrap();
+        let archive = run_bytes(&format!("-c -C {base} {deep} {single}"), None).unwrap();
+        let list = run_bytes("-t", Some(archive)).unwrap();
+        assert_eq!(
+            String::from_utf8(list).unwrap(),
+            format!("{deep}\n{single}\n")
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_and_invalid() {
+        let mut archive = Vec::new();
+        let header = Header {
+            name: "../evil",
+            size: 0,
+            mtime: 0,
+            kind: b'0',
+        };
+        write_header(&mut archive, &header);
+        archive.resize(RECORD, 0);
+        let out = tempfile::tempdir().unwrap();
+        let err = run_bytes(&format!("-x -C {}", dir_path(&out)), Some(archive)).unwrap_err();
+        assert!(err.contains("Member name contains '..'"));
+        let err = run_bytes("-t", Some(vec![b'x'; 1024])).unwrap_err();
+        assert_eq!(err, "tar: This does not look like a tar archive");
+    }
+
+    #[test]
+    fn option_errors() {
+        assert!(run("-ct", None).unwrap_err().contains("more than one"));
+        assert!(run("-f x", None).unwrap_err().contains("must specify one"));
+        assert!(run("-c", None).unwrap_err().contains("empty archive"));
+    }
+
+    #[test]
+    fn format_dates() {
+        assert_eq!(format_date(0), "1970-01-01 00:00");
+        assert_eq!(format_date(951_782_400 + 3_660), "2000-02-29 01:01");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
 struct Opts {
@@ -5,30 +6,32 @@ struct Opts {
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
         append: false,
         paths: Vec::new(),
     };
-    for token in args.split_whitespace() {
-        if token == "-a" {
-            opts.append = true;
-        } else {
-            opts.paths.push(token.to_string());
+    let mut parser = Parser::new("tee", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('a') => opts.append = true,
+            Arg::Long(l) if l == "append" => opts.append = true,
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
-    opts
+    Ok(opts)
 }
 
-pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
     let input = stdin.unwrap_or_default();
     for path in &opts.paths {
         if opts.append {
-            fs_ops::append(path, input.as_bytes())
+            fs_ops::append(path, &input)
                 .map_err(|e| format!("tee: {path}: {e}"))?;
         } else {
-            fs_ops::write(path, input.as_bytes())
+            fs_ops::write(path, &input)
                 .map_err(|e| format!("tee: {path}: {e}"))?;
         }
     }
@@ -38,6 +41,11 @@ pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("tee", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
 
     fn cmd(args: &str, stdin: &str) -> Result<String, String> {
         run(args, Some(stdin.to_string()))
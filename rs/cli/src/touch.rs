@@ -1,7 +1,8 @@
+use crate::args::operands;
 use crate::fs_ops;
 
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
-    let paths: Vec<&str> = args.split_whitespace().collect();
+    let paths = operands("touch", args)?;
     if paths.is_empty() {
         return Err("touch: missing operand".into());
     }
@@ -1,3 +1,5 @@
+use crate::args::{Arg, Parser};
+
 struct Opts {
     delete: bool,
     set1: Vec<char>,
@@ -10,21 +12,22 @@ fn parse_opts(args: &str) -> Result<Opts, String> {
         set1: Vec::new(),
         set2: Vec::new(),
     };
-    let tokens: Vec<&str> = args.split_whitespace().collect();
     let mut positional = Vec::new();
-    for token in &tokens {
-        if *token == "-d" {
-            opts.delete = true;
-        } else {
-            positional.push(*token);
+    let mut parser = Parser::new("tr", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('d') => opts.delete = true,
+            Arg::Long(l) if l == "delete" => opts.delete = true,
+            Arg::Value(set) => positional.push(set),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
     if positional.is_empty() {
         return Err("tr: missing operand".into());
     }
-    opts.set1 = expand_set(positional[0]);
+    opts.set1 = expand_set(&positional[0]);
     if positional.len() > 1 {
-        opts.set2 = expand_set(positional[1]);
+        opts.set2 = expand_set(&positional[1]);
     } else if !opts.delete {
         return Err("tr: missing operand after set1".into());
     }
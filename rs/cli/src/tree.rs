@@ -1,3 +1,4 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
 struct Opts {
@@ -6,33 +7,26 @@ struct Opts {
     dirs_only: bool,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
         root: ".".into(),
         max_depth: None,
         dirs_only: false,
     };
-    let tokens: Vec<&str> = args.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        match tokens[i] {
-            "-L" => {
-                i += 1;
-                if let Some(n) = tokens.get(i) {
-                    opts.max_depth = Some(n.parse().unwrap_or(1));
-                }
-            }
-            "-d" => opts.dirs_only = true,
-            t if !t.starts_with('-') => opts.root = t.to_string(),
-            _ => {}
+    let mut parser = Parser::new("tree", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('L') => opts.max_depth = Some(parser.number()?),
+            Arg::Short('d') => opts.dirs_only = true,
+            Arg::Value(root) => opts.root = root,
+            arg => return Err(parser.unexpected(&arg)),
         }
-        i += 1;
     }
-    opts
+    Ok(opts)
 }
 
 pub fn run(args: &str, _stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+    let opts = parse_opts(args)?;
     let mut output = String::new();
     let mut dirs = 0usize;
     let mut files = 0usize;
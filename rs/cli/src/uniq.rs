@@ -1,3 +1,4 @@
+use crate::args::{Arg, Parser};
 use crate::fs_ops;
 
 struct Opts {
@@ -7,32 +8,34 @@ struct Opts {
     path: Option<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
         count: false,
         duplicates_only: false,
         ignore_case: false,
         path: None,
     };
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 && !token.starts_with("--") {
-            for c in token[1..].chars() {
-                match c {
-                    'c' => opts.count = true,
-                    'd' => opts.duplicates_only = true,
-                    'i' => opts.ignore_case = true,
-                    _ => {}
-                }
-            }
-        } else if opts.path.is_none() {
-            opts.path = Some(token.to_string());
+    let mut parser = Parser::new("uniq", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('c') => opts.count = true,
+            Arg::Short('d') => opts.duplicates_only = true,
+            Arg::Short('i') => opts.ignore_case = true,
+            Arg::Long(l) => match l.as_str() {
+                "count" => opts.count = true,
+                "repeated" => opts.duplicates_only = true,
+                "ignore-case" => opts.ignore_case = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) if opts.path.is_none() => opts.path = Some(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
-    opts
+    Ok(opts)
 }
 
 pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+    let opts = parse_opts(args)?;
     let input = if let Some(path) = &opts.path {
         fs_ops::read_to_string(path).map_err(|e| format!("uniq: {path}: {e}"))?
     } else {
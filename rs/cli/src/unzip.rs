@@ -0,0 +1,327 @@
+//! `unzip` lists (`-l`), tests (`-t`), prints (`-p`) or extracts the
+//! entries of a zip archive, optionally only those matching the given
+//! patterns. Existing files are kept unless `-o` is given.
+
+use crate::args::{Arg, Parser};
+use crate::fs_ops;
+use crate::glob;
+use crate::zip::{self, Entry};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Extract,
+    List,
+    Test,
+    Pipe,
+}
+
+struct Opts {
+    mode: Mode,
+    overwrite: Option<bool>,
+    quiet: bool,
+    directory: Option<String>,
+    archive: String,
+    patterns: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut mode = Mode::Extract;
+    let mut overwrite = None;
+    let mut quiet = false;
+    let mut directory = None;
+    let mut operands = Vec::new();
+    let mut parser = Parser::new("unzip", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('l') => mode = Mode::List,
+            Arg::Short('t') => mode = Mode::Test,
+            Arg::Short('p') => mode = Mode::Pipe,
+            Arg::Short('o') => overwrite = Some(true),
+            Arg::Short('n') => overwrite = Some(false),
+            Arg::Short('q') => quiet = true,
+            Arg::Short('d') => directory = Some(parser.value()?),
+            Arg::Value(v) => operands.push(v),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    let mut operands = operands.into_iter();
+    let archive = operands.next().ok_or("unzip: missing archive name")?;
+    Ok(Opts {
+        mode,
+        overwrite,
+        quiet,
+        directory,
+        archive,
+        patterns: operands.collect(),
+    })
+}
+
+pub fn run_bytes(args: &str, _stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    // Like Info-ZIP, `unzip name` also tries `name.zip`.
+    let mut archive = opts.archive.clone();
+    if !fs_ops::exists(&archive).unwrap_or(false) && !archive.ends_with(".zip") {
+        archive.push_str(".zip");
+    }
+    let data = fs_ops::read(&archive).map_err(|e| format!("unzip: {}: {e}", opts.archive))?;
+    let entries = zip::read_archive(&data).map_err(|e| format!("unzip: {archive}: {e}"))?;
+
+    let mut matched = vec![false; opts.patterns.len()];
+    let selected: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| {
+            if opts.patterns.is_empty() {
+                return true;
+            }
+            let mut any = false;
+            for (i, pattern) in opts.patterns.iter().enumerate() {
+                if glob::matches(pattern, &entry.name) {
+                    matched[i] = true;
+                    any = true;
+                }
+            }
+            any
+        })
+        .collect();
+
+    let mut unzip = Unzip {
+        opts: &opts,
+        output: Vec::new(),
+        messages: Vec::new(),
+    };
+    if !opts.quiet && opts.mode != Mode::Pipe {
+        unzip.line(format!("Archive:  {archive}"));
+    }
+    match opts.mode {
+        Mode::List => unzip.list(&selected),
+        Mode::Test => unzip.test(&selected, &archive),
+        Mode::Pipe => {
+            for entry in selected {
+                match entry.contents() {
+                    Ok(data) => unzip.output.extend(data),
+                    Err(e) => unzip.message(format!("{}: {e}", entry.name)),
+                }
+            }
+        }
+        Mode::Extract => {
+            for entry in selected {
+                unzip.extract(entry);
+            }
+        }
+    }
+    for (pattern, matched) in opts.patterns.iter().zip(matched) {
+        if !matched {
+            unzip
+                .messages
+                .push(format!("caution: filename not matched:  {pattern}"));
+        }
+    }
+    unzip.finish()
+}
+
+struct Unzip<'a> {
+    opts: &'a Opts,
+    output: Vec<u8>,
+    /// Warnings and errors, reported after the output.
+    messages: Vec<String>,
+}
+
+impl Unzip<'_> {
+    fn line(&mut self, line: String) {
+        self.output.extend(line.as_bytes());
+        self.output.push(b'\n');
+    }
+
+    fn message(&mut self, message: String) {
+        self.messages.push(format!("unzip: {message}"));
+    }
+
+    fn list(&mut self, entries: &[&Entry]) {
+        self.line("  Length      Date    Time    Name".into());
+        self.line("---------  ---------- -----   ----".into());
+        let mut total = 0u64;
+        for entry in entries {
+            total += entry.size as u64;
+            self.line(format!(
+                "{:>9}  {}   {}",
+                entry.size,
+                entry.modified(),
+                entry.name
+            ));
+        }
+        self.line("---------                     -------".into());
+        let files = match entries.len() {
+            1 => "1 file".to_string(),
+            n => format!("{n} files"),
+        };
+        self.line(format!("{total:>9}                     {files}"));
+    }
+
+    fn test(&mut self, entries: &[&Entry], archive: &str) {
+        let mut failed = false;
+        for entry in entries {
+            let status = match entry.contents() {
+                Ok(_) => "OK".to_string(),
+                Err(e) => {
+                    failed = true;
+                    e
+                }
+            };
+            if !self.opts.quiet || status != "OK" {
+                self.line(format!("    testing: {:<22}   {status}", entry.name));
+            }
+        }
+        match failed {
+            true => self.message(format!("At least one error was detected in {archive}.")),
+            false => self.line(format!(
+                "No errors detected in compressed data of {archive}."
+            )),
+        }
+    }
+
+    fn extract(&mut self, entry: &Entry) {
+        let name = entry.name.trim_start_matches('/');
+        if name.split('/').any(|part| part == "..") {
+            self.message(format!("{}: skipped: path contains '..'", entry.name));
+            return;
+        }
+        let target = match &self.opts.directory {
+            Some(dir) => format!("{}/{name}", dir.trim_end_matches('/')),
+            None => name.to_string(),
+        };
+        if entry.is_dir() {
+            if let Err(e) = fs_ops::mkdir(target.trim_end_matches('/')) {
+                self.message(format!("{target}: {e}"));
+            } else if !self.opts.quiet {
+                self.line(format!("   creating: {target}"));
+            }
+            return;
+        }
+        if fs_ops::exists(&target).unwrap_or(false) {
+            match self.opts.overwrite {
+                Some(true) => {}
+                Some(false) => return,
+                None => {
+                    self.message(format!("{target} already exists; use -o to overwrite"));
+                    return;
+                }
+            }
+        }
+        let data = match entry.contents() {
+            Ok(data) => data,
+            Err(e) => {
+                self.message(format!("{}: {e}", entry.name));
+                return;
+            }
+        };
+        if let Err(e) = fs_ops::write(&target, &data) {
+            self.message(format!("{target}: {e}"));
+            return;
+        }
+        if !self.opts.quiet {
+            let verb = match entry.method {
+                0 => " extracting",
+                _ => "  inflating",
+            };
+            self.line(format!("{verb}: {target}"));
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>, String> {
+        match self.messages.is_empty() {
+            true => Ok(self.output),
+            false => Err(format!(
+                "{}{}",
+                String::from_utf8_lossy(&self.output),
+                self.messages.join("\n")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("unzip", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    /// A directory holding `a.zip`, made from `src/dir/a.txt` and
+    /// `src/b.txt`, and the prefix of its entry names.
+    fn archive() -> (tempfile::TempDir, String, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_str().unwrap().to_string();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("dir")).unwrap();
+        std::fs::write(src.join("dir/a.txt"), "aaaa ".repeat(20)).unwrap();
+        std::fs::write(src.join("b.txt"), "b").unwrap();
+        let args = format!("-q -r {base}/a.zip {base}/src/dir {base}/src/b.txt");
+        zip::run_bytes(&args, None).unwrap();
+        let prefix = format!("{}/src", base.trim_start_matches('/'));
+        (dir, base, prefix)
+    }
+
+    #[test]
+    fn extract_to_directory() {
+        let (_dir, base, prefix) = archive();
+        let out = run(&format!("{base}/a.zip -d {base}/out"), None).unwrap();
+        let target = format!("{base}/out/{prefix}");
+        assert_eq!(
+            out,
+            format!(
+                "Archive:  {base}/a.zip\n   creating: {target}/dir/\n  inflating: {target}/dir/a.txt\n extracting: {target}/b.txt\n"
+            )
+        );
+        let a = std::fs::read_to_string(format!("{target}/dir/a.txt")).unwrap();
+        assert_eq!(a, "aaaa ".repeat(20));
+
+        let args = format!("-q {base}/a.zip '*/b.txt' -d {base}/out");
+        let err = run(&args, None).unwrap_err();
+        assert_eq!(
+            err,
+            format!("unzip: {target}/b.txt already exists; use -o to overwrite")
+        );
+        std::fs::write(format!("{target}/b.txt"), "changed").unwrap();
+        run(&format!("-n -q {base}/a.zip -d {base}/out"), None).unwrap();
+        let b = std::fs::read_to_string(format!("{target}/b.txt")).unwrap();
+        assert_eq!(b, "changed");
+        run(&format!("-o -q {base}/a.zip -d {base}/out"), None).unwrap();
+        let b = std::fs::read_to_string(format!("{target}/b.txt")).unwrap();
+        assert_eq!(b, "b");
+    }
+
+    #[test]
+    fn list_test_and_pipe() {
+        let (_dir, base, prefix) = archive();
+        let list = run(&format!("-l {base}/a"), None).unwrap();
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines[1], "  Length      Date    Time    Name");
+        assert!(lines[4].starts_with("      100  "));
+        assert!(lines[4].ends_with(&format!("   {prefix}/dir/a.txt")));
+        assert_eq!(lines[7], "      101                     3 files");
+
+        let test = run(&format!("-t {base}/a.zip"), None).unwrap();
+        assert!(test.contains(&format!("    testing: {prefix}/b.txt   OK\n")));
+        assert!(test.ends_with(&format!(
+            "No errors detected in compressed data of {base}/a.zip.\n"
+        )));
+
+        let piped = run(&format!("-p {base}/a.zip '*.txt'"), None).unwrap();
+        assert_eq!(piped, format!("{}b", "aaaa ".repeat(20)));
+    }
+
+    #[test]
+    fn unmatched_and_invalid() {
+        let (_dir, base, _) = archive();
+        let err = run(&format!("-p {base}/a.zip nope"), None).unwrap_err();
+        assert_eq!(err, "caution: filename not matched:  nope");
+        std::fs::write(format!("{base}/bad.zip"), "junk").unwrap();
+        let err = run(&format!("-l {base}/bad.zip"), None).unwrap_err();
+        assert_eq!(
+            err,
+            format!("unzip: {base}/bad.zip: End-of-central-directory signature not found")
+        );
+    }
+}
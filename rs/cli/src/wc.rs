@@ -1,31 +1,40 @@
+use crate::args::{Arg, Parser};
+use crate::bytes;
 use crate::fs_ops;
+use crate::json;
+use serde_json::{Map, Value};
 
 struct Opts {
     lines: bool,
     words: bool,
     chars: bool,
+    json: bool,
     paths: Vec<String>,
 }
 
-fn parse_opts(args: &str) -> Opts {
+fn parse_opts(args: &str) -> Result<Opts, String> {
     let mut opts = Opts {
         lines: false,
         words: false,
         chars: false,
+        json: false,
         paths: Vec::new(),
     };
-    for token in args.split_whitespace() {
-        if token.starts_with('-') && token.len() > 1 && !token.starts_with("--") {
-            for c in token[1..].chars() {
-                match c {
-                    'l' => opts.lines = true,
-                    'w' => opts.words = true,
-                    'c' | 'm' => opts.chars = true,
-                    _ => {}
-                }
-            }
-        } else {
-            opts.paths.push(token.to_string());
+    let mut parser = Parser::new("wc", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('l') => opts.lines = true,
+            Arg::Short('w') => opts.words = true,
+            Arg::Short('c' | 'm') => opts.chars = true,
+            Arg::Long(l) => match l.as_str() {
+                "lines" => opts.lines = true,
+                "words" => opts.words = true,
+                "chars" | "bytes" => opts.chars = true,
+                "json" => opts.json = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) => opts.paths.push(path),
+            arg => return Err(parser.unexpected(&arg)),
         }
     }
     // default: show all
@@ -34,39 +43,54 @@ fn parse_opts(args: &str) -> Opts {
         opts.words = true;
         opts.chars = true;
     }
-    opts
+    Ok(opts)
 }
 
-pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
-    let opts = parse_opts(args);
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
     let mut output = String::new();
     let mut total = (0usize, 0usize, 0usize);
+    let mut entries = Vec::new();
 
     if opts.paths.is_empty() {
         let input = stdin.unwrap_or_default();
         let (l, w, c) = count(&input);
-        format_line(&opts, l, w, c, None, &mut output);
+        if opts.json {
+            entries.push(format_json(&opts, l, w, c, None));
+        } else {
+            format_line(&opts, l, w, c, None, &mut output);
+        }
     } else {
         for path in &opts.paths {
-            let content = fs_ops::read_to_string(path)
-                .map_err(|e| format!("wc: {path}: {e}"))?;
+            let content = fs_ops::read(path).map_err(|e| format!("wc: {path}: {e}"))?;
             let (l, w, c) = count(&content);
             total.0 += l;
             total.1 += w;
             total.2 += c;
-            format_line(&opts, l, w, c, Some(path), &mut output);
+            if opts.json {
+                entries.push(format_json(&opts, l, w, c, Some(path)));
+            } else {
+                format_line(&opts, l, w, c, Some(path), &mut output);
+            }
         }
-        if opts.paths.len() > 1 {
+        // JSON leaves the total to the consumer.
+        if opts.paths.len() > 1 && !opts.json {
             format_line(&opts, total.0, total.1, total.2, Some("total"), &mut output);
         }
     }
-    Ok(output)
+    if opts.json {
+        return Ok(json::array(&entries).into_bytes());
+    }
+    Ok(output.into_bytes())
 }
 
-fn count(text: &str) -> (usize, usize, usize) {
-    let lines = text.lines().count();
-    let words = text.split_whitespace().count();
-    let chars = text.len();
+fn count(data: &[u8]) -> (usize, usize, usize) {
+    let lines = bytes::lines(data).count();
+    let words = data
+        .split(u8::is_ascii_whitespace)
+        .filter(|w| !w.is_empty())
+        .count();
+    let chars = data.len();
     (lines, words, chars)
 }
 
@@ -89,10 +113,36 @@ fn format_line(opts: &Opts, lines: usize, words: usize, chars: usize, name: Opti
     output.push('\n');
 }
 
+/// The counts as a JSON object, with a `null` path for stdin.
+fn format_json(
+    opts: &Opts,
+    lines: usize,
+    words: usize,
+    chars: usize,
+    name: Option<&str>,
+) -> Value {
+    let mut fields = Map::new();
+    fields.insert("path".into(), name.into());
+    if opts.lines {
+        fields.insert("lines".into(), lines.into());
+    }
+    if opts.words {
+        fields.insert("words".into(), words.into());
+    }
+    if opts.chars {
+        fields.insert("bytes".into(), chars.into());
+    }
+    Value::Object(fields)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("wc", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
     fn cmd(args: &str, stdin: Option<&str>) -> Result<String, String> {
         run(args, stdin.map(String::from))
     }
@@ -169,4 +219,26 @@ mod tests {
         let out = cmd("-m", Some("hello")).unwrap();
         assert!(out.trim().starts_with("5"));
     }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            cmd("--json", Some("hello world\nfoo\n")).unwrap(),
+            "[\n  {\"path\":null,\"lines\":2,\"words\":3,\"bytes\":16}\n]\n"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::write(&a, "x\n").unwrap();
+        std::fs::write(&b, "y z\n").unwrap();
+        let out = cmd(&format!("-lw --json {} {}", a.display(), b.display()), None).unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "[\n  {{\"path\":\"{}\",\"lines\":1,\"words\":1}},\n  \
+                 {{\"path\":\"{}\",\"lines\":1,\"words\":2}}\n]\n",
+                a.display(),
+                b.display()
+            )
+        );
+    }
 }
@@ -0,0 +1,305 @@
+//! `xargs` builds command lines from its input and runs them through the
+//! shell's dispatch, so any cli utility can be the command (`echo` by
+//! default). Input items are separated by blanks and newlines, honouring
+//! quotes and backslashes, unless `-d` or `-0` give a delimiter.
+
+use crate::args::{self, Arg, Parser};
+
+struct Opts {
+    /// Items per command line with `-n`.
+    max_args: Option<usize>,
+    /// Input lines per command line with `-L` (and `-I`).
+    max_lines: Option<usize>,
+    replace: Option<String>,
+    delimiter: Option<char>,
+    no_run_if_empty: bool,
+    command: Vec<String>,
+}
+
+fn positive(value: &str, option: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "xargs: invalid number \"{value}\" for -{option} option"
+        )),
+    }
+}
+
+fn parse_delimiter(spec: &str) -> Result<char, String> {
+    let mut chars = spec.chars();
+    let delimiter = match (chars.next(), chars.as_str()) {
+        (Some('\\'), "n") => Some('\n'),
+        (Some('\\'), "t") => Some('\t'),
+        (Some('\\'), "0") => Some('\0'),
+        (Some('\\'), "\\") => Some('\\'),
+        (Some('\\'), hex) if hex.starts_with('x') => u32::from_str_radix(&hex[1..], 16)
+            .ok()
+            .and_then(char::from_u32),
+        (Some(c), "") => Some(c),
+        _ => None,
+    };
+    delimiter.ok_or_else(|| {
+        format!(
+            "xargs: invalid input delimiter specification {spec}: the delimiter must be \
+             either a single character or an escape sequence starting with \\."
+        )
+    })
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        max_args: None,
+        max_lines: None,
+        replace: None,
+        delimiter: None,
+        no_run_if_empty: false,
+        command: Vec::new(),
+    };
+    let mut parser = Parser::new("xargs", args)?;
+    // Options end at the command; its own arguments pass through as is.
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('n') => {
+                opts.max_args = Some(positive(&parser.value()?, "n")?);
+                opts.max_lines = None;
+            }
+            Arg::Short('L') => {
+                opts.max_lines = Some(positive(&parser.value()?, "L")?);
+                opts.max_args = None;
+            }
+            Arg::Short('I') => opts.replace = Some(parser.value()?),
+            Arg::Short('d') => opts.delimiter = Some(parse_delimiter(&parser.value()?)?),
+            Arg::Short('0') => opts.delimiter = Some('\0'),
+            Arg::Number(n) if n == "0" => opts.delimiter = Some('\0'),
+            Arg::Short('r') => opts.no_run_if_empty = true,
+            Arg::Long(l) => match l.as_str() {
+                "max-args" => {
+                    opts.max_args = Some(positive(&parser.value()?, "n")?);
+                    opts.max_lines = None;
+                }
+                "max-lines" => {
+                    opts.max_lines = Some(positive(&parser.value()?, "L")?);
+                    opts.max_args = None;
+                }
+                "replace" => opts.replace = Some(parser.value()?),
+                "delimiter" => opts.delimiter = Some(parse_delimiter(&parser.value()?)?),
+                "null" => opts.delimiter = Some('\0'),
+                "no-run-if-empty" => opts.no_run_if_empty = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(name) => {
+                opts.command.push(name);
+                opts.command.extend(parser.rest());
+            }
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.command.is_empty() {
+        opts.command.push("echo".into());
+    }
+    if opts.replace.is_some() {
+        opts.max_lines = Some(1);
+        opts.max_args = None;
+    }
+    Ok(opts)
+}
+
+/// Splits the input into lines of items. Blanks separate items unless
+/// `blanks` is false (for `-I`, where each line is one item).
+fn items(input: &str, blanks: bool) -> Result<Vec<Vec<String>>, String> {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut item = String::new();
+    let mut in_item = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                if in_item {
+                    line.push(std::mem::take(&mut item));
+                    in_item = false;
+                }
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+            }
+            ' ' | '\t' if blanks || !in_item => {
+                if in_item {
+                    line.push(std::mem::take(&mut item));
+                    in_item = false;
+                }
+            }
+            '\'' | '"' => {
+                in_item = true;
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\n') | None => {
+                            let kind = if c == '\'' { "single" } else { "double" };
+                            return Err(format!(
+                                "xargs: unmatched {kind} quote; by default quotes are special \
+                                 to xargs unless you use the -0 option"
+                            ));
+                        }
+                        Some(q) => item.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                in_item = true;
+                if let Some(escaped) = chars.next() {
+                    item.push(escaped);
+                }
+            }
+            c => {
+                in_item = true;
+                item.push(c);
+            }
+        }
+    }
+    if in_item {
+        line.push(item);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+pub fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+    let opts = parse_opts(args)?;
+    let input = stdin.unwrap_or_default();
+    let lines: Vec<Vec<String>> = match opts.delimiter {
+        // With a delimiter every item stands alone and nothing is special.
+        Some(delimiter) => {
+            let mut pieces: Vec<&str> = input.split(delimiter).collect();
+            if pieces.last() == Some(&"") {
+                pieces.pop();
+            }
+            pieces
+                .into_iter()
+                .map(|item| vec![item.to_string()])
+                .collect()
+        }
+        None => items(&input, opts.replace.is_none())?,
+    };
+
+    // Each batch of items becomes one command line.
+    let mut batches: Vec<Vec<String>> = match (opts.max_args, opts.max_lines) {
+        (Some(n), _) => lines.concat().chunks(n).map(<[String]>::to_vec).collect(),
+        (None, Some(n)) => lines.chunks(n).map(<[Vec<String>]>::concat).collect(),
+        (None, None) => vec![lines.concat()],
+    };
+    batches.retain(|batch| !batch.is_empty());
+    // Like GNU xargs, run the command once even without input.
+    if batches.is_empty() && !opts.no_run_if_empty {
+        batches.push(Vec::new());
+    }
+
+    let (name, initial) = (&opts.command[0], &opts.command[1..]);
+    let mut output = String::new();
+    let mut failed = false;
+    for batch in batches {
+        let words: Vec<String> = match &opts.replace {
+            Some(replace) => {
+                let item = batch.concat();
+                initial
+                    .iter()
+                    .map(|arg| arg.replace(replace.as_str(), &item))
+                    .collect()
+            }
+            None => initial.iter().cloned().chain(batch).collect(),
+        };
+        let command_args: Vec<String> = words.iter().map(|w| args::quote(w)).collect();
+        let result = crate::shell::dispatch(name, &command_args.join(" "), None);
+        match result {
+            Ok(out) => output.push_str(&String::from_utf8_lossy(&out)),
+            Err(e) if e == format!("sh: {name}: command not found") => {
+                return Err(format!("{output}xargs: {name}: No such file or directory"));
+            }
+            Err(e) => {
+                failed = true;
+                output.push_str(&e);
+                if !e.is_empty() && !e.ends_with('\n') {
+                    output.push('\n');
+                }
+            }
+        }
+    }
+    match failed {
+        true => Err(format!("{output}xargs: exit status 123")),
+        false => Ok(output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str, stdin: &str) -> Result<String, String> {
+        run(args, Some(stdin.into()))
+    }
+
+    #[test]
+    fn echoes_items_by_default() {
+        assert_eq!(cmd("", "a b\nc\n").unwrap(), "a b c\n");
+        assert_eq!(cmd("", "").unwrap(), "\n");
+        assert_eq!(cmd("-r", "").unwrap(), "");
+        assert_eq!(
+            cmd("echo x", "'a  b' c\\ d \"e\"\n").unwrap(),
+            "x a  b c d e\n"
+        );
+    }
+
+    #[test]
+    fn batches() {
+        assert_eq!(cmd("-n 2", "1 2 3 4 5").unwrap(), "1 2\n3 4\n5\n");
+        assert_eq!(cmd("-L 2", "1 2\n3\n\n4\n").unwrap(), "1 2 3\n4\n");
+        assert_eq!(
+            cmd("-I {} echo [{}] {}x", "  one\ntwo words\n").unwrap(),
+            "[one] onex\n[two words] two wordsx\n"
+        );
+    }
+
+    #[test]
+    fn delimiters() {
+        assert_eq!(cmd("-0 -n 1", "a b\0c\0").unwrap(), "a b\nc\n");
+        assert_eq!(cmd("-d , echo", "x,'y',z").unwrap(), "x 'y' z\n");
+        assert!(
+            cmd("-d ab", "")
+                .unwrap_err()
+                .contains("invalid input delimiter")
+        );
+        assert!(
+            cmd("", "'open")
+                .unwrap_err()
+                .contains("unmatched single quote")
+        );
+    }
+
+    #[test]
+    fn runs_cli_utilities() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b c"));
+        std::fs::write(&a, "hello\n").unwrap();
+        std::fs::write(&b, "world\n").unwrap();
+        let input = format!("{}\n{}\n", a.display(), b.display());
+        assert_eq!(cmd("-d '\\n' cat", &input).unwrap(), "hello\nworld\n");
+        assert_eq!(
+            cmd("-d '\\n' grep -l world", &input).unwrap(),
+            format!("{}\n", b.display())
+        );
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(
+            cmd("-n 1 false", "a b").unwrap_err(),
+            "xargs: exit status 123"
+        );
+        assert_eq!(
+            cmd("nope", "a").unwrap_err(),
+            "xargs: nope: No such file or directory"
+        );
+    }
+}
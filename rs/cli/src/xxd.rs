@@ -0,0 +1,274 @@
+//! `xxd` hex dumps: the default offset/hex/ASCII layout, plain hex with
+//! `-p`, and `-r` to turn either form back into bytes.
+
+use crate::args::{Arg, Parser};
+use crate::bytes;
+use crate::fs_ops;
+
+struct Opts {
+    plain: bool,
+    reverse: bool,
+    upper: bool,
+    cols: Option<usize>,
+    group: Option<usize>,
+    len: Option<usize>,
+    seek: usize,
+    path: Option<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut opts = Opts {
+        plain: false,
+        reverse: false,
+        upper: false,
+        cols: None,
+        group: None,
+        len: None,
+        seek: 0,
+        path: None,
+    };
+    let mut parser = Parser::new("xxd", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('p') => opts.plain = true,
+            Arg::Short('r') => opts.reverse = true,
+            Arg::Short('u') => opts.upper = true,
+            Arg::Short('c') => opts.cols = Some(parser.number()?),
+            Arg::Short('g') => opts.group = Some(parser.number()?),
+            Arg::Short('l') => opts.len = Some(parser.number()?),
+            Arg::Short('s') => opts.seek = parser.number()?,
+            Arg::Long(l) => match l.as_str() {
+                "plain" | "ps" | "postscript" => opts.plain = true,
+                "revert" => opts.reverse = true,
+                "cols" => opts.cols = Some(parser.number()?),
+                "groupsize" => opts.group = Some(parser.number()?),
+                "len" => opts.len = Some(parser.number()?),
+                "seek" => opts.seek = parser.number()?,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(path) if opts.path.is_none() && path != "-" => opts.path = Some(path),
+            Arg::Value(path) if path == "-" => {}
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    if opts.cols == Some(0) {
+        return Err("xxd: invalid number of columns (0)".into());
+    }
+    Ok(opts)
+}
+
+pub fn run_bytes(args: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    let input = match &opts.path {
+        Some(path) => fs_ops::read(path).map_err(|e| format!("xxd: {path}: {e}"))?,
+        None => stdin.unwrap_or_default(),
+    };
+    if opts.reverse {
+        return match opts.plain {
+            true => revert_plain(&input),
+            false => revert(&input),
+        };
+    }
+    let start = opts.seek.min(input.len());
+    let end = match opts.len {
+        Some(len) => (start + len).min(input.len()),
+        None => input.len(),
+    };
+    let data = &input[start..end];
+    let output = match opts.plain {
+        true => dump_plain(data, opts.cols.unwrap_or(30), opts.upper),
+        false => dump(
+            data,
+            start,
+            opts.cols.unwrap_or(16),
+            opts.group.unwrap_or(2),
+            opts.upper,
+        ),
+    };
+    Ok(output.into_bytes())
+}
+
+fn hex(byte: u8, upper: bool) -> String {
+    match upper {
+        true => format!("{byte:02X}"),
+        false => format!("{byte:02x}"),
+    }
+}
+
+fn dump(data: &[u8], offset: usize, cols: usize, group: usize, upper: bool) -> String {
+    // A group size of 0 puts all bytes of a line in one group.
+    let group = if group == 0 { cols } else { group };
+    let width = cols * 2 + cols.div_ceil(group) - 1;
+    let mut output = String::new();
+    for (i, line) in data.chunks(cols).enumerate() {
+        let mut hex_area = String::new();
+        for (j, &byte) in line.iter().enumerate() {
+            if j > 0 && j % group == 0 {
+                hex_area.push(' ');
+            }
+            hex_area.push_str(&hex(byte, upper));
+        }
+        let text: String = line
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7e => b as char,
+                _ => '.',
+            })
+            .collect();
+        output.push_str(&format!(
+            "{:08x}: {hex_area:width$}  {text}\n",
+            offset + i * cols
+        ));
+    }
+    output
+}
+
+fn dump_plain(data: &[u8], cols: usize, upper: bool) -> String {
+    let mut output = String::new();
+    for line in data.chunks(cols) {
+        for &byte in line {
+            output.push_str(&hex(byte, upper));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Reverts a plain hex dump: pairs of hex digits, whitespace ignored.
+fn revert_plain(input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(input.len() / 2);
+    let mut high = None;
+    for &c in input {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        let digit =
+            hex_value(c).ok_or_else(|| format!("xxd: invalid hex digit '{}'", c as char))?;
+        match high.take() {
+            Some(h) => output.push(h << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    Ok(output)
+}
+
+/// Reverts the default layout. Each line's offset says where its bytes go;
+/// the hex area ends at the first run of two spaces, before the ASCII column.
+fn revert(input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    for (n, line) in bytes::lines(input).enumerate() {
+        let invalid = || format!("xxd: line {}: invalid hex dump", n + 1);
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            continue;
+        };
+        let offset = std::str::from_utf8(&line[..colon])
+            .ok()
+            .and_then(|s| usize::from_str_radix(s.trim(), 16).ok())
+            .ok_or_else(invalid)?;
+        let rest = &line[colon + 1..];
+        let rest = rest.strip_prefix(b" ").unwrap_or(rest);
+        let hex_area = match rest.windows(2).position(|w| w == b"  ") {
+            Some(end) => &rest[..end],
+            None => rest,
+        };
+        let data = revert_plain(hex_area).map_err(|_| invalid())?;
+        if output.len() < offset {
+            output.resize(offset, 0);
+        }
+        let end = offset + data.len();
+        if output.len() < end {
+            output.resize(end, 0);
+        }
+        output[offset..end].copy_from_slice(&data);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("xxd", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn cmd(args: &str, stdin: &[u8]) -> Result<String, String> {
+        run_bytes(args, Some(stdin.to_vec())).map(|out| String::from_utf8(out).unwrap())
+    }
+
+    const DATA: &[u8] = b"Hello, world!\nbinary\x00\x01\xff end";
+
+    #[test]
+    fn default_layout() {
+        assert_eq!(
+            cmd("", DATA).unwrap(),
+            "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 6269  Hello, world!.bi\n\
+             00000010: 6e61 7279 0001 ff20 656e 64              nary... end\n"
+        );
+    }
+
+    #[test]
+    fn columns_and_groups() {
+        assert_eq!(
+            cmd("-c 8 -g 1 -u", &DATA[..10]).unwrap(),
+            "00000000: 48 65 6C 6C 6F 2C 20 77  Hello, w\n\
+             00000008: 6F 72                    or\n"
+        );
+        assert_eq!(
+            cmd("-g 4", &DATA[..6]).unwrap(),
+            "00000000: 48656c6c 6f2c                        Hello,\n"
+        );
+    }
+
+    #[test]
+    fn seek_and_length() {
+        assert_eq!(
+            cmd("-s 7 -l 5", DATA).unwrap(),
+            "00000007: 776f 726c 64                             world\n"
+        );
+    }
+
+    #[test]
+    fn plain() {
+        let data: Vec<u8> = (0..40).collect();
+        let out = cmd("-p", &data).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 60);
+        assert_eq!(lines[1], "1e1f2021222324252627");
+    }
+
+    #[test]
+    fn revert_round_trip() {
+        let data: Vec<u8> = (0..=255).rev().collect();
+        let dump = cmd("", &data).unwrap();
+        assert_eq!(run_bytes("-r", Some(dump.into_bytes())).unwrap(), data);
+        let dump = cmd("-p", &data).unwrap();
+        assert_eq!(run_bytes("-r -p", Some(dump.into_bytes())).unwrap(), data);
+    }
+
+    #[test]
+    fn revert_honours_offsets() {
+        let out = run_bytes("-r", Some(b"00000004: 4142  AB\n".to_vec())).unwrap();
+        assert_eq!(out, b"\0\0\0\0AB");
+    }
+
+    #[test]
+    fn revert_plain_rejects_non_hex() {
+        let err = run_bytes("-r -p", Some(b"4g".to_vec())).unwrap_err();
+        assert!(err.contains("invalid hex digit 'g'"));
+    }
+
+    #[test]
+    fn reads_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("b.bin");
+        std::fs::write(&path, [0xde, 0xad]).unwrap();
+        let out = run(&format!("-p {}", path.display()), None).unwrap();
+        assert_eq!(out, "dead\n");
+    }
+}
@@ -0,0 +1,485 @@
+//! `zip` adds files to a zip archive, creating it or replacing existing
+//! entries of the same name, with Info-ZIP style output. The archive
+//! reader is shared with `unzip`.
+
+use crate::args::{Arg, Parser};
+use crate::deflate;
+use crate::fs_ops;
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR: u32 = 0x06054b50;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// An archive entry with its data as stored in the archive.
+pub struct Entry {
+    pub name: String,
+    pub method: u16,
+    pub crc: u32,
+    pub size: u32,
+    pub data: Vec<u8>,
+    pub time: u16,
+    pub date: u16,
+    attrs: u32,
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// The entry's contents, decompressed and checked against its CRC.
+    pub fn contents(&self) -> Result<Vec<u8>, String> {
+        let data = match self.method {
+            STORED => self.data.clone(),
+            DEFLATED => deflate::decompress(&self.data)?.0,
+            method => return Err(format!("unsupported compression method {method}")),
+        };
+        let crc = deflate::crc32(&data);
+        if crc != self.crc {
+            return Err(format!("bad CRC {crc:08x}  (should be {:08x})", self.crc));
+        }
+        Ok(data)
+    }
+
+    /// The modification time as `YYYY-MM-DD HH:MM`.
+    pub fn modified(&self) -> String {
+        let (date, time) = (self.date, self.time);
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}",
+            1980 + (date >> 9),
+            date >> 5 & 15,
+            date & 31,
+            time >> 11,
+            time >> 5 & 63
+        )
+    }
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16, String> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| "unexpected end of archive".to_string())
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32, String> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "unexpected end of archive".to_string())
+}
+
+/// Reads the entries listed in the archive's central directory.
+pub fn read_archive(data: &[u8]) -> Result<Vec<Entry>, String> {
+    // The end record is followed by a comment of at most 64 KiB.
+    let lowest = data.len().saturating_sub(22 + 0xffff);
+    let end = (lowest..data.len().saturating_sub(21))
+        .rev()
+        .find(|&pos| u32_at(data, pos) == Ok(END_OF_CENTRAL_DIR))
+        .ok_or("End-of-central-directory signature not found")?;
+    let count = u16_at(data, end + 10)?;
+    let mut pos = u32_at(data, end + 16)? as usize;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        if u32_at(data, pos)? != CENTRAL_HEADER {
+            return Err("bad central directory entry".into());
+        }
+        let flags = u16_at(data, pos + 8)?;
+        let compressed = u32_at(data, pos + 20)? as usize;
+        let name_len = u16_at(data, pos + 28)? as usize;
+        let extra_len = u16_at(data, pos + 30)? as usize;
+        let comment_len = u16_at(data, pos + 32)? as usize;
+        let offset = u32_at(data, pos + 42)? as usize;
+        let name = data
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or("unexpected end of archive")?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        if flags & 1 != 0 {
+            return Err(format!("{name}: encrypted entries are not supported"));
+        }
+        if u32_at(data, offset)? != LOCAL_HEADER {
+            return Err(format!("{name}: bad local header"));
+        }
+        let start =
+            offset + 30 + u16_at(data, offset + 26)? as usize + u16_at(data, offset + 28)? as usize;
+        let stored = data
+            .get(start..start + compressed)
+            .ok_or("unexpected end of archive")?;
+        entries.push(Entry {
+            name,
+            method: u16_at(data, pos + 10)?,
+            time: u16_at(data, pos + 12)?,
+            date: u16_at(data, pos + 14)?,
+            crc: u32_at(data, pos + 16)?,
+            size: u32_at(data, pos + 24)?,
+            data: stored.to_vec(),
+            attrs: u32_at(data, pos + 38)?,
+        });
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+fn write_archive(entries: &[Entry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for entry in entries {
+        let offset = out.len() as u32;
+        let version: u16 = if entry.method == DEFLATED || entry.is_dir() {
+            20
+        } else {
+            10
+        };
+        let mut common = Vec::new();
+        common.extend(version.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(entry.method.to_le_bytes());
+        common.extend(entry.time.to_le_bytes());
+        common.extend(entry.date.to_le_bytes());
+        common.extend(entry.crc.to_le_bytes());
+        common.extend((entry.data.len() as u32).to_le_bytes());
+        common.extend(entry.size.to_le_bytes());
+        common.extend((entry.name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+
+        out.extend(LOCAL_HEADER.to_le_bytes());
+        out.extend(&common);
+        out.extend(entry.name.as_bytes());
+        out.extend(&entry.data);
+
+        central.extend(CENTRAL_HEADER.to_le_bytes());
+        // Made by: Unix, zip 3.0.
+        central.extend((3u16 << 8 | 30).to_le_bytes());
+        central.extend(&common);
+        // Comment length, disk number and internal attributes.
+        central.extend([0; 6]);
+        central.extend(entry.attrs.to_le_bytes());
+        central.extend(offset.to_le_bytes());
+        central.extend(entry.name.as_bytes());
+    }
+    let offset = out.len() as u32;
+    out.extend(&central);
+    out.extend(END_OF_CENTRAL_DIR.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((entries.len() as u16).to_le_bytes());
+    out.extend((entries.len() as u16).to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+/// Converts seconds since the epoch (UTC) to MS-DOS date and time fields.
+fn dos_date_time(ts: u64) -> (u16, u16) {
+    let secs = ts;
+    let days = secs / 86400;
+    let time_of_day = secs % 86400;
+    let hour = time_of_day / 3600;
+    let minute = (time_of_day % 3600) / 60;
+    let second = time_of_day % 60;
+    let z = days as i64 + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = if m <= 2 { y + 1 } else { y };
+    // DOS dates start in 1980.
+    if y < 1980 {
+        return (0x21, 0);
+    }
+    let date = ((y - 1980) as u16) << 9 | (m as u16) << 5 | d as u16;
+    let time = (hour as u16) << 11 | (minute as u16) << 5 | (second / 2) as u16;
+    (date, time)
+}
+
+/// The percentage saved, rounded like Info-ZIP.
+fn percent(size: usize, compressed: usize) -> usize {
+    match size > compressed {
+        true => (200 * (size - compressed) / size).div_ceil(2),
+        false => 0,
+    }
+}
+
+struct Opts {
+    recursive: bool,
+    junk_paths: bool,
+    quiet: bool,
+    level: u32,
+    archive: String,
+    paths: Vec<String>,
+}
+
+fn parse_opts(args: &str) -> Result<Opts, String> {
+    let mut recursive = false;
+    let mut junk_paths = false;
+    let mut quiet = false;
+    let mut level = 6;
+    let mut operands = Vec::new();
+    let mut parser = Parser::new("zip", args)?;
+    while let Some(arg) = parser.next_arg()? {
+        match arg {
+            Arg::Short('r') => recursive = true,
+            Arg::Short('j') => junk_paths = true,
+            Arg::Short('q') => quiet = true,
+            Arg::Short(c @ '0'..='9') => level = c as u32 - '0' as u32,
+            Arg::Number(n) if n.len() == 1 => level = n.parse().unwrap_or(6),
+            Arg::Long(l) => match l.as_str() {
+                "recurse-paths" => recursive = true,
+                "junk-paths" => junk_paths = true,
+                "quiet" => quiet = true,
+                _ => return Err(parser.unexpected(&Arg::Long(l))),
+            },
+            Arg::Value(v) => operands.push(v),
+            arg => return Err(parser.unexpected(&arg)),
+        }
+    }
+    let mut operands = operands.into_iter();
+    let archive = operands.next().ok_or("zip: missing archive name")?;
+    // Like Info-ZIP, a new archive without an extension gets `.zip`.
+    let archive = match archive.rsplit('/').next().unwrap_or(&archive).contains('.')
+        || fs_ops::exists(&archive).unwrap_or(false)
+    {
+        true => archive,
+        false => format!("{archive}.zip"),
+    };
+    Ok(Opts {
+        recursive,
+        junk_paths,
+        quiet,
+        level,
+        archive,
+        paths: operands.collect(),
+    })
+}
+
+pub fn run_bytes(args: &str, _stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let opts = parse_opts(args)?;
+    let archive = &opts.archive;
+    let mut entries = match fs_ops::exists(archive).unwrap_or(false) {
+        true => {
+            let data = fs_ops::read(archive).map_err(|e| format!("zip: {archive}: {e}"))?;
+            read_archive(&data).map_err(|e| format!("zip error: {archive}: {e}"))?
+        }
+        false => Vec::new(),
+    };
+    let mut output = String::new();
+    let mut messages = Vec::new();
+
+    // (path on disk, name in the archive, is a directory)
+    let mut sources = Vec::new();
+    for path in &opts.paths {
+        let meta = match fs_ops::stat(path) {
+            Ok(meta) => meta,
+            Err(_) => {
+                messages.push(format!("zip warning: name not matched: {path}"));
+                continue;
+            }
+        };
+        let name = path.trim_start_matches('/').trim_start_matches("./");
+        let name = name.trim_end_matches('/');
+        if !meta.is_dir() {
+            sources.push((path.clone(), name.to_string(), false));
+            continue;
+        }
+        sources.push((path.clone(), format!("{name}/"), true));
+        if opts.recursive {
+            let children = fs_ops::ls(path, true).map_err(|e| format!("zip: {path}: {e}"))?;
+            let base = path.trim_end_matches('/');
+            for child in children {
+                let suffix = if child.is_dir() { "/" } else { "" };
+                sources.push((
+                    format!("{base}/{}", child.name),
+                    format!("{name}/{}{suffix}", child.name),
+                    child.is_dir(),
+                ));
+            }
+        }
+    }
+    let archive_name = archive.trim_start_matches("./");
+
+    let mut changed = false;
+    for (path, name, is_dir) in sources {
+        if path.trim_start_matches("./") == archive_name {
+            continue;
+        }
+        let name = match opts.junk_paths {
+            true if is_dir => continue,
+            true => name.rsplit('/').next().unwrap_or(&name).to_string(),
+            false => name,
+        };
+        let contents = match is_dir {
+            true => Vec::new(),
+            false => match fs_ops::read(&path) {
+                Ok(data) => data,
+                Err(e) => {
+                    messages.push(format!(
+                        "zip warning: could not open for reading: {path}: {e}"
+                    ));
+                    continue;
+                }
+            },
+        };
+        let mtime = fs_ops::stat(&path)
+            .ok()
+            .and_then(|m| m.last_modified)
+            .unwrap_or(0);
+        let (date, time) = dos_date_time(mtime);
+        let deflated = match opts.level > 0 && !contents.is_empty() {
+            true => Some(deflate::compress(&contents, opts.level)),
+            false => None,
+        };
+        // Data that does not shrink is stored, as Info-ZIP does.
+        let (method, data) = match deflated {
+            Some(deflated) if deflated.len() < contents.len() => (DEFLATED, deflated),
+            _ => (STORED, contents.clone()),
+        };
+        let summary = match method {
+            DEFLATED => format!("deflated {}%", percent(contents.len(), data.len())),
+            _ => "stored 0%".to_string(),
+        };
+        let mode: u32 = if is_dir { 0o40755 } else { 0o100644 };
+        let entry = Entry {
+            name,
+            method,
+            crc: deflate::crc32(&contents),
+            size: contents.len() as u32,
+            data,
+            time,
+            date,
+            attrs: mode << 16 | if is_dir { 0x10 } else { 0 },
+        };
+        if !opts.quiet {
+            let verb = match entries.iter().any(|e| e.name == entry.name) {
+                true => "updating",
+                false => "  adding",
+            };
+            output.push_str(&format!("{verb}: {} ({summary})\n", entry.name));
+        }
+        match entries.iter().position(|e| e.name == entry.name) {
+            Some(i) => entries[i] = entry,
+            None => entries.push(entry),
+        }
+        changed = true;
+    }
+    if !changed {
+        messages.push(format!("zip error: Nothing to do! ({archive})"));
+    } else {
+        fs_ops::write(archive, &write_archive(&entries))
+            .map_err(|e| format!("zip: {archive}: {e}"))?;
+    }
+    match messages.is_empty() {
+        true => Ok(output.into_bytes()),
+        false => Err(format!("{output}{}", messages.join("\n"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes;
+
+    fn run(args: &str, stdin: Option<String>) -> Result<String, String> {
+        bytes::into_text("zip", run_bytes(args, stdin.map(String::into_bytes))?)
+    }
+
+    fn tree() -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("proj");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("notes.txt"), "notes ".repeat(50)).unwrap();
+        std::fs::write(root.join("src/empty"), "").unwrap();
+        let base = dir.path().to_str().unwrap().to_string();
+        (dir, base)
+    }
+
+    fn entries(archive: &str) -> Vec<Entry> {
+        read_archive(&std::fs::read(archive).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn create_recursive() {
+        let (_dir, base) = tree();
+        let archive = format!("{base}/out.zip");
+        let out = run(&format!("-r {archive} {base}/proj"), None).unwrap();
+        let root = base.trim_start_matches('/');
+        assert_eq!(
+            out,
+            format!(
+                "  adding: {root}/proj/ (stored 0%)\n  adding: {root}/proj/notes.txt (deflated 92%)\n  adding: {root}/proj/src/ (stored 0%)\n  adding: {root}/proj/src/empty (stored 0%)\n"
+            )
+        );
+        let entries = entries(&archive);
+        assert_eq!(entries.len(), 4);
+        assert!(entries[0].is_dir());
+        assert_eq!(entries[1].method, DEFLATED);
+        assert_eq!(
+            entries[1].contents().unwrap(),
+            "notes ".repeat(50).as_bytes()
+        );
+        assert_eq!(entries[3].contents().unwrap(), b"");
+    }
+
+    #[test]
+    fn update_and_junk_paths() {
+        let (_dir, base) = tree();
+        let archive = format!("{base}/out");
+        let notes = format!("{base}/proj/notes.txt");
+        run(&format!("-j -q {archive} {notes}"), None).unwrap();
+        std::fs::write(&notes, "new").unwrap();
+        let out = run(&format!("-j -0 {archive} {notes}"), None).unwrap();
+        assert_eq!(out, "updating: notes.txt (stored 0%)\n");
+        // A missing extension is added to new archives.
+        let entries = entries(&format!("{archive}.zip"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].contents().unwrap(), b"new");
+    }
+
+    #[test]
+    fn nothing_to_do() {
+        let (_dir, base) = tree();
+        let err = run(&format!("{base}/out.zip {base}/missing"), None).unwrap_err();
+        assert_eq!(
+            err,
+            format!(
+                "zip warning: name not matched: {base}/missing\nzip error: Nothing to do! ({base}/out.zip)"
+            )
+        );
+    }
+
+    #[test]
+    fn corrupt_entries() {
+        let (_dir, base) = tree();
+        let archive = format!("{base}/out.zip");
+        run(&format!("-q {archive} {base}/proj/notes.txt"), None).unwrap();
+        let mut entries = entries(&archive);
+        entries[0].crc ^= 1;
+        assert!(entries[0].contents().unwrap_err().starts_with("bad CRC"));
+        let err = read_archive(b"not a zip").err();
+        assert_eq!(
+            err.as_deref(),
+            Some("End-of-central-directory signature not found")
+        );
+    }
+
+    #[test]
+    fn dos_dates() {
+        // 2024-02-29 13:45:30 UTC
+        let (date, time) = dos_date_time(1_709_214_330);
+        let entry = Entry {
+            name: String::new(),
+            method: STORED,
+            crc: 0,
+            size: 0,
+            data: Vec::new(),
+            time,
+            date,
+            attrs: 0,
+        };
+        assert_eq!(entry.modified(), "2024-02-29 13:45");
+        assert_eq!(dos_date_time(0), (0x21, 0));
+    }
+}
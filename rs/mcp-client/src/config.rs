@@ -0,0 +1,81 @@
+use std::env;
+use std::sync::LazyLock;
+
+const SERVERS_ENV: &str = "MCP_CLIENT_SERVERS";
+const TOKEN_ENV_PREFIX: &str = "MCP_CLIENT_TOKEN_";
+
+/// Remote servers, configured as a comma-separated list of
+/// `name=url` entries in `MCP_CLIENT_SERVERS`, with an optional
+/// bearer token per server in `MCP_CLIENT_TOKEN_<NAME>`.
+static SERVERS: LazyLock<Vec<Server>> = LazyLock::new(|| {
+    let raw = env::var(SERVERS_ENV).unwrap_or_default();
+    parse_servers(&raw, |key| env::var(key).ok().filter(|v| !v.is_empty()))
+});
+
+pub struct Server {
+    pub name: String,
+    pub url: String,
+    pub token: Option<String>,
+}
+
+pub fn servers() -> &'static [Server] {
+    &SERVERS
+}
+
+pub fn find_server(name: &str) -> Result<&'static Server, String> {
+    SERVERS
+        .iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("unknown MCP server: {name}"))
+}
+
+/// Parses `name=url` entries, skipping invalid ones.
+/// Names may only contain ASCII alphanumerics and `-`, since
+/// they are used in env var names and LLM tool names.
+fn parse_servers(raw: &str, lookup_token: impl Fn(&str) -> Option<String>) -> Vec<Server> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (name, url) = entry.trim().split_once('=')?;
+            let (name, url) = (name.trim(), url.trim());
+            if !is_valid_name(name) || !(url.starts_with("http://") || url.starts_with("https://"))
+            {
+                eprintln!("mcp-client: skipping invalid server entry: {entry}");
+                return None;
+            }
+            Some(Server {
+                name: name.to_owned(),
+                url: url.to_owned(),
+                token: lookup_token(&token_env_key(name)),
+            })
+        })
+        .collect()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn token_env_key(name: &str) -> String {
+    format!(
+        "{TOKEN_ENV_PREFIX}{}",
+        name.to_ascii_uppercase().replace('-', "_")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_servers() {
+        let servers = parse_servers(
+            " github=https://api.example.com/mcp/, bad name=https://x, my-docs=http://localhost:8080/mcp,nourl=ftp://x,",
+            |key| (key == "MCP_CLIENT_TOKEN_MY_DOCS").then(|| "t".to_owned()),
+        );
+        let names: Vec<&str> = servers.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["github", "my-docs"]);
+        assert_eq!(servers[0].url, "https://api.example.com/mcp/");
+        assert_eq!(servers[0].token, None);
+        assert_eq!(servers[1].token.as_deref(), Some("t"));
+    }
+}
@@ -0,0 +1,104 @@
+use serde_json::{Value, json};
+
+pub fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": params,
+    })
+}
+
+pub fn notification(method: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": method,
+    })
+}
+
+/// Extracts the result of request `id` from a response body.
+/// Streamable HTTP servers may answer with either a JSON document
+/// or an SSE stream, which can carry notifications (e.g. progress)
+/// before the response itself.
+pub fn parse_response(body: &str, content_type: &str, id: u64) -> Result<Value, String> {
+    let messages = match content_type.starts_with("text/event-stream") {
+        true => parse_event_stream(body),
+        false => {
+            let value: Value = serde_json::from_str(body)
+                .map_err(|e| format!("failed to parse response: {e}: {body}"))?;
+            match value {
+                Value::Array(items) => items,
+                value => vec![value],
+            }
+        }
+    };
+    let response = messages
+        .into_iter()
+        .find(|m| m.get("id").and_then(Value::as_u64) == Some(id))
+        .ok_or_else(|| format!("no response for request {id}"))?;
+    if let Some(error) = response.get("error") {
+        let message = error["message"].as_str().unwrap_or("unknown error");
+        return Err(match error["code"].as_i64() {
+            Some(code) => format!("{message} ({code})"),
+            None => message.to_owned(),
+        });
+    }
+    Ok(response.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Returns the JSON messages in the `data` fields of an SSE stream.
+fn parse_event_stream(body: &str) -> Vec<Value> {
+    body.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            match data.is_empty() {
+                true => None,
+                false => serde_json::from_str(&data.join("\n")).ok(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_response() {
+        let body = r#"{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}"#;
+        assert_eq!(
+            parse_response(body, "application/json", 2),
+            Ok(json!({ "tools": [] }))
+        );
+        assert!(parse_response(body, "application/json", 3).is_err());
+    }
+
+    #[test]
+    fn test_parse_error_response() {
+        let body =
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method not found"}}"#;
+        assert_eq!(
+            parse_response(body, "application/json", 1),
+            Err("Method not found (-32601)".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_parse_event_stream_response() {
+        let body = "event: message\r\n\
+            data: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\",\"params\":{}}\r\n\r\n\
+            : keep-alive\n\n\
+            event: message\n\
+            data: {\"jsonrpc\":\"2.0\",\n\
+            data: \"id\":4,\"result\":{\"content\":[]}}\n\n";
+        assert_eq!(
+            parse_response(body, "text/event-stream; charset=utf-8", 4),
+            Ok(json!({ "content": [] }))
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::bindings::exports::asterai::mcp_client::client::Guest as ClientGuest;
+use crate::bindings::exports::asterai::mcp_client::llm_tools::Guest as LlmToolsGuest;
+use crate::bindings::exports::asterai::mcp_client::types::{RemoteTool, ToolDefinition};
+use crate::config::Server;
+use crate::session::Session;
+use serde_json::Value;
+
+mod config;
+mod jsonrpc;
+mod names;
+mod session;
+
+#[allow(warnings)]
+mod bindings {
+    wit_bindgen::generate!({
+        path: "wit/package.wasm",
+        world: "component",
+        generate_all,
+    });
+}
+
+struct Component;
+
+impl ClientGuest for Component {
+    fn list_servers() -> Vec<String> {
+        config::servers().iter().map(|s| s.name.clone()).collect()
+    }
+
+    fn list_tools() -> Result<Vec<RemoteTool>, String> {
+        let tools = list_all_tools()?
+            .into_iter()
+            .map(|(server, tool)| RemoteTool {
+                server: server.name.clone(),
+                name: tool.name,
+                description: tool.description,
+                input_schema_json: tool.input_schema.to_string(),
+            })
+            .collect();
+        Ok(tools)
+    }
+
+    fn call_tool(server: String, name: String, arguments_json: String) -> Result<String, String> {
+        call(config::find_server(&server)?, &name, &arguments_json)
+    }
+}
+
+impl LlmToolsGuest for Component {
+    fn tool_definitions() -> Result<Vec<ToolDefinition>, String> {
+        let definitions = list_all_tools()?
+            .into_iter()
+            .map(|(server, tool)| ToolDefinition {
+                name: names::llm_tool_name(&server.name, &tool.name),
+                description: tool.description,
+                parameters_json_schema: tool.input_schema.to_string(),
+            })
+            .collect();
+        Ok(definitions)
+    }
+
+    fn call(name: String, arguments_json: String) -> Result<String, String> {
+        let (server, tool) = resolve_llm_tool(&name)?;
+        call(server, &tool, &arguments_json)
+    }
+}
+
+/// Finds the server and tool an LLM tool name refers to,
+/// by looking it up in the listed tools.
+fn resolve_llm_tool(name: &str) -> Result<(&'static Server, String), String> {
+    list_all_tools()?
+        .into_iter()
+        .find(|(server, tool)| names::llm_tool_name(&server.name, &tool.name) == name)
+        .map(|(server, tool)| (server, tool.name))
+        .ok_or_else(|| format!("unknown tool: {name}"))
+}
+
+/// Lists the tools of every configured server.
+/// Servers that fail are logged and skipped, unless all of them fail.
+fn list_all_tools() -> Result<Vec<(&'static Server, session::RemoteTool)>, String> {
+    let mut tools = Vec::new();
+    let mut errors = Vec::new();
+    for server in config::servers() {
+        let result = Session::open(server).and_then(|mut session| {
+            let tools = session.list_tools();
+            session.release();
+            tools
+        });
+        match result {
+            Ok(server_tools) => tools.extend(server_tools.into_iter().map(|t| (server, t))),
+            Err(e) => {
+                eprintln!("mcp-client: failed to list tools: {e}");
+                errors.push(e);
+            }
+        }
+    }
+    match tools.is_empty() && !errors.is_empty() {
+        true => Err(errors.join("; ")),
+        false => Ok(tools),
+    }
+}
+
+fn call(server: &Server, name: &str, arguments_json: &str) -> Result<String, String> {
+    let arguments: Value = match arguments_json.trim() {
+        "" => Value::Object(Default::default()),
+        raw => serde_json::from_str(raw).map_err(|e| format!("invalid arguments JSON: {e}"))?,
+    };
+    if !arguments.is_object() {
+        return Err("arguments must be a JSON object".to_owned());
+    }
+    let mut session = Session::open(server)?;
+    let result = session.call_tool(name, arguments);
+    session.release();
+    session::result_to_text(&result?)
+}
+
+bindings::export!(Component with_types_in bindings);
@@ -0,0 +1,82 @@
+/// Separates the server name from the tool name in LLM tool names.
+const SEPARATOR: &str = "__";
+/// The longest tool name LLM providers accept.
+const MAX_LEN: usize = 64;
+/// Length of the hash suffix of rewritten names.
+const HASH_LEN: usize = 8;
+
+/// Returns the LLM tool name for a server's tool, `{server}__{tool}`.
+/// LLM providers only accept names matching `^[a-zA-Z0-9_-]{1,64}$`,
+/// so any other character is replaced with `_`, and names over
+/// [`MAX_LEN`] are truncated. Rewritten names end in `_` and a hash
+/// of the original name, so they stay unique.
+/// Names cannot be split back into the server and tool, so calls
+/// are resolved by looking the name up in the listed tools.
+pub fn llm_tool_name(server: &str, tool: &str) -> String {
+    let name = format!("{server}{SEPARATOR}{tool}");
+    let sanitized: String = name
+        .chars()
+        .map(|c| if is_name_char(c) { c } else { '_' })
+        .collect();
+    if sanitized == name && name.len() <= MAX_LEN {
+        return name;
+    }
+    let end = sanitized.len().min(MAX_LEN - HASH_LEN - 1);
+    format!("{}_{:08x}", &sanitized[..end], fnv1a(name.as_bytes()))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-')
+}
+
+/// 32-bit FNV-1a, which is stable across builds unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_valid(name: &str) -> bool {
+        (1..=MAX_LEN).contains(&name.len()) && name.chars().all(is_name_char)
+    }
+
+    #[test]
+    fn test_short_names() {
+        assert_eq!(llm_tool_name("docs", "search"), "docs__search");
+        assert_eq!(llm_tool_name("docs", "search-all"), "docs__search-all");
+        let longest = format!("docs__{}", "a".repeat(58));
+        assert_eq!(llm_tool_name("docs", &"a".repeat(58)), longest);
+    }
+
+    #[test]
+    fn test_sanitized_names() {
+        let name = llm_tool_name("files", "asterai:cli/common/ls");
+        assert!(is_valid(&name));
+        assert!(name.starts_with("files__asterai_cli_common_ls_"));
+        assert_eq!(name.len(), "files__asterai_cli_common_ls_".len() + HASH_LEN);
+        assert_ne!(name, llm_tool_name("files", "asterai_cli/common/ls"));
+        assert_ne!(name, llm_tool_name("files", "asterai_cli_common_ls"));
+        assert!(is_valid(&llm_tool_name("s", &"é".repeat(40))));
+    }
+
+    #[test]
+    fn test_long_names() {
+        let tool = "asterai:cli/common/a-very-long-function-name-that-keeps-going";
+        let name = llm_tool_name("files", tool);
+        assert_eq!(name.len(), MAX_LEN);
+        assert!(is_valid(&name));
+        assert!(name.starts_with("files__asterai_cli_common_"));
+        assert_ne!(name, llm_tool_name("files", &format!("{tool}2")));
+        assert_eq!(name, llm_tool_name("files", tool));
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0x811c9dc5);
+        assert_eq!(fnv1a(b"a"), 0xe40c292c);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::config::Server;
+use crate::jsonrpc;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use waki::Client;
+
+const CLIENT_NAME: &str = "asterai-mcp-client";
+const CLIENT_VERSION: &str = "0.1.0";
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+/// Upper bound on `tools/list` pages, in case a server
+/// keeps returning a cursor.
+const MAX_TOOL_PAGES: usize = 100;
+
+/// Initialized sessions by server name, so calls skip the handshake.
+/// Servers may expire a session at any time, answering HTTP 404,
+/// in which case a new one is started.
+static SESSIONS: LazyLock<Mutex<HashMap<String, CachedSession>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct CachedSession {
+    session_id: Option<String>,
+    next_id: u64,
+}
+
+/// A remote tool as returned by `tools/list`.
+pub struct RemoteTool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+/// An initialized connection to a remote MCP server
+/// over the streamable HTTP transport.
+pub struct Session<'a> {
+    server: &'a Server,
+    session_id: Option<String>,
+    next_id: u64,
+}
+
+impl<'a> Session<'a> {
+    /// Resumes the cached session for `server`, or starts a new one.
+    /// Call [`Session::release`] when done so later calls can reuse it.
+    pub fn open(server: &'a Server) -> Result<Self, String> {
+        let cached = SESSIONS.lock().unwrap().remove(&server.name);
+        if let Some(cached) = cached {
+            return Ok(Self {
+                server,
+                session_id: cached.session_id,
+                next_id: cached.next_id,
+            });
+        }
+        let mut session = Self {
+            server,
+            session_id: None,
+            next_id: 1,
+        };
+        session.initialize()?;
+        Ok(session)
+    }
+
+    /// Caches the session for the next call to the same server.
+    pub fn release(self) {
+        let cached = CachedSession {
+            session_id: self.session_id,
+            next_id: self.next_id,
+        };
+        SESSIONS
+            .lock()
+            .unwrap()
+            .insert(self.server.name.clone(), cached);
+    }
+
+    fn initialize(&mut self) -> Result<(), String> {
+        self.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": {
+                    "name": CLIENT_NAME,
+                    "version": CLIENT_VERSION,
+                },
+            }),
+        )?;
+        self.send(&jsonrpc::notification("notifications/initialized"))?;
+        Ok(())
+    }
+
+    pub fn list_tools(&mut self) -> Result<Vec<RemoteTool>, String> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_TOOL_PAGES {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params)?;
+            let page = result["tools"].as_array().cloned().unwrap_or_default();
+            tools.extend(page.into_iter().filter_map(parse_tool));
+            cursor = result["nextCursor"].as_str().map(str::to_owned);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(tools)
+    }
+
+    pub fn call_tool(&mut self, name: &str, arguments: Value) -> Result<Value, String> {
+        self.request(
+            "tools/call",
+            json!({
+                "name": name,
+                "arguments": arguments,
+            }),
+        )
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = jsonrpc::request(id, method, params);
+        let (status, body, content_type) = self.post(&message)?;
+        let (body, content_type) = match status {
+            // The server expired the session: start a new one and retry once.
+            404 if self.session_id.is_some() && method != "initialize" => {
+                self.session_id = None;
+                self.initialize()?;
+                self.send(&message)?
+            }
+            _ => self.check_status(status, body, content_type)?,
+        };
+        jsonrpc::parse_response(&body, &content_type, id)
+            .map_err(|e| format!("{}: {method}: {e}", self.server.name))
+    }
+
+    /// Posts a message, returning the response body and content type.
+    fn send(&mut self, message: &Value) -> Result<(String, String), String> {
+        let (status, body, content_type) = self.post(message)?;
+        self.check_status(status, body, content_type)
+    }
+
+    fn check_status(
+        &self,
+        status: u16,
+        body: String,
+        content_type: String,
+    ) -> Result<(String, String), String> {
+        if !(200..300).contains(&status) {
+            return Err(format!("{}: HTTP {status}: {body}", self.server.name));
+        }
+        Ok((body, content_type))
+    }
+
+    /// Posts a message, returning the response status, body and content type.
+    fn post(&mut self, message: &Value) -> Result<(u16, String, String), String> {
+        let body = message.to_string();
+        let mut request = Client::new()
+            .post(&self.server.url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json, text/event-stream");
+        if let Some(token) = &self.server.token {
+            request = request.header("Authorization", &format!("Bearer {token}"));
+        }
+        if let Some(session_id) = &self.session_id {
+            request = request
+                .header(SESSION_ID_HEADER, session_id)
+                .header(PROTOCOL_VERSION_HEADER, PROTOCOL_VERSION);
+        }
+        let response = request
+            .body(body.as_bytes())
+            .send()
+            .map_err(|e| format!("{}: request failed: {e}", self.server.name))?;
+        if let Some(session_id) = header(&response, SESSION_ID_HEADER) {
+            self.session_id = Some(session_id);
+        }
+        let status = response.status_code();
+        let content_type = header(&response, "content-type").unwrap_or_default();
+        let body = response
+            .body()
+            .map_err(|e| format!("{}: failed to read response: {e}", self.server.name))?;
+        let body = String::from_utf8(body)
+            .map_err(|e| format!("{}: invalid response encoding: {e}", self.server.name))?;
+        Ok((status, body, content_type))
+    }
+}
+
+fn header(response: &waki::Response, name: &str) -> Option<String> {
+    response
+        .header(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+}
+
+fn parse_tool(tool: Value) -> Option<RemoteTool> {
+    Some(RemoteTool {
+        name: tool["name"].as_str()?.to_owned(),
+        description: tool["description"].as_str().unwrap_or_default().to_owned(),
+        input_schema: match tool.get("inputSchema") {
+            Some(schema) if schema.is_object() => schema.clone(),
+            _ => json!({ "type": "object", "properties": {} }),
+        },
+    })
+}
+
+/// Converts a `tools/call` result into text: the structured content
+/// as JSON if present, otherwise the content items joined by newlines.
+/// Results flagged with `isError` become errors.
+pub fn result_to_text(result: &Value) -> Result<String, String> {
+    let text = match result.get("structuredContent") {
+        Some(structured) if !structured.is_null() => structured.to_string(),
+        _ => result["content"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .map(content_to_text)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .unwrap_or_default(),
+    };
+    match result["isError"].as_bool().unwrap_or(false) {
+        true => Err(text),
+        false => Ok(text),
+    }
+}
+
+fn content_to_text(item: &Value) -> String {
+    let str_field = |value: &Value, key: &str| value[key].as_str().unwrap_or_default().to_owned();
+    match item["type"].as_str() {
+        Some("text") => str_field(item, "text"),
+        Some("image") | Some("audio") => {
+            let kind = str_field(item, "type");
+            let mime_type = str_field(item, "mimeType");
+            let len = item["data"].as_str().map(str::len).unwrap_or(0);
+            format!("[{kind} {mime_type}, {len} bytes base64]")
+        }
+        Some("resource") => {
+            let resource = &item["resource"];
+            match resource["text"].as_str() {
+                Some(text) => text.to_owned(),
+                None => format!("[resource {}]", str_field(resource, "uri")),
+            }
+        }
+        Some("resource_link") => format!("[resource {}]", str_field(item, "uri")),
+        _ => item.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_to_text() {
+        let result = json!({
+            "content": [
+                { "type": "text", "text": "hello" },
+                { "type": "image", "mimeType": "image/png", "data": "aGk=" },
+                { "type": "resource", "resource": { "uri": "file:///a", "text": "body" } },
+                { "type": "resource_link", "uri": "file:///b", "name": "b" },
+            ],
+        });
+        assert_eq!(
+            result_to_text(&result),
+            Ok("hello\n[image image/png, 4 bytes base64]\nbody\n[resource file:///b]".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_structured_result_to_text() {
+        let result = json!({
+            "content": [{ "type": "text", "text": "{\"n\":1}" }],
+            "structuredContent": { "n": 1 },
+        });
+        assert_eq!(result_to_text(&result), Ok(r#"{"n":1}"#.to_owned()));
+    }
+
+    #[test]
+    fn test_error_result_to_text() {
+        let result = json!({
+            "content": [{ "type": "text", "text": "not found" }],
+            "isError": true,
+        });
+        assert_eq!(result_to_text(&result), Err("not found".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_tool_defaults_schema() {
+        let tool = parse_tool(json!({ "name": "search" })).unwrap();
+        assert_eq!(tool.input_schema["type"], "object");
+        assert_eq!(tool.description, "");
+        assert!(parse_tool(json!({ "description": "no name" })).is_none());
+    }
+}
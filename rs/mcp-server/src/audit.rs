@@ -0,0 +1,313 @@
+use crate::bindings::asterai::host::api;
+use crate::keys::Caller;
+use serde_json::{Map, Value, json};
+use std::env;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const FS_COMPONENT_ENV: &str = "MCP_SERVER_AUDIT_FS";
+const LOG_PATH_ENV: &str = "MCP_SERVER_AUDIT_LOG";
+const ARGUMENTS_ENV: &str = "MCP_SERVER_AUDIT_ARGUMENTS";
+const FS_APPEND_FUNCTION: &str = "fs/append";
+const REDACTED: &str = "[REDACTED]";
+/// Keys whose values must never be logged, compared case-insensitively.
+const SENSITIVE_KEYS: &[&str] = &[
+    "secret",
+    "password",
+    "token",
+    "authorization",
+    "apikey",
+    "credential",
+    "credentials",
+    "code_verifier",
+    "accesstoken",
+    "refreshtoken",
+    "idtoken",
+    "authtoken",
+    "clientsecret",
+];
+/// Suffixes of `snake_case` or `kebab-case` keys whose values must never
+/// be logged, e.g. `refresh_token`. Keys like `max_tokens` or
+/// `progressToken` are not matched.
+const SENSITIVE_SUFFIXES: &[&str] = &[
+    "_token",
+    "-token",
+    "_secret",
+    "-secret",
+    "_password",
+    "-password",
+    "api_key",
+    "api-key",
+];
+/// Form keys that are only sensitive in OAuth requests, such as the
+/// authorization `code`. Tool arguments named `code` are left alone.
+const SENSITIVE_FORM_KEYS: &[&str] = &["code"];
+
+static CONFIG: LazyLock<AuditConfig> = LazyLock::new(|| {
+    let fs_component = env::var(FS_COMPONENT_ENV).ok().filter(|s| !s.is_empty());
+    let log_path = env::var(LOG_PATH_ENV).ok().filter(|s| !s.is_empty());
+    AuditConfig {
+        sink: match (fs_component, log_path) {
+            (Some(component), Some(path)) => Sink::Fs { component, path },
+            _ => Sink::Stderr,
+        },
+        log_arguments: env::var(ARGUMENTS_ENV).is_ok_and(|v| v == "redacted"),
+    }
+});
+
+/// Where audit records are written.
+/// Writing to a file requires an `asterai:fs` implementation
+/// (e.g. `asterai:fs-local` or `asterai:s3`) in the environment,
+/// set via `MCP_SERVER_AUDIT_FS`, and a path via `MCP_SERVER_AUDIT_LOG`.
+enum Sink {
+    Stderr,
+    Fs { component: String, path: String },
+}
+
+struct AuditConfig {
+    sink: Sink,
+    /// Whether to include arguments (with sensitive values redacted)
+    /// in addition to their size. Set `MCP_SERVER_AUDIT_ARGUMENTS=redacted`.
+    log_arguments: bool,
+}
+
+pub enum Outcome<'a> {
+    Success,
+    Error(&'a str),
+    /// The tool does not exist or the caller may not use it.
+    Rejected,
+    /// The client cancelled the request before the tool was called.
+    Cancelled,
+}
+
+/// Records a `tools/call` invocation as a JSON line.
+pub fn record_tool_call(
+    caller: &Caller,
+    tool_name: &str,
+    arguments: &Value,
+    duration: Duration,
+    outcome: Outcome,
+) {
+    let config = &*CONFIG;
+    let record = build_record(
+        caller,
+        tool_name,
+        arguments,
+        duration,
+        outcome,
+        config.log_arguments,
+        now_millis(),
+    );
+    let line = format!("{record}\n");
+    match &config.sink {
+        Sink::Stderr => eprint!("mcp-server: audit {line}"),
+        Sink::Fs { component, path } => {
+            let args = json!([path, line.as_bytes()]).to_string();
+            if let Err(e) = api::call_component_function(component, FS_APPEND_FUNCTION, &args) {
+                eprintln!("mcp-server: audit log write failed: {}", e.message);
+                eprint!("mcp-server: audit {line}");
+            }
+        }
+    }
+}
+
+fn build_record(
+    caller: &Caller,
+    tool_name: &str,
+    arguments: &Value,
+    duration: Duration,
+    outcome: Outcome,
+    log_arguments: bool,
+    timestamp_ms: u128,
+) -> Value {
+    let mut record = json!({
+        "timestamp_ms": timestamp_ms,
+        "caller": caller.to_string(),
+        "tool": tool_name,
+        "argument_bytes": arguments.to_string().len(),
+        "duration_ms": duration.as_millis(),
+    });
+    match outcome {
+        Outcome::Success => record["success"] = json!(true),
+        Outcome::Error(message) => {
+            record["success"] = json!(false);
+            record["error"] = json!(redact_text(message));
+        }
+        Outcome::Rejected => {
+            record["success"] = json!(false);
+            record["rejected"] = json!(true);
+        }
+        Outcome::Cancelled => {
+            record["success"] = json!(false);
+            record["cancelled"] = json!(true);
+        }
+    }
+    if log_arguments {
+        record["arguments"] = redact_json(arguments);
+    }
+    record
+}
+
+/// Returns a copy of `value` with the values of sensitive
+/// object keys (e.g. `client_secret`) replaced, recursively.
+pub fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = match is_sensitive_key(k) {
+                        true => Value::String(REDACTED.to_owned()),
+                        false => redact_json(v),
+                    };
+                    (k.clone(), v)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_json).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Redacts a JSON document if `text` parses as one,
+/// otherwise treats it as a form body.
+pub fn redact_text(text: &str) -> String {
+    match serde_json::from_str::<Value>(text) {
+        Ok(value) => redact_json(&value).to_string(),
+        Err(_) => redact_form(text),
+    }
+}
+
+/// Redacts sensitive values in a `key=value&...` form body,
+/// including authorization codes.
+pub fn redact_form(body: &str) -> String {
+    body.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((k, _))
+                if is_sensitive_key(k)
+                    || SENSITIVE_FORM_KEYS.contains(&k.to_ascii_lowercase().as_str()) =>
+            {
+                format!("{k}={REDACTED}")
+            }
+            _ => pair.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.contains(&key.as_str()) || SENSITIVE_SUFFIXES.iter().any(|s| key.ends_with(s))
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_json() {
+        let value = json!({
+            "path": "a.txt",
+            "client_secret": "s3cret",
+            "nested": [{ "API_KEY": "k", "keep": 1 }],
+        });
+        assert_eq!(
+            redact_json(&value),
+            json!({
+                "path": "a.txt",
+                "client_secret": REDACTED,
+                "nested": [{ "API_KEY": REDACTED, "keep": 1 }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_redact_form() {
+        assert_eq!(
+            redact_form("grant_type=authorization_code&code=abc&client_secret=s&code_verifier=v"),
+            "grant_type=authorization_code&code=[REDACTED]&client_secret=[REDACTED]&code_verifier=[REDACTED]"
+        );
+        assert_eq!(
+            redact_form("grant_type=refresh_token&refresh_token=r"),
+            "grant_type=refresh_token&refresh_token=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_is_sensitive_key() {
+        for key in [
+            "token",
+            "access_token",
+            "refresh_token",
+            "accessToken",
+            "client_secret",
+            "clientSecret",
+            "API_KEY",
+            "x-api-key",
+            "Authorization",
+            "code_verifier",
+        ] {
+            assert!(is_sensitive_key(key), "{key}");
+        }
+        for key in [
+            "max_tokens",
+            "progressToken",
+            "tokens",
+            "token_count",
+            "path",
+        ] {
+            assert!(!is_sensitive_key(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_redact_text() {
+        assert_eq!(
+            redact_text(r#"{"password":"p"}"#),
+            r#"{"password":"[REDACTED]"}"#
+        );
+        assert_eq!(redact_text("plain error"), "plain error");
+    }
+
+    #[test]
+    fn test_build_record() {
+        let arguments = json!({ "args": "-la", "token": "t" });
+        let record = build_record(
+            &Caller::anonymous(),
+            "asterai--cli_common_ls",
+            &arguments,
+            Duration::from_millis(12),
+            Outcome::Success,
+            true,
+            1000,
+        );
+        assert_eq!(record["caller"], "anonymous");
+        assert_eq!(record["tool"], "asterai--cli_common_ls");
+        assert_eq!(record["duration_ms"], 12);
+        assert_eq!(record["success"], true);
+        assert_eq!(record["arguments"]["token"], REDACTED);
+        assert_eq!(record["argument_bytes"], arguments.to_string().len());
+    }
+
+    #[test]
+    fn test_build_record_without_arguments() {
+        let record = build_record(
+            &Caller::anonymous(),
+            "t",
+            &json!({ "x": 1 }),
+            Duration::ZERO,
+            Outcome::Rejected,
+            false,
+            0,
+        );
+        assert!(record.get("arguments").is_none());
+        assert_eq!(record["rejected"], true);
+        assert_eq!(record["success"], false);
+    }
+}
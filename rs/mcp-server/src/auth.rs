@@ -1,94 +1,636 @@
+use crate::state;
 use base64::Engine;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::env;
 use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const SECRET_ENV: &str = "MCP_SERVER_SECRET";
+const REDIRECT_URIS_ENV: &str = "MCP_SERVER_REDIRECT_URIS";
+const LEGACY_TOKEN_ENV: &str = "MCP_SERVER_ALLOW_LEGACY_TOKEN";
+const AUTH_CODE_TTL_SECS: u64 = 5 * 60;
+pub const ACCESS_TOKEN_TTL_SECS: u64 = 60 * 60;
+const REFRESH_TOKEN_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+/// Token family record value once a family has been revoked.
+const REVOKED: &str = "revoked";
 
 static AUTH_CONFIG: LazyLock<Option<AuthConfig>> = LazyLock::new(|| {
     let secret = env::var(SECRET_ENV).ok()?;
     if secret.is_empty() {
         return None;
     }
-    let token = sha256_hex(&secret);
-    Some(AuthConfig { secret, token })
+    let redirect_uris = env::var(REDIRECT_URIS_ENV).ok().map(|raw| {
+        raw.split(',')
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+            .collect()
+    });
+    let allow_legacy_token = env::var(LEGACY_TOKEN_ENV).is_ok_and(|v| v == "true");
+    let legacy_token = hex::encode(Sha256::digest(secret.as_bytes()));
+    Some(AuthConfig {
+        secret,
+        redirect_uris,
+        legacy_token,
+        allow_legacy_token,
+    })
 });
 
 struct AuthConfig {
     secret: String,
-    token: String,
+    /// Redirect URIs accepted for clients that were not
+    /// dynamically registered. If unset, only registered
+    /// clients may be authorized.
+    redirect_uris: Option<Vec<String>>,
+    /// The static bearer token issued before OAuth token expiry
+    /// was added: the hex SHA-256 of the secret.
+    legacy_token: String,
+    /// Whether the legacy token is still accepted.
+    /// Set `MCP_SERVER_ALLOW_LEGACY_TOKEN=true`.
+    allow_legacy_token: bool,
+}
+
+/// Payloads of the signed, stateless credentials issued by the server.
+/// Each is encoded as `{base64url(json)}.{base64url(HMAC-SHA256)}`
+/// using the server secret as the key.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "typ", rename_all = "snake_case")]
+enum Claims {
+    /// A dynamically registered client. The client ID is the
+    /// signed claims, so no client storage is needed.
+    Client {
+        redirect_uris: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    Code {
+        client_id: String,
+        redirect_uri: String,
+        code_challenge: String,
+        exp: u64,
+    },
+    Access {
+        client_id: String,
+        exp: u64,
+    },
+    /// Every refresh token descends from one authorization code,
+    /// and shares that code's `family`. Each refresh issues the
+    /// next `generation`.
+    Refresh {
+        client_id: String,
+        family: String,
+        generation: u64,
+        iat: u64,
+        exp: u64,
+    },
+}
+
+pub struct TokenSet {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: u64,
 }
 
 pub fn is_auth_enabled() -> bool {
     AUTH_CONFIG.is_some()
 }
 
-pub fn verify_bearer(header_value: &str) -> bool {
+/// Returns whether `token` is an unexpired OAuth access token.
+pub fn verify_access_token(token: &str) -> bool {
     let Some(config) = AUTH_CONFIG.as_ref() else {
-        return true;
+        return false;
     };
-    let token = header_value.strip_prefix("Bearer ").unwrap_or(header_value);
-    token == config.token
+    match verify(&config.secret, token) {
+        Some(Claims::Access { exp, .. }) => exp > now(),
+        _ => false,
+    }
 }
 
-pub fn access_token() -> Option<&'static str> {
-    AUTH_CONFIG.as_ref().map(|c| c.token.as_str())
+/// Returns whether `token` is the legacy static bearer token and it
+/// is still accepted. Clients sending it while it is disabled get a
+/// log line explaining why they are rejected.
+pub fn verify_legacy_token(token: &str) -> bool {
+    AUTH_CONFIG
+        .as_ref()
+        .is_some_and(|config| legacy_token_allowed(config, token))
 }
 
-/// Generates a stateless authorization code.
-/// Format: `{code_challenge}.{hex(SHA256(code_challenge + ":" + secret))}`.
-pub fn generate_auth_code(code_challenge: &str) -> Option<String> {
-    let config = AUTH_CONFIG.as_ref()?;
-    let sig = sha256_hex(&format!("{code_challenge}:{}", config.secret));
-    Some(format!("{code_challenge}.{sig}"))
+fn legacy_token_allowed(config: &AuthConfig, token: &str) -> bool {
+    if !constant_time_eq(token.as_bytes(), config.legacy_token.as_bytes()) {
+        return false;
+    }
+    if !config.allow_legacy_token {
+        eprintln!(
+            "mcp-server: rejected legacy static bearer token; \
+             re-authorize the client or set {LEGACY_TOKEN_ENV}=true"
+        );
+    }
+    config.allow_legacy_token
 }
 
-/// Verifies an authorization code and PKCE code_verifier.
-/// Also validates client_secret if provided.
-pub fn verify_auth_code(code: &str, code_verifier: &str, client_secret: Option<&str>) -> bool {
+/// Checks a secret entered by the resource owner on the authorize page,
+/// or sent as `client_secret` by a confidential client.
+pub fn verify_secret(candidate: &str) -> bool {
     let Some(config) = AUTH_CONFIG.as_ref() else {
         return false;
     };
-    if let Some(cs) = client_secret {
-        if cs != config.secret {
-            return false;
+    constant_time_eq(candidate.as_bytes(), config.secret.as_bytes())
+}
+
+/// Registers a client (RFC 7591) and returns its client ID.
+pub fn register_client(redirect_uris: Vec<String>, name: Option<String>) -> Result<String, String> {
+    let config = AUTH_CONFIG.as_ref().ok_or("auth not enabled")?;
+    if redirect_uris.is_empty() {
+        return Err("redirect_uris is required".to_owned());
+    }
+    if let Some(invalid) = redirect_uris.iter().find(|u| !is_valid_redirect_uri(u)) {
+        return Err(format!("invalid redirect URI: {invalid}"));
+    }
+    let claims = Claims::Client {
+        redirect_uris,
+        name,
+    };
+    Ok(sign(&config.secret, &claims))
+}
+
+/// Returns whether `redirect_uri` may be used with `client_id`.
+/// Registered clients must use one of their registered URIs exactly.
+/// Other client IDs are rejected unless the URI is one configured
+/// in `MCP_SERVER_REDIRECT_URIS`.
+pub fn is_redirect_allowed(client_id: &str, redirect_uri: &str) -> bool {
+    AUTH_CONFIG
+        .as_ref()
+        .is_some_and(|config| redirect_allowed(config, client_id, redirect_uri))
+}
+
+fn redirect_allowed(config: &AuthConfig, client_id: &str, redirect_uri: &str) -> bool {
+    if let Some(Claims::Client { redirect_uris, .. }) = verify(&config.secret, client_id) {
+        return redirect_uris.iter().any(|u| u == redirect_uri);
+    }
+    match &config.redirect_uris {
+        Some(allowed) => allowed.iter().any(|u| u == redirect_uri),
+        None => false,
+    }
+}
+
+/// Returns the name a client gave when it registered, if any.
+pub fn client_name(client_id: &str) -> Option<String> {
+    let config = AUTH_CONFIG.as_ref()?;
+    match verify(&config.secret, client_id) {
+        Some(Claims::Client { name, .. }) => name,
+        _ => None,
+    }
+}
+
+/// Generates a short-lived authorization code bound to the client,
+/// redirect URI and PKCE challenge.
+pub fn generate_auth_code(
+    client_id: &str,
+    redirect_uri: &str,
+    code_challenge: &str,
+) -> Option<String> {
+    let config = AUTH_CONFIG.as_ref()?;
+    let claims = Claims::Code {
+        client_id: client_id.to_owned(),
+        redirect_uri: redirect_uri.to_owned(),
+        code_challenge: code_challenge.to_owned(),
+        exp: now() + AUTH_CODE_TTL_SECS,
+    };
+    Some(sign(&config.secret, &claims))
+}
+
+/// Verifies an authorization code and PKCE code_verifier,
+/// returning a new token set on success.
+/// `client_id` must match the code, as must `redirect_uri` if sent.
+/// Also validates client_secret if provided.
+///
+/// With a state store configured, each code can be redeemed once:
+/// redeeming it again is rejected and revokes the tokens it was
+/// exchanged for (see [`start_family`]). Without one, codes are stateless, so a code can be
+/// redeemed again, with the same `code_verifier`, until it expires
+/// after [`AUTH_CODE_TTL_SECS`].
+pub fn exchange_auth_code(
+    code: &str,
+    code_verifier: &str,
+    client_id: &str,
+    redirect_uri: Option<&str>,
+    client_secret: Option<&str>,
+) -> Option<TokenSet> {
+    let config = AUTH_CONFIG.as_ref()?;
+    if client_secret.is_some_and(|cs| !verify_secret(cs)) {
+        return None;
+    }
+    redeem_code(&config.secret, code, code_verifier, client_id, redirect_uri)?;
+    let family = token_family(code);
+    if state::is_enabled() {
+        start_family(&family)?;
+    }
+    Some(issue_tokens(&config.secret, client_id, &family, 0))
+}
+
+/// Exchanges a refresh token for a new token set, including a new
+/// refresh token. `client_id` must match the client the token was
+/// issued to.
+///
+/// With a state store configured, refresh tokens are rotated: only
+/// the latest generation of a family is accepted, and presenting an
+/// earlier one revokes the whole family. Without one, tokens are
+/// stateless and do not rotate, so every refresh token remains valid
+/// until it expires.
+pub fn refresh(refresh_token: &str, client_id: &str) -> Option<TokenSet> {
+    let config = AUTH_CONFIG.as_ref()?;
+    let (family, generation) = redeem_refresh(&config.secret, refresh_token, client_id)?;
+    let generation = match state::is_enabled() {
+        true => rotate_family(&family, generation)?,
+        false => generation + 1,
+    };
+    Some(issue_tokens(&config.secret, client_id, &family, generation))
+}
+
+/// Records a new token family. An existing record means the
+/// authorization code was already redeemed, so the family is
+/// revoked (OAuth 2.1 section 4.1.2).
+fn start_family(family: &str) -> Option<()> {
+    let key = family_key(family);
+    match state::read(&key) {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            eprintln!("mcp-server: authorization code reused, revoking token family");
+            revoke_family(&key);
+            return None;
+        }
+        Err(e) => {
+            eprintln!("mcp-server: failed to read token family: {e}");
+            return None;
+        }
+    }
+    match state::write(&key, "0") {
+        Ok(()) => Some(()),
+        Err(e) => {
+            eprintln!("mcp-server: failed to record token family: {e}");
+            None
         }
     }
-    let Some((challenge, sig)) = code.split_once('.') else {
+}
+
+/// Advances a family to its next generation, revoking
+/// it if `generation` is not the latest.
+fn rotate_family(family: &str, generation: u64) -> Option<u64> {
+    let key = family_key(family);
+    let record = match state::read(&key) {
+        Ok(record) => record,
+        Err(e) => {
+            eprintln!("mcp-server: failed to read token family: {e}");
+            return None;
+        }
+    };
+    let Some(next) = next_generation(record.as_deref(), generation) else {
+        eprintln!("mcp-server: stale or revoked refresh token, revoking token family");
+        revoke_family(&key);
+        return None;
+    };
+    match state::write(&key, &next.to_string()) {
+        Ok(()) => Some(next),
+        Err(e) => {
+            eprintln!("mcp-server: failed to record token family: {e}");
+            None
+        }
+    }
+}
+
+fn revoke_family(key: &str) {
+    if let Err(e) = state::write(key, REVOKED) {
+        eprintln!("mcp-server: failed to revoke token family: {e}");
+    }
+}
+
+/// Returns the generation to issue when `generation` is presented
+/// for a family whose latest recorded generation is `record`, or
+/// `None` if the token is stale, revoked or unknown.
+fn next_generation(record: Option<&str>, generation: u64) -> Option<u64> {
+    let latest: u64 = record?.parse().ok()?;
+    (latest == generation).then_some(generation + 1)
+}
+
+/// Derives the family ID from the authorization code,
+/// so redeeming the same code twice is detectable.
+fn token_family(code: &str) -> String {
+    hex::encode(&Sha256::digest(code.as_bytes())[..16])
+}
+
+fn family_key(family: &str) -> String {
+    format!("refresh/{family}")
+}
+
+fn redeem_code(
+    secret: &str,
+    code: &str,
+    code_verifier: &str,
+    client_id: &str,
+    redirect_uri: Option<&str>,
+) -> Option<()> {
+    let Some(Claims::Code {
+        client_id: code_client_id,
+        redirect_uri: code_redirect_uri,
+        code_challenge,
+        exp,
+    }) = verify(secret, code)
+    else {
+        return None;
+    };
+    let valid = exp > now()
+        && client_id == code_client_id
+        && redirect_uri.is_none_or(|r| r == code_redirect_uri)
+        && pkce_challenge(code_verifier) == code_challenge;
+    valid.then_some(())
+}
+
+/// Returns the family and generation of a valid refresh token.
+fn redeem_refresh(secret: &str, refresh_token: &str, client_id: &str) -> Option<(String, u64)> {
+    let Some(Claims::Refresh {
+        client_id: token_client_id,
+        family,
+        generation,
+        exp,
+        ..
+    }) = verify(secret, refresh_token)
+    else {
+        return None;
+    };
+    (exp > now() && client_id == token_client_id).then_some((family, generation))
+}
+
+/// Redirect URIs must be absolute, have no fragment, and use https
+/// unless they point to a loopback address (OAuth 2.1 section 2.3.1).
+/// Custom schemes used by native apps (e.g. `cursor://`) are allowed.
+pub fn is_valid_redirect_uri(uri: &str) -> bool {
+    let Some((scheme, rest)) = uri.split_once("://") else {
         return false;
     };
-    let expected_sig = sha256_hex(&format!("{challenge}:{}", config.secret));
-    if sig != expected_sig {
+    if rest.is_empty() || uri.contains('#') {
         return false;
     }
-    let verifier_hash = Sha256::digest(code_verifier.as_bytes());
-    let expected_challenge = URL_SAFE_NO_PAD.encode(verifier_hash);
-    challenge == expected_challenge
+    match scheme {
+        "https" => true,
+        "http" => {
+            let host = rest.split(['/', '?']).next().unwrap_or("");
+            let host = match host.starts_with('[') {
+                true => host.split(']').next().map(|h| &h[1..]).unwrap_or(""),
+                false => host.split(':').next().unwrap_or(""),
+            };
+            matches!(host, "localhost" | "127.0.0.1" | "::1")
+        }
+        "javascript" | "data" | "file" => false,
+        _ => scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')),
+    }
 }
 
-fn sha256_hex(input: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(input.as_bytes());
-    hex::encode(hasher.finalize())
+fn issue_tokens(secret: &str, client_id: &str, family: &str, generation: u64) -> TokenSet {
+    let now = now();
+    let access = Claims::Access {
+        client_id: client_id.to_owned(),
+        exp: now + ACCESS_TOKEN_TTL_SECS,
+    };
+    let refresh = Claims::Refresh {
+        client_id: client_id.to_owned(),
+        family: family.to_owned(),
+        generation,
+        iat: now,
+        exp: now + REFRESH_TOKEN_TTL_SECS,
+    };
+    TokenSet {
+        access_token: sign(secret, &access),
+        refresh_token: sign(secret, &refresh),
+        expires_in: ACCESS_TOKEN_TTL_SECS,
+    }
+}
+
+fn sign(secret: &str, claims: &Claims) -> String {
+    let payload = serde_json::to_vec(claims).unwrap_or_default();
+    let payload = URL_SAFE_NO_PAD.encode(payload);
+    let sig = URL_SAFE_NO_PAD.encode(mac(secret, &payload).finalize().into_bytes());
+    format!("{payload}.{sig}")
+}
+
+fn verify(secret: &str, token: &str) -> Option<Claims> {
+    let (payload, sig) = token.rsplit_once('.')?;
+    let sig = URL_SAFE_NO_PAD.decode(sig).ok()?;
+    mac(secret, payload).verify_slice(&sig).ok()?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    serde_json::from_slice(&payload).ok()
+}
+
+fn mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SECRET: &str = "test-secret";
+
+    fn test_config() -> AuthConfig {
+        AuthConfig {
+            secret: SECRET.to_owned(),
+            redirect_uris: None,
+            legacy_token: hex::encode(Sha256::digest(SECRET.as_bytes())),
+            allow_legacy_token: false,
+        }
+    }
+
+    #[test]
+    fn test_sign_verify_roundtrip() {
+        let claims = Claims::Access {
+            client_id: "client".to_owned(),
+            exp: 42,
+        };
+        let token = sign(SECRET, &claims);
+        assert_eq!(verify(SECRET, &token), Some(claims));
+        assert_eq!(verify("other-secret", &token), None);
+    }
+
+    #[test]
+    fn test_verify_rejects_tampering() {
+        let token = sign(
+            SECRET,
+            &Claims::Access {
+                client_id: "client".to_owned(),
+                exp: 42,
+            },
+        );
+        let (_, sig) = token.rsplit_once('.').unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(br#"{"typ":"access","client_id":"x","exp":99}"#);
+        assert_eq!(verify(SECRET, &format!("{forged}.{sig}")), None);
+        assert_eq!(verify(SECRET, "garbage"), None);
+    }
+
+    #[test]
+    fn test_issue_tokens() {
+        let tokens = issue_tokens(SECRET, "client", "fam", 3);
+        assert!(matches!(
+            verify(SECRET, &tokens.access_token),
+            Some(Claims::Access { exp, .. }) if exp > now()
+        ));
+        assert!(matches!(
+            verify(SECRET, &tokens.refresh_token),
+            Some(Claims::Refresh { family, generation: 3, .. }) if family == "fam"
+        ));
+        assert_eq!(tokens.expires_in, ACCESS_TOKEN_TTL_SECS);
+    }
+
+    #[test]
+    fn test_redeem_code() {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let code = sign(
+            SECRET,
+            &Claims::Code {
+                client_id: "client".to_owned(),
+                redirect_uri: "https://app.example.com/cb".to_owned(),
+                code_challenge: pkce_challenge(verifier),
+                exp: now() + AUTH_CODE_TTL_SECS,
+            },
+        );
+        let cb = Some("https://app.example.com/cb");
+        assert!(redeem_code(SECRET, &code, verifier, "client", cb).is_some());
+        assert!(redeem_code(SECRET, &code, verifier, "client", None).is_some());
+        assert!(redeem_code(SECRET, &code, verifier, "other", cb).is_none());
+        assert!(redeem_code(SECRET, &code, verifier, "", cb).is_none());
+        assert!(redeem_code(SECRET, &code, "wrong", "client", cb).is_none());
+        let evil = Some("https://evil.example.com/cb");
+        assert!(redeem_code(SECRET, &code, verifier, "client", evil).is_none());
+    }
+
+    #[test]
+    fn test_redeem_refresh() {
+        let tokens = issue_tokens(SECRET, "client", "fam", 2);
+        assert_eq!(
+            redeem_refresh(SECRET, &tokens.refresh_token, "client"),
+            Some(("fam".to_owned(), 2))
+        );
+        assert!(redeem_refresh(SECRET, &tokens.refresh_token, "other").is_none());
+        assert!(redeem_refresh(SECRET, &tokens.access_token, "client").is_none());
+    }
+
+    #[test]
+    fn test_next_generation() {
+        assert_eq!(next_generation(Some("0"), 0), Some(1));
+        assert_eq!(next_generation(Some("4"), 4), Some(5));
+        // A rotated-out token.
+        assert_eq!(next_generation(Some("4"), 3), None);
+        assert_eq!(next_generation(Some(REVOKED), 0), None);
+        assert_eq!(next_generation(None, 0), None);
+    }
+
+    #[test]
+    fn test_token_family() {
+        assert_eq!(token_family("code").len(), 32);
+        assert_eq!(token_family("code"), token_family("code"));
+        assert_ne!(token_family("code"), token_family("other"));
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // Example from RFC 7636 appendix B.
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_is_valid_redirect_uri() {
+        assert!(is_valid_redirect_uri(
+            "https://claude.ai/api/mcp/auth_callback"
+        ));
+        assert!(is_valid_redirect_uri(
+            "http://localhost:6274/oauth/callback"
+        ));
+        assert!(is_valid_redirect_uri("http://127.0.0.1/cb"));
+        assert!(is_valid_redirect_uri("http://[::1]:8080/cb"));
+        assert!(is_valid_redirect_uri("cursor://anysphere.cursor-mcp/oauth"));
+        assert!(!is_valid_redirect_uri("http://evil.example.com/cb"));
+        assert!(!is_valid_redirect_uri("http://localhost.evil.com/cb"));
+        assert!(!is_valid_redirect_uri("https://example.com/cb#frag"));
+        assert!(!is_valid_redirect_uri("javascript://alert(1)"));
+        assert!(!is_valid_redirect_uri("/relative"));
+    }
+
+    #[test]
+    fn test_redirect_allowed() {
+        let mut config = test_config();
+        let client_id = sign(
+            SECRET,
+            &Claims::Client {
+                redirect_uris: vec!["https://app.example.com/cb".to_owned()],
+                name: None,
+            },
+        );
+        assert!(redirect_allowed(
+            &config,
+            &client_id,
+            "https://app.example.com/cb"
+        ));
+        assert!(!redirect_allowed(
+            &config,
+            &client_id,
+            "https://evil.example.com/cb"
+        ));
+        // Unregistered or forged client IDs need a configured URI.
+        assert!(!redirect_allowed(
+            &config,
+            "forged",
+            "https://evil.example.com/cb"
+        ));
+        config.redirect_uris = Some(vec!["https://known.example.com/cb".to_owned()]);
+        assert!(redirect_allowed(
+            &config,
+            "static",
+            "https://known.example.com/cb"
+        ));
+        assert!(!redirect_allowed(
+            &config,
+            "static",
+            "https://evil.example.com/cb"
+        ));
+    }
+
     #[test]
-    fn test_sha256_hex() {
-        let hash = sha256_hex("test-secret");
-        assert_eq!(hash.len(), 64);
-        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    fn test_legacy_token_allowed() {
+        let mut config = test_config();
+        let legacy = hex::encode(Sha256::digest(SECRET.as_bytes()));
+        assert!(!legacy_token_allowed(&config, &legacy));
+        config.allow_legacy_token = true;
+        assert!(legacy_token_allowed(&config, &legacy));
+        assert!(!legacy_token_allowed(&config, SECRET));
     }
 
     #[test]
-    fn test_auth_code_roundtrip() {
-        // Can't test with AUTH_CONFIG (needs env var), but test the hashing logic
-        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(b"test-verifier"));
-        assert!(!challenge.is_empty());
-        assert!(!challenge.contains('='));
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
     }
 }
@@ -0,0 +1,233 @@
+use crate::bindings::asterai::host::api::FunctionInfo;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Value, json};
+
+const STRUCTURED_RESULT_KEY: &str = "result";
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Magic byte prefixes used to sniff the MIME type of binary results.
+const MAGIC_TYPES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+];
+
+/// Returns the JSON Schema for `structuredContent` if the function
+/// returns a structured (record, list, tuple, etc) value.
+pub fn output_schema(func: &FunctionInfo) -> Option<Value> {
+    let (type_name, type_schema) = output_type(func)?;
+    schema_for_output(type_name, type_schema)
+}
+
+/// Builds the `tools/call` result for a successful function call.
+/// `result` is the JSON-encoded return value from the host.
+pub fn tool_result(component_name: &str, func: &FunctionInfo, result: &str) -> Value {
+    let uri = resource_uri(component_name, func);
+    build_result(output_type(func), &uri, result)
+}
+
+/// MCP requires output schemas to describe an object, so non-object
+/// return types are wrapped in `{ "result": ... }`.
+fn schema_for_output(type_name: &str, type_schema: &str) -> Option<Value> {
+    if is_bytes_type(type_name) {
+        return None;
+    }
+    let schema: Value = serde_json::from_str(type_schema).ok()?;
+    if !is_structured_schema(&schema) {
+        return None;
+    }
+    if schema_type(&schema) == Some("object") {
+        return Some(schema);
+    }
+    Some(json!({
+        "type": "object",
+        "properties": { STRUCTURED_RESULT_KEY: schema },
+        "required": [STRUCTURED_RESULT_KEY],
+    }))
+}
+
+fn build_result(output_type: Option<(&str, &str)>, uri: &str, result: &str) -> Value {
+    let Some((type_name, type_schema)) = output_type else {
+        return text_result(result, false);
+    };
+    let Ok(value) = serde_json::from_str::<Value>(result) else {
+        return text_result(result, false);
+    };
+    if is_bytes_type(type_name) {
+        return match json_to_bytes(&value) {
+            Some(bytes) => binary_result(uri, &bytes),
+            None => text_result(result, false),
+        };
+    }
+    let Some(schema) = schema_for_output(type_name, type_schema) else {
+        return text_result(result, false);
+    };
+    let structured = match schema_type(&schema) {
+        Some("object") if value.is_object() => value,
+        _ => json!({ STRUCTURED_RESULT_KEY: value }),
+    };
+    let text = serde_json::to_string(&structured).unwrap_or_default();
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": structured,
+        "isError": false,
+    })
+}
+
+pub fn text_result(text: &str, is_error: bool) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+fn binary_result(uri: &str, bytes: &[u8]) -> Value {
+    let mime_type = sniff_mime_type(bytes);
+    let data = STANDARD.encode(bytes);
+    let content = match mime_type.starts_with("image/") {
+        true => json!({
+            "type": "image",
+            "data": data,
+            "mimeType": mime_type,
+        }),
+        false => json!({
+            "type": "resource",
+            "resource": {
+                "uri": uri,
+                "mimeType": mime_type,
+                "blob": data,
+            },
+        }),
+    };
+    json!({
+        "content": [content],
+        "isError": false,
+    })
+}
+
+/// Returns a URI identifying the output of a function call,
+/// e.g. `asterai://asterai:steel/steel/pdf`.
+fn resource_uri(component_name: &str, func: &FunctionInfo) -> String {
+    match &func.interface_name {
+        Some(iface) => format!("asterai://{component_name}/{iface}/{}", func.name),
+        None => format!("asterai://{component_name}/{}", func.name),
+    }
+}
+
+fn output_type(func: &FunctionInfo) -> Option<(&str, &str)> {
+    let output = func.output.as_ref()?;
+    Some((output.type_name.as_str(), output.type_schema.as_str()))
+}
+
+fn is_structured_schema(schema: &Value) -> bool {
+    matches!(schema_type(schema), Some("object") | Some("array"))
+}
+
+fn schema_type(schema: &Value) -> Option<&str> {
+    schema.get("type").and_then(|t| t.as_str())
+}
+
+fn is_bytes_type(type_name: &str) -> bool {
+    matches!(
+        type_name,
+        "list<u8>" | "option<list<u8>>" | "result<list<u8>, string>" | "result<list<u8>>"
+    )
+}
+
+/// Converts a JSON byte array (e.g. `[137, 80, 78]`) to bytes.
+/// Returns `None` if any element is not a `u8`.
+fn json_to_bytes(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|v| v.as_u64().and_then(|n| u8::try_from(n).ok()))
+        .collect()
+}
+
+fn sniff_mime_type(bytes: &[u8]) -> &'static str {
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    MAGIC_TYPES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map(|(_, mime)| *mime)
+        .unwrap_or(OCTET_STREAM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "asterai://asterai:x/api/get";
+
+    #[test]
+    fn test_output_schema_object() {
+        let schema = schema_for_output("entry", r#"{"type":"object","properties":{}}"#).unwrap();
+        assert_eq!(schema["type"], "object");
+        assert!(schema.get("properties").is_some());
+    }
+
+    #[test]
+    fn test_output_schema_wraps_array() {
+        let schema = schema_for_output("list<string>", r#"{"type":"array"}"#).unwrap();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["result"]["type"], "array");
+        assert_eq!(schema["required"], json!(["result"]));
+    }
+
+    #[test]
+    fn test_output_schema_none_for_scalars_and_bytes() {
+        assert!(schema_for_output("string", r#"{"type":"string"}"#).is_none());
+        assert!(schema_for_output("list<u8>", r#"{"type":"array"}"#).is_none());
+    }
+
+    #[test]
+    fn test_result_structured() {
+        let output = Some(("list<string>", r#"{"type":"array"}"#));
+        let result = build_result(output, URI, r#"["a","b"]"#);
+        assert_eq!(result["structuredContent"], json!({ "result": ["a", "b"] }));
+        assert_eq!(result["content"][0]["type"], "text");
+        assert_eq!(result["isError"], false);
+    }
+
+    #[test]
+    fn test_result_text() {
+        let output = Some(("string", r#"{"type":"string"}"#));
+        let result = build_result(output, URI, r#""hello""#);
+        assert!(result.get("structuredContent").is_none());
+        assert_eq!(result["content"][0]["text"], r#""hello""#);
+    }
+
+    #[test]
+    fn test_result_image() {
+        let output = Some(("list<u8>", r#"{"type":"array"}"#));
+        let result = build_result(output, URI, "[137,80,78,71,13,10,26,10,0]");
+        let content = &result["content"][0];
+        assert_eq!(content["type"], "image");
+        assert_eq!(content["mimeType"], "image/png");
+        assert_eq!(content["data"], STANDARD.encode(b"\x89PNG\r\n\x1a\n\0"));
+    }
+
+    #[test]
+    fn test_result_resource() {
+        let output = Some(("list<u8>", r#"{"type":"array"}"#));
+        let result = build_result(output, URI, "[37,80,68,70,45,49]");
+        let content = &result["content"][0];
+        assert_eq!(content["type"], "resource");
+        assert_eq!(content["resource"]["mimeType"], "application/pdf");
+        assert_eq!(content["resource"]["uri"], URI);
+    }
+
+    #[test]
+    fn test_sniff_mime_type() {
+        assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_mime_type(b"hello"), OCTET_STREAM);
+    }
+}
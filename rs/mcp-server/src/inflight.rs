@@ -0,0 +1,108 @@
+use crate::keys::Caller;
+use crate::state;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const RUNNING: &str = "running";
+const CANCELLED: &str = "cancelled";
+
+/// A `tools/call` request that can be cancelled.
+///
+/// `notifications/cancelled` arrives in a separate HTTP request,
+/// so requests are tracked through the state store (see [`state`]),
+/// keyed by caller and request ID. Without a state store,
+/// cancellation is unsupported and the notification is ignored.
+pub struct InFlight {
+    key: Option<String>,
+}
+
+impl InFlight {
+    /// Registers a request as running.
+    pub fn start(caller: &Caller, id: &Value) -> Self {
+        if !state::is_enabled() {
+            return Self::none();
+        }
+        let key = request_key(caller, id);
+        if let Err(e) = state::write(&key, RUNNING) {
+            eprintln!("mcp-server: [{caller}] failed to track request {id}: {e}");
+            return Self::none();
+        }
+        Self { key: Some(key) }
+    }
+
+    /// A request that is not tracked and so can never be cancelled.
+    pub fn none() -> Self {
+        Self { key: None }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        let Some(key) = &self.key else {
+            return false;
+        };
+        state::read(key).is_ok_and(|record| record.as_deref() == Some(CANCELLED))
+    }
+
+    /// Stops tracking the request, returning whether it was cancelled.
+    pub fn finish(self) -> bool {
+        let cancelled = self.is_cancelled();
+        if let Some(key) = &self.key
+            && let Err(e) = state::remove(key)
+        {
+            eprintln!("mcp-server: failed to untrack request: {e}");
+        }
+        cancelled
+    }
+}
+
+/// Marks a running request as cancelled, returning whether one was found.
+/// Requests that are unknown or already finished are ignored.
+pub fn cancel(caller: &Caller, id: &Value) -> bool {
+    if !state::is_enabled() {
+        return false;
+    }
+    let key = request_key(caller, id);
+    match state::read(&key) {
+        Ok(Some(record)) if record == RUNNING => match state::write(&key, CANCELLED) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("mcp-server: [{caller}] failed to cancel request {id}: {e}");
+                false
+            }
+        },
+        Ok(_) => false,
+        Err(e) => {
+            eprintln!("mcp-server: [{caller}] failed to read request {id}: {e}");
+            false
+        }
+    }
+}
+
+/// Request IDs are scoped to the caller, so one caller
+/// cannot cancel another's requests.
+fn request_key(caller: &Caller, id: &Value) -> String {
+    let id = hex::encode(&Sha256::digest(id.to_string().as_bytes())[..16]);
+    format!("requests/{caller}/{id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_key() {
+        let caller = Caller::anonymous();
+        let key = request_key(&caller, &json!(1));
+        assert!(key.starts_with("requests/anonymous/"));
+        assert_eq!(key, request_key(&caller, &json!(1)));
+        assert_ne!(key, request_key(&caller, &json!("1")));
+        assert_ne!(key, request_key(&Caller::oauth(), &json!(1)));
+    }
+
+    #[test]
+    fn test_untracked_request() {
+        let request = InFlight::none();
+        assert!(!request.is_cancelled());
+        assert!(!request.finish());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::auth::constant_time_eq;
+use crate::policy::{self, Pattern};
+use std::env;
+use std::fmt;
+use std::sync::LazyLock;
+
+const KEY_ENV_PREFIX: &str = "MCP_SERVER_KEY_";
+const TOOLS_ENV_SUFFIX: &str = "_TOOLS";
+const ANONYMOUS: &str = "anonymous";
+const OAUTH: &str = "oauth";
+const LEGACY: &str = "legacy";
+
+/// Named bearer keys, configured as one env var per key:
+///
+/// - `MCP_SERVER_KEY_<NAME>`: the bearer token.
+/// - `MCP_SERVER_KEY_<NAME>_TOOLS`: optional comma-separated tool
+///   patterns the key is restricted to (see [`Pattern`]).
+///   If unset, the key can use every tool allowed by the server.
+///
+/// e.g. `MCP_SERVER_KEY_CONTRACTOR=abc` and
+/// `MCP_SERVER_KEY_CONTRACTOR_TOOLS=asterai:cli/common/ls,*/fs/read`.
+static API_KEYS: LazyLock<Vec<ApiKey>> = LazyLock::new(|| parse_keys(env::vars()));
+
+struct ApiKey {
+    name: String,
+    token: String,
+    tools: Option<Vec<Pattern>>,
+}
+
+/// The authenticated identity making an MCP request.
+pub struct Caller {
+    name: String,
+    /// If set, the caller may only use tools matching these patterns.
+    tools: Option<Vec<Pattern>>,
+}
+
+impl Caller {
+    /// The caller when no authentication is configured.
+    pub fn anonymous() -> Self {
+        Self {
+            name: ANONYMOUS.to_owned(),
+            tools: None,
+        }
+    }
+
+    /// A caller holding an OAuth access token, which grants
+    /// every tool allowed by the server.
+    pub fn oauth() -> Self {
+        Self {
+            name: OAUTH.to_owned(),
+            tools: None,
+        }
+    }
+
+    /// A caller holding the legacy static bearer token,
+    /// which grants every tool allowed by the server.
+    pub fn legacy() -> Self {
+        Self {
+            name: LEGACY.to_owned(),
+            tools: None,
+        }
+    }
+
+    pub fn can_use(&self, component_name: &str, function_name: &str) -> bool {
+        match &self.tools {
+            Some(tools) => {
+                policy::matches_any(tools, &policy::tool_path(component_name, function_name))
+            }
+            None => true,
+        }
+    }
+}
+
+impl fmt::Display for Caller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+pub fn has_keys() -> bool {
+    !API_KEYS.is_empty()
+}
+
+/// Returns the caller for a bearer token matching a configured key.
+pub fn find_caller(token: &str) -> Option<Caller> {
+    find_key(&API_KEYS, token).map(|key| Caller {
+        name: key.name.clone(),
+        tools: key.tools.clone(),
+    })
+}
+
+fn find_key<'a>(keys: &'a [ApiKey], token: &str) -> Option<&'a ApiKey> {
+    // Compare against every key so timing does not reveal which matched.
+    keys.iter().fold(None, |found, key| {
+        match constant_time_eq(key.token.as_bytes(), token.as_bytes()) {
+            true => Some(key),
+            false => found,
+        }
+    })
+}
+
+fn parse_keys(vars: impl Iterator<Item = (String, String)>) -> Vec<ApiKey> {
+    let vars: Vec<(String, String)> = vars
+        .filter(|(k, _)| k.starts_with(KEY_ENV_PREFIX))
+        .collect();
+    let mut keys: Vec<ApiKey> = vars
+        .iter()
+        .filter(|(k, v)| !k.ends_with(TOOLS_ENV_SUFFIX) && !v.is_empty())
+        .map(|(k, token)| {
+            let tools_key = format!("{k}{TOOLS_ENV_SUFFIX}");
+            let tools = vars
+                .iter()
+                .find(|(k, _)| k == &tools_key)
+                .map(|(_, v)| policy::parse_patterns(v));
+            ApiKey {
+                name: k[KEY_ENV_PREFIX.len()..].to_lowercase(),
+                token: token.clone(),
+                tools,
+            }
+        })
+        .collect();
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<ApiKey> {
+        let vars = [
+            ("MCP_SERVER_KEY_TEAM", "team-token"),
+            ("MCP_SERVER_KEY_CONTRACTOR", "contractor-token"),
+            (
+                "MCP_SERVER_KEY_CONTRACTOR_TOOLS",
+                "asterai:cli/common/ls, */fs/read",
+            ),
+            ("MCP_SERVER_KEY_EMPTY", ""),
+            ("MCP_SERVER_TOOLS", "asterai:cli"),
+        ];
+        parse_keys(vars.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())))
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let keys = keys();
+        let names: Vec<&str> = keys.iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(names, vec!["contractor", "team"]);
+        assert!(keys[0].tools.is_some());
+        assert!(keys[1].tools.is_none());
+    }
+
+    #[test]
+    fn test_find_key() {
+        let keys = keys();
+        assert_eq!(
+            find_key(&keys, "team-token").map(|k| k.name.as_str()),
+            Some("team")
+        );
+        assert!(find_key(&keys, "wrong").is_none());
+        assert!(find_key(&keys, "").is_none());
+    }
+
+    #[test]
+    fn test_caller_scopes() {
+        let keys = keys();
+        let contractor = Caller {
+            name: keys[0].name.clone(),
+            tools: keys[0].tools.clone(),
+        };
+        assert!(contractor.can_use("asterai:cli", "common/ls"));
+        assert!(contractor.can_use("asterai:s3", "fs/read"));
+        assert!(!contractor.can_use("asterai:cli", "common/rm"));
+        assert!(Caller::oauth().can_use("asterai:cli", "common/rm"));
+    }
+}
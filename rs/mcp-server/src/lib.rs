@@ -1,10 +1,22 @@
 use crate::bindings::exports::wasi::http::incoming_handler::Guest as HttpGuest;
 use crate::bindings::wasi::http::types::{
-    Fields, IncomingBody, IncomingRequest, Method, OutgoingBody, OutgoingResponse, ResponseOutparam,
+    Fields, IncomingBody, IncomingRequest, Method, OutgoingBody, OutgoingResponse, OutputStream,
+    ResponseOutparam,
 };
+use crate::inflight::InFlight;
+use crate::keys::Caller;
+use crate::mcp::Progress;
+use crate::oauth::Endpoint;
 
+mod audit;
 mod auth;
+mod content;
+mod inflight;
+mod keys;
 mod mcp;
+mod oauth;
+mod policy;
+mod state;
 
 #[allow(warnings)]
 mod bindings {
@@ -21,8 +33,6 @@ impl HttpGuest for Component {
     fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
         let full_path = request.path_with_query().unwrap_or_default();
         let path = full_path.split('?').next().unwrap_or("");
-        let is_token = path == "/token" || path.ends_with("/token");
-        let is_authorize = path == "/authorize" || path.ends_with("/authorize");
         eprintln!(
             "mcp-server: {} {}",
             match request.method() {
@@ -32,142 +42,60 @@ impl HttpGuest for Component {
             },
             path,
         );
-        match (request.method(), is_authorize, is_token) {
-            (Method::Get, true, _) => handle_authorize(&full_path, response_out),
-            (Method::Post, _, true) => handle_token(&request, response_out),
-            (Method::Post, _, false) => handle_mcp(&request, response_out),
+        let Some((endpoint, prefix)) = Endpoint::from_path(path) else {
+            match request.method() {
+                Method::Post => handle_mcp(&request, path, response_out),
+                _ => respond(response_out, 405, "application/json", ""),
+            }
+            return;
+        };
+        match (request.method(), endpoint) {
+            (Method::Get, Endpoint::AuthorizationServerMetadata) => {
+                let base_url = oauth::base_url(&request, prefix);
+                oauth::handle_authorization_server_metadata(&base_url, response_out);
+            }
+            (Method::Get, Endpoint::ProtectedResourceMetadata) => {
+                let base_url = oauth::base_url(&request, prefix);
+                oauth::handle_protected_resource_metadata(&base_url, response_out);
+            }
+            (Method::Get, Endpoint::Authorize) => oauth::handle_authorize(&full_path, response_out),
+            (Method::Post, Endpoint::Authorize) => {
+                oauth::handle_authorize_submit(&request, response_out)
+            }
+            (Method::Post, Endpoint::Token) => oauth::handle_token(&request, response_out),
+            (Method::Post, Endpoint::Register) => oauth::handle_register(&request, response_out),
             _ => respond(response_out, 405, "application/json", ""),
         }
     }
 }
 
-fn handle_authorize(full_path: &str, response_out: ResponseOutparam) {
-    if !auth::is_auth_enabled() {
-        respond(response_out, 400, "text/plain", "Auth not enabled");
-        return;
-    }
-    let query = full_path.split_once('?').map(|(_, q)| q).unwrap_or("");
-    let params = parse_query(query);
-    let get = |key: &str| -> Option<&str> {
-        params
-            .iter()
-            .find(|(k, _)| k == key)
-            .map(|(_, v)| v.as_str())
-    };
-    let Some(redirect_uri) = get("redirect_uri") else {
-        respond(response_out, 400, "text/plain", "Missing redirect_uri");
-        return;
-    };
-    let Some(code_challenge) = get("code_challenge") else {
-        respond(response_out, 400, "text/plain", "Missing code_challenge");
-        return;
-    };
-    let state = get("state").unwrap_or("");
-    let Some(code) = auth::generate_auth_code(code_challenge) else {
-        respond(response_out, 500, "text/plain", "Failed to generate code");
-        return;
-    };
-    let location = if state.is_empty() {
-        format!("{redirect_uri}?code={code}")
-    } else {
-        format!("{redirect_uri}?code={code}&state={state}")
-    };
-    eprintln!("mcp-server: authorize redirecting to {location}");
-    redirect(response_out, &location);
-}
-
-fn handle_token(request: &IncomingRequest, response_out: ResponseOutparam) {
-    if !auth::is_auth_enabled() {
-        respond_json(
-            response_out,
-            400,
-            &serde_json::json!({"error": "invalid_request", "error_description": "Auth not enabled"}),
-        );
-        return;
-    }
-    let Some(body) = read_body(request) else {
-        respond_json(
+fn handle_mcp(request: &IncomingRequest, path: &str, response_out: ResponseOutparam) {
+    let Some(caller) = authenticate(request) else {
+        eprintln!("mcp-server: 401 unauthorized");
+        let challenge = oauth::bearer_challenge(&oauth::base_url(request, path));
+        respond_with_headers(
             response_out,
-            400,
-            &serde_json::json!({"error": "invalid_request"}),
+            401,
+            &[
+                ("content-type", "application/json"),
+                ("www-authenticate", &challenge),
+            ],
+            "",
         );
         return;
     };
-    eprintln!("mcp-server: token request body={body}");
-    let params = parse_form_body(&body);
-    let get = |key: &str| -> Option<&str> {
-        params
-            .iter()
-            .find(|(k, _)| k == key)
-            .map(|(_, v)| v.as_str())
-    };
-    let grant_type = get("grant_type").unwrap_or("");
-    match grant_type {
-        "authorization_code" => {
-            let code = get("code").unwrap_or("");
-            let code_verifier = get("code_verifier").unwrap_or("");
-            let client_secret = get("client_secret");
-            if code.is_empty() || code_verifier.is_empty() {
-                respond_json(
-                    response_out,
-                    400,
-                    &serde_json::json!({"error": "invalid_request", "error_description": "Missing code or code_verifier"}),
-                );
-                return;
-            }
-            if !auth::verify_auth_code(code, code_verifier, client_secret) {
-                eprintln!("mcp-server: auth code verification failed");
-                respond_json(
-                    response_out,
-                    401,
-                    &serde_json::json!({"error": "invalid_grant"}),
-                );
-                return;
-            }
-            let token = auth::access_token().unwrap();
-            eprintln!("mcp-server: token issued");
-            respond_json(
-                response_out,
-                200,
-                &serde_json::json!({
-                    "access_token": token,
-                    "token_type": "Bearer",
-                }),
-            );
-        }
-        _ => {
-            respond_json(
-                response_out,
-                400,
-                &serde_json::json!({"error": "unsupported_grant_type"}),
-            );
-        }
-    }
-}
-
-fn handle_mcp(request: &IncomingRequest, response_out: ResponseOutparam) {
-    if auth::is_auth_enabled() {
-        let headers = request.headers();
-        let auth_values = headers.get(&"authorization".to_string());
-        let authorized = auth_values
-            .iter()
-            .filter_map(|v| std::str::from_utf8(v).ok())
-            .any(|v| auth::verify_bearer(v));
-        if !authorized {
-            eprintln!("mcp-server: 401 unauthorized");
-            respond(response_out, 401, "application/json", "");
-            return;
-        }
-    }
     let Some(body) = read_body(request) else {
         eprintln!("mcp-server: failed to read request body");
         respond(response_out, 400, "application/json", "");
         return;
     };
-    eprintln!("mcp-server: received: {body}");
     let rpc_request: serde_json::Value = match serde_json::from_str(&body) {
         Ok(v) => v,
         Err(_) => {
+            eprintln!(
+                "mcp-server: [{caller}] unparseable request ({} bytes)",
+                body.len()
+            );
             let error = mcp::error_response(serde_json::Value::Null, -32700, "Parse error");
             respond_json(response_out, 200, &error);
             return;
@@ -176,17 +104,41 @@ fn handle_mcp(request: &IncomingRequest, response_out: ResponseOutparam) {
     let method = rpc_request.get("method").and_then(|m| m.as_str());
     let id = rpc_request.get("id").cloned();
     let params = rpc_request.get("params").cloned();
+    // Only log the envelope, as params may hold tool arguments.
+    eprintln!(
+        "mcp-server: [{caller}] {} id={} ({} bytes)",
+        method.unwrap_or("<no method>"),
+        id.as_ref().unwrap_or(&serde_json::Value::Null),
+        body.len()
+    );
     match (method, id) {
-        (Some("notifications/initialized"), None) => {
+        (Some(method), None) if method.starts_with("notifications/") => {
+            mcp::handle_notification(method, params.as_ref(), &caller);
             respond(response_out, 202, "application/json", "");
         }
         (Some(method), Some(id)) => {
-            let result = mcp::handle_method(method, params);
-            let response = match result {
-                Ok(value) => mcp::success_response(id, value),
-                Err((code, msg)) => mcp::error_response(id, code, msg),
+            let in_flight = match method {
+                "tools/call" => InFlight::start(&caller, &id),
+                _ => InFlight::none(),
             };
-            respond_json(response_out, 200, &response);
+            let progress_token = mcp::progress_token(params.as_ref());
+            if progress_token.is_some() && accepts_event_stream(request) {
+                let stream = EventStream::open(response_out);
+                let send = |notification: &serde_json::Value| stream.send(notification);
+                let progress = Progress::new(progress_token, &send);
+                let response = call_method(id, method, params, &caller, &progress, &in_flight);
+                // Cancelled requests get no response.
+                if !in_flight.finish() {
+                    stream.send(&response);
+                }
+                stream.finish();
+                return;
+            }
+            let response = call_method(id, method, params, &caller, &Progress::none(), &in_flight);
+            match in_flight.finish() {
+                true => respond(response_out, 202, "application/json", ""),
+                false => respond_json(response_out, 200, &response),
+            }
         }
         _ => {
             let error = mcp::error_response(serde_json::Value::Null, -32600, "Invalid request");
@@ -195,6 +147,52 @@ fn handle_mcp(request: &IncomingRequest, response_out: ResponseOutparam) {
     }
 }
 
+fn call_method(
+    id: serde_json::Value,
+    method: &str,
+    params: Option<serde_json::Value>,
+    caller: &Caller,
+    progress: &Progress,
+    in_flight: &InFlight,
+) -> serde_json::Value {
+    match mcp::handle_method(method, params, caller, progress, in_flight) {
+        Ok(value) => mcp::success_response(id, value),
+        Err((code, msg)) => mcp::error_response(id, code, msg),
+    }
+}
+
+/// Whether the client accepts a streamed (SSE) response,
+/// per the MCP streamable HTTP transport.
+fn accepts_event_stream(request: &IncomingRequest) -> bool {
+    request
+        .headers()
+        .get(&"accept".to_string())
+        .iter()
+        .filter_map(|v| std::str::from_utf8(v).ok())
+        .any(|v| v.contains("text/event-stream"))
+}
+
+/// Identifies the caller from the bearer token, which may be
+/// a named API key, an OAuth access token or, if still allowed,
+/// the legacy static token.
+/// Returns `None` if auth is configured and no valid token was sent.
+fn authenticate(request: &IncomingRequest) -> Option<Caller> {
+    if !auth::is_auth_enabled() && !keys::has_keys() {
+        return Some(Caller::anonymous());
+    }
+    let headers = request.headers();
+    let auth_values = headers.get(&"authorization".to_string());
+    auth_values
+        .iter()
+        .filter_map(|v| std::str::from_utf8(v).ok())
+        .map(|v| v.strip_prefix("Bearer ").unwrap_or(v))
+        .find_map(|token| {
+            keys::find_caller(token)
+                .or_else(|| auth::verify_access_token(token).then(Caller::oauth))
+                .or_else(|| auth::verify_legacy_token(token).then(Caller::legacy))
+        })
+}
+
 fn parse_form_body(body: &str) -> Vec<(String, String)> {
     body.split('&')
         .filter_map(|pair| {
@@ -225,6 +223,19 @@ fn urldecode(s: &str) -> String {
     result
 }
 
+fn urlencode(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    result
+}
+
 fn read_body(request: &IncomingRequest) -> Option<String> {
     let incoming_body = request.consume().ok()?;
     let stream = incoming_body.stream().ok()?;
@@ -263,31 +274,86 @@ fn redirect(response_out: ResponseOutparam, location: &str) {
 }
 
 fn respond(response_out: ResponseOutparam, status: u16, content_type: &str, body: &str) {
+    respond_with_headers(
+        response_out,
+        status,
+        &[("content-type", content_type)],
+        body,
+    );
+}
+
+fn respond_with_headers(
+    response_out: ResponseOutparam,
+    status: u16,
+    header_values: &[(&str, &str)],
+    body: &str,
+) {
     let headers = Fields::new();
-    headers
-        .set(
-            &"content-type".to_string(),
-            &[content_type.as_bytes().to_vec()],
-        )
-        .ok();
+    for (name, value) in header_values {
+        headers
+            .set(&name.to_string(), &[value.as_bytes().to_vec()])
+            .ok();
+    }
     let response = OutgoingResponse::new(headers);
     response.set_status_code(status).unwrap();
     let out_body = response.body().unwrap();
     ResponseOutparam::set(response_out, Ok(response));
     if !body.is_empty() {
         let stream = out_body.write().unwrap();
-        let bytes = body.as_bytes();
-        let mut offset = 0;
-        while offset < bytes.len() {
-            let end = (offset + 4096).min(bytes.len());
-            stream
-                .blocking_write_and_flush(&bytes[offset..end])
-                .unwrap();
-            offset = end;
-        }
+        write_all(&stream, body.as_bytes());
         drop(stream);
     }
     OutgoingBody::finish(out_body, None).unwrap();
 }
 
+fn write_all(stream: &OutputStream, bytes: &[u8]) {
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let end = (offset + 4096).min(bytes.len());
+        stream
+            .blocking_write_and_flush(&bytes[offset..end])
+            .unwrap();
+        offset = end;
+    }
+}
+
+/// A `text/event-stream` response carrying JSON-RPC messages,
+/// so notifications can be sent before the final response.
+struct EventStream {
+    stream: OutputStream,
+    body: OutgoingBody,
+}
+
+impl EventStream {
+    fn open(response_out: ResponseOutparam) -> Self {
+        let headers = Fields::new();
+        headers
+            .set(
+                &"content-type".to_string(),
+                &[b"text/event-stream".to_vec()],
+            )
+            .ok();
+        headers
+            .set(&"cache-control".to_string(), &[b"no-cache".to_vec()])
+            .ok();
+        let response = OutgoingResponse::new(headers);
+        response.set_status_code(200).unwrap();
+        let body = response.body().unwrap();
+        ResponseOutparam::set(response_out, Ok(response));
+        let stream = body.write().unwrap();
+        Self { stream, body }
+    }
+
+    fn send(&self, message: &serde_json::Value) {
+        let event = format!("event: message\ndata: {message}\n\n");
+        write_all(&self.stream, event.as_bytes());
+    }
+
+    fn finish(self) {
+        let Self { stream, body } = self;
+        drop(stream);
+        OutgoingBody::finish(body, None).unwrap();
+    }
+}
+
 bindings::export!(Component with_types_in bindings);
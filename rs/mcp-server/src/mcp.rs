@@ -1,39 +1,105 @@
+use crate::audit::{self, Outcome};
 use crate::bindings::asterai::host::api;
 use crate::bindings::asterai::host::api::{ComponentInfo, FunctionInfo};
+use crate::inflight::{self, InFlight};
+use crate::keys::Caller;
+use crate::{content, policy};
 use serde_json::{Value, json};
-use std::env;
-use std::sync::LazyLock;
+use std::time::Instant;
 
 const SELF_COMPONENT: &str = "asterai:mcp-server";
 const SERVER_NAME: &str = "asterai-mcp-server";
 const SERVER_VERSION: &str = "0.1.0";
-const PROTOCOL_VERSION: &str = "2025-03-26";
-const TOOLS_ENV: &str = "MCP_SERVER_TOOLS";
+const PROTOCOL_VERSION: &str = "2025-06-18";
 const SKIP_INTERFACES: &[&str] = &["run", "incoming-handler"];
 
-static ALLOWED_COMPONENTS: LazyLock<Option<Vec<String>>> = LazyLock::new(|| {
-    let raw = env::var(TOOLS_ENV).ok()?;
-    let items: Vec<String> = raw
-        .split(',')
-        .map(|s| s.trim().to_owned())
-        .filter(|s| !s.is_empty())
-        .collect();
-    match items.is_empty() {
-        true => None,
-        false => Some(items),
+/// Sends `notifications/progress` for a request whose
+/// params carried a `_meta.progressToken`.
+/// Host calls block and report nothing while they run, so a tool call
+/// only notifies when it is dispatched, without a total.
+pub struct Progress<'a> {
+    token: Option<Value>,
+    send: Option<&'a dyn Fn(&Value)>,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(token: Option<Value>, send: &'a dyn Fn(&Value)) -> Self {
+        Self {
+            token,
+            send: Some(send),
+        }
+    }
+
+    /// A reporter that discards progress, for non-streaming responses.
+    pub fn none() -> Self {
+        Self {
+            token: None,
+            send: None,
+        }
     }
-});
 
-pub fn handle_method(method: &str, params: Option<Value>) -> Result<Value, (i32, &'static str)> {
+    pub fn report(&self, progress: u64, message: &str) {
+        let (Some(token), Some(send)) = (&self.token, self.send) else {
+            return;
+        };
+        send(&json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": {
+                "progressToken": token,
+                "progress": progress,
+                "message": message,
+            },
+        }));
+    }
+}
+
+pub fn handle_method(
+    method: &str,
+    params: Option<Value>,
+    caller: &Caller,
+    progress: &Progress,
+    in_flight: &InFlight,
+) -> Result<Value, (i32, &'static str)> {
     match method {
         "initialize" => handle_initialize(),
         "ping" => Ok(json!({})),
-        "tools/list" => handle_tools_list(params),
-        "tools/call" => handle_tools_call(params),
+        "tools/list" => handle_tools_list(params, caller),
+        "tools/call" => handle_tools_call(params, caller, progress, in_flight),
         _ => Err((-32601, "Method not found")),
     }
 }
 
+/// Handles a JSON-RPC notification. Notifications have no response.
+pub fn handle_notification(method: &str, params: Option<&Value>, caller: &Caller) {
+    match method {
+        "notifications/cancelled" => {
+            // A tool call that was already dispatched cannot be
+            // interrupted, but its response is suppressed.
+            let Some(request_id) = params.and_then(|p| p.get("requestId")) else {
+                return;
+            };
+            match inflight::cancel(caller, request_id) {
+                true => eprintln!("mcp-server: [{caller}] request {request_id} cancelled"),
+                false => eprintln!(
+                    "mcp-server: [{caller}] ignored cancel for request {request_id}: \
+                     not in flight, or no state store configured"
+                ),
+            }
+        }
+        _ => eprintln!("mcp-server: [{caller}] {method}"),
+    }
+}
+
+/// Returns the progress token from a request's `params._meta`, if any.
+pub fn progress_token(params: Option<&Value>) -> Option<Value> {
+    let token = params?.get("_meta")?.get("progressToken")?;
+    match token {
+        Value::String(_) | Value::Number(_) => Some(token.clone()),
+        _ => None,
+    }
+}
+
 pub fn success_response(id: Value, result: Value) -> Value {
     json!({
         "jsonrpc": "2.0",
@@ -66,14 +132,17 @@ fn handle_initialize() -> Result<Value, (i32, &'static str)> {
     }))
 }
 
-fn handle_tools_list(_params: Option<Value>) -> Result<Value, (i32, &'static str)> {
-    eprintln!("mcp-server: tools/list called");
+fn handle_tools_list(
+    _params: Option<Value>,
+    caller: &Caller,
+) -> Result<Value, (i32, &'static str)> {
+    eprintln!("mcp-server: [{caller}] tools/list called");
     let components = list_allowed_components();
-    eprintln!("mcp-server: got {} components", components.len());
+    eprintln!("mcp-server: [{caller}] got {} components", components.len());
     let mut tools = Vec::new();
     for comp in &components {
         eprintln!(
-            "mcp-server: component {} has {} functions",
+            "mcp-server: [{caller}] component {} has {} functions",
             comp.name,
             comp.functions.len()
         );
@@ -81,24 +150,71 @@ fn handle_tools_list(_params: Option<Value>) -> Result<Value, (i32, &'static str
             if is_skip_function(func) {
                 continue;
             }
+            let fn_name = format_function_name(func);
+            if !policy::is_tool_allowed(&comp.name, &fn_name)
+                || !caller.can_use(&comp.name, &fn_name)
+            {
+                continue;
+            }
             tools.push(function_to_tool(&comp.name, func));
         }
     }
-    eprintln!("mcp-server: returning {} tools", tools.len());
+    eprintln!("mcp-server: [{caller}] returning {} tools", tools.len());
     Ok(json!({ "tools": tools }))
 }
 
-fn handle_tools_call(params: Option<Value>) -> Result<Value, (i32, &'static str)> {
+fn handle_tools_call(
+    params: Option<Value>,
+    caller: &Caller,
+    progress: &Progress,
+    in_flight: &InFlight,
+) -> Result<Value, (i32, &'static str)> {
     let params = params.ok_or((-32602, "Invalid params"))?;
     let tool_name = params
         .get("name")
         .and_then(|v| v.as_str())
         .ok_or((-32602, "Invalid params"))?;
-    eprintln!("mcp-server: tools/call {tool_name}");
+    eprintln!("mcp-server: [{caller}] tools/call {tool_name}");
     let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let start = Instant::now();
+    if in_flight.is_cancelled() {
+        eprintln!("mcp-server: [{caller}] {tool_name} cancelled before dispatch");
+        audit::record_tool_call(
+            caller,
+            tool_name,
+            &arguments,
+            start.elapsed(),
+            Outcome::Cancelled,
+        );
+        return Err((-32800, "Request cancelled"));
+    }
+    progress.report(0, &format!("Running {tool_name}"));
+    let result = call_tool(tool_name, &arguments, caller);
+    let outcome = match &result {
+        Ok(value) if value["isError"] == true => {
+            Outcome::Error(value["content"][0]["text"].as_str().unwrap_or_default())
+        }
+        Ok(_) => Outcome::Success,
+        Err(_) => Outcome::Rejected,
+    };
+    audit::record_tool_call(caller, tool_name, &arguments, start.elapsed(), outcome);
+    result
+}
+
+fn call_tool(
+    tool_name: &str,
+    arguments: &Value,
+    caller: &Caller,
+) -> Result<Value, (i32, &'static str)> {
     let (component_name, function_name) =
         decode_tool_name(tool_name).ok_or((-32602, "Invalid params"))?;
-    if !is_component_allowed(&component_name) {
+    if !is_component_allowed(&component_name)
+        || !policy::is_tool_allowed(&component_name, &function_name)
+    {
+        return Err((-32602, "Invalid params"));
+    }
+    if !caller.can_use(&component_name, &function_name) {
+        eprintln!("mcp-server: [{caller}] denied {tool_name}");
         return Err((-32602, "Invalid params"));
     }
     let comp = api::get_component(&component_name).ok_or((-32602, "Invalid params"))?;
@@ -107,31 +223,34 @@ fn handle_tools_call(params: Option<Value>) -> Result<Value, (i32, &'static str)
         .iter()
         .find(|f| format_function_name(f) == function_name)
         .ok_or((-32602, "Invalid params"))?;
-    let args_json = build_args_json(&arguments, func);
-    match api::call_component_function(&component_name, &function_name, &args_json) {
-        Ok(result) => Ok(json!({
-            "content": [{ "type": "text", "text": result }],
-            "isError": false,
-        })),
-        Err(e) => Ok(json!({
-            "content": [{ "type": "text", "text": e.message }],
-            "isError": true,
-        })),
+    let args_json = build_args_json(arguments, func);
+    let start = Instant::now();
+    let result = api::call_component_function(&component_name, &function_name, &args_json);
+    // The host call cannot be interrupted, so the timeout is applied
+    // once it returns: the late result is discarded and an error returned.
+    if let Some(timeout) = policy::tool_timeout(&component_name, &function_name)
+        && start.elapsed() > timeout
+    {
+        eprintln!(
+            "mcp-server: [{caller}] {tool_name} overran its {}s timeout ({}ms)",
+            timeout.as_secs(),
+            start.elapsed().as_millis()
+        );
+        let message = format!("{tool_name} timed out after {}s", timeout.as_secs());
+        return Ok(content::text_result(&message, true));
+    }
+    match result {
+        Ok(result) => Ok(content::tool_result(&component_name, func, &result)),
+        Err(e) => Ok(content::text_result(&e.message, true)),
     }
 }
 
+/// Lists other components in the environment. Individual functions
+/// are filtered by the tool policy, see [`policy::is_tool_allowed`].
 fn list_allowed_components() -> Vec<ComponentInfo> {
-    let all = api::list_other_components();
-    let filtered: Vec<ComponentInfo> = match ALLOWED_COMPONENTS.as_ref() {
-        Some(allowed) => all
-            .into_iter()
-            .filter(|c| allowed.iter().any(|a| a == &c.name))
-            .collect(),
-        None => all,
-    };
     // Filter out self — list_other_components should exclude it but
     // doesn't always work in HTTP handler contexts.
-    filtered
+    api::list_other_components()
         .into_iter()
         .filter(|c| c.name != SELF_COMPONENT)
         .collect()
@@ -141,10 +260,7 @@ fn is_component_allowed(name: &str) -> bool {
     if name == SELF_COMPONENT {
         return false;
     }
-    match ALLOWED_COMPONENTS.as_ref() {
-        Some(allowed) => allowed.iter().any(|a| a == name),
-        None => api::list_other_components().iter().any(|c| c.name == name),
-    }
+    api::list_other_components().iter().any(|c| c.name == name)
 }
 
 fn is_skip_function(func: &FunctionInfo) -> bool {
@@ -171,7 +287,7 @@ fn function_to_tool(component_name: &str, func: &FunctionInfo) -> Value {
             required.push(Value::String(param.name.clone()));
         }
     }
-    json!({
+    let mut tool = json!({
         "name": tool_name,
         "description": description,
         "inputSchema": {
@@ -179,7 +295,14 @@ fn function_to_tool(component_name: &str, func: &FunctionInfo) -> Value {
             "properties": properties,
             "required": required,
         },
-    })
+    });
+    if let Some(schema) = content::output_schema(func) {
+        tool["outputSchema"] = schema;
+    }
+    if let Some(annotations) = policy::tool_annotations(component_name, &fn_name) {
+        tool["annotations"] = annotations;
+    }
+    tool
 }
 
 fn is_optional_type(type_name: &str) -> bool {
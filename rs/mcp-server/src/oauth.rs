@@ -0,0 +1,514 @@
+use crate::bindings::wasi::http::types::{IncomingRequest, ResponseOutparam, Scheme};
+use crate::{audit, auth};
+use crate::{
+    parse_form_body, parse_query, read_body, redirect, respond, respond_json, respond_with_headers,
+    urlencode,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+use std::sync::LazyLock;
+
+const AUTHORIZATION_SERVER_METADATA_PATH: &str = "/.well-known/oauth-authorization-server";
+const PROTECTED_RESOURCE_METADATA_PATH: &str = "/.well-known/oauth-protected-resource";
+const AUTHORIZE_PATH: &str = "/authorize";
+const TOKEN_PATH: &str = "/token";
+const REGISTER_PATH: &str = "/register";
+const PUBLIC_URL_ENV: &str = "MCP_SERVER_PUBLIC_URL";
+const TRUST_FORWARDED_HEADERS_ENV: &str = "MCP_SERVER_TRUST_FORWARDED_HEADERS";
+
+/// The public origin the component is reachable at, e.g.
+/// `https://example.com`, if set via `MCP_SERVER_PUBLIC_URL`.
+static PUBLIC_URL: LazyLock<Option<String>> = LazyLock::new(|| {
+    env::var(PUBLIC_URL_ENV)
+        .ok()
+        .map(|url| url.trim().trim_end_matches('/').to_owned())
+        .filter(|url| !url.is_empty())
+});
+
+/// Whether `x-forwarded-proto` and `x-forwarded-host` are trusted.
+/// Only set `MCP_SERVER_TRUST_FORWARDED_HEADERS=true` behind a proxy
+/// that overwrites them, as clients can send them too.
+static TRUST_FORWARDED_HEADERS: LazyLock<bool> =
+    LazyLock::new(|| env::var(TRUST_FORWARDED_HEADERS_ENV).is_ok_and(|v| v == "true"));
+
+/// An OAuth endpoint served by the component.
+/// The MCP endpoint itself is any other path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endpoint {
+    AuthorizationServerMetadata,
+    ProtectedResourceMetadata,
+    Authorize,
+    Token,
+    Register,
+}
+
+impl Endpoint {
+    /// Matches a request path, returning the endpoint and the path
+    /// prefix the component is mounted at.
+    /// Well-known paths may carry a suffix (RFC 8414 section 3.1),
+    /// e.g. `/.well-known/oauth-protected-resource/mcp`.
+    pub fn from_path(path: &str) -> Option<(Self, &str)> {
+        let well_known = [
+            (
+                AUTHORIZATION_SERVER_METADATA_PATH,
+                Self::AuthorizationServerMetadata,
+            ),
+            (
+                PROTECTED_RESOURCE_METADATA_PATH,
+                Self::ProtectedResourceMetadata,
+            ),
+        ];
+        for (suffix, endpoint) in well_known {
+            if let Some(pos) = path.find(suffix) {
+                return Some((endpoint, &path[..pos]));
+            }
+        }
+        let endpoints = [
+            (AUTHORIZE_PATH, Self::Authorize),
+            (TOKEN_PATH, Self::Token),
+            (REGISTER_PATH, Self::Register),
+        ];
+        endpoints.into_iter().find_map(|(suffix, endpoint)| {
+            path.strip_suffix(suffix).map(|prefix| (endpoint, prefix))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RegistrationRequest {
+    #[serde(default)]
+    redirect_uris: Vec<String>,
+    client_name: Option<String>,
+}
+
+/// Returns the public base URL the component is reachable at,
+/// e.g. `https://example.com/ns/env/ns/mcp-server`.
+/// The origin is `MCP_SERVER_PUBLIC_URL` if set, otherwise the
+/// request's own scheme and authority. Forwarded headers are only
+/// used with `MCP_SERVER_TRUST_FORWARDED_HEADERS=true`.
+pub fn base_url(request: &IncomingRequest, prefix: &str) -> String {
+    let origin = match &*PUBLIC_URL {
+        Some(url) => url.clone(),
+        None => request_origin(request),
+    };
+    format!("{origin}{}", prefix.trim_end_matches('/'))
+}
+
+fn request_origin(request: &IncomingRequest) -> String {
+    let headers = request.headers();
+    let header = |name: &str| {
+        headers
+            .get(&name.to_string())
+            .first()
+            .and_then(|v| String::from_utf8(v.clone()).ok())
+    };
+    let forwarded = |name: &str| header(name).filter(|_| *TRUST_FORWARDED_HEADERS);
+    let scheme = forwarded("x-forwarded-proto").unwrap_or_else(|| match request.scheme() {
+        Some(Scheme::Http) => "http".to_owned(),
+        _ => "https".to_owned(),
+    });
+    let host = forwarded("x-forwarded-host")
+        .or_else(|| request.authority())
+        .or_else(|| header("host"))
+        .unwrap_or_default();
+    format!("{scheme}://{host}")
+}
+
+/// Returns the `WWW-Authenticate` challenge pointing MCP clients
+/// at the protected resource metadata (RFC 9728 section 5.1).
+pub fn bearer_challenge(base_url: &str) -> String {
+    format!("Bearer resource_metadata=\"{base_url}{PROTECTED_RESOURCE_METADATA_PATH}\"")
+}
+
+/// Authorization server metadata (RFC 8414).
+pub fn handle_authorization_server_metadata(base_url: &str, response_out: ResponseOutparam) {
+    respond_json(
+        response_out,
+        200,
+        &json!({
+            "issuer": base_url,
+            "authorization_endpoint": format!("{base_url}{AUTHORIZE_PATH}"),
+            "token_endpoint": format!("{base_url}{TOKEN_PATH}"),
+            "registration_endpoint": format!("{base_url}{REGISTER_PATH}"),
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code", "refresh_token"],
+            "code_challenge_methods_supported": ["S256"],
+            "token_endpoint_auth_methods_supported": ["none", "client_secret_post"],
+        }),
+    );
+}
+
+/// Protected resource metadata (RFC 9728).
+pub fn handle_protected_resource_metadata(base_url: &str, response_out: ResponseOutparam) {
+    respond_json(
+        response_out,
+        200,
+        &json!({
+            "resource": base_url,
+            "authorization_servers": [base_url],
+            "bearer_methods_supported": ["header"],
+        }),
+    );
+}
+
+/// Dynamic client registration (RFC 7591).
+/// All registered clients are public clients using PKCE.
+pub fn handle_register(request: &IncomingRequest, response_out: ResponseOutparam) {
+    let registration: Option<RegistrationRequest> =
+        read_body(request).and_then(|body| serde_json::from_str(&body).ok());
+    let Some(registration) = registration else {
+        respond_json(
+            response_out,
+            400,
+            &json!({"error": "invalid_client_metadata"}),
+        );
+        return;
+    };
+    let redirect_uris = registration.redirect_uris.clone();
+    match auth::register_client(registration.redirect_uris, registration.client_name.clone()) {
+        Ok(client_id) => {
+            eprintln!(
+                "mcp-server: registered client {:?}",
+                registration.client_name
+            );
+            respond_json(
+                response_out,
+                201,
+                &json!({
+                    "client_id": client_id,
+                    "client_name": registration.client_name,
+                    "redirect_uris": redirect_uris,
+                    "token_endpoint_auth_method": "none",
+                    "grant_types": ["authorization_code", "refresh_token"],
+                    "response_types": ["code"],
+                }),
+            );
+        }
+        Err(e) => respond_json(
+            response_out,
+            400,
+            &json!({"error": "invalid_redirect_uri", "error_description": e}),
+        ),
+    }
+}
+
+/// Shows the consent page, where the resource owner
+/// enters the server secret to approve the client.
+pub fn handle_authorize(full_path: &str, response_out: ResponseOutparam) {
+    let query = full_path.split_once('?').map(|(_, q)| q).unwrap_or("");
+    let params = parse_query(query);
+    handle_authorize_params(&params, None, response_out);
+}
+
+/// Handles the consent page form submission.
+pub fn handle_authorize_submit(request: &IncomingRequest, response_out: ResponseOutparam) {
+    let Some(body) = read_body(request) else {
+        respond(response_out, 400, "text/plain", "Invalid request");
+        return;
+    };
+    let params = parse_form_body(&body);
+    let secret = get_param(&params, "secret").unwrap_or("");
+    handle_authorize_params(&params, Some(secret), response_out);
+}
+
+fn handle_authorize_params(
+    params: &[(String, String)],
+    secret: Option<&str>,
+    response_out: ResponseOutparam,
+) {
+    if !auth::is_auth_enabled() {
+        respond(response_out, 400, "text/plain", "Auth not enabled");
+        return;
+    }
+    let get = |key: &str| get_param(params, key);
+    let Some(redirect_uri) = get("redirect_uri") else {
+        respond(response_out, 400, "text/plain", "Missing redirect_uri");
+        return;
+    };
+    let client_id = get("client_id").unwrap_or("");
+    // Errors before the redirect URI is validated must not redirect.
+    if !auth::is_redirect_allowed(client_id, redirect_uri) {
+        respond(response_out, 400, "text/plain", "Invalid redirect_uri");
+        return;
+    }
+    let state = get("state").unwrap_or("");
+    let redirect_error = |response_out: ResponseOutparam, error: &str| {
+        let location = append_query(redirect_uri, &[("error", error), ("state", state)]);
+        redirect(response_out, &location);
+    };
+    if get("response_type") != Some("code") {
+        redirect_error(response_out, "unsupported_response_type");
+        return;
+    }
+    let Some(code_challenge) = get("code_challenge") else {
+        redirect_error(response_out, "invalid_request");
+        return;
+    };
+    if get("code_challenge_method").is_some_and(|m| m != "S256") {
+        redirect_error(response_out, "invalid_request");
+        return;
+    }
+    let Some(secret) = secret else {
+        respond_consent_page(response_out, params, None);
+        return;
+    };
+    if !auth::verify_secret(secret) {
+        eprintln!("mcp-server: authorize rejected invalid secret");
+        respond_consent_page(response_out, params, Some("Invalid secret"));
+        return;
+    }
+    let Some(code) = auth::generate_auth_code(client_id, redirect_uri, code_challenge) else {
+        respond(response_out, 500, "text/plain", "Failed to generate code");
+        return;
+    };
+    let location = append_query(redirect_uri, &[("code", &code), ("state", state)]);
+    eprintln!("mcp-server: authorize approved, redirecting");
+    redirect(response_out, &location);
+}
+
+pub fn handle_token(request: &IncomingRequest, response_out: ResponseOutparam) {
+    if !auth::is_auth_enabled() {
+        respond_json(
+            response_out,
+            400,
+            &json!({"error": "invalid_request", "error_description": "Auth not enabled"}),
+        );
+        return;
+    }
+    let Some(body) = read_body(request) else {
+        respond_json(response_out, 400, &json!({"error": "invalid_request"}));
+        return;
+    };
+    eprintln!(
+        "mcp-server: token request body={}",
+        audit::redact_form(&body)
+    );
+    let params = parse_form_body(&body);
+    let get = |key: &str| get_param(&params, key);
+    let Some(client_id) = get("client_id").filter(|c| !c.is_empty()) else {
+        respond_json(
+            response_out,
+            400,
+            &json!({"error": "invalid_request", "error_description": "Missing client_id"}),
+        );
+        return;
+    };
+    let tokens = match get("grant_type").unwrap_or("") {
+        "authorization_code" => {
+            let code = get("code").unwrap_or("");
+            let code_verifier = get("code_verifier").unwrap_or("");
+            if code.is_empty() || code_verifier.is_empty() {
+                respond_json(
+                    response_out,
+                    400,
+                    &json!({"error": "invalid_request", "error_description": "Missing code or code_verifier"}),
+                );
+                return;
+            }
+            auth::exchange_auth_code(
+                code,
+                code_verifier,
+                client_id,
+                get("redirect_uri"),
+                get("client_secret"),
+            )
+        }
+        "refresh_token" => {
+            let refresh_token = get("refresh_token").unwrap_or("");
+            auth::refresh(refresh_token, client_id)
+        }
+        _ => {
+            respond_json(
+                response_out,
+                400,
+                &json!({"error": "unsupported_grant_type"}),
+            );
+            return;
+        }
+    };
+    let Some(tokens) = tokens else {
+        eprintln!("mcp-server: token grant verification failed");
+        respond_json(response_out, 400, &json!({"error": "invalid_grant"}));
+        return;
+    };
+    eprintln!("mcp-server: token issued");
+    let body = json!({
+        "access_token": tokens.access_token,
+        "token_type": "Bearer",
+        "expires_in": tokens.expires_in,
+        "refresh_token": tokens.refresh_token,
+    });
+    respond_with_headers(
+        response_out,
+        200,
+        &[
+            ("content-type", "application/json"),
+            ("cache-control", "no-store"),
+        ],
+        &body.to_string(),
+    );
+}
+
+fn respond_consent_page(
+    response_out: ResponseOutparam,
+    params: &[(String, String)],
+    error: Option<&str>,
+) {
+    let hidden_inputs: String = params
+        .iter()
+        .filter(|(k, _)| k != "secret")
+        .map(|(k, v)| {
+            format!(
+                r#"<input type="hidden" name="{}" value="{}">"#,
+                html_escape(k),
+                html_escape(v)
+            )
+        })
+        .collect();
+    let error = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, html_escape(e)))
+        .unwrap_or_default();
+    let client_id = get_param(params, "client_id").unwrap_or("");
+    let client = auth::client_name(client_id).unwrap_or_else(|| "An unnamed client".to_owned());
+    let origin = redirect_origin(get_param(params, "redirect_uri").unwrap_or(""));
+    let (client, origin) = (html_escape(&client), html_escape(origin));
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Authorize MCP client</title>
+<style>
+body {{ font-family: sans-serif; max-width: 24rem; margin: 4rem auto; padding: 0 1rem; }}
+input[type=password] {{ width: 100%; padding: 0.5rem; box-sizing: border-box; }}
+button {{ margin-top: 1rem; padding: 0.5rem 1rem; }}
+.error {{ color: #b00020; }}
+</style>
+</head>
+<body>
+<h1>Authorize MCP client</h1>
+<p><strong>{client}</strong> is requesting access to this server.
+Approving sends an authorization code to <strong>{origin}</strong>.</p>
+<p>Only enter the server secret if you started this request and trust that address.</p>
+{error}
+<form method="post">
+{hidden_inputs}
+<input type="password" name="secret" placeholder="Server secret" autofocus required>
+<button type="submit">Authorize</button>
+</form>
+</body>
+</html>
+"#
+    );
+    respond(response_out, 200, "text/html; charset=utf-8", &body);
+}
+
+/// Returns the scheme and host of a redirect URI, which is where
+/// the authorization code will be sent.
+fn redirect_origin(uri: &str) -> &str {
+    let Some((scheme, rest)) = uri.split_once("://") else {
+        return uri;
+    };
+    let host_len = rest.find(['/', '?']).unwrap_or(rest.len());
+    &uri[..scheme.len() + 3 + host_len]
+}
+
+fn get_param<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Appends query parameters to a URI, skipping empty values.
+fn append_query(uri: &str, params: &[(&str, &str)]) -> String {
+    let mut result = uri.to_owned();
+    let mut separator = match uri.contains('?') {
+        true => '&',
+        false => '?',
+    };
+    for (key, value) in params.iter().filter(|(_, v)| !v.is_empty()) {
+        result.push(separator);
+        result.push_str(key);
+        result.push('=');
+        result.push_str(&urlencode(value));
+        separator = '&';
+    }
+    result
+}
+
+fn html_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_from_path() {
+        assert_eq!(
+            Endpoint::from_path("/ns/env/asterai/mcp-server/token"),
+            Some((Endpoint::Token, "/ns/env/asterai/mcp-server"))
+        );
+        assert_eq!(
+            Endpoint::from_path("/authorize"),
+            Some((Endpoint::Authorize, ""))
+        );
+        assert_eq!(
+            Endpoint::from_path("/ns/env/a/b/.well-known/oauth-protected-resource"),
+            Some((Endpoint::ProtectedResourceMetadata, "/ns/env/a/b"))
+        );
+        assert_eq!(
+            Endpoint::from_path("/ns/env/a/b/.well-known/oauth-authorization-server/mcp"),
+            Some((Endpoint::AuthorizationServerMetadata, "/ns/env/a/b"))
+        );
+        assert_eq!(Endpoint::from_path("/ns/env/a/b"), None);
+    }
+
+    #[test]
+    fn test_append_query() {
+        assert_eq!(
+            append_query("https://a.b/cb", &[("code", "x y"), ("state", "")]),
+            "https://a.b/cb?code=x%20y"
+        );
+        assert_eq!(
+            append_query("https://a.b/cb?x=1", &[("code", "c"), ("state", "s")]),
+            "https://a.b/cb?x=1&code=c&state=s"
+        );
+    }
+
+    #[test]
+    fn test_redirect_origin() {
+        assert_eq!(
+            redirect_origin("https://claude.ai/api/mcp/auth_callback"),
+            "https://claude.ai"
+        );
+        assert_eq!(
+            redirect_origin("http://localhost:6274?x=1"),
+            "http://localhost:6274"
+        );
+        assert_eq!(
+            redirect_origin("cursor://anysphere.cursor-mcp/oauth"),
+            "cursor://anysphere.cursor-mcp"
+        );
+    }
+
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(html_escape(r#""><script>"#), "&quot;&gt;&lt;script&gt;");
+    }
+}
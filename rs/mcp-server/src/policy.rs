@@ -0,0 +1,267 @@
+use serde_json::{Value, json};
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+const ALLOW_ENV: &str = "MCP_SERVER_TOOLS";
+const DENY_ENV: &str = "MCP_SERVER_DENY";
+const READ_ONLY_ENV: &str = "MCP_SERVER_READ_ONLY";
+const DESTRUCTIVE_ENV: &str = "MCP_SERVER_DESTRUCTIVE";
+const TIMEOUT_ENV: &str = "MCP_SERVER_TOOL_TIMEOUT";
+const TIMEOUTS_ENV: &str = "MCP_SERVER_TOOL_TIMEOUTS";
+
+static POLICY: LazyLock<ToolPolicy> = LazyLock::new(|| ToolPolicy {
+    allow: patterns_from_env(ALLOW_ENV),
+    deny: patterns_from_env(DENY_ENV).unwrap_or_default(),
+    read_only: patterns_from_env(READ_ONLY_ENV).unwrap_or_default(),
+    destructive: patterns_from_env(DESTRUCTIVE_ENV).unwrap_or_default(),
+    timeouts: env::var(TIMEOUTS_ENV)
+        .map(|raw| parse_timeouts(&raw))
+        .unwrap_or_default(),
+    default_timeout: env::var(TIMEOUT_ENV).ok().and_then(|v| parse_secs(&v)),
+});
+
+/// Decides which tools are exposed and how they are annotated.
+/// Tools are addressed by their path, `component/interface/function`
+/// (or `component/function` for functions outside an interface).
+struct ToolPolicy {
+    /// If set, only tools matching one of these patterns are exposed.
+    allow: Option<Vec<Pattern>>,
+    /// Tools matching any of these patterns are never exposed,
+    /// even if they are allowed.
+    deny: Vec<Pattern>,
+    read_only: Vec<Pattern>,
+    destructive: Vec<Pattern>,
+    /// Per-tool timeouts, e.g. `asterai:steel/**=120,*/fs/cp=300`.
+    /// The first matching pattern wins. Calls cannot be interrupted,
+    /// so a call that overruns its timeout returns an error once it finishes.
+    timeouts: Vec<(Pattern, Duration)>,
+    /// Timeout in seconds for tools without a per-tool timeout.
+    default_timeout: Option<Duration>,
+}
+
+impl ToolPolicy {
+    fn is_allowed(&self, path: &str) -> bool {
+        let allowed = match &self.allow {
+            Some(allow) => matches_any(allow, path),
+            None => true,
+        };
+        allowed && !matches_any(&self.deny, path)
+    }
+
+    fn annotations(&self, path: &str) -> Option<Value> {
+        if matches_any(&self.read_only, path) {
+            return Some(json!({ "readOnlyHint": true }));
+        }
+        if matches_any(&self.destructive, path) {
+            return Some(json!({ "readOnlyHint": false, "destructiveHint": true }));
+        }
+        None
+    }
+
+    fn timeout(&self, path: &str) -> Option<Duration> {
+        self.timeouts
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, timeout)| *timeout)
+            .or(self.default_timeout)
+    }
+}
+
+/// A glob pattern over tool paths.
+/// `*` matches within a path segment, `**` matches across segments
+/// and `?` matches a single character other than `/`.
+/// A pattern without a `/` names a whole component,
+/// e.g. `asterai:cli` is equivalent to `asterai:cli/**`.
+#[derive(Debug, Clone)]
+pub struct Pattern(String);
+
+impl Pattern {
+    pub fn new(raw: &str) -> Self {
+        match raw.contains('/') {
+            true => Self(raw.to_owned()),
+            false => Self(format!("{raw}/**")),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        glob_match(self.0.as_bytes(), path.as_bytes())
+    }
+}
+
+pub fn tool_path(component_name: &str, function_name: &str) -> String {
+    format!("{component_name}/{function_name}")
+}
+
+pub fn is_tool_allowed(component_name: &str, function_name: &str) -> bool {
+    POLICY.is_allowed(&tool_path(component_name, function_name))
+}
+
+/// Returns the MCP tool annotations configured for a tool, if any.
+pub fn tool_annotations(component_name: &str, function_name: &str) -> Option<Value> {
+    POLICY.annotations(&tool_path(component_name, function_name))
+}
+
+/// Returns the configured timeout for a tool, if any.
+/// Calls that run longer return an error instead of their result.
+pub fn tool_timeout(component_name: &str, function_name: &str) -> Option<Duration> {
+    POLICY.timeout(&tool_path(component_name, function_name))
+}
+
+/// Parses a comma-separated list of patterns.
+pub fn parse_patterns(raw: &str) -> Vec<Pattern> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(Pattern::new)
+        .collect()
+}
+
+pub fn matches_any(patterns: &[Pattern], path: &str) -> bool {
+    patterns.iter().any(|p| p.matches(path))
+}
+
+fn patterns_from_env(key: &str) -> Option<Vec<Pattern>> {
+    let patterns = parse_patterns(&env::var(key).ok()?);
+    match patterns.is_empty() {
+        true => None,
+        false => Some(patterns),
+    }
+}
+
+/// Parses a comma-separated list of `pattern=seconds` entries,
+/// skipping invalid ones.
+fn parse_timeouts(raw: &str) -> Vec<(Pattern, Duration)> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (pattern, secs) = entry.trim().rsplit_once('=')?;
+            Some((Pattern::new(pattern.trim()), parse_secs(secs)?))
+        })
+        .collect()
+}
+
+fn parse_secs(raw: &str) -> Option<Duration> {
+    let secs: u64 = raw.trim().parse().ok()?;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            // `a/**/b` also matches `a/b`.
+            if rest.first() == Some(&b'/') && glob_match(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            let segment_len = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment_len).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some(b'?') => match text.first() {
+            Some(&c) if c != b'/' => glob_match(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some(&p) => match text.first() {
+            Some(&c) if c == p => glob_match(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: Option<&str>, deny: &str) -> ToolPolicy {
+        ToolPolicy {
+            allow: allow.map(parse_patterns),
+            deny: parse_patterns(deny),
+            read_only: parse_patterns("asterai:cli/common/ls,*/fs/read"),
+            destructive: parse_patterns("*/*/rm"),
+            timeouts: parse_timeouts("asterai:steel/**=120, */fs/cp=300, bad, x=0"),
+            default_timeout: Some(Duration::from_secs(30)),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let p = Pattern::new("asterai:cli/common/*");
+        assert!(p.matches("asterai:cli/common/ls"));
+        assert!(!p.matches("asterai:cli/command/run"));
+        let p = Pattern::new("*/fs/rm");
+        assert!(p.matches("asterai:fs-local/fs/rm"));
+        assert!(!p.matches("asterai:fs-local/fs/rmdir"));
+        let p = Pattern::new("asterai:s3/**");
+        assert!(p.matches("asterai:s3/fs/read"));
+        let p = Pattern::new("asterai:cli/common/?s");
+        assert!(p.matches("asterai:cli/common/ls"));
+        assert!(!p.matches("asterai:cli/common/cp"));
+    }
+
+    #[test]
+    fn test_bare_component_pattern() {
+        let p = Pattern::new("asterai:cli");
+        assert!(p.matches("asterai:cli/common/ls"));
+        assert!(p.matches("asterai:cli/bare-func"));
+        assert!(!p.matches("asterai:cli-extra/common/ls"));
+    }
+
+    #[test]
+    fn test_double_star_matches_zero_segments() {
+        let p = Pattern::new("my:comp/**/do-it");
+        assert!(p.matches("my:comp/do-it"));
+        assert!(p.matches("my:comp/api/do-it"));
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let p = policy(Some("asterai:cli,asterai:fs-local"), "*/common/rm,*/fs/rm");
+        assert!(p.is_allowed("asterai:cli/common/ls"));
+        assert!(!p.is_allowed("asterai:cli/common/rm"));
+        assert!(!p.is_allowed("asterai:fs-local/fs/rm"));
+        assert!(!p.is_allowed("asterai:s3/fs/read"));
+    }
+
+    #[test]
+    fn test_deny_without_allow() {
+        let p = policy(None, "*/fs/rm");
+        assert!(p.is_allowed("asterai:s3/fs/read"));
+        assert!(!p.is_allowed("asterai:s3/fs/rm"));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let p = policy(None, "");
+        assert_eq!(
+            p.annotations("asterai:cli/common/ls"),
+            Some(json!({ "readOnlyHint": true }))
+        );
+        assert_eq!(
+            p.annotations("asterai:cli/common/rm"),
+            Some(json!({ "readOnlyHint": false, "destructiveHint": true }))
+        );
+        assert_eq!(p.annotations("asterai:cli/common/cp"), None);
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let p = policy(None, "");
+        assert_eq!(p.timeouts.len(), 2);
+        assert_eq!(
+            p.timeout("asterai:steel/steel/scrape"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            p.timeout("asterai:s3/fs/cp"),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            p.timeout("asterai:cli/common/ls"),
+            Some(Duration::from_secs(30))
+        );
+    }
+}
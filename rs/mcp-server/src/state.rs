@@ -0,0 +1,106 @@
+use crate::bindings::asterai::host::api;
+use serde_json::{Value, json};
+use std::env;
+use std::sync::LazyLock;
+
+const FS_COMPONENT_ENV: &str = "MCP_SERVER_STATE_FS";
+const DIR_ENV: &str = "MCP_SERVER_STATE_DIR";
+const DEFAULT_DIR: &str = "mcp-server";
+
+static CONFIG: LazyLock<Option<StateConfig>> = LazyLock::new(|| {
+    let component = env::var(FS_COMPONENT_ENV).ok().filter(|s| !s.is_empty())?;
+    let dir = env::var(DIR_ENV)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_DIR.to_owned());
+    Some(StateConfig { component, dir })
+});
+
+/// Small records that must outlive a single request, such as
+/// refresh token generations. Each record is a file under `dir`
+/// in an `asterai:fs` implementation (e.g. `asterai:fs-local`
+/// or `asterai:s3`) set via `MCP_SERVER_STATE_FS`.
+/// Writes are not atomic with reads, so concurrent updates
+/// of the same record may race.
+struct StateConfig {
+    component: String,
+    dir: String,
+}
+
+pub fn is_enabled() -> bool {
+    CONFIG.is_some()
+}
+
+/// Reads a record, returning `None` if it does not exist.
+pub fn read(key: &str) -> Result<Option<String>, String> {
+    let config = CONFIG.as_ref().ok_or("state store not configured")?;
+    let path = record_path(&config.dir, key);
+    let exists = call(config, "fs/exists", json!([path]))?;
+    if exists != Value::Bool(true) {
+        return Ok(None);
+    }
+    let bytes = call(config, "fs/read", json!([path]))?;
+    let bytes: Vec<u8> = serde_json::from_value(bytes).map_err(|e| e.to_string())?;
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Creates or overwrites a record.
+pub fn write(key: &str, value: &str) -> Result<(), String> {
+    let config = CONFIG.as_ref().ok_or("state store not configured")?;
+    let path = record_path(&config.dir, key);
+    call(config, "fs/write", json!([path, value.as_bytes()]))?;
+    Ok(())
+}
+
+/// Removes a record if it exists.
+pub fn remove(key: &str) -> Result<(), String> {
+    let config = CONFIG.as_ref().ok_or("state store not configured")?;
+    let path = record_path(&config.dir, key);
+    if call(config, "fs/exists", json!([path]))? != Value::Bool(true) {
+        return Ok(());
+    }
+    call(config, "fs/rm", json!([path, false]))?;
+    Ok(())
+}
+
+fn call(config: &StateConfig, function: &str, args: Value) -> Result<Value, String> {
+    let result = api::call_component_function(&config.component, function, &args.to_string())
+        .map_err(|e| e.message)?;
+    Ok(serde_json::from_str(&result).unwrap_or(Value::Null))
+}
+
+/// Keys are `/`-separated names made of ASCII letters, digits,
+/// `-` and `_`. Anything else is replaced so a key can never
+/// escape the state directory.
+fn record_path(dir: &str, key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    let key = key
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}/{key}", dir.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_path() {
+        assert_eq!(record_path("state", "refresh/abc"), "state/refresh/abc");
+        assert_eq!(record_path("state/", "a//b"), "state/a/b");
+        assert_eq!(record_path("state", "../../etc"), "state/__/__/etc");
+        assert_eq!(record_path("state", "k.e y"), "state/k_e_y");
+    }
+}